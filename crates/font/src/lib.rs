@@ -12,6 +12,7 @@
 
 mod cluster_map;
 mod emoji;
+mod names;
 mod rasterize;
 mod shaping;
 
@@ -20,6 +21,7 @@ pub use emoji::{
     contains_emoji, contains_nerd_symbols, contains_special_chars, is_emoji, is_nerd_font_symbol,
     is_variation_selector,
 };
+pub use names::{named_chars, nerd_font_name, unicode_name, NameSource, NamedChar};
 pub use rasterize::{FontMetrics, RasterResult, Rasterizer};
 pub use shaping::{RunFontType, ShapedGlyph, Shaper, ShapingOptions, ShapingResult, TextRun};
 
@@ -33,11 +35,13 @@ use tiny_sdk::services::{
 };
 use tiny_sdk::types::{LayoutPos, PhysicalPos, PhysicalSizeF};
 
-/// Helper to expand tabs to spaces and filter variation selectors
-fn expand_tabs(text: &str) -> String {
-    const TAB_WIDTH: usize = 4;
-    const SPACES: &str = "    "; // Pre-allocated 4 spaces
+/// JetBrains Mono Nerd Font: fallback glyphs for symbols and Nerd Font icons, and the
+/// icons' names
+static NERD_FONT: &[u8] = include_bytes!("../assets/JetBrainsMonoNerdFont-Regular.ttf");
 
+/// Helper to expand tabs to spaces and filter variation selectors
+fn expand_tabs(text: &str, tab_width: usize) -> String {
+    let tab_width = tab_width.max(1);
     let mut result = String::with_capacity(text.len() + (text.len() / 8)); // Estimate extra space for tabs
     let mut column = 0;
 
@@ -48,8 +52,8 @@ fn expand_tabs(text: &str) -> String {
         }
 
         if ch == '\t' {
-            let spaces_needed = TAB_WIDTH - (column % TAB_WIDTH);
-            result.push_str(&SPACES[..spaces_needed]);
+            let spaces_needed = tab_width - (column % tab_width);
+            result.extend(std::iter::repeat_n(' ', spaces_needed));
             column += spaces_needed;
         } else if ch == '\n' {
             result.push(ch);
@@ -156,6 +160,8 @@ pub struct FontSystem {
     shaping_options: ShapingOptions,
     /// Current frame counter for LRU eviction
     current_frame: u64,
+    /// Columns per tab stop when expanding tabs
+    tab_width: usize,
 }
 
 /// Helper to load a font from a file path
@@ -189,6 +195,16 @@ impl FontSystem {
         // Old glyphs will be replaced as text is re-laid out with new italic
     }
 
+    /// Set columns per tab stop used when expanding tabs
+    pub fn set_tab_width(&mut self, tab_width: usize) {
+        self.tab_width = tab_width.max(1);
+    }
+
+    /// Get columns per tab stop
+    pub fn tab_width(&self) -> usize {
+        self.tab_width
+    }
+
     /// Get current default weight
     pub fn default_weight(&self) -> f32 {
         self.shaping_options.weight
@@ -552,8 +568,7 @@ impl FontSystem {
         // Load nerd font for glyphs and Unicode symbols
         // Using full JetBrainsMonoNerdFont instead of Symbols-only because we need
         // standard Unicode symbols (✓ ✗ arrows etc) in addition to PUA nerd icons
-        let nerd_font_static = include_bytes!("../assets/JetBrainsMonoNerdFont-Regular.ttf");
-        let nerd_font_data = Arc::new(nerd_font_static.to_vec());
+        let nerd_font_data = Arc::new(NERD_FONT.to_vec());
         let nerd_font_data_ref: &'static [u8] =
             unsafe { std::slice::from_raw_parts(nerd_font_data.as_ptr(), nerd_font_data.len()) };
         let nerd_font_ref =
@@ -616,6 +631,7 @@ impl FontSystem {
             color_dirty: true, // Color atlas also needs initial upload
             shaping_options: ShapingOptions::default(),
             current_frame: 0,
+            tab_width: 4,
         }
     }
 
@@ -692,7 +708,7 @@ impl FontSystem {
         opts_with_size.font_size = font_size_px;
 
         // Expand tabs manually
-        let expanded = expand_tabs(text);
+        let expanded = expand_tabs(text, self.tab_width);
 
         // Segment text by checking which font has each character
         let runs = self.segment_text_by_font(&expanded, opts.italic);
@@ -969,7 +985,7 @@ impl FontSystem {
         let baseline_y = main_font_metrics.ascent;
 
         // Expand tabs
-        let expanded = expand_tabs(text);
+        let expanded = expand_tabs(text, self.tab_width);
 
         // Segment text by checking which font has each character
         let runs = self.segment_text_by_font(&expanded, opts_clone.italic);
@@ -1368,7 +1384,8 @@ impl SharedFontSystem {
         }
 
         // Expand tabs to match what layout_text_shaped_with_tabs does internally
-        let expanded = expand_tabs(line_text);
+        let tab_width = self.tab_width();
+        let expanded = expand_tabs(line_text, tab_width);
 
         // Use shaped layout to get cluster map
         let shaped = self.layout_text_shaped_with_tabs(
@@ -1449,8 +1466,7 @@ impl SharedFontSystem {
 
             if ch == '\t' {
                 // Tab expands to multiple spaces
-                const TAB_WIDTH: usize = 4;
-                let spaces_added = TAB_WIDTH - (exp_char_idx % TAB_WIDTH);
+                let spaces_added = tab_width - (exp_char_idx % tab_width);
                 exp_char_idx += spaces_added;
             } else {
                 exp_char_idx += 1;
@@ -1491,6 +1507,16 @@ impl SharedFontSystem {
         self.inner.lock().set_default_italic(italic);
     }
 
+    /// Set columns per tab stop
+    pub fn set_tab_width(&self, tab_width: usize) {
+        self.inner.lock().set_tab_width(tab_width);
+    }
+
+    /// Get columns per tab stop
+    pub fn tab_width(&self) -> usize {
+        self.inner.lock().tab_width()
+    }
+
     /// Get current default weight
     pub fn default_weight(&self) -> f32 {
         self.inner.lock().default_weight()
@@ -0,0 +1,300 @@
+//! Character names - from Unicode and from the bundled Nerd Font
+//!
+//! Unicode names come from a trimmed copy of UnicodeData.txt (`assets/unicode-names.txt`,
+//! the blocks symbols are usually picked from plus emoji, under the Unicode license in
+//! `assets/LICENSE-UNICODE.txt`). Nerd Font icons live in the
+//! Private Use Area and have no Unicode names, so theirs are the glyph names (`post`
+//! table) of the bundled JetBrains Mono Nerd Font, mapped to characters through its
+//! `cmap`. The font is the one the font system already embeds for fallback glyphs.
+
+use std::sync::OnceLock;
+
+static UNICODE_NAMES: &str = include_str!("../assets/unicode-names.txt");
+
+/// Where a character's name comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameSource {
+    Unicode,
+    NerdFont,
+}
+
+/// A character with its name, e.g. for picking characters by name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NamedChar {
+    pub ch: char,
+    pub name: &'static str,
+    pub source: NameSource,
+}
+
+/// Unicode name of a character, None for characters outside the bundled table
+/// (CJK ideograph names are derived from the code point)
+pub fn unicode_name(ch: char) -> Option<String> {
+    if let Some(name) = lookup(unicode_names(), ch) {
+        return Some(name.to_string());
+    }
+    is_cjk_ideograph(ch).then(|| format!("CJK UNIFIED IDEOGRAPH-{:04X}", ch as u32))
+}
+
+/// Glyph name of a symbol in the bundled Nerd Font (e.g. "git-branch")
+pub fn nerd_font_name(ch: char) -> Option<&'static str> {
+    lookup(nerd_font_names(), ch)
+}
+
+/// Every named character: the Unicode table, then Nerd Font symbols it doesn't name
+pub fn named_chars() -> &'static [NamedChar] {
+    static CHARS: OnceLock<Vec<NamedChar>> = OnceLock::new();
+    CHARS.get_or_init(|| {
+        let unicode = unicode_names().iter().map(|(ch, name)| NamedChar {
+            ch: *ch,
+            name,
+            source: NameSource::Unicode,
+        });
+        let nerd_font = nerd_font_names()
+            .iter()
+            .filter(|(ch, _)| lookup(unicode_names(), *ch).is_none())
+            .map(|(ch, name)| NamedChar {
+                ch: *ch,
+                name: name.as_str(),
+                source: NameSource::NerdFont,
+            });
+        unicode.chain(nerd_font).collect()
+    })
+}
+
+fn lookup<S: AsRef<str>>(table: &'static [(char, S)], ch: char) -> Option<&'static str> {
+    let index = table.binary_search_by_key(&ch, |(c, _)| *c).ok()?;
+    Some(table[index].1.as_ref())
+}
+
+fn is_cjk_ideograph(ch: char) -> bool {
+    matches!(ch,
+        '\u{3400}'..='\u{4DBF}' |   // Extension A
+        '\u{4E00}'..='\u{9FFF}' |   // Unified Ideographs
+        '\u{20000}'..='\u{2A6DF}' | // Extension B
+        '\u{2A700}'..='\u{2EBEF}' | // Extensions C-F
+        '\u{30000}'..='\u{3134F}'   // Extension G
+    )
+}
+
+/// The bundled Unicode names, sorted by character
+fn unicode_names() -> &'static [(char, &'static str)] {
+    static NAMES: OnceLock<Vec<(char, &'static str)>> = OnceLock::new();
+    NAMES.get_or_init(|| {
+        let mut names: Vec<(char, &'static str)> = UNICODE_NAMES
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| {
+                let (code, name) = line.split_once(';')?;
+                let ch = char::from_u32(u32::from_str_radix(code, 16).ok()?)?;
+                Some((ch, name))
+            })
+            .collect();
+        names.sort_by_key(|(ch, _)| *ch);
+        names
+    })
+}
+
+/// Named glyphs of the bundled Nerd Font, sorted by character
+fn nerd_font_names() -> &'static [(char, String)] {
+    static NAMES: OnceLock<Vec<(char, String)>> = OnceLock::new();
+    NAMES.get_or_init(|| glyph_names(crate::NERD_FONT).unwrap_or_default())
+}
+
+/// Characters of a TrueType font with the names of their glyphs
+/// Glyphs without a real name (`uniE0A0`, `glyph12`) are left out
+fn glyph_names(font: &[u8]) -> Option<Vec<(char, String)>> {
+    let names = post_names(font)?;
+    let mut chars: Vec<(char, String)> = cmap_glyphs(font)?
+        .into_iter()
+        .filter_map(|(ch, glyph)| {
+            let name = names.get(glyph as usize).copied().flatten()?;
+            (!is_generated_name(name)).then(|| (ch, name.to_string()))
+        })
+        .collect();
+    chars.sort_by_key(|(ch, _)| *ch);
+    Some(chars)
+}
+
+/// Names made up from the code point or glyph index rather than describing the glyph
+fn is_generated_name(name: &str) -> bool {
+    let hex = |digits: &str| {
+        digits.len() >= 4
+            && digits
+                .chars()
+                .all(|c| c.is_ascii_digit() || ('A'..='F').contains(&c))
+    };
+    name.strip_prefix("uni").is_some_and(hex)
+        || name.strip_prefix('u').is_some_and(hex)
+        || name
+            .strip_prefix("glyph")
+            .is_some_and(|digits| digits.chars().all(|c| c.is_ascii_digit()))
+}
+
+fn read_u16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// Offset and length of a table in a TrueType font
+fn find_table(font: &[u8], tag: &[u8; 4]) -> Option<(usize, usize)> {
+    let count = read_u16(font, 4)? as usize;
+    let record = (0..count)
+        .map(|i| 12 + 16 * i)
+        .find(|&record| font.get(record..record + 4) == Some(tag.as_slice()))?;
+    let offset = read_u32(font, record + 8)? as usize;
+    let length = read_u32(font, record + 12)? as usize;
+    Some((offset, length))
+}
+
+/// Glyph index of every character in the `cmap`: from the Windows full Unicode subtable
+/// (format 12) if there is one, which also covers the supplementary planes (e.g. the
+/// Material Design icons from U+F0001), otherwise from the Windows BMP one (format 4)
+fn cmap_glyphs(font: &[u8]) -> Option<Vec<(char, u16)>> {
+    let (cmap, _) = find_table(font, b"cmap")?;
+    let subtable_count = read_u16(font, cmap + 2)? as usize;
+    let subtable = |encoding: u16, format: u16| {
+        let record = (0..subtable_count)
+            .map(|i| cmap + 4 + 8 * i)
+            .find(|&record| {
+                read_u16(font, record) == Some(3) && read_u16(font, record + 2) == Some(encoding)
+            })?;
+        let subtable = cmap + read_u32(font, record + 4)? as usize;
+        (read_u16(font, subtable)? == format).then_some(subtable)
+    };
+
+    match subtable(10, 12) {
+        Some(subtable) => format12_glyphs(font, subtable),
+        None => format4_glyphs(font, subtable(1, 4)?),
+    }
+}
+
+/// Characters of a format 12 `cmap` subtable: groups of consecutive characters mapped
+/// to consecutive glyphs
+fn format12_glyphs(font: &[u8], subtable: usize) -> Option<Vec<(char, u16)>> {
+    let groups = read_u32(font, subtable + 12)? as usize;
+    let mut glyphs = Vec::new();
+    for group in (0..groups).map(|i| subtable + 16 + 12 * i) {
+        let start = read_u32(font, group)?;
+        let end = read_u32(font, group + 4)?.min(char::MAX as u32);
+        let start_glyph = read_u32(font, group + 8)?;
+        for code in start..=end {
+            let glyph = start_glyph + (code - start);
+            let Ok(glyph) = u16::try_from(glyph) else {
+                break;
+            };
+            if let Some(ch) = char::from_u32(code).filter(|_| glyph != 0) {
+                glyphs.push((ch, glyph));
+            }
+        }
+    }
+    Some(glyphs)
+}
+
+/// Characters of a format 4 `cmap` subtable (the Basic Multilingual Plane)
+fn format4_glyphs(font: &[u8], subtable: usize) -> Option<Vec<(char, u16)>> {
+    // Parallel arrays of segment ends, starts, deltas and range offsets
+    let segments = read_u16(font, subtable + 6)? as usize / 2;
+    let ends = subtable + 14;
+    let starts = ends + 2 * segments + 2;
+    let deltas = starts + 2 * segments;
+    let range_offsets = deltas + 2 * segments;
+
+    let mut glyphs = Vec::new();
+    for i in 0..segments {
+        let start = read_u16(font, starts + 2 * i)?;
+        let end = read_u16(font, ends + 2 * i)?;
+        let delta = read_u16(font, deltas + 2 * i)?;
+        let range_offset = read_u16(font, range_offsets + 2 * i)? as usize;
+        for code in start..=end.min(0xFFFE) {
+            let glyph = if range_offset == 0 {
+                code.wrapping_add(delta)
+            } else {
+                // Offset from the range offset entry itself into the glyph index array
+                let at = range_offsets + 2 * i + range_offset + 2 * (code - start) as usize;
+                match read_u16(font, at)? {
+                    0 => 0,
+                    glyph => glyph.wrapping_add(delta),
+                }
+            };
+            if let Some(ch) = char::from_u32(code as u32).filter(|_| glyph != 0) {
+                glyphs.push((ch, glyph));
+            }
+        }
+    }
+    Some(glyphs)
+}
+
+/// Custom glyph names from the `post` table (format 2), by glyph index
+/// Glyphs using one of the standard Macintosh names get None
+fn post_names(font: &[u8]) -> Option<Vec<Option<&str>>> {
+    let (post, length) = find_table(font, b"post")?;
+    if read_u32(font, post)? != 0x0002_0000 {
+        return None;
+    }
+    let glyph_count = read_u16(font, post + 32)? as usize;
+
+    // Pascal strings follow the name index array
+    let mut strings = Vec::new();
+    let mut at = post + 34 + 2 * glyph_count;
+    while at < post + length {
+        let len = *font.get(at)? as usize;
+        strings.push(std::str::from_utf8(font.get(at + 1..at + 1 + len)?).ok());
+        at += 1 + len;
+    }
+
+    (0..glyph_count)
+        .map(|glyph| {
+            let index = read_u16(font, post + 34 + 2 * glyph)? as usize;
+            Some(
+                index
+                    .checked_sub(258)
+                    .and_then(|i| strings.get(i).copied().flatten()),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unicode_names_from_table() {
+        assert_eq!(unicode_name('A').as_deref(), Some("LATIN CAPITAL LETTER A"));
+        assert_eq!(
+            unicode_name('\u{200B}').as_deref(),
+            Some("ZERO WIDTH SPACE")
+        );
+        assert_eq!(unicode_name('😀').as_deref(), Some("GRINNING FACE"));
+        assert_eq!(
+            unicode_name('中').as_deref(),
+            Some("CJK UNIFIED IDEOGRAPH-4E2D")
+        );
+        assert_eq!(unicode_name('\u{E0A0}'), None);
+    }
+
+    #[test]
+    fn nerd_font_names_from_bundled_font() {
+        assert_eq!(nerd_font_name('\u{F004}'), Some("fa-heart"));
+        assert_eq!(nerd_font_name('\u{E0A0}'), Some("pl-branch"));
+        // Material Design icons are past the Basic Multilingual Plane
+        assert_eq!(nerd_font_name('\u{F0001}'), Some("md-vector_square"));
+        // Not in the font
+        assert_eq!(nerd_font_name('\u{E0FF}'), None);
+        assert!(named_chars()
+            .iter()
+            .any(|named| named.source == NameSource::NerdFont && named.name == "fa-heart"));
+    }
+
+    #[test]
+    fn generated_glyph_names() {
+        assert!(is_generated_name("uniE0A0"));
+        assert!(is_generated_name("u1F600"));
+        assert!(is_generated_name("glyph12"));
+        assert!(!is_generated_name("udemy"));
+        assert!(!is_generated_name("git-branch"));
+    }
+}
@@ -146,6 +146,7 @@ impl Chord {
                 "tab" => Trigger::Named("Tab".to_string()),
                 "backspace" => Trigger::Named("Backspace".to_string()),
                 "delete" => Trigger::Named("Delete".to_string()),
+                "insert" => Trigger::Named("Insert".to_string()),
                 "escape" | "esc" => Trigger::Named("Escape".to_string()),
                 "space" => Trigger::Named("Space".to_string()),
                 "up" | "arrowup" => Trigger::Named("ArrowUp".to_string()),
@@ -4,21 +4,32 @@
 
 use crate::{
     accelerator::{Modifiers, MouseButton, Trigger, WheelDirection},
-    coordinates::TextMetrics,
-    event_data::{self, FileGotoData, FileOpenData},
+    command_palette_plugin::PaletteCommand,
+    confusables,
+    coordinates::{LineMode, TextMetrics},
+    diagnostics_manager::EditorDiagnostic,
+    diagnostics_plugin::DiagnosticSeverity,
+    event_data::{self, FileGotoData, FileOpenData, IndentationData, InsertCharData, MarkData},
+    ime::{Composition, ImeAction},
+    indentation::{self, IndentStyle},
     input::{Event, EventBus, InputAction},
     lsp_manager::LspManager,
-    render::Renderer,
+    marks::MarkCommand,
+    render::{Preedit, Renderer},
     scroll::{ScrollFocusManager, Scrollable, WidgetId},
     shortcuts::ShortcutRegistry,
+    snippets::SnippetLibrary,
+    surround,
     tab_manager::TabManager,
-    winit_adapter,
+    text_effects::{priority, EffectType, TextEffect, UnderlineStyle},
+    transforms, unicode_info, winit_adapter,
+    words::WordConfig,
 };
 
 pub use crate::editor_logic::EditorLogic;
 use anyhow::{anyhow, Result};
 use serde_json::json;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{
     atomic::{AtomicBool, Ordering},
     Arc, Mutex,
@@ -26,7 +37,7 @@ use std::sync::{
 use tiny_font::SharedFontSystem;
 use winit::{
     application::ApplicationHandler,
-    dpi::PhysicalPosition,
+    dpi::{LogicalPosition, LogicalSize, PhysicalPosition},
     event::{ElementState, MouseButton as WinitMouseButton, MouseScrollDelta, WindowEvent},
     event_loop::{ActiveEventLoop, EventLoop},
     keyboard::Key,
@@ -148,12 +159,32 @@ pub struct TinyApp {
     scroll_lock_enabled: bool, // true = lock to one direction at a time
     current_scroll_direction: Option<ScrollDirection>, // which direction is currently locked
 
+    // Word definition for word movement/deletion (handed to the focused input handler)
+    word_config: Arc<WordConfig>,
+    auto_close_pairs: Arc<crate::config::AutoCloseConfig>,
+
+    // Indentation: columns per tab stop, spaces instead of tab characters
+    tab_width: u32,
+    soft_tabs: bool,
+
+    // User snippets by language (<language>.snippets.toml next to init.toml)
+    snippets: Arc<SnippetLibrary>,
+
+    // Soft wrap: enabled, fixed column (None = editor width), indented continuation rows
+    soft_wrap: bool,
+    wrap_column: Option<u32>,
+    wrap_indent: bool,
+
     // Mouse and keyboard state (inlined from MouseState)
     mouse_position: Option<PhysicalPosition<f64>>,
     mouse_pressed: bool,
     mouse_drag_start: Option<PhysicalPosition<f64>>,
     modifiers: Modifiers,
 
+    // Input method composition and where its candidate window was last placed
+    ime: Composition,
+    ime_cursor_area: Option<(f32, f32, f32)>,
+
     // Track if cursor moved for scrolling
     cursor_needs_scroll: bool,
 
@@ -255,6 +286,15 @@ impl TinyApp {
                 ));
             }
 
+            // Command palette (overlay, high z-index)
+            if self.editor.command_palette.visible {
+                widget_bounds.push((
+                    WidgetId::CommandPalette,
+                    self.editor.command_palette.get_bounds(),
+                    1000, // z-index
+                ));
+            }
+
             // Editor (full screen, low z-index)
             if let Some(cpu_renderer) = &self.cpu_renderer {
                 widget_bounds.push((WidgetId::Editor, cpu_renderer.editor_bounds, 0));
@@ -505,6 +545,9 @@ impl TinyApp {
                     // Grep handles scroll through event bus (see grep_plugin.rs)
                     // Event already emitted above, nothing more to do here
                 }
+                Some(WidgetId::CommandPalette) => {
+                    // Command palette handles scroll through event bus too
+                }
                 Some(WidgetId::Editor) | None => {
                     // Route to active editor tab with editor bounds
                     let tab = self.editor.tab_manager.active_tab_mut();
@@ -561,10 +604,20 @@ impl TinyApp {
             title_bar_height: 28.0, // Logical pixels
             scroll_lock_enabled: true,
             current_scroll_direction: None,
+            word_config: Arc::new(WordConfig::default()),
+            auto_close_pairs: Arc::new(crate::config::AutoCloseConfig::default()),
+            tab_width: 4,
+            soft_tabs: true,
+            snippets: Arc::new(SnippetLibrary::default()),
+            soft_wrap: false,
+            wrap_column: None,
+            wrap_indent: true,
             mouse_position: None,
             mouse_pressed: false,
             mouse_drag_start: None,
             modifiers: Modifiers::default(),
+            ime: Composition::default(),
+            ime_cursor_area: None,
             cursor_needs_scroll: false,
             continuous_rendering: false,
             last_frame_time: std::time::Instant::now(),
@@ -580,6 +633,7 @@ impl TinyApp {
         // Create text metrics from config
         let mut text_metrics = TextMetrics::new(config.editor.font_size);
         text_metrics.line_height = config.editor.font_size * config.editor.line_height;
+        text_metrics.tab_stops = config.editor.tab_width.max(1);
         self.text_metrics = text_metrics;
 
         // Track base font size from config
@@ -587,6 +641,14 @@ impl TinyApp {
 
         self.title_bar_height = config.editor.title_bar_height;
         self.scroll_lock_enabled = config.editor.scroll_lock_enabled;
+        self.word_config = Arc::new(config.editor.word_config());
+        self.auto_close_pairs = Arc::new(config.auto_close_config());
+        self.tab_width = config.editor.tab_width.max(1);
+        self.soft_tabs = config.editor.soft_tabs;
+        self.snippets = Arc::new(SnippetLibrary::load(Path::new(".")));
+        self.soft_wrap = config.editor.soft_wrap;
+        self.wrap_column = config.editor.wrap_column;
+        self.wrap_indent = config.editor.wrap_indent;
         self.continuous_rendering = config.editor.continuous_rendering;
         self
     }
@@ -603,6 +665,7 @@ impl TinyApp {
 
     pub fn with_font_size(mut self, size: f32) -> Self {
         self.text_metrics = TextMetrics::new(size);
+        self.text_metrics.tab_stops = self.tab_width;
         self.base_font_size = size;
         self
     }
@@ -618,6 +681,219 @@ impl TinyApp {
         Ok(())
     }
 
+    /// Indentation of the active file (detected or chosen for it), else the configured one
+    fn indent_style(&self) -> IndentStyle {
+        self.editor
+            .tab_manager
+            .active_tab()
+            .and_then(|tab| tab.indentation)
+            .unwrap_or_else(|| IndentStyle::from_config(self.soft_tabs, self.tab_width))
+    }
+
+    /// Lay out tabs with the active file's tab width (.editorconfig, else the config)
+    fn sync_tab_width(&mut self) {
+        let tab_width = self
+            .editor
+            .tab_manager
+            .active_tab()
+            .and_then(|tab| tab.plugin.editorconfig.tab_width)
+            .unwrap_or(self.tab_width);
+        if tab_width == self.text_metrics.tab_stops {
+            return;
+        }
+
+        self.text_metrics.tab_stops = tab_width;
+        if let Some(font_system) = &self.font_system {
+            font_system.set_tab_width(tab_width as usize);
+        }
+        if let Some(renderer) = &mut self.cpu_renderer {
+            renderer.clear_all_caches();
+            renderer.set_tab_stops(tab_width);
+            renderer.mark_ui_dirty();
+        }
+        self.editor.ui_changed = true;
+    }
+
+    /// Underline the IME preedit and keep the candidate window next to the cursor
+    fn sync_ime(&mut self) {
+        let (Some(cpu_renderer), Some(tab)) =
+            (&mut self.cpu_renderer, self.editor.tab_manager.active_tab())
+        else {
+            return;
+        };
+
+        // The selected clause is underlined more heavily than the rest
+        let color = cpu_renderer.get_token_color(0);
+        let underline = |range, style| TextEffect {
+            range,
+            effect: EffectType::Underline { color, style },
+            priority: priority::SELECTION,
+        };
+        let preedit = tab
+            .plugin
+            .editor
+            .input
+            .preedit()
+            .map(|(text, clause, positions)| {
+                let mut underlines = vec![underline(0..text.len(), UnderlineStyle::Solid)];
+                underlines.extend(
+                    clause
+                        .filter(|clause| !clause.is_empty())
+                        .map(|clause| underline(clause, UnderlineStyle::Double)),
+                );
+                Preedit {
+                    text: text.to_string(),
+                    positions,
+                    underlines,
+                }
+            });
+        cpu_renderer.set_preedit(preedit);
+
+        if !self.ime.is_enabled() {
+            self.ime_cursor_area = None;
+            return;
+        }
+        let (Some(window), Some(area)) = (&self.window, self.cursor_screen_pos()) else {
+            return;
+        };
+        if self.ime_cursor_area != Some(area) {
+            self.ime_cursor_area = Some(area);
+            let (x, y, line_height) = area;
+            window.set_ime_cursor_area(
+                LogicalPosition::new(x as f64, y as f64),
+                LogicalSize::new(1.0, line_height as f64),
+            );
+        }
+    }
+
+    /// Backgrounds behind the document text: find matches and confusable characters
+    fn sync_text_backgrounds(&mut self) {
+        let mut effects = self.search_highlights();
+        effects.extend(self.sync_confusables());
+        if let Some(cpu_renderer) = &mut self.cpu_renderer {
+            cpu_renderer.set_background_effects(effects);
+        }
+    }
+
+    /// Highlight the find bar's matches in view, the one selected in the editor brighter
+    fn search_highlights(&mut self) -> Vec<TextEffect> {
+        const MATCH_BACKGROUND: u32 = 0xE0B04040;
+        const CURRENT_MATCH_BACKGROUND: u32 = 0xF0C04090;
+
+        if self.editor.refresh_find_matches() {
+            self.editor.ui_changed = true;
+        }
+        let bar = &self.editor.find_bar;
+        let (Some(cpu_renderer), Some(tab)) =
+            (&self.cpu_renderer, self.editor.tab_manager.active_tab())
+        else {
+            return Vec::new();
+        };
+        if !bar.visible {
+            return Vec::new();
+        }
+
+        let visible = {
+            let tree = tab.plugin.editor.view.doc.read();
+            cpu_renderer.viewport.visible_byte_range_with_tree(&tree)
+        };
+        let first = bar.matches().partition_point(|m| m.end <= visible.start);
+        bar.matches()[first..]
+            .iter()
+            .take_while(|m| m.start < visible.end)
+            .zip(first..)
+            .map(|(m, index)| TextEffect {
+                range: m.clone(),
+                effect: EffectType::Background(if bar.current() == Some(index) {
+                    CURRENT_MATCH_BACKGROUND
+                } else {
+                    MATCH_BACKGROUND
+                }),
+                priority: priority::SEARCH,
+            })
+            .collect()
+    }
+
+    /// Mark confusable, invisible and bidi-control characters in view, explained on hover
+    /// Returns their backgrounds
+    fn sync_confusables(&mut self) -> Vec<TextEffect> {
+        // Bidi controls can change what code does, the others only hide what it is
+        const BIDI_BACKGROUND: u32 = 0xE0505060;
+        const CONFUSABLE_BACKGROUND: u32 = 0xD0A03040;
+
+        let Some(cpu_renderer) = &mut self.cpu_renderer else {
+            return Vec::new();
+        };
+        let tab = self.editor.tab_manager.active_tab_mut();
+        let findings = {
+            let tree = tab.plugin.editor.view.doc.read();
+            let visible = cpu_renderer.viewport.visible_byte_range_with_tree(&tree);
+            // Whole lines around the view, so words cut by its edges are judged whole
+            let start = tree.find_line_start_at(visible.start);
+            let end = tree.find_line_end_at(visible.end);
+            confusables::find_in(&tree.get_text_slice(start..end), start, visible)
+        };
+
+        let effects: Vec<TextEffect> = findings
+            .iter()
+            .map(|finding| TextEffect {
+                range: finding.range.clone(),
+                effect: EffectType::Background(match finding.kind {
+                    confusables::Kind::Bidi => BIDI_BACKGROUND,
+                    _ => CONFUSABLE_BACKGROUND,
+                }),
+                priority: priority::ERROR,
+            })
+            .collect();
+        let placeholders = findings
+            .iter()
+            .filter_map(|finding| Some((finding.range.start, finding.kind.placeholder()?)))
+            .collect();
+        cpu_renderer.set_placeholder_glyphs(placeholders);
+
+        let diagnostics = findings
+            .iter()
+            .map(|finding| EditorDiagnostic {
+                range: finding.range.clone(),
+                message: finding.message(),
+                severity: match finding.kind {
+                    confusables::Kind::Bidi => DiagnosticSeverity::Warning,
+                    _ => DiagnosticSeverity::Info,
+                },
+            })
+            .collect();
+        tab.diagnostics.set_editor_diagnostics(diagnostics);
+        effects
+    }
+
+    /// Keep the completion popup attached to the cursor
+    fn sync_completion(&mut self) {
+        if !self.editor.completion.visible {
+            return;
+        }
+        if let Some((x, y, line_height)) = self.cursor_screen_pos() {
+            if self.editor.completion.set_anchor(x, y, line_height) {
+                self.editor.ui_changed = true;
+            }
+        }
+    }
+
+    /// Top-left of the primary cursor in window coordinates, with the line height
+    fn cursor_screen_pos(&self) -> Option<(f32, f32, f32)> {
+        let cpu_renderer = self.cpu_renderer.as_ref()?;
+        let tab = self.editor.tab_manager.active_tab()?;
+        let cursor_pos = tab.plugin.get_cursor_doc_pos()?;
+        let layout_pos = {
+            let tree = tab.plugin.editor.view.doc.read();
+            cpu_renderer
+                .viewport
+                .doc_to_layout_with_tree(cursor_pos, &tree)
+        };
+        let x = layout_pos.x.0 - tab.scroll_position.x.0 + cpu_renderer.editor_bounds.x.0;
+        let y = layout_pos.y.0 - tab.scroll_position.y.0 + cpu_renderer.editor_bounds.y.0;
+        Some((x, y, cpu_renderer.viewport.metrics.line_height))
+    }
+
     /// Adjust font size (for Cmd+=/Cmd+-)
     fn adjust_font_size(&mut self, increase: bool) {
         let delta = if increase { 1.0 } else { -1.0 };
@@ -631,6 +907,7 @@ impl TinyApp {
         // Update source of truth (render_frame will propagate to all viewports)
         let mut new_metrics = TextMetrics::new(new_font_size);
         new_metrics.line_height = new_font_size * line_height_multiplier;
+        new_metrics.tab_stops = self.text_metrics.tab_stops;
         self.text_metrics = new_metrics;
 
         // Update renderer and invalidate all caches
@@ -756,6 +1033,7 @@ impl TinyApp {
 
             let mut new_metrics = TextMetrics::new(new_font_size);
             new_metrics.line_height = new_line_height;
+            new_metrics.tab_stops = self.text_metrics.tab_stops;
             self.text_metrics = new_metrics;
 
             if let Some(renderer) = &mut self.cpu_renderer {
@@ -780,6 +1058,13 @@ impl TinyApp {
         }
 
         self.scroll_lock_enabled = config.editor.scroll_lock_enabled;
+        self.word_config = Arc::new(config.editor.word_config());
+        self.auto_close_pairs = Arc::new(config.auto_close_config());
+        self.soft_tabs = config.editor.soft_tabs;
+        self.snippets = Arc::new(SnippetLibrary::load(Path::new(".")));
+        self.soft_wrap = config.editor.soft_wrap;
+        self.wrap_column = config.editor.wrap_column;
+        self.wrap_indent = config.editor.wrap_indent;
         self.title_bar_height = config.editor.title_bar_height;
 
         if let Some(font_system) = &self.font_system {
@@ -806,6 +1091,9 @@ impl TinyApp {
             }
         }
 
+        // Picked up by sync_tab_width on the next frame
+        self.tab_width = config.editor.tab_width.max(1);
+
         self.request_redraw();
     }
 
@@ -823,6 +1111,26 @@ impl TinyApp {
         self.request_redraw();
     }
 
+    /// Send typing to the find bar while one of its fields has focus, else to the editor
+    fn sync_find_focus(&mut self) {
+        self.focused_editable_view_id = self.editor.find_bar.focused_id().or_else(|| {
+            self.editor
+                .tab_manager
+                .active_tab()
+                .map(|tab| tab.plugin.editor.id)
+        });
+        self.editor.ui_changed = true;
+        self.request_redraw();
+    }
+
+    /// Select the next (or previous) find match and scroll to it
+    fn find_step(&mut self, backward: bool) {
+        if self.editor.find_step(backward) {
+            self.cursor_needs_scroll = true;
+        }
+        self.sync_find_focus();
+    }
+
     /// Helper for navigation with optional redraw
     fn navigate(&mut self, nav: impl FnOnce(&mut EditorLogic) -> Result<bool>) -> Result<()> {
         if nav(&mut self.editor)? {
@@ -840,9 +1148,11 @@ impl TinyApp {
         // Dispatch through priority-ordered subscribers
         // Components emit follow-up events (file.open, grep.navigate, etc.)
         let mut subscribers: Vec<&mut dyn EventSubscriber> = vec![
-            &mut self.editor.grep, // Priority 100
-            &mut self.editor.file_picker, // Priority 100
-                                   // Main editor (priority 0) doesn't claim navigate/action events
+            &mut self.editor.grep,            // Priority 100
+            &mut self.editor.file_picker,     // Priority 100
+            &mut self.editor.command_palette, // Priority 100
+            &mut self.editor.find_bar,        // Priority 50
+                                              // Main editor (priority 0) doesn't claim navigate/action events
         ];
         dispatch_event(event, &mut subscribers, &mut self.event_bus);
     }
@@ -872,6 +1182,15 @@ impl TinyApp {
             return (&mut input.input, &input.view.doc, viewport);
         }
 
+        // Try command palette input
+        if self.editor.command_palette.visible
+            && focused_id == self.editor.command_palette.input().id
+        {
+            let input = self.editor.command_palette.input_mut();
+            let viewport = input.view.viewport.clone();
+            return (&mut input.input, &input.view.doc, viewport);
+        }
+
         // Fallback to main editor
         let tab = self.editor.tab_manager.active_tab_mut();
         let viewport = self
@@ -991,6 +1310,15 @@ impl TinyApp {
                 );
             }
 
+            "view.toggle_soft_wrap" => {
+                self.soft_wrap = !self.soft_wrap;
+                // Wrapped lines fit the editor, nothing to scroll to sideways
+                let tab = self.editor.tab_manager.active_tab_mut();
+                tab.scroll_position.x.0 = 0.0;
+                self.cursor_needs_scroll = true;
+                self.request_redraw();
+            }
+
             // Scrollbar events
             "scrollbar.set_position" => {
                 if let Some(y) = event.data.get("y").and_then(|v| v.as_f64()) {
@@ -1085,8 +1413,58 @@ impl TinyApp {
                         }
                         self.request_redraw();
                     }
+                } else if self.editor.command_palette.visible {
+                    if let (Some(x), Some(y)) = (screen_x, screen_y) {
+                        use crate::filterable_dropdown::DropdownAction;
+                        let action = self
+                            .editor
+                            .command_palette
+                            .picker
+                            .handle_click(x as f32, y as f32, shift);
+                        if let DropdownAction::Selected(command) = action {
+                            self.editor.command_palette.hide();
+                            self.scroll_focus.clear_focus();
+                            if let Some(tab) = self.editor.tab_manager.active_tab() {
+                                self.focused_editable_view_id = Some(tab.plugin.editor.id);
+                            }
+                            self.event_bus
+                                .emit(command.event, command.data, 10, "command_palette");
+                        }
+                        self.request_redraw();
+                    }
+                } else if self.editor.completion.visible
+                    && screen_x
+                        .zip(screen_y)
+                        .is_some_and(|(x, y)| self.editor.completion.select_at(x as f32, y as f32))
+                {
+                    // Clicking a completion picks it
+                    if self.editor.accept_completion() {
+                        self.update_window_title();
+                        self.cursor_needs_scroll = true;
+                    }
+                    self.editor.ui_changed = true;
+                    self.request_redraw();
+                } else if let Some((x, y)) = screen_x
+                    .zip(screen_y)
+                    .map(|(x, y)| (x as f32, y as f32))
+                    .filter(|&(x, y)| self.editor.find_bar.contains(x, y))
+                {
+                    // Clicks on the find bar focus a field, or flip a toggle
+                    if let Some(action) = self.editor.find_bar.handle_click(x, y, shift) {
+                        self.event_bus.emit(action, json!({}), 10, "find");
+                    }
+                    self.sync_find_focus();
                 } else if let (Some(x), Some(y)) = (editor_x, editor_y) {
                     // No overlay - route to main editor (use editor-local coordinates)
+                    if self.editor.completion.visible {
+                        self.editor.completion.hide();
+                        self.editor.ui_changed = true;
+                    }
+                    // The find bar stays up, typing goes back to the editor
+                    if self.editor.find_bar.focused_id().is_some() {
+                        self.editor.find_bar.blur();
+                        self.sync_find_focus();
+                    }
                     // Set drag state here since we're actually handling the click in the editor
                     if let (Some(phys_x), Some(phys_y)) = (
                         event.data.get("physical_x").and_then(|v| v.as_f64()),
@@ -1111,7 +1489,14 @@ impl TinyApp {
                     }
                 }
             }
-            "mouse.release" => self.editor.on_mouse_release(),
+            "mouse.release" => {
+                // Alt copies dragged text instead of moving it
+                let copy = event.data.get("alt").and_then(|v| v.as_bool());
+                if self.editor.on_mouse_release(copy.unwrap_or(false)) {
+                    self.cursor_needs_scroll = true;
+                    self.request_redraw();
+                }
+            }
             "mouse.drag" => {
                 if let Some(viewport) = self.cpu_renderer.as_ref().map(|r| r.viewport.clone()) {
                     let plugin = self.editor.active_plugin_mut();
@@ -1159,6 +1544,23 @@ impl TinyApp {
             "navigation.back" => self.navigate(|e| e.navigate_back())?,
             "navigation.forward" => self.navigate(|e| e.navigate_forward())?,
 
+            // Marks and jump list (vim-style)
+            "mark.set" | "mark.goto" | "mark.goto_exact" => {
+                let data: MarkData = event_data::from_value(&event.data).unwrap_or_default();
+                self.editor.pending_mark = Some(match event.name.as_str() {
+                    "mark.set" => MarkCommand::Set,
+                    "mark.goto" => MarkCommand::Goto { exact: false },
+                    _ => MarkCommand::Goto { exact: true },
+                });
+                if let Some(mark) = data.mark {
+                    self.navigate(|e| e.complete_mark_command(mark))?;
+                }
+            }
+            "jump.prev" => self.navigate(|e| e.navigate_back())?,
+            "jump.next" => self.navigate(|e| e.navigate_forward())?,
+            "jump.change_prev" => self.navigate(|e| e.goto_change(true))?,
+            "jump.change_next" => self.navigate(|e| e.goto_change(false))?,
+
             // Tabs
             "tabs.close" => {
                 self.editor.tab_manager.close_active_tab();
@@ -1180,6 +1582,183 @@ impl TinyApp {
                 let grep_input_id = self.editor.grep.input().id;
                 self.show_overlay(|e| e.grep.show(String::new()), GrepWidget, grep_input_id);
             }
+            // Find bar: search the active file, matches get selected in the editor
+            "editor.search" | "editor.replace" | "search.forward" | "search.backward" => {
+                self.editor.open_find_bar(
+                    event.name == "editor.replace",
+                    event.name == "search.backward",
+                );
+                self.sync_find_focus();
+            }
+            "editor.find_next" | "find.next" => self.find_step(false),
+            "editor.find_previous" | "find.previous" => self.find_step(true),
+            // Vim's n and N keep the direction of the last search
+            "search.next" | "search.previous" => {
+                let backward = self.editor.find_bar.backward != (event.name == "search.previous");
+                self.find_step(backward);
+            }
+            "search.word_under_cursor" | "search.word_under_cursor_backward" => {
+                let backward = event.name == "search.word_under_cursor_backward";
+                if self.editor.find_word_under_cursor(backward) {
+                    self.cursor_needs_scroll = true;
+                }
+                self.sync_find_focus();
+            }
+            // Search as the query is typed
+            "find.query_changed" => {
+                if self.editor.find_from_origin() {
+                    self.cursor_needs_scroll = true;
+                }
+                self.sync_find_focus();
+            }
+            "find.focus" => self.sync_find_focus(),
+            // Clicked on the bar, or a shortcut while one of its fields has focus
+            "find.toggle_case"
+            | "find.toggle_whole_word"
+            | "find.toggle_regex"
+            | "find.toggle_in_selection"
+                if event.source == "find" || self.editor.find_bar.focused_id().is_some() =>
+            {
+                self.editor.toggle_find_option(&event.name);
+                self.editor.refresh_find_matches();
+                self.request_redraw();
+            }
+            "find.replace_one" => {
+                if self.editor.replace_find_match() {
+                    self.update_window_title();
+                    self.cursor_needs_scroll = true;
+                }
+                self.sync_find_focus();
+            }
+            "find.replace_all" => {
+                if self.editor.replace_all_find_matches() > 0 {
+                    self.update_window_title();
+                }
+                self.sync_find_focus();
+            }
+            // Surround with: pick a template, which comes back as editor.surround
+            "editor.surround_with" => {
+                let language = self
+                    .editor
+                    .tab_manager
+                    .active_tab()
+                    .and_then(|tab| tab.syntax_arc.as_ref())
+                    .map_or("", |highlighter| highlighter.language());
+                let commands = surround::templates(language)
+                    .into_iter()
+                    .map(|template| {
+                        PaletteCommand::new(
+                            template.label,
+                            "editor.surround",
+                            json!({"body": template.body, "block": template.block}),
+                        )
+                    })
+                    .collect();
+                let palette_input_id = self.editor.command_palette.input().id;
+                self.show_overlay(
+                    |e| e.command_palette.show("Surround With", commands),
+                    WidgetId::CommandPalette,
+                    palette_input_id,
+                );
+            }
+            // Indentation of the active file, picked from the command palette
+            "editor.indentation" => {
+                let current = self.indent_style();
+                let mut commands = vec![PaletteCommand::new(
+                    "Indent Using Tabs",
+                    "editor.set_indentation",
+                    json!({}),
+                )];
+                commands.extend([2, 4, 8].map(|width| {
+                    PaletteCommand::new(
+                        format!("Indent Using {} Spaces", width),
+                        "editor.set_indentation",
+                        json!({"spaces": width}),
+                    )
+                }));
+                commands.push(PaletteCommand::new(
+                    "Detect Indentation from Content",
+                    "editor.set_indentation",
+                    json!({"detect": true}),
+                ));
+                let title = format!("Indentation ({})", current.label());
+                let palette_input_id = self.editor.command_palette.input().id;
+                self.show_overlay(
+                    |e| e.command_palette.show(&title, commands),
+                    WidgetId::CommandPalette,
+                    palette_input_id,
+                );
+            }
+            "editor.set_indentation" => {
+                let data: IndentationData = event_data::from_value(&event.data)?;
+                let tab = self.editor.tab_manager.active_tab_mut();
+                tab.indentation = if data.detect {
+                    indentation::detect(&tab.plugin.editor.view.doc.read().flatten_to_string())
+                } else {
+                    Some(data.spaces.map_or(IndentStyle::Tabs, IndentStyle::Spaces))
+                };
+            }
+            // Unicode inspector: details of the grapheme under the cursor, picked to copy
+            "editor.inspect_char" => {
+                let Some(grapheme) = self.editor.tab_manager.active_tab().and_then(|tab| {
+                    let editor = &tab.plugin.editor;
+                    editor.input.grapheme_at_cursor(&editor.view.doc)
+                }) else {
+                    return Ok(());
+                };
+                let commands = unicode_info::describe(&grapheme)
+                    .into_iter()
+                    .map(|line| {
+                        let data = json!({"text": line});
+                        PaletteCommand::new(line, "editor.copy_text", data)
+                    })
+                    .collect();
+                let title = format!("Character {}", grapheme.escape_debug());
+                let palette_input_id = self.editor.command_palette.input().id;
+                self.show_overlay(
+                    |e| e.command_palette.show(&title, commands),
+                    WidgetId::CommandPalette,
+                    palette_input_id,
+                );
+            }
+            // Insert any Unicode character or Nerd Font symbol, searched by name
+            "editor.insert_unicode" => {
+                let commands = tiny_font::named_chars()
+                    .iter()
+                    .map(|named| {
+                        PaletteCommand::new(
+                            unicode_info::picker_label(named),
+                            "editor.insert_text",
+                            json!({"text": named.ch.to_string()}),
+                        )
+                    })
+                    .collect();
+                let palette_input_id = self.editor.command_palette.input().id;
+                self.show_overlay(
+                    |e| e.command_palette.show("Insert Character", commands),
+                    WidgetId::CommandPalette,
+                    palette_input_id,
+                );
+            }
+            // Text transforms, picked by name and applied as editor.transform
+            "editor.transform_palette" => {
+                let commands = transforms::TRANSFORMS
+                    .iter()
+                    .map(|transform| {
+                        PaletteCommand::new(
+                            transform.label,
+                            "editor.transform",
+                            json!({"name": transform.name}),
+                        )
+                    })
+                    .collect();
+                let palette_input_id = self.editor.command_palette.input().id;
+                self.show_overlay(
+                    |e| e.command_palette.show("Transform", commands),
+                    WidgetId::CommandPalette,
+                    palette_input_id,
+                );
+            }
 
             // Component-emitted events
             "ui.redraw" => {
@@ -1231,12 +1810,51 @@ impl TinyApp {
             }
             "overlay.closed" => {
                 self.scroll_focus.clear_focus();
+                // A picked palette command applies to the editor, and the find bar is docked
+                // to it, so typing goes back there
+                let source = event.data.get("source").and_then(|v| v.as_str());
+                if matches!(source, Some("command_palette") | Some("find")) {
+                    if let Some(tab) = self.editor.tab_manager.active_tab() {
+                        self.focused_editable_view_id = Some(tab.plugin.editor.id);
+                    }
+                }
                 self.request_redraw();
             }
 
             // Editor events - delegate to components first, then main editor if not handled
             "editor.code_action" => self.editor.handle_code_action_request()?,
-            name if name.starts_with("editor.") => {
+            "editor.complete" => {
+                if self.editor.update_completion(true) {
+                    self.editor.ui_changed = true;
+                    self.request_redraw();
+                }
+            }
+            "completion.accept" => {
+                if self.editor.accept_completion() {
+                    self.update_window_title();
+                    self.cursor_needs_scroll = true;
+                }
+                self.editor.ui_changed = true;
+                self.request_redraw();
+            }
+            "editor.insert_char" if self.editor.pending_mark.is_some() => {
+                // Typed character names the pending mark
+                let data: InsertCharData = event_data::from_value(&event.data)?;
+                if let Some(mark) = data.char.chars().next() {
+                    self.navigate(|e| e.complete_mark_command(mark))?;
+                }
+                self.editor.pending_mark = None;
+            }
+            name if name.starts_with("editor.")
+                || name.starts_with("cursor.")
+                || name.starts_with("fold.")
+                || name.starts_with("case.")
+                || name.starts_with("indent.")
+                || name.starts_with("visual.") =>
+            {
+                // Any other editor event cancels a pending mark command
+                self.editor.pending_mark = None;
+
                 // First, try dispatching to overlay components (file picker, grep)
                 // They check is_active() internally and return Stop if they consumed the event
                 use crate::input::EventSubscriber;
@@ -1260,11 +1878,60 @@ impl TinyApp {
                     handled = true;
                 }
 
+                // Then the command palette
+                if !handled
+                    && self
+                        .editor
+                        .command_palette
+                        .handle_event(&event, &mut self.event_bus)
+                        == crate::input::PropagationControl::Stop
+                {
+                    handled = true;
+                }
+
+                // Then the find bar, while one of its fields has focus
+                if !handled
+                    && self
+                        .editor
+                        .find_bar
+                        .handle_event(&event, &mut self.event_bus)
+                        == crate::input::PropagationControl::Stop
+                {
+                    handled = true;
+                }
+
+                // Then the completion popup (it keeps Enter, Tab, Up/Down and Escape)
+                if !handled
+                    && self
+                        .editor
+                        .completion
+                        .handle_event(&event, &mut self.event_bus)
+                        == crate::input::PropagationControl::Stop
+                {
+                    handled = true;
+                }
+
                 // If no overlay handled it, route to main editor
                 if !handled {
+                    let word_config = self.word_config.clone();
+                    let auto_close_pairs = self.auto_close_pairs.clone();
+                    let indent_unit = self.indent_style().unit();
+                    let snippets = self.snippets.clone();
                     let (input_handler, doc, viewport) = self.get_focused_view_mut();
+                    input_handler.set_word_config(word_config);
+                    input_handler.set_auto_close_pairs(auto_close_pairs);
+                    input_handler.set_indent_unit(indent_unit);
+                    input_handler.set_snippets(snippets);
                     let action = input_handler.handle_event(&event, doc, &viewport);
 
+                    // Language server ranges refine the following expansions
+                    if event.name == "editor.extend_selection" {
+                        self.editor.request_selection_ranges();
+                    }
+                    if event.name.starts_with("fold.") {
+                        self.editor.request_folding_ranges();
+                    }
+
                     match action {
                         InputAction::Save => {
                             // Save only makes sense for main editor
@@ -1302,6 +1969,28 @@ impl TinyApp {
                             self.request_redraw();
                         }
                     }
+
+                    // Typing keeps completions up to date, landing on a snippet tab stop
+                    // with choices offers them, anything else closes them
+                    let completion_changed = match event.name.as_str() {
+                        "editor.insert_char" => self.editor.update_completion(false),
+                        "editor.delete_backward" if self.editor.completion.visible => {
+                            self.editor.update_completion(false)
+                        }
+                        "editor.insert_tab" | "editor.unindent"
+                            if self.editor.show_snippet_choices() =>
+                        {
+                            true
+                        }
+                        _ => {
+                            let visible = self.editor.completion.visible;
+                            self.editor.completion.hide();
+                            visible
+                        }
+                    };
+                    if completion_changed {
+                        self.editor.ui_changed = true;
+                    }
                 }
             }
 
@@ -1390,6 +2079,43 @@ impl TinyApp {
             self.request_redraw();
         }
 
+        self.sync_tab_width();
+
+        // Hide folded lines and wrap long ones in layout, hit-testing and the gutter
+        if let Some(cpu_renderer) = &mut self.cpu_renderer {
+            let space_width = cpu_renderer.viewport.metrics.space_width;
+            // A file's .editorconfig max_line_length beats the configured wrap column
+            let max_line_length = self
+                .editor
+                .tab_manager
+                .active_tab()
+                .and_then(|tab| tab.plugin.editorconfig.max_line_length);
+            let wrap_columns = self.soft_wrap.then(|| {
+                max_line_length.or(self.wrap_column).unwrap_or_else(|| {
+                    // Leave room for the cursor at the end of a full row
+                    ((cpu_renderer.editor_bounds.width.0 / space_width) as u32).saturating_sub(2)
+                })
+            });
+            let line_mode = match wrap_columns {
+                Some(columns) => LineMode::SoftWrap {
+                    wrap_width: columns as f32 * space_width,
+                },
+                None => LineMode::default(),
+            };
+
+            let tab = self.editor.tab_manager.active_tab_mut();
+            let editor = &mut tab.plugin.editor;
+            editor.input.set_soft_wrap(wrap_columns, self.wrap_indent);
+            let display_map = editor.input.update_display_map(&editor.view.doc).clone();
+            let markers = editor.input.fold_markers(&editor.view.doc);
+            editor.view.viewport.display_map = display_map.clone();
+            editor.view.viewport.line_mode = line_mode;
+            tab.line_numbers
+                .set_display_map(display_map.clone(), markers);
+            cpu_renderer.viewport.line_mode = line_mode;
+            cpu_renderer.set_display_map(display_map);
+        }
+
         // Handle cursor scroll when selection actually changed
         if self.cursor_needs_scroll {
             self.cursor_needs_scroll = false;
@@ -1421,6 +2147,18 @@ impl TinyApp {
             }
         }
 
+        self.sync_ime();
+        self.sync_text_backgrounds();
+        self.editor.update_word_index();
+        self.sync_completion();
+
+        // Preview where dragged text will be dropped
+        if let (Some(cpu_renderer), Some(tab)) =
+            (&mut self.cpu_renderer, self.editor.tab_manager.active_tab())
+        {
+            cpu_renderer.set_drop_caret(tab.plugin.editor.input.drop_caret());
+        }
+
         // Check if we have all required components
         if self.window.is_none() || self.gpu_renderer.is_none() || self.cpu_renderer.is_none() {
             return Ok(());
@@ -1450,6 +2188,16 @@ impl TinyApp {
 
             // Update grep bounds based on viewport (overlay mode)
             self.editor.grep.calculate_bounds(&cpu_renderer.viewport);
+
+            // Update command palette bounds based on viewport (overlay mode)
+            self.editor
+                .command_palette
+                .calculate_bounds(&cpu_renderer.viewport);
+
+            // The find bar sits at the top of the editor
+            self.editor
+                .find_bar
+                .calculate_bounds(&cpu_renderer.viewport, cpu_renderer.editor_bounds);
         }
 
         // Update plugins for editor
@@ -1488,6 +2236,10 @@ impl TinyApp {
             }
 
             // Sync cursor/selection state to main editor's plugins
+            // (carets render hollow while an overlay input has focus)
+            tab.plugin.editor.has_focus = self
+                .focused_editable_view_id
+                .map_or(true, |id| id == tab.plugin.editor.id);
             tab.plugin.editor.sync_plugins();
 
             // Set line numbers plugin with fresh document reference
@@ -1531,6 +2283,36 @@ impl TinyApp {
             }
             cpu_renderer.set_grep_plugin(&mut self.editor.grep);
 
+            // Propagate metrics and font system to command palette input
+            self.editor
+                .command_palette
+                .input_mut()
+                .view
+                .viewport
+                .update_metrics(&self.text_metrics);
+            if let Some(ref font_system) = self.font_system {
+                self.editor
+                    .command_palette
+                    .input_mut()
+                    .view
+                    .viewport
+                    .set_font_system(font_system.clone());
+            }
+            cpu_renderer.set_command_palette_plugin(&mut self.editor.command_palette);
+
+            // Propagate metrics and font system to the find bar inputs
+            for input in [
+                &mut self.editor.find_bar.query,
+                &mut self.editor.find_bar.replacement,
+            ] {
+                input.view.viewport.update_metrics(&self.text_metrics);
+                if let Some(ref font_system) = self.font_system {
+                    input.view.viewport.set_font_system(font_system.clone());
+                }
+            }
+            cpu_renderer.set_find_bar_plugin(&mut self.editor.find_bar);
+            cpu_renderer.set_completion_plugin(&mut self.editor.completion);
+
             // Mark renderer UI dirty if UI changed
             if self.editor.ui_changed {
                 cpu_renderer.mark_ui_dirty();
@@ -1718,6 +2500,8 @@ impl ApplicationHandler for TinyApp {
                     .create_window(window_attributes)
                     .expect("Failed to create window"),
             );
+            // Receive composed text (CJK input, dead keys) as Ime events
+            window.set_ime_allowed(true);
 
             // Setup GPU renderer
             let mut gpu_renderer = {
@@ -1772,6 +2556,7 @@ impl ApplicationHandler for TinyApp {
             let config = crate::config::AppConfig::load().unwrap_or_default();
             font_system.set_default_weight(config.editor.font_weight);
             font_system.set_default_italic(config.editor.font_italic);
+            font_system.set_tab_width(self.tab_width as usize);
 
             // Get scale factor for high DPI displays
             let scale_factor = window.scale_factor() as f32;
@@ -1792,6 +2577,7 @@ impl ApplicationHandler for TinyApp {
             cpu_renderer.set_font_system(font_system.clone());
             // Set line height after font_system (to override auto-calculated height)
             cpu_renderer.set_line_height(self.text_metrics.line_height);
+            cpu_renderer.set_tab_stops(self.text_metrics.tab_stops);
             // Set theme for decoration color lookup
             cpu_renderer.set_theme(theme.clone());
 
@@ -1921,6 +2707,11 @@ impl ApplicationHandler for TinyApp {
                     return;
                 }
 
+                // Keys belong to the input method while it's composing
+                if self.ime.is_composing() {
+                    return;
+                }
+
                 // Only handle key presses below
                 if key_event.state == ElementState::Pressed {
                     // Capture original character BEFORE lowercasing (preserves shift for case/symbols)
@@ -1992,6 +2783,35 @@ impl ApplicationHandler for TinyApp {
                 self.request_redraw();
             }
 
+            WindowEvent::DroppedFile(path) => {
+                // Files dropped onto the window open in tabs
+                if path.is_file() {
+                    self.event_bus.emit(
+                        "file.open",
+                        json!({ "path": path.to_string_lossy() }),
+                        10,
+                        "winit",
+                    );
+                    // Handled with the rest of the queue at the start of the next frame
+                    self.request_redraw();
+                }
+            }
+
+            WindowEvent::Ime(ime) => {
+                let (name, data) = match self.ime.handle(winit_adapter::convert_ime(&ime)) {
+                    ImeAction::None => return,
+                    ImeAction::Preedit { text, cursor } => (
+                        "editor.ime_preedit",
+                        json!({ "text": text, "cursor": cursor }),
+                    ),
+                    ImeAction::Commit(text) => ("editor.ime_commit", json!({ "text": text })),
+                    ImeAction::Cancel => ("editor.ime_cancel", json!({})),
+                };
+                self.event_bus.emit(name, data, 10, "ime");
+                // Handled with the rest of the queue at the start of the next frame
+                self.request_redraw();
+            }
+
             WindowEvent::ModifiersChanged(new_modifiers) => {
                 // Convert winit modifiers to accelerator format
                 self.modifiers = winit_adapter::convert_modifiers(&new_modifiers);
@@ -2012,13 +2832,37 @@ impl ApplicationHandler for TinyApp {
 
                 match state {
                     ElementState::Pressed => {
+                        // Clicking keeps the preedit as typed; restart the input method so
+                        // it doesn't commit it again
+                        if self.ime.is_composing() {
+                            if let Some(window) = &self.window {
+                                window.set_ime_allowed(false);
+                                window.set_ime_allowed(true);
+                            }
+                        }
+
                         // Try to match shortcuts first
                         let event_names = self.shortcuts.match_input(&self.modifiers, &trigger);
 
                         if !event_names.is_empty() {
                             // Shortcut matched (e.g., "cmd+click" or "click click")
+                            // Include the editor-local mouse position (e.g., for alt+click cursors)
+                            let data = self
+                                .mouse_position
+                                .and_then(|position| self.physical_to_logical_point(position))
+                                .map(|point| {
+                                    let local = match &self.cpu_renderer {
+                                        Some(cpu_renderer) => {
+                                            cpu_renderer.screen_to_editor_local(point)
+                                        }
+                                        None => point,
+                                    };
+                                    json!({ "x": local.x.0, "y": local.y.0 })
+                                })
+                                .unwrap_or_else(|| json!({}));
                             for event_name in event_names {
-                                self.event_bus.emit(event_name, json!({}), 10, "shortcuts");
+                                self.event_bus
+                                    .emit(event_name, data.clone(), 10, "shortcuts");
                             }
                         } else {
                             // No shortcut - emit default mouse press event
@@ -2199,7 +3043,12 @@ impl ApplicationHandler for TinyApp {
                         }
                         self.mouse_pressed = false;
                         self.mouse_drag_start = None;
-                        self.event_bus.emit("mouse.release", json!({}), 10, "winit");
+                        self.event_bus.emit(
+                            "mouse.release",
+                            json!({ "alt": self.modifiers.alt }),
+                            10,
+                            "winit",
+                        );
                     }
                 }
             }
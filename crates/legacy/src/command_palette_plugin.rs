@@ -0,0 +1,237 @@
+//! Command palette plugin - searchable list of commands, each emitting an event
+
+use crate::coordinates::Viewport;
+use crate::input::{Event, EventSubscriber, PropagationControl};
+use crate::{overlay_picker::OverlayPicker, scroll::Scrollable, Widget};
+use serde_json::Value;
+use tiny_core::tree::{Point, Rect};
+use tiny_sdk::Plugin;
+
+/// A palette entry: picking it emits `event` with `data`
+#[derive(Clone, Debug)]
+pub struct PaletteCommand {
+    pub label: String,
+    pub event: String,
+    pub data: Value,
+}
+
+impl PaletteCommand {
+    pub fn new(label: impl Into<String>, event: impl Into<String>, data: Value) -> Self {
+        Self {
+            label: label.into(),
+            event: event.into(),
+            data,
+        }
+    }
+}
+
+/// Command palette for picking one of a list of commands (surround with, transforms, ...)
+pub struct CommandPalettePlugin {
+    pub picker: OverlayPicker<PaletteCommand>,
+    pub visible: bool,
+}
+
+impl CommandPalettePlugin {
+    /// Get the input field for cursor/selection routing
+    pub fn input(&self) -> &crate::editable_text_view::EditableTextView {
+        &self.picker.dropdown.input
+    }
+
+    /// Get mutable input field
+    pub fn input_mut(&mut self) -> &mut crate::editable_text_view::EditableTextView {
+        &mut self.picker.dropdown.input
+    }
+
+    pub fn new() -> Self {
+        let format_fn = |command: &PaletteCommand| command.label.clone();
+
+        // Search function (substring filter, earlier matches first)
+        let search_fn = |query: &str, items: &[PaletteCommand]| {
+            let query_lower = query.to_lowercase();
+            let mut results: Vec<(usize, &PaletteCommand)> = items
+                .iter()
+                .filter_map(|command| {
+                    let position = command.label.to_lowercase().find(&query_lower)?;
+                    Some((position, command))
+                })
+                .collect();
+            results.sort_by_key(|(position, _)| *position);
+            results
+                .into_iter()
+                .map(|(_, command)| command.clone())
+                .collect()
+        };
+
+        Self {
+            picker: OverlayPicker::new(format_fn, search_fn),
+            visible: false,
+        }
+    }
+
+    /// Show the palette with a list of commands
+    pub fn show(&mut self, title: &str, commands: Vec<PaletteCommand>) {
+        self.visible = true;
+        self.picker.show_with_title(commands, title);
+    }
+
+    pub fn hide(&mut self) {
+        self.visible = false;
+        self.picker.hide();
+    }
+
+    pub fn move_up(&mut self) {
+        self.picker.move_up();
+    }
+
+    pub fn move_down(&mut self) {
+        self.picker.move_down();
+    }
+
+    pub fn selected_command(&self) -> Option<&PaletteCommand> {
+        self.picker.selected_item()
+    }
+}
+
+impl EventSubscriber for CommandPalettePlugin {
+    fn handle_event(
+        &mut self,
+        event: &Event,
+        event_bus: &mut crate::input::EventBus,
+    ) -> PropagationControl {
+        if !self.visible {
+            return PropagationControl::Continue; // Not active, pass through
+        }
+
+        use serde_json::json;
+
+        match event.name.as_str() {
+            // Single-line input - Enter picks the command instead of inserting a newline
+            "editor.insert_newline" => {
+                event_bus.emit("action.submit", json!({}), 10, "command_palette");
+                PropagationControl::Stop
+            }
+            // Handle text editing events internally
+            event_name if event_name.starts_with("editor.") => {
+                let input = self.input_mut();
+                let text_before = input.view.text();
+
+                let _action =
+                    input
+                        .input
+                        .handle_event(event, &input.view.doc, &input.view.viewport);
+
+                let text_after = input.view.text();
+                if text_before != text_after {
+                    let query = text_after.to_string();
+                    self.picker.trigger_filter(query);
+                    event_bus.emit("ui.redraw", json!({}), 20, "command_palette");
+                }
+
+                PropagationControl::Stop
+            }
+            "navigate.up" => {
+                self.move_up();
+                event_bus.emit("ui.redraw", json!({}), 20, "command_palette");
+                PropagationControl::Stop
+            }
+            "navigate.down" => {
+                self.move_down();
+                event_bus.emit("ui.redraw", json!({}), 20, "command_palette");
+                PropagationControl::Stop
+            }
+            "action.cancel" => {
+                self.hide();
+                event_bus.emit(
+                    "overlay.closed",
+                    json!({"source": "command_palette"}),
+                    10,
+                    "command_palette",
+                );
+                PropagationControl::Stop
+            }
+            "action.submit" => {
+                if let Some(command) = self.selected_command().cloned() {
+                    self.hide();
+                    event_bus.emit(
+                        "overlay.closed",
+                        json!({"source": "command_palette"}),
+                        10,
+                        "command_palette",
+                    );
+                    event_bus.emit(command.event, command.data, 10, "command_palette");
+                }
+                PropagationControl::Stop
+            }
+            "app.mouse.scroll" => {
+                let delta_y = event
+                    .data
+                    .get("delta_y")
+                    .and_then(|v| v.as_f64())
+                    .unwrap_or(0.0) as f32;
+
+                self.picker.dropdown.handle_scroll(delta_y);
+                event_bus.emit("ui.redraw", json!({}), 20, "command_palette");
+                PropagationControl::Stop
+            }
+            "app.mouse.move" => {
+                // Handle mouse hover to highlight items
+                let x = event.data.get("x").and_then(|v| v.as_f64()).unwrap_or(0.0) as f32;
+                let y = event.data.get("y").and_then(|v| v.as_f64()).unwrap_or(0.0) as f32;
+
+                let bounds = self.picker.get_bounds();
+                let is_over_picker = x >= bounds.x.0
+                    && x < bounds.x.0 + bounds.width.0
+                    && y >= bounds.y.0
+                    && y < bounds.y.0 + bounds.height.0;
+
+                if is_over_picker {
+                    if self.picker.handle_hover(x, y) {
+                        event_bus.emit("ui.redraw", json!({}), 20, "command_palette");
+                    }
+                    PropagationControl::Stop
+                } else {
+                    PropagationControl::Continue
+                }
+            }
+            _ => PropagationControl::Continue,
+        }
+    }
+
+    fn priority(&self) -> i32 {
+        100 // High priority (overlays filter events before main editor)
+    }
+
+    fn is_active(&self) -> bool {
+        self.visible
+    }
+}
+
+tiny_sdk::plugin! {
+    CommandPalettePlugin {
+        name: "command_palette",
+        version: "1.0.0",
+        z_index: 1000,
+        traits: [Init, Paint],
+        defaults: [Init, Paint],
+    }
+}
+
+impl Scrollable for CommandPalettePlugin {
+    fn get_scroll(&self) -> Point {
+        self.picker.get_scroll()
+    }
+
+    fn set_scroll(&mut self, scroll: Point) {
+        self.picker.set_scroll(scroll);
+    }
+
+    fn handle_scroll(&mut self, delta: Point, viewport: &Viewport, widget_bounds: Rect) -> bool {
+        self.picker.handle_scroll(delta, viewport, widget_bounds)
+    }
+
+    fn get_content_bounds(&self, viewport: &Viewport) -> Rect {
+        self.picker.get_content_bounds(viewport)
+    }
+}
+
+tiny_ui::impl_widget_delegate!(CommandPalettePlugin, picker);
@@ -0,0 +1,163 @@
+//! Toggle line and block comments
+//!
+//! Pure text logic: callers pick the comment tokens for the language at the
+//! edit location and apply the returned replacements.
+
+use std::ops::Range;
+
+/// A replacement within one line of a block of lines
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineEdit {
+    /// Index into the lines passed in
+    pub line: usize,
+    /// Byte range within that line
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+fn indent_len(line: &str) -> usize {
+    line.len() - line.trim_start_matches([' ', '\t']).len()
+}
+
+fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+/// Index and indentation of the first non-blank line
+pub fn first_content(lines: &[&str]) -> Option<(usize, usize)> {
+    lines
+        .iter()
+        .position(|line| !is_blank(line))
+        .map(|i| (i, indent_len(lines[i])))
+}
+
+/// Comment or uncomment lines with a line comment token
+/// Lines are uncommented only if every non-blank line is already commented.
+/// New comments are aligned at the smallest indentation; blank lines are skipped.
+pub fn toggle_line_comments(lines: &[&str], token: &str) -> Vec<LineEdit> {
+    let content: Vec<(usize, &str)> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !is_blank(line))
+        .map(|(i, line)| (i, *line))
+        .collect();
+
+    let all_commented = !content.is_empty()
+        && content
+            .iter()
+            .all(|(_, line)| line[indent_len(line)..].starts_with(token));
+
+    if all_commented {
+        content
+            .iter()
+            .map(|&(i, line)| {
+                let start = indent_len(line);
+                let mut end = start + token.len();
+                if line[end..].starts_with(' ') {
+                    end += 1;
+                }
+                LineEdit {
+                    line: i,
+                    range: start..end,
+                    text: String::new(),
+                }
+            })
+            .collect()
+    } else {
+        let column = content
+            .iter()
+            .map(|(_, line)| indent_len(line))
+            .min()
+            .unwrap_or(0);
+        content
+            .iter()
+            .map(|&(i, _)| LineEdit {
+                line: i,
+                range: column..column,
+                text: format!("{} ", token),
+            })
+            .collect()
+    }
+}
+
+/// Wrap text in a block comment, or unwrap it if it already is one
+/// Surrounding whitespace stays outside the comment. Ranges are relative to `text`.
+pub fn toggle_block_comment(text: &str, open: &str, close: &str) -> Vec<(Range<usize>, String)> {
+    let start = text.len() - text.trim_start().len();
+    let end = text.trim_end().len();
+    if start >= end {
+        return Vec::new();
+    }
+
+    let inner = &text[start..end];
+    if inner.len() >= open.len() + close.len() && inner.starts_with(open) && inner.ends_with(close)
+    {
+        let mut open_end = start + open.len();
+        let mut close_start = end - close.len();
+        if close_start > open_end && text[open_end..].starts_with(' ') {
+            open_end += 1;
+        }
+        if close_start > open_end && text[..close_start].ends_with(' ') {
+            close_start -= 1;
+        }
+        vec![
+            (start..open_end, String::new()),
+            (close_start..end, String::new()),
+        ]
+    } else {
+        vec![
+            (start..start, format!("{} ", open)),
+            (end..end, format!(" {}", close)),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(lines: &[&str], edits: &[LineEdit]) -> Vec<String> {
+        let mut result: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+        for edit in edits.iter().rev() {
+            result[edit.line].replace_range(edit.range.clone(), &edit.text);
+        }
+        result
+    }
+
+    #[test]
+    fn test_line_comment_aligns_and_skips_blank_lines() {
+        let lines = ["    fn a() {", "", "        b();", "    }"];
+        let commented = apply(&lines, &toggle_line_comments(&lines, "//"));
+        assert_eq!(
+            commented,
+            vec!["    // fn a() {", "", "    //     b();", "    // }"]
+        );
+
+        let refs: Vec<&str> = commented.iter().map(String::as_str).collect();
+        let uncommented = apply(&refs, &toggle_line_comments(&refs, "//"));
+        assert_eq!(uncommented, lines);
+    }
+
+    #[test]
+    fn test_partially_commented_lines_get_commented() {
+        let lines = ["// a", "b"];
+        let result = apply(&lines, &toggle_line_comments(&lines, "//"));
+        assert_eq!(result, vec!["// // a", "// b"]);
+    }
+
+    #[test]
+    fn test_block_comment_round_trip() {
+        let text = "  let x = 1;";
+        let mut wrapped = text.to_string();
+        for (range, replacement) in toggle_block_comment(text, "/*", "*/").iter().rev() {
+            wrapped.replace_range(range.clone(), replacement);
+        }
+        assert_eq!(wrapped, "  /* let x = 1; */");
+
+        let mut unwrapped = wrapped.clone();
+        for (range, replacement) in toggle_block_comment(&wrapped, "/*", "*/").iter().rev() {
+            unwrapped.replace_range(range.clone(), replacement);
+        }
+        assert_eq!(unwrapped, text);
+    }
+}
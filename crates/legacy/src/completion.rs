@@ -0,0 +1,307 @@
+//! Buffer-word completion - identifiers from the open documents, ranked for the cursor
+//!
+//! Every open document's identifiers are counted once when it's first seen, then kept
+//! up to date from the edits the InputHandler applies: only the lines an edit batch
+//! touched are re-counted. Candidates are ranked by fuzzy score, with a bonus for
+//! words used near the cursor.
+
+use ahash::AHashMap as HashMap;
+use nucleo::pattern::{CaseMatching, Normalization, Pattern};
+use nucleo::{Config, Matcher, Utf32Str};
+use std::ops::Range;
+use std::sync::Arc;
+use tiny_core::tree::{Content, Edit, Tree};
+
+/// Shortest identifier worth offering
+const MIN_WORD_LEN: usize = 3;
+
+/// Most candidates offered at once
+const MAX_ITEMS: usize = 50;
+
+/// Lines around the cursor whose words get a proximity bonus
+pub const NEARBY_LINES: u32 = 50;
+
+/// Score bonus per line closer than `NEARBY_LINES` to the cursor
+const PROXIMITY_WEIGHT: u32 = 2;
+
+/// Characters identifiers are made of
+pub fn is_word_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+/// Identifiers in a text: runs of word characters not starting with a digit
+pub fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|ch: char| !is_word_char(ch)).filter(|word| {
+        word.chars().count() >= MIN_WORD_LEN && !word.starts_with(|ch: char| ch.is_ascii_digit())
+    })
+}
+
+/// The partial word ending at the end of `text` (what the user is typing)
+pub fn prefix(text: &str) -> &str {
+    let start = text
+        .char_indices()
+        .rev()
+        .take_while(|&(_, ch)| is_word_char(ch))
+        .last()
+        .map_or(text.len(), |(i, _)| i);
+    &text[start..]
+}
+
+fn content_len(content: &Content) -> usize {
+    match content {
+        Content::Text(s) => s.len(),
+        Content::Spatial(_) => 0,
+    }
+}
+
+/// Byte span a batch of edits changed, before and after: (old span, new span)
+/// Everything before the spans and after them is the same in both versions
+pub fn changed_span(edits: &[Edit]) -> Option<(Range<usize>, Range<usize>)> {
+    let mut span: Option<(usize, usize)> = None;
+    let mut delta: isize = 0;
+    for edit in edits {
+        let (start, removed, inserted) = match edit {
+            Edit::Insert { pos, content } => (*pos, 0, content_len(content)),
+            Edit::Delete { range } => (range.start, range.len(), 0),
+            Edit::Replace { range, content } => (range.start, range.len(), content_len(content)),
+        };
+        let inserted_end = start + inserted;
+        span = Some(match span {
+            None => (start, inserted_end),
+            Some((lo, hi)) => {
+                // Carry the end of the span through this edit
+                let hi = if hi >= start + removed {
+                    hi - removed + inserted
+                } else if hi > start {
+                    inserted_end
+                } else {
+                    hi
+                };
+                (lo.min(start), hi.max(inserted_end))
+            }
+        });
+        delta += inserted as isize - removed as isize;
+    }
+    let (lo, hi) = span?;
+    let old_hi = (hi as isize - delta).max(lo as isize) as usize;
+    Some((lo..old_hi, lo..hi))
+}
+
+/// Byte range of the whole lines around `range`
+fn line_span(tree: &Tree, range: Range<usize>) -> Range<usize> {
+    let start = tree
+        .line_to_byte(tree.byte_to_line(range.start))
+        .unwrap_or(0);
+    let end = tree
+        .line_to_byte(tree.byte_to_line(range.end) + 1)
+        .unwrap_or_else(|| tree.byte_count());
+    start..end.max(start)
+}
+
+/// Words within `NEARBY_LINES` of a line, with their distance in lines
+pub fn nearby_words(tree: &Tree, line: u32) -> HashMap<String, u32> {
+    let mut nearby = HashMap::new();
+    let first = line.saturating_sub(NEARBY_LINES);
+    let last = (line + NEARBY_LINES).min(tree.line_count().saturating_sub(1));
+    for current in first..=last {
+        let distance = current.abs_diff(line);
+        for word in words(&tree.line_text(current)) {
+            let entry = nearby.entry(word.to_string()).or_insert(distance);
+            *entry = (*entry).min(distance);
+        }
+    }
+    nearby
+}
+
+/// Word counts of one document and the tree they were counted from
+struct Document {
+    tree: Arc<Tree>,
+    counts: HashMap<String, u32>,
+}
+
+impl Document {
+    fn new(tree: Arc<Tree>) -> Self {
+        let mut document = Self {
+            tree: tree.clone(),
+            counts: HashMap::new(),
+        };
+        document.add(&tree.flatten_to_string());
+        document
+    }
+
+    fn add(&mut self, text: &str) {
+        for word in words(text) {
+            *self.counts.entry(word.to_string()).or_default() += 1;
+        }
+    }
+
+    fn remove(&mut self, text: &str) {
+        for word in words(text) {
+            if let Some(count) = self.counts.get_mut(word) {
+                *count -= 1;
+                if *count == 0 {
+                    self.counts.remove(word);
+                }
+            }
+        }
+    }
+
+    /// Re-count the lines the edits changed, false if they don't explain the new tree
+    fn apply(&mut self, tree: Arc<Tree>, edits: &[Edit]) -> bool {
+        let Some((old, new)) = changed_span(edits) else {
+            return false;
+        };
+        let (old_tree, new_tree) = (&self.tree, &tree);
+        if old.end > old_tree.byte_count()
+            || new.end > new_tree.byte_count()
+            || old_tree.byte_count() - old.len() != new_tree.byte_count() - new.len()
+        {
+            return false;
+        }
+
+        let removed = old_tree.get_text_slice(line_span(old_tree, old));
+        let added = new_tree.get_text_slice(line_span(new_tree, new));
+        self.remove(&removed);
+        self.add(&added);
+        self.tree = tree;
+        true
+    }
+}
+
+/// Identifiers of all open documents, by document id
+#[derive(Default)]
+pub struct WordIndex {
+    documents: HashMap<u64, Document>,
+}
+
+impl WordIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bring a document up to date
+    /// `edits` are the edits applied since it was last updated, None if its tree was
+    /// replaced (undo, reload) and it has to be counted again
+    pub fn update(&mut self, id: u64, tree: Arc<Tree>, edits: Option<&[Edit]>) {
+        if let (Some(document), Some(edits)) = (self.documents.get_mut(&id), edits) {
+            if document.tree.version == tree.version || document.apply(tree.clone(), edits) {
+                return;
+            }
+        }
+        self.documents.insert(id, Document::new(tree));
+    }
+
+    /// Forget documents that are no longer open
+    pub fn retain(&mut self, ids: &[u64]) {
+        self.documents.retain(|id, _| ids.contains(id));
+    }
+
+    /// Number of times a word occurs in the open documents
+    pub fn count(&self, word: &str) -> u32 {
+        self.documents
+            .values()
+            .filter_map(|document| document.counts.get(word))
+            .sum()
+    }
+
+    /// Words completing `prefix`, best first
+    /// `typed` is the whole word at the cursor: it's only offered if it occurs elsewhere
+    /// too. `nearby` gives the distance in lines of words around the cursor.
+    pub fn complete(
+        &self,
+        prefix: &str,
+        typed: &str,
+        nearby: &HashMap<String, u32>,
+    ) -> Vec<String> {
+        let Some(first) = prefix.chars().next() else {
+            return Vec::new();
+        };
+
+        let mut counts: HashMap<&str, u32> = HashMap::new();
+        for document in self.documents.values() {
+            for (word, count) in &document.counts {
+                *counts.entry(word.as_str()).or_default() += count;
+            }
+        }
+
+        let pattern = Pattern::parse(prefix, CaseMatching::Smart, Normalization::Smart);
+        let mut matcher = Matcher::new(Config::DEFAULT);
+        let mut buf = Vec::new();
+        let mut ranked: Vec<(u32, u32, &str)> = counts
+            .into_iter()
+            .filter(|&(word, count)| word != prefix && (word != typed || count > 1))
+            // Completions start like what's typed; the rest may match fuzzily
+            .filter(|&(word, _)| {
+                word.chars()
+                    .next()
+                    .is_some_and(|ch| ch.to_lowercase().eq(first.to_lowercase()))
+            })
+            .filter_map(|(word, _)| {
+                let score = pattern.score(Utf32Str::new(word, &mut buf), &mut matcher)?;
+                let distance = nearby.get(word).copied().unwrap_or(u32::MAX);
+                let bonus = NEARBY_LINES.saturating_sub(distance) * PROXIMITY_WEIGHT;
+                Some((score + bonus, distance, word))
+            })
+            .collect();
+
+        ranked.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)).then(a.2.cmp(b.2)));
+        ranked
+            .into_iter()
+            .take(MAX_ITEMS)
+            .map(|(_, _, word)| word.to_string())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(pos: usize, text: &str) -> Edit {
+        Edit::Insert {
+            pos,
+            content: Content::Text(text.to_string()),
+        }
+    }
+
+    #[test]
+    fn words_are_identifiers() {
+        let found: Vec<&str> = words("let foo_bar = 42 + x1y2z3(émile, 3rd, ab);").collect();
+        assert_eq!(found, vec!["let", "foo_bar", "x1y2z3", "émile"]);
+        assert_eq!(prefix("let x = foo_b"), "foo_b");
+        assert_eq!(prefix("call("), "");
+    }
+
+    #[test]
+    fn changed_span_covers_a_batch() {
+        // Edits are queued from the end of the document backwards
+        let edits = vec![
+            insert(20, "xx"),
+            Edit::Delete { range: 5..8 },
+            insert(2, "y"),
+        ];
+        assert_eq!(changed_span(&edits), Some((2..20, 2..20)));
+        assert_eq!(changed_span(&[insert(4, "abc")]), Some((4..4, 4..7)));
+        assert_eq!(changed_span(&[]), None);
+    }
+
+    #[test]
+    fn index_follows_edits() {
+        let before = Arc::new(Tree::from_str("alpha beta\ngamma\n"));
+        let mut index = WordIndex::new();
+        index.update(1, before.clone(), None);
+        assert_eq!(index.count("beta"), 1);
+
+        // "beta" -> "betamax", a new line with another "alpha"
+        let edits = vec![insert(11, "alpha\n"), insert(10, "max")];
+        let after = Arc::new(before.apply_edits(&edits));
+        index.update(1, after, Some(&edits));
+        assert_eq!(index.count("beta"), 0);
+        assert_eq!(index.count("betamax"), 1);
+        assert_eq!(index.count("alpha"), 2);
+        assert_eq!(index.count("gamma"), 1);
+
+        index.retain(&[]);
+        assert_eq!(index.count("alpha"), 0);
+    }
+}
@@ -0,0 +1,222 @@
+//! Completion popup - candidates for the word at the cursor, picked without leaving the editor
+//!
+//! Unlike the pickers it has no input field: typing goes on in the editor and the popup
+//! follows the cursor. Items come from the buffer-word index today; language servers
+//! provide the same items, and a snippet tab stop with choices offers those.
+
+use crate::coordinates::Viewport;
+use crate::filterable_dropdown::FilterableDropdown;
+use crate::input::{Event, EventSubscriber, PropagationControl};
+use std::sync::Arc;
+use tiny_core::tree::Rect;
+use tiny_font::SharedFontSystem;
+use tiny_sdk::LogicalPixels;
+
+/// A completion candidate: `label` is shown, `insert_text` replaces the typed word
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompletionItem {
+    pub label: String,
+    pub insert_text: String,
+    /// The language server's item, inserted with its own edit range and snippet format
+    pub lsp: Option<lsp_types::CompletionItem>,
+    /// A choice for the snippet tab stop being filled in, replacing all of its ranges
+    pub snippet_choice: bool,
+}
+
+impl CompletionItem {
+    /// Candidate that inserts a word as it's shown
+    pub fn word(word: impl Into<String>) -> Self {
+        let word = word.into();
+        Self {
+            label: word.clone(),
+            insert_text: word,
+            lsp: None,
+            snippet_choice: false,
+        }
+    }
+
+    /// Candidate for a snippet tab stop with choices (`${1|a,b|}`)
+    pub fn snippet_choice(choice: impl Into<String>) -> Self {
+        Self {
+            snippet_choice: true,
+            ..Self::word(choice)
+        }
+    }
+
+    /// Candidate from a language server
+    pub fn lsp(item: lsp_types::CompletionItem) -> Self {
+        Self {
+            label: item.label.clone(),
+            insert_text: item
+                .insert_text
+                .clone()
+                .unwrap_or_else(|| item.label.clone()),
+            lsp: Some(item),
+            snippet_choice: false,
+        }
+    }
+}
+
+/// Popup list of completions below the cursor
+pub struct CompletionPlugin {
+    pub dropdown: FilterableDropdown<CompletionItem>,
+    pub visible: bool,
+    /// Cursor the popup is attached to (screen coordinates, one line tall)
+    anchor: Rect,
+}
+
+impl CompletionPlugin {
+    pub fn new() -> Self {
+        Self {
+            dropdown: FilterableDropdown::new(|item: &CompletionItem| item.label.clone()),
+            visible: false,
+            anchor: Rect::default(),
+        }
+    }
+
+    /// Show (or update) the popup with a list of candidates, the first one selected
+    pub fn show(&mut self, items: Vec<CompletionItem>) {
+        if self.visible && self.dropdown.items() == items.as_slice() {
+            return;
+        }
+        self.visible = true;
+        self.dropdown.show(items);
+        // Typing stays in the editor
+        self.dropdown.input.set_focused(false);
+    }
+
+    pub fn hide(&mut self) {
+        if self.visible {
+            self.visible = false;
+            self.dropdown.hide();
+        }
+    }
+
+    /// Attach the popup to the cursor at (x, y) with the given line height
+    /// Returns whether it moved
+    pub fn set_anchor(&mut self, x: f32, y: f32, line_height: f32) -> bool {
+        let anchor = Rect {
+            x: LogicalPixels(x),
+            y: LogicalPixels(y),
+            width: LogicalPixels(1.0),
+            height: LogicalPixels(line_height),
+        };
+        let moved = anchor != self.anchor;
+        self.anchor = anchor;
+        moved
+    }
+
+    /// Select the candidate at a screen position, false if it's outside the popup
+    pub fn select_at(&mut self, x: f32, y: f32) -> bool {
+        let bounds = self.get_bounds();
+        let inside = x >= bounds.x.0
+            && x < bounds.x.0 + bounds.width.0
+            && y >= bounds.y.0
+            && y < bounds.y.0 + bounds.height.0;
+        if inside {
+            self.dropdown.handle_hover(x, y);
+        }
+        inside
+    }
+
+    pub fn move_up(&mut self) {
+        self.dropdown.move_selection_up();
+    }
+
+    pub fn move_down(&mut self) {
+        self.dropdown.move_selection_down();
+    }
+
+    pub fn selected_item(&self) -> Option<&CompletionItem> {
+        self.dropdown.items().get(self.dropdown.selected_index())
+    }
+
+    /// Calculate bounds next to the cursor
+    pub fn calculate_bounds(&mut self, viewport: &Viewport) {
+        self.dropdown
+            .calculate_anchored_bounds(viewport, self.anchor);
+    }
+
+    pub fn get_bounds(&self) -> Rect {
+        self.dropdown.bounds()
+    }
+
+    /// Collect glyphs for rendering (the results list only)
+    pub fn collect_glyphs(
+        &mut self,
+        font_system: &Arc<SharedFontSystem>,
+    ) -> Vec<(Vec<tiny_sdk::GlyphInstance>, (u32, u32, u32, u32))> {
+        if !self.visible {
+            return Vec::new();
+        }
+
+        self.dropdown.results.update_layout(font_system);
+        let glyphs = self.dropdown.results.collect_glyphs(font_system);
+        if glyphs.is_empty() {
+            return Vec::new();
+        }
+        vec![(glyphs, self.dropdown.results.get_scissor_rect())]
+    }
+
+    /// Collect background rects (the selection highlight)
+    pub fn collect_background_rects(&self) -> Vec<tiny_sdk::types::RectInstance> {
+        if !self.visible {
+            return Vec::new();
+        }
+        self.dropdown
+            .get_selection_highlight_rect()
+            .into_iter()
+            .collect()
+    }
+
+    pub fn get_frame_rounded_rect(&self) -> Option<tiny_sdk::types::RoundedRectInstance> {
+        self.dropdown.get_frame_rounded_rect()
+    }
+}
+
+impl EventSubscriber for CompletionPlugin {
+    fn handle_event(
+        &mut self,
+        event: &Event,
+        event_bus: &mut crate::input::EventBus,
+    ) -> PropagationControl {
+        if !self.visible {
+            return PropagationControl::Continue; // Not active, pass through
+        }
+
+        use serde_json::json;
+
+        match event.name.as_str() {
+            // Enter and Tab pick the selected candidate instead of editing
+            "editor.insert_newline" | "editor.insert_tab" => {
+                event_bus.emit("completion.accept", json!({}), 10, "completion");
+                PropagationControl::Stop
+            }
+            "editor.move_up" => {
+                self.move_up();
+                event_bus.emit("ui.redraw", json!({}), 20, "completion");
+                PropagationControl::Stop
+            }
+            "editor.move_down" => {
+                self.move_down();
+                event_bus.emit("ui.redraw", json!({}), 20, "completion");
+                PropagationControl::Stop
+            }
+            // Escape only closes the popup
+            "cursor.remove" | "editor.escape" => {
+                self.hide();
+                event_bus.emit("ui.redraw", json!({}), 20, "completion");
+                PropagationControl::Stop
+            }
+            _ => PropagationControl::Continue,
+        }
+    }
+
+    fn priority(&self) -> i32 {
+        100 // High priority (overlays filter events before main editor)
+    }
+
+    fn is_active(&self) -> bool {
+        self.visible
+    }
+}
@@ -13,6 +13,9 @@ pub struct AppConfig {
     pub plugins: PluginSystemConfig,
     #[serde(default)]
     pub development: DevelopmentConfig,
+    /// Per-language settings from `[languages.<name>]` tables - parsed manually
+    #[serde(skip)]
+    pub languages: HashMap<String, LanguageConfig>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -39,6 +42,30 @@ pub struct EditorConfig {
     pub font_weight: f32,
     #[serde(default)]
     pub font_italic: bool,
+    /// Characters that separate words for word-wise movement and deletion
+    #[serde(default = "default_word_separators")]
+    pub word_separators: String,
+    /// Stop at camelCase humps and underscores when moving by word
+    #[serde(default)]
+    pub subword_navigation: bool,
+    /// Insert the closing bracket or quote when typing an opening one
+    #[serde(default = "default_auto_close_pairs")]
+    pub auto_close_pairs: bool,
+    /// Wrap long lines instead of scrolling horizontally
+    #[serde(default)]
+    pub soft_wrap: bool,
+    /// Column to wrap at (defaults to the editor width)
+    #[serde(default)]
+    pub wrap_column: Option<u32>,
+    /// Indent wrapped rows like the line they continue
+    #[serde(default = "default_wrap_indent")]
+    pub wrap_indent: bool,
+    /// Columns between tab stops
+    #[serde(default = "default_tab_width")]
+    pub tab_width: u32,
+    /// Indent with spaces instead of tab characters
+    #[serde(default = "default_soft_tabs")]
+    pub soft_tabs: bool,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -71,6 +98,40 @@ pub struct PluginConfig {
     pub auto_reload: bool,
 }
 
+/// Settings that override the `[editor]` ones for a language
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct LanguageConfig {
+    /// Insert the closing bracket or quote when typing an opening one
+    #[serde(default)]
+    pub auto_close_pairs: Option<bool>,
+}
+
+/// Whether brackets and quotes auto-close, by language
+#[derive(Debug, Clone)]
+pub struct AutoCloseConfig {
+    /// For languages without their own setting
+    pub enabled: bool,
+    pub languages: HashMap<String, bool>,
+}
+
+impl AutoCloseConfig {
+    pub fn enabled_for(&self, language: &str) -> bool {
+        self.languages
+            .get(language)
+            .copied()
+            .unwrap_or(self.enabled)
+    }
+}
+
+impl Default for AutoCloseConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_auto_close_pairs(),
+            languages: HashMap::new(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct DevelopmentConfig {
     #[serde(default)]
@@ -93,6 +154,14 @@ impl Default for EditorConfig {
             continuous_rendering: false,
             font_weight: default_font_weight(),
             font_italic: false,
+            word_separators: default_word_separators(),
+            subword_navigation: false,
+            auto_close_pairs: default_auto_close_pairs(),
+            soft_wrap: false,
+            wrap_column: None,
+            wrap_indent: default_wrap_indent(),
+            tab_width: default_tab_width(),
+            soft_tabs: default_soft_tabs(),
         }
     }
 }
@@ -166,11 +235,48 @@ fn default_scroll_lock_enabled() -> bool {
 fn default_font_weight() -> f32 {
     400.0 // Normal weight
 }
+fn default_word_separators() -> String {
+    crate::words::DEFAULT_WORD_SEPARATORS.to_string()
+}
+fn default_auto_close_pairs() -> bool {
+    true
+}
+fn default_wrap_indent() -> bool {
+    true
+}
+fn default_tab_width() -> u32 {
+    4
+}
+fn default_soft_tabs() -> bool {
+    true
+}
 fn default_plugin_dir() -> String {
     "target/plugins/release".to_string()
 }
 
+impl EditorConfig {
+    /// Word definition for word-wise movement and deletion
+    pub fn word_config(&self) -> crate::words::WordConfig {
+        crate::words::WordConfig {
+            separators: self.word_separators.clone(),
+            subword: self.subword_navigation,
+        }
+    }
+}
+
 impl AppConfig {
+    /// Auto-closing of brackets and quotes, with the per-language overrides
+    pub fn auto_close_config(&self) -> AutoCloseConfig {
+        AutoCloseConfig {
+            enabled: self.editor.auto_close_pairs,
+            languages: self
+                .languages
+                .iter()
+                .filter_map(|(name, language)| Some((name.clone(), language.auto_close_pairs?)))
+                .collect(),
+        }
+    }
+
     /// Load configuration from init.toml
     pub fn load() -> Result<Self> {
         let config_path = PathBuf::from("init.toml");
@@ -197,6 +303,14 @@ impl AppConfig {
                 continuous_rendering: false,
                 font_weight: default_font_weight(),
                 font_italic: false,
+                word_separators: default_word_separators(),
+                subword_navigation: false,
+                auto_close_pairs: default_auto_close_pairs(),
+                soft_wrap: false,
+                wrap_column: None,
+                wrap_indent: default_wrap_indent(),
+                tab_width: default_tab_width(),
+                soft_tabs: default_soft_tabs(),
             });
         }
 
@@ -224,6 +338,14 @@ impl AppConfig {
             }
         }
 
+        if let Some(languages_table) = toml_value.get("languages").and_then(|v| v.as_table()) {
+            for (name, value) in languages_table {
+                if let Ok(language_config) = value.clone().try_into::<LanguageConfig>() {
+                    config.languages.insert(name.clone(), language_config);
+                }
+            }
+        }
+
         Ok(config)
     }
 }
@@ -234,6 +356,7 @@ impl Default for AppConfig {
             editor: EditorConfig::default(),
             plugins: PluginSystemConfig::default(),
             development: DevelopmentConfig::default(),
+            languages: HashMap::new(),
         }
     }
 }
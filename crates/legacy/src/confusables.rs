@@ -0,0 +1,287 @@
+//! Confusable, invisible and bidi-control characters - text that doesn't read as it runs
+//!
+//! Bidi controls can reorder how a line is displayed ("Trojan Source"), zero-width
+//! characters and odd spaces hide inside identifiers and strings, and letters from other
+//! scripts pass for ASCII. Lookalike letters are only reported inside words that also
+//! have ASCII letters, so text written in those scripts isn't flagged.
+
+use std::ops::Range;
+
+/// Why a character is reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// Changes the display order of the text around it
+    Bidi,
+    /// Takes no space at all
+    Invisible,
+    /// Looks like a plain space
+    Space,
+    /// Looks like an ASCII character
+    Homoglyph,
+}
+
+impl Kind {
+    /// Glyph drawn in place of the character (lookalikes are visible already)
+    pub fn placeholder(self) -> Option<char> {
+        match self {
+            Kind::Bidi => Some('↔'),
+            Kind::Invisible | Kind::Space => Some('·'),
+            Kind::Homoglyph => None,
+        }
+    }
+}
+
+/// A reported character and where it is
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub range: Range<usize>,
+    pub ch: char,
+    pub kind: Kind,
+}
+
+impl Finding {
+    /// What to replace the character with: nothing, a space or its ASCII lookalike
+    pub fn replacement(&self) -> String {
+        match self.kind {
+            Kind::Bidi | Kind::Invisible => String::new(),
+            Kind::Space => " ".to_string(),
+            Kind::Homoglyph => lookalike(self.ch).map(String::from).unwrap_or_default(),
+        }
+    }
+
+    /// Explanation shown on hover
+    pub fn message(&self) -> String {
+        let name = match tiny_font::unicode_name(self.ch) {
+            Some(name) => format!("U+{:04X} {}", self.ch as u32, name),
+            None => format!("U+{:04X}", self.ch as u32),
+        };
+        match self.kind {
+            Kind::Bidi => format!(
+                "{}: bidirectional control character, the code may run in a different \
+                 order than it reads",
+                name
+            ),
+            Kind::Invisible => format!("{}: invisible character", name),
+            Kind::Space => format!("{}: looks like a space but isn't one", name),
+            Kind::Homoglyph => match lookalike(self.ch) {
+                Some(ascii) => format!("{}: looks like '{}' (U+{:04X})", name, ascii, ascii as u32),
+                None => name,
+            },
+        }
+    }
+}
+
+/// The ASCII character a lookalike passes for
+fn lookalike(ch: char) -> Option<char> {
+    let ascii = match ch {
+        // Cyrillic
+        'а' => 'a',
+        'е' => 'e',
+        'о' => 'o',
+        'р' => 'p',
+        'с' => 'c',
+        'у' => 'y',
+        'х' => 'x',
+        'ѕ' => 's',
+        'і' => 'i',
+        'ј' => 'j',
+        'ԁ' => 'd',
+        'А' => 'A',
+        'В' => 'B',
+        'Е' => 'E',
+        'К' => 'K',
+        'М' => 'M',
+        'Н' => 'H',
+        'О' => 'O',
+        'Р' => 'P',
+        'С' => 'C',
+        'Т' => 'T',
+        'Х' => 'X',
+        'Ѕ' => 'S',
+        'І' => 'I',
+        'Ј' => 'J',
+        // Greek
+        'ο' => 'o',
+        'ν' => 'v',
+        'Α' => 'A',
+        'Β' => 'B',
+        'Ε' => 'E',
+        'Ζ' => 'Z',
+        'Η' => 'H',
+        'Ι' => 'I',
+        'Κ' => 'K',
+        'Μ' => 'M',
+        'Ν' => 'N',
+        'Ο' => 'O',
+        'Ρ' => 'P',
+        'Τ' => 'T',
+        'Υ' => 'Y',
+        'Χ' => 'X',
+        // Punctuation
+        '\u{037E}' => ';', // Greek question mark
+        '\u{2010}' | '\u{2011}' | '\u{2212}' => '-',
+        '\u{FF1B}' => ';',
+        _ => return None,
+    };
+    Some(ascii)
+}
+
+/// How a character is reported, ignoring its context
+pub fn kind(ch: char) -> Option<Kind> {
+    match ch {
+        '\u{061C}'
+        | '\u{200E}'
+        | '\u{200F}'
+        | '\u{202A}'..='\u{202E}'
+        | '\u{2066}'..='\u{2069}' => Some(Kind::Bidi),
+        '\u{00AD}'
+        | '\u{034F}'
+        | '\u{115F}'
+        | '\u{1160}'
+        | '\u{180E}'
+        | '\u{200B}'..='\u{200D}'
+        | '\u{2060}'..='\u{2064}'
+        | '\u{3164}'
+        | '\u{FEFF}'
+        | '\u{E0000}'..='\u{E007F}' => Some(Kind::Invisible),
+        '\u{00A0}'
+        | '\u{1680}'
+        | '\u{2000}'..='\u{200A}'
+        | '\u{202F}'
+        | '\u{205F}'
+        | '\u{2800}'
+        | '\u{3000}' => Some(Kind::Space),
+        _ => lookalike(ch).map(|_| Kind::Homoglyph),
+    }
+}
+
+/// Characters to report in `text`, which starts at byte `offset` of the document
+pub fn find(text: &str, offset: usize) -> Vec<Finding> {
+    find_in(text, offset, offset..offset + text.len())
+}
+
+/// Characters to report in `range` of the document, given the text around it (starting
+/// at byte `offset`). Lookalikes are judged by their whole word, so the text should run
+/// to the ends of the lines the range starts and ends on.
+pub fn find_in(text: &str, offset: usize, range: Range<usize>) -> Vec<Finding> {
+    let start = range.start.saturating_sub(offset).min(text.len());
+    let end = range.end.saturating_sub(offset).min(text.len());
+    let mut findings = Vec::new();
+    let mut previous = text[..start].chars().next_back();
+    for (i, ch) in text[start..end].char_indices() {
+        let i = start + i;
+        let before = previous.replace(ch);
+        let Some(kind) = kind(ch) else {
+            continue;
+        };
+        let reported = match kind {
+            // Joins emoji into one (e.g. family emoji)
+            Kind::Invisible if ch == '\u{200D}' => !before.is_some_and(tiny_font::is_emoji),
+            // Keeps letters from joining where scripts like Persian need it
+            Kind::Invisible if ch == '\u{200C}' => {
+                let after = text[i + ch.len_utf8()..].chars().next();
+                !(before.is_some_and(is_non_ascii_letter) && after.is_some_and(is_non_ascii_letter))
+            }
+            Kind::Homoglyph if ch.is_alphabetic() => mixes_ascii(text, i),
+            _ => true,
+        };
+        if reported {
+            findings.push(Finding {
+                range: offset + i..offset + i + ch.len_utf8(),
+                ch,
+                kind,
+            });
+        }
+    }
+    findings
+}
+
+fn is_non_ascii_letter(ch: char) -> bool {
+    ch.is_alphabetic() && !ch.is_ascii()
+}
+
+/// Whether the word around byte `at` has ASCII letters
+fn mixes_ascii(text: &str, at: usize) -> bool {
+    let is_word = |ch: char| ch.is_alphanumeric() || ch == '_';
+    let start = text[..at]
+        .char_indices()
+        .rev()
+        .take_while(|&(_, ch)| is_word(ch))
+        .last()
+        .map_or(at, |(i, _)| i);
+    let end = text[at..]
+        .find(|ch: char| !is_word(ch))
+        .map_or(text.len(), |i| at + i);
+    text[start..end].chars().any(|ch| ch.is_ascii_alphabetic())
+}
+
+/// Edits removing or replacing every reported character of a document
+pub fn replacements(text: &str) -> Vec<(Range<usize>, String)> {
+    find(text, 0)
+        .into_iter()
+        .map(|finding| {
+            let replacement = finding.replacement();
+            (finding.range, replacement)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_bidi_and_invisible_characters() {
+        // Trojan Source: the comment hides where the string ends
+        let text =
+            "if access_level != \"user\u{202E} \u{2066}// Check if admin\u{2069} \u{2066}\" {";
+        let kinds: Vec<Kind> = find(text, 0).iter().map(|f| f.kind).collect();
+        assert_eq!(kinds, vec![Kind::Bidi, Kind::Bidi, Kind::Bidi, Kind::Bidi]);
+
+        let findings = find("let a\u{200B}b = 1;\u{00A0}", 100);
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[0].range, 105..108);
+        assert_eq!(findings[0].kind, Kind::Invisible);
+        assert_eq!(findings[1].kind, Kind::Space);
+        assert_eq!(
+            findings[0].message(),
+            "U+200B ZERO WIDTH SPACE: invisible character"
+        );
+
+        // Joiners inside emoji sequences are fine
+        assert!(find("👨\u{200D}👩\u{200D}👧", 0).is_empty());
+        // So are non-joiners between letters of scripts that use them
+        assert!(find("می\u{200C}خواهم", 0).is_empty());
+        assert_eq!(find("ab\u{200C}c", 0)[0].kind, Kind::Invisible);
+    }
+
+    #[test]
+    fn lookalikes_only_in_mixed_words() {
+        // Cyrillic 'а' and 'о' in an otherwise ASCII identifier
+        let findings = find("let pаssw\u{043E}rd = 1;", 0);
+        assert_eq!(findings.len(), 2);
+        assert_eq!(
+            findings[0].message(),
+            "U+0430 CYRILLIC SMALL LETTER A: looks like 'a' (U+0061)"
+        );
+
+        // Russian text on its own is left alone
+        assert!(find("// проверка", 0).is_empty());
+        // The whole word counts, even where the range stops inside it
+        let text = "let pаss = 1;";
+        assert_eq!(find_in(text, 10, 15..17)[0].range, 15..17);
+        assert!(find_in("// проверка", 10, 13..17).is_empty());
+        // Punctuation lookalikes are reported anywhere
+        assert_eq!(find("x = 1\u{037E}", 0)[0].kind, Kind::Homoglyph);
+    }
+
+    #[test]
+    fn replacements_clean_up_the_text() {
+        let text = "a\u{202E}b\u{00A0}c сat";
+        let mut cleaned = text.to_string();
+        for (range, replacement) in replacements(text).into_iter().rev() {
+            cleaned.replace_range(range, &replacement);
+        }
+        assert_eq!(cleaned, "ab c cat");
+    }
+}
@@ -3,6 +3,8 @@
 use crate::lsp_manager::{LspManager, ParsedDiagnostic};
 use crate::lsp_service::{LspResult, LspService};
 use ahash::AHashMap;
+use diagnostics_plugin::DiagnosticSeverity;
+use std::ops::Range;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
@@ -12,6 +14,15 @@ use tiny_tree::Doc;
 
 const DEFINITION_CACHE_SAVE_DEBOUNCE_SECS: u64 = 5;
 
+/// A diagnostic the editor finds itself rather than a language server
+#[derive(Debug, Clone, PartialEq)]
+pub struct EditorDiagnostic {
+    /// Byte range in the document
+    pub range: Range<usize>,
+    pub message: String,
+    pub severity: DiagnosticSeverity,
+}
+
 /// High-level diagnostics manager (now uses LspService for broader LSP support)
 pub struct DiagnosticsManager {
     plugin: Option<Arc<Mutex<Box<dyn Plugin>>>>,
@@ -26,6 +37,10 @@ pub struct DiagnosticsManager {
     cmd_hover_position: Option<(usize, usize)>,
     /// Pending text edits from code actions
     pending_text_edits: Option<Vec<crate::lsp_service::TextEdit>>,
+    /// Pending selection ranges (UTF-16 positions, one chain per requested position)
+    pending_selection_ranges: Option<Vec<crate::lsp_service::SelectionRangeChain>>,
+    /// Pending folding ranges (first and last line of each)
+    pending_folding_ranges: Option<Vec<(usize, usize)>>,
     /// Last hover request position (for matching hover responses)
     last_hover_request: Option<(usize, usize)>,
     /// Last mouse position (screen coordinates) for hover detection
@@ -38,6 +53,12 @@ pub struct DiagnosticsManager {
     definition_cache_modified: Option<Instant>,
     /// Last time definition cache was saved to disk
     definition_cache_last_saved: Option<Instant>,
+    /// Latest diagnostics from the language server
+    lsp_diagnostics: Vec<ParsedDiagnostic>,
+    /// Diagnostics found by the editor (shown along with the language server's)
+    editor_diagnostics: Vec<EditorDiagnostic>,
+    /// Diagnostics changed since they were last sent to the plugin
+    diagnostics_dirty: bool,
 }
 
 impl DiagnosticsManager {
@@ -51,12 +72,17 @@ impl DiagnosticsManager {
             user_navigation_pending: false,
             cmd_hover_position: None,
             pending_text_edits: None,
+            pending_selection_ranges: None,
+            pending_folding_ranges: None,
             last_hover_request: None,
             last_mouse_position: None,
             definition_cache: AHashMap::new(),
             document_symbols: Vec::new(),
             definition_cache_modified: None,
             definition_cache_last_saved: None,
+            lsp_diagnostics: Vec::new(),
+            editor_diagnostics: Vec::new(),
+            diagnostics_dirty: false,
         }
     }
 
@@ -126,6 +152,8 @@ impl DiagnosticsManager {
         self.user_requested_goto_position = None;
         self.user_navigation_pending = false;
         self.pending_text_edits = None;
+        self.pending_selection_ranges = None;
+        self.pending_folding_ranges = None;
         self.cmd_hover_position = None;
 
         // NOTE: We skip applying cached diagnostics here because layout isn't ready yet
@@ -315,11 +343,12 @@ impl DiagnosticsManager {
         for result in self.lsp_service.poll_results() {
             match result {
                 LspResult::Diagnostics(diagnostics) => {
-                    self.apply_diagnostics(&diagnostics, text_renderer);
                     let content = doc.read().flatten_to_string();
                     if let Some(file_path) = self.lsp_service.current_file() {
                         LspManager::cache_diagnostics(file_path, &content, &diagnostics);
                     }
+                    self.lsp_diagnostics = diagnostics;
+                    self.diagnostics_dirty = true;
                 }
                 LspResult::Hover(Some(hover)) => {
                     // Send hover content to plugin with the position we requested
@@ -441,9 +470,27 @@ impl DiagnosticsManager {
                 LspResult::TextEdits(edits) => {
                     self.pending_text_edits = Some(edits);
                 }
+                LspResult::SelectionRanges(chains) => {
+                    self.pending_selection_ranges = Some(chains);
+                }
+                LspResult::FoldingRanges(ranges) => {
+                    self.pending_folding_ranges = Some(ranges);
+                }
                 _ => {}
             }
         }
+
+        if self.diagnostics_dirty && self.apply_diagnostics(doc, text_renderer, line_height) {
+            self.diagnostics_dirty = false;
+        }
+    }
+
+    /// Replace the diagnostics the editor found itself
+    pub fn set_editor_diagnostics(&mut self, diagnostics: Vec<EditorDiagnostic>) {
+        if self.editor_diagnostics != diagnostics {
+            self.editor_diagnostics = diagnostics;
+            self.diagnostics_dirty = true;
+        }
     }
 
     /// Handle mouse movement for Cmd+hover go-to-definition preview
@@ -497,6 +544,32 @@ impl DiagnosticsManager {
         result
     }
 
+    /// Request selection ranges at (line, UTF-16 column) positions
+    pub fn request_selection_ranges(&mut self, positions: &[(usize, usize)]) {
+        let positions: Vec<crate::lsp_service::DocPosition> = positions
+            .iter()
+            .map(|&(line, column)| crate::lsp_service::DocPosition { line, column })
+            .collect();
+        self.lsp_service.request_selection_ranges(&positions);
+    }
+
+    /// Take pending selection ranges (consumes them)
+    pub fn take_selection_ranges(
+        &mut self,
+    ) -> Option<Vec<crate::lsp_service::SelectionRangeChain>> {
+        self.pending_selection_ranges.take()
+    }
+
+    /// Request folding ranges for the current document
+    pub fn request_folding_ranges(&mut self) {
+        self.lsp_service.request_folding_ranges();
+    }
+
+    /// Take pending folding ranges (consumes them)
+    pub fn take_folding_ranges(&mut self) -> Option<Vec<(usize, usize)>> {
+        self.pending_folding_ranges.take()
+    }
+
     /// Take pending text edits (consumes them)
     pub fn take_text_edits(&mut self) -> Option<Vec<crate::lsp_service::TextEdit>> {
         self.pending_text_edits.take()
@@ -512,16 +585,18 @@ impl DiagnosticsManager {
         self.plugin.as_ref()
     }
 
-    /// Apply diagnostics to the plugin (internal helper)
+    /// Send the language server's and the editor's diagnostics to the plugin
     /// REQUIRES: TextRenderer layout cache must be populated
+    /// Returns false if the layout isn't ready yet
     fn apply_diagnostics(
-        &mut self,
-        diagnostics: &[ParsedDiagnostic],
+        &self,
+        doc: &Doc,
         text_renderer: &crate::text_renderer::TextRenderer,
-    ) {
+        line_height: f32,
+    ) -> bool {
         // Skip if layout isn't ready yet (can happen during startup)
         if text_renderer.layout_cache.is_empty() {
-            return;
+            return false;
         }
 
         // Clear diagnostics first
@@ -534,62 +609,95 @@ impl DiagnosticsManager {
         }
 
         // Add each diagnostic via Library trait
-        for diag in diagnostics {
+        for diag in &self.lsp_diagnostics {
             // Get precise positions from layout cache
-            let start_x = text_renderer
-                .get_x_at_line_col(diag.line as u32, diag.column_start)
-                .expect(&format!(
-                    "Failed to get X position for diagnostic at line {}, col {}. \
-                     Layout has {} lines in cache.",
-                    diag.line,
-                    diag.column_start,
-                    text_renderer.line_cache.len()
-                ));
-            let end_x = text_renderer
-                .get_x_at_line_col(diag.line as u32, diag.column_end)
-                .expect(&format!(
-                    "Failed to get X position for diagnostic at line {}, col {}. \
-                     Layout has {} lines in cache.",
-                    diag.line,
-                    diag.column_end,
-                    text_renderer.line_cache.len()
-                ));
-
-            // Call plugin via Library trait using proper bytemuck serialization
-            if let Some(ref plugin_arc) = self.plugin {
-                if let Ok(mut plugin) = plugin_arc.lock() {
-                    if let Some(library) = plugin.as_library_mut() {
-                        #[repr(C)]
-                        #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-                        struct DiagnosticHeader {
-                            line: u32,
-                            col_start: u32,
-                            col_end: u32,
-                            severity: u8,
-                            _pad: [u8; 3], // Padding for alignment
-                            start_x: f32,
-                            end_x: f32,
-                            message_len: u32,
-                        }
-
-                        let message_bytes = diag.message.as_bytes();
-                        let header = DiagnosticHeader {
-                            line: diag.line as u32,
-                            col_start: diag.column_start as u32,
-                            col_end: diag.column_end as u32,
-                            severity: diag.severity as u8,
-                            _pad: [0; 3],
-                            start_x,
-                            end_x,
-                            message_len: message_bytes.len() as u32,
-                        };
+            // (none if the line changed since the server sent them; it sends new ones)
+            let (Some(start_x), Some(end_x)) = (
+                text_renderer.get_x_at_line_col(diag.line as u32, diag.column_start),
+                text_renderer.get_x_at_line_col(diag.line as u32, diag.column_end),
+            ) else {
+                continue;
+            };
+            self.add_diagnostic(
+                diag.line,
+                diag.column_start..diag.column_end,
+                diag.severity,
+                (start_x, end_x),
+                &diag.message,
+            );
+        }
 
-                        let mut args = Vec::new();
-                        args.extend_from_slice(bytemuck::bytes_of(&header));
-                        args.extend_from_slice(message_bytes);
+        let tree = doc.read();
+        for diag in &self.editor_diagnostics {
+            let (Some(start), Some(end)) = (
+                text_renderer.get_position_at_byte(diag.range.start),
+                text_renderer.get_position_at_byte(diag.range.end),
+            ) else {
+                continue;
+            };
+            let line = tree.byte_to_line(diag.range.start);
+            let line_start = tree.line_to_byte(line).unwrap_or(0);
+            let column_start = tree
+                .get_text_slice(line_start..diag.range.start)
+                .chars()
+                .count();
+            let column_end = column_start + tree.get_text_slice(diag.range.clone()).chars().count();
+            // Zero-width characters still get room to hover over
+            let end_x = end.x.0.max(start.x.0 + line_height * 0.25);
+            self.add_diagnostic(
+                line as usize,
+                column_start..column_end,
+                diag.severity,
+                (start.x.0, end_x),
+                &diag.message,
+            );
+        }
+        true
+    }
 
-                        let _ = library.call("add_diagnostic", &args);
+    /// Add one diagnostic to the plugin, at layout x positions
+    fn add_diagnostic(
+        &self,
+        line: usize,
+        columns: Range<usize>,
+        severity: DiagnosticSeverity,
+        (start_x, end_x): (f32, f32),
+        message: &str,
+    ) {
+        // Call plugin via Library trait using proper bytemuck serialization
+        if let Some(ref plugin_arc) = self.plugin {
+            if let Ok(mut plugin) = plugin_arc.lock() {
+                if let Some(library) = plugin.as_library_mut() {
+                    #[repr(C)]
+                    #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+                    struct DiagnosticHeader {
+                        line: u32,
+                        col_start: u32,
+                        col_end: u32,
+                        severity: u8,
+                        _pad: [u8; 3], // Padding for alignment
+                        start_x: f32,
+                        end_x: f32,
+                        message_len: u32,
                     }
+
+                    let message_bytes = message.as_bytes();
+                    let header = DiagnosticHeader {
+                        line: line as u32,
+                        col_start: columns.start as u32,
+                        col_end: columns.end as u32,
+                        severity: severity as u8,
+                        _pad: [0; 3],
+                        start_x,
+                        end_x,
+                        message_len: message_bytes.len() as u32,
+                    };
+
+                    let mut args = Vec::new();
+                    args.extend_from_slice(bytemuck::bytes_of(&header));
+                    args.extend_from_slice(message_bytes);
+
+                    let _ = library.call("add_diagnostic", &args);
                 }
             }
         }
@@ -22,7 +22,7 @@ use tiny_core::{
     plugin_loader::PluginLoader,
     tree::{Doc, Point, Rect},
 };
-use tiny_sdk::LayoutPos;
+use tiny_sdk::{CaretInfo, DocPos, LayoutPos, ViewPos};
 use tiny_ui::{ArrowDirection, TextViewCapabilities};
 
 /// Edit mode for text view
@@ -53,6 +53,9 @@ pub struct EditableTextView {
     /// Whether to show cursor
     pub show_cursor: bool,
 
+    /// Whether this view has keyboard focus (unfocused carets use the hollow shape)
+    pub has_focus: bool,
+
     /// Submit callback for single-line mode
     pub on_submit: Option<Box<dyn Fn(String) + Send + Sync>>,
 
@@ -93,6 +96,7 @@ impl EditableTextView {
             input: InputHandler::new(),
             mode,
             show_cursor: true,
+            has_focus: true,
             on_submit: None,
             id: NEXT_VIEW_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed),
             cursor_plugin: None,
@@ -433,32 +437,59 @@ impl EditableTextView {
     /// Call this whenever cursor/selection changes or viewport updates
     /// Note: Plugins get ViewportInfo from PaintContext during paint(), we just send positions
     pub fn sync_plugins(&mut self) {
-        // Update cursor plugin with VIEW coordinates (layout - scroll)
+        // Update cursor plugin with one caret per selection in VIEW coordinates (layout - scroll)
         if let Some(ref plugin_arc) = self.cursor_plugin {
             if let Ok(mut plugin) = plugin_arc.lock() {
                 if let Some(library) = plugin.as_library_mut() {
-                    if let Some(sel) = self.input.selections().first() {
-                        let tree = self.view.doc.read();
-                        let line_text = tree.line_text(sel.cursor.line);
-
-                        // Get layout position (0,0 relative to content)
-                        let layout_pos = self
-                            .view
-                            .viewport
-                            .doc_to_layout_with_text(sel.cursor, &line_text);
-
-                        // Convert to view coordinates (subtract scroll, add padding to match text rendering)
-                        // Text is rendered at: bounds.origin + padding + view_pos (see TextView::collect_glyphs)
-                        // So plugins should receive: padding + view_pos
-                        let view_x =
-                            layout_pos.x.0 - self.view.viewport.scroll.x.0 + self.view.padding_x;
-                        let view_y =
-                            layout_pos.y.0 - self.view.viewport.scroll.y.0 + self.view.padding_y;
-
-                        // Send as LayoutPos type (confusing naming, but it's view coords)
-                        let view_pos = tiny_sdk::LayoutPos::new(view_x, view_y);
-                        let _ = library.call("set_position", tiny_sdk::bytemuck::bytes_of(&view_pos));
-                    }
+                    let tree = self.view.doc.read();
+                    let viewport = &self.view.viewport;
+
+                    let carets: Vec<CaretInfo> = self
+                        .input
+                        .selections()
+                        .iter()
+                        .enumerate()
+                        .map(|(i, sel)| {
+                            let line_text = tree.line_text(sel.cursor.line);
+
+                            // Get layout position (0,0 relative to content)
+                            let layout_pos =
+                                viewport.doc_to_layout_with_text(sel.cursor, &line_text);
+
+                            // Width of the character under the caret (space width past line end)
+                            let line_length = tree.line_char_count(sel.cursor.line) as u32;
+                            let char_width = if sel.cursor.column < line_length {
+                                let next = DocPos {
+                                    column: sel.cursor.column + 1,
+                                    ..sel.cursor
+                                };
+                                viewport.doc_to_layout_with_text(next, &line_text).x.0
+                                    - layout_pos.x.0
+                            } else {
+                                viewport.metrics.space_width
+                            };
+
+                            // Convert to view coordinates (subtract scroll, add padding to match text rendering)
+                            // Text is rendered at: bounds.origin + padding + view_pos (see TextView::collect_glyphs)
+                            // So plugins should receive: padding + view_pos
+                            let view_pos = ViewPos::new(
+                                layout_pos.x.0 - viewport.scroll.x.0 + self.view.padding_x,
+                                layout_pos.y.0 - viewport.scroll.y.0 + self.view.padding_y,
+                            );
+                            CaretInfo::new(view_pos, char_width, i == 0)
+                        })
+                        .collect();
+
+                    // Encode carets for plugin: count (u32), then CaretInfo array
+                    let mut args = Vec::new();
+                    let len = carets.len() as u32;
+                    args.extend_from_slice(tiny_sdk::bytemuck::bytes_of(&len));
+                    args.extend_from_slice(tiny_sdk::bytemuck::cast_slice(&carets));
+                    let _ = library.call("set_carets", &args);
+
+                    let _ = library.call("set_mode", self.input.current_mode().name().as_bytes());
+                    let focused = self.has_focus as u32;
+                    let _ = library.call("set_focused", tiny_sdk::bytemuck::bytes_of(&focused));
                 }
             }
         }
@@ -1,13 +1,15 @@
 use crate::{
-    coordinates, file_picker_plugin, grep_plugin, history,
+    command_palette_plugin, completion, completion_plugin, coordinates, editorconfig,
+    file_picker_plugin, find, find_bar_plugin, grep_plugin, history,
     input::{self},
-    io, syntax, tab_bar_plugin, tab_manager,
+    io, marks, syntax, tab_bar_plugin, tab_manager,
     text_editor_plugin::TextEditorPlugin,
     text_effects::TextStyleProvider,
 };
-use ahash::AHasher;
+use ahash::{AHashMap, AHasher};
 use anyhow::{Context, Result};
 use std::hash::{Hash, Hasher};
+use std::ops::Range;
 use std::path::PathBuf;
 use tiny_core::tree::{Doc, Point};
 use tiny_sdk::DocPos;
@@ -21,6 +23,14 @@ pub struct EditorLogic {
     pub file_picker: file_picker_plugin::FilePickerPlugin,
     /// Grep plugin for full codebase search (global UI)
     pub grep: grep_plugin::GrepPlugin,
+    /// Command palette for picking one of a list of commands (global UI)
+    pub command_palette: command_palette_plugin::CommandPalettePlugin,
+    /// Completion popup at the cursor (global UI)
+    pub completion: completion_plugin::CompletionPlugin,
+    /// Find and replace bar docked to the editor (global UI, searches the active tab)
+    pub find_bar: find_bar_plugin::FindBarPlugin,
+    /// Identifiers of all open tabs, for buffer-word completion
+    pub word_index: completion::WordIndex,
     /// Flag to indicate widgets need updating
     widgets_dirty: bool,
     /// Extra text style providers (e.g., for effects)
@@ -33,6 +43,10 @@ pub struct EditorLogic {
     pub global_nav_history: history::FileNavigationHistory,
     /// Flag to indicate cursor should be centered (for goto definition)
     pub cursor_needs_centering: bool,
+    /// Mark command waiting for its mark name (next typed character)
+    pub pending_mark: Option<marks::MarkCommand>,
+    /// Global (uppercase) marks - live position is tracked by the owning tab's MarkSet
+    global_marks: AHashMap<char, marks::GlobalMark>,
 }
 
 impl EditorLogic {
@@ -66,6 +80,20 @@ impl EditorLogic {
             newly_initialized.push(&mut self.grep.picker.dropdown.input as *mut _);
         }
 
+        // Initialize plugins for command palette input
+        if !self.command_palette.picker.dropdown.input.has_plugins() {
+            self.command_palette.picker.dropdown.input.initialize_plugins(plugin_loader)?;
+            newly_initialized.push(&mut self.command_palette.picker.dropdown.input as *mut _);
+        }
+
+        // Initialize plugins for find bar inputs
+        for input in [&mut self.find_bar.query, &mut self.find_bar.replacement] {
+            if !input.has_plugins() {
+                input.initialize_plugins(plugin_loader)?;
+                newly_initialized.push(input as *mut _);
+            }
+        }
+
         Ok(newly_initialized)
     }
 
@@ -106,6 +134,18 @@ impl EditorLogic {
             views.push(&mut self.grep.picker.dropdown.input as *mut _);
         }
 
+        // Reinitialize for command palette input
+        if let Ok(_) = self.command_palette.picker.dropdown.input.reinitialize_single_plugin(plugin_loader, plugin_name) {
+            views.push(&mut self.command_palette.picker.dropdown.input as *mut _);
+        }
+
+        // Reinitialize for find bar inputs
+        for input in [&mut self.find_bar.query, &mut self.find_bar.replacement] {
+            if let Ok(_) = input.reinitialize_single_plugin(plugin_loader, plugin_name) {
+                views.push(input as *mut _);
+            }
+        }
+
         views
     }
 
@@ -273,10 +313,303 @@ impl EditorLogic {
         false
     }
 
-    /// Handle mouse button release (for cleaning up drag state)
-    pub fn on_mouse_release(&mut self) {
-        self.active_plugin_mut().editor.input.clear_drag_anchor();
+    /// Handle mouse button release (drops dragged text, cleans up drag state)
+    /// Returns whether dropping text changed the document or selections
+    pub fn on_mouse_release(&mut self, copy: bool) -> bool {
+        let plugin = self.active_plugin_mut();
+        let dropped = plugin.editor.input.end_drag(&plugin.editor.view.doc, copy);
         self.pending_scroll = None;
+        dropped
+    }
+
+    /// Bring the completion word index up to date with every open tab's edits
+    pub fn update_word_index(&mut self) {
+        let mut ids = Vec::with_capacity(self.tab_manager.tabs().len());
+        for tab in self.tab_manager.tabs_mut() {
+            let editor = &mut tab.plugin.editor;
+            let edits = editor.input.take_word_edits();
+            self.word_index
+                .update(editor.id, editor.view.doc.read(), edits.as_deref());
+            ids.push(editor.id);
+        }
+        self.word_index.retain(&ids);
+    }
+
+    /// Offer completions for the word being typed in the active tab, or hide the popup
+    /// if there are none. While typing it waits for a couple of characters, an explicit
+    /// request completes from the first one. Returns whether the popup changed
+    pub fn update_completion(&mut self, explicit: bool) -> bool {
+        const MIN_TYPED_PREFIX: usize = 2;
+
+        self.update_word_index();
+        let mut items = Vec::new();
+        if let Some(tab) = self.tab_manager.active_tab() {
+            let editor = &tab.plugin.editor;
+            let doc = &editor.view.doc;
+            let (prefix, word) = editor.input.word_at_cursor(doc);
+            let min_len = if explicit { 1 } else { MIN_TYPED_PREFIX };
+            if prefix.chars().count() >= min_len {
+                let line = editor.input.primary_cursor_doc_pos(doc).line;
+                let nearby = completion::nearby_words(&doc.read(), line);
+                items = self.word_index.complete(&prefix, &word, &nearby);
+            }
+        }
+
+        let was_visible = self.completion.visible;
+        if items.is_empty() {
+            self.completion.hide();
+        } else {
+            let items = items
+                .into_iter()
+                .map(completion_plugin::CompletionItem::word)
+                .collect();
+            self.completion.show(items);
+        }
+        was_visible || self.completion.visible
+    }
+
+    /// Replace the word being typed with the selected completion
+    /// Language server items use their own edit range and snippets are expanded, offering
+    /// the first stop's choices. A snippet choice fills every range of its stop
+    pub fn accept_completion(&mut self) -> bool {
+        let Some(item) = self.completion.selected_item().cloned() else {
+            return false;
+        };
+        self.completion.hide();
+        let editor = &mut self.active_plugin_mut().editor;
+        let doc = &editor.view.doc;
+        if item.snippet_choice {
+            let action = editor.input.choose_snippet_choice(doc, &item.insert_text);
+            return action == input::InputAction::Redraw;
+        }
+        let action = match &item.lsp {
+            Some(lsp_item) => {
+                let action = editor.input.insert_completion(doc, lsp_item);
+                self.show_snippet_choices();
+                action
+            }
+            None => editor.input.complete_word(doc, &item.insert_text),
+        };
+        action == input::InputAction::Redraw
+    }
+
+    /// Offer the choices of the snippet tab stop the active tab is on in the completion
+    /// popup. Returns whether there are any
+    pub fn show_snippet_choices(&mut self) -> bool {
+        let Some(choices) = self
+            .tab_manager
+            .active_tab()
+            .and_then(|tab| tab.plugin.editor.input.snippet_choices())
+        else {
+            return false;
+        };
+        let items = choices
+            .iter()
+            .map(completion_plugin::CompletionItem::snippet_choice)
+            .collect();
+        self.completion.show(items);
+        true
+    }
+
+    /// Open the find bar (with the replace row if `replacing`), searching from the
+    /// selection. Text selected within a line becomes the query, a selection over several
+    /// lines becomes the search scope. Where it opens is a jump origin, so the jump list
+    /// returns there from matches selected as the query is typed
+    pub fn open_find_bar(&mut self, replacing: bool, backward: bool) {
+        let selection = self.primary_selection();
+        let editor = &mut self.tab_manager.active_tab_mut().plugin.editor;
+        let cursor = editor.input.primary_cursor_doc_pos(&editor.view.doc);
+        editor.input.record_jump(cursor);
+        let selected = editor.input.selected_text(&editor.view.doc);
+        let origin = selection.as_ref().map_or(0, |range| range.start);
+
+        let multi_line = selected.as_ref().is_some_and(|text| text.contains('\n'));
+        let seed = selected.filter(|_| !multi_line);
+        self.find_bar
+            .show(replacing, backward, seed.as_deref(), origin);
+        if multi_line {
+            self.set_find_scope(selection);
+        }
+        self.ui_changed = true;
+    }
+
+    /// Bring the find bar's matches and counter up to date with the active tab
+    /// Returns whether they changed
+    pub fn refresh_find_matches(&mut self) -> bool {
+        if !self.find_bar.visible {
+            return false;
+        }
+        let editor = &self.tab_manager.active_tab_mut().plugin.editor;
+        let searched = self.find_bar.update_matches(
+            editor.id,
+            &editor.view.doc.read(),
+            editor.input.find_scope(),
+        );
+        let selection = self.primary_selection();
+        let current_changed = selection.is_some_and(|range| self.find_bar.set_selection(range));
+        searched || current_changed
+    }
+
+    /// Select the match after (or before) the selection in the active tab, opening the
+    /// find bar with the last query if it's closed
+    pub fn find_step(&mut self, backward: bool) -> bool {
+        self.step_to_match(backward, true)
+    }
+
+    /// `find_step`, recording where the selection was as a jump origin if `jump`
+    fn step_to_match(&mut self, backward: bool, jump: bool) -> bool {
+        if !self.find_bar.visible {
+            self.open_find_bar(false, backward);
+            self.find_bar.blur();
+        }
+        self.refresh_find_matches();
+
+        let Some(selection) = self.primary_selection() else {
+            return false;
+        };
+        let from = if backward {
+            selection.start
+        } else {
+            selection.end
+        };
+        match find::next_match(self.find_bar.matches(), from, backward) {
+            Some(index) => self.select_find_match(index, jump),
+            None => false,
+        }
+    }
+
+    /// Select the first match from where the search started (as the query is typed)
+    pub fn find_from_origin(&mut self) -> bool {
+        self.refresh_find_matches();
+        let bar = &self.find_bar;
+        match find::next_match(bar.matches(), bar.origin, bar.backward) {
+            Some(index) => self.select_find_match(index, false),
+            None => false,
+        }
+    }
+
+    /// Search for the word under the cursor as a whole word, stepping past it (vim's `*`
+    /// and `#`)
+    pub fn find_word_under_cursor(&mut self, backward: bool) -> bool {
+        let editor = &mut self.tab_manager.active_tab_mut().plugin.editor;
+        let doc = &editor.view.doc;
+        let Some(word) = editor.input.word_range_at_cursor(doc) else {
+            return false;
+        };
+        let text = doc.read().get_text_slice(word.clone());
+        let cursor = editor.input.primary_cursor_doc_pos(doc);
+        editor.input.record_jump(cursor);
+        editor.input.select_range(doc, word.clone());
+
+        self.find_bar.show_word(backward, &text, word.start);
+        self.find_bar.blur();
+        self.step_to_match(backward, false)
+    }
+
+    /// Byte range of the primary selection in the active tab
+    fn primary_selection(&self) -> Option<Range<usize>> {
+        let editor = &self.tab_manager.active_tab()?.plugin.editor;
+        let doc = &editor.view.doc;
+        editor
+            .input
+            .selections()
+            .first()
+            .map(|sel| sel.byte_range(doc))
+    }
+
+    /// Select a match, first recording the cursor as a jump origin if `jump` and the
+    /// selection moves
+    fn select_find_match(&mut self, index: usize, jump: bool) -> bool {
+        let Some(range) = self.find_bar.matches().get(index).cloned() else {
+            return false;
+        };
+        let moves = self.primary_selection() != Some(range.clone());
+        let editor = &mut self.tab_manager.active_tab_mut().plugin.editor;
+        if jump && moves {
+            let cursor = editor.input.primary_cursor_doc_pos(&editor.view.doc);
+            editor.input.record_jump(cursor);
+        }
+        editor.input.select_range(&editor.view.doc, range.clone());
+        self.find_bar.set_selection(range);
+        self.ui_changed = true;
+        true
+    }
+
+    /// Flip one of the find bar's toggles. "In selection" takes the selection in the
+    /// active tab as the scope
+    pub fn toggle_find_option(&mut self, event: &str) {
+        if event != "find.toggle_in_selection" {
+            self.find_bar.toggle(event);
+        } else if self.find_bar.options.in_selection {
+            self.set_find_scope(None);
+        } else {
+            let selection = self.primary_selection();
+            self.set_find_scope(selection.filter(|range| !range.is_empty()));
+        }
+        self.ui_changed = true;
+    }
+
+    /// Limit the find bar to `scope` in the active tab (turns "in selection" on), or
+    /// search all of it again. Other tabs drop the scopes they kept from earlier searches
+    fn set_find_scope(&mut self, scope: Option<Range<usize>>) {
+        let active = self.tab_manager.active_tab().map(|tab| tab.plugin.editor.id);
+        for tab in self.tab_manager.tabs_mut() {
+            let editor = &mut tab.plugin.editor;
+            let keep = Some(editor.id) == active;
+            editor.input.set_find_scope(scope.clone().filter(|_| keep));
+        }
+        self.find_bar.set_in_selection(scope.is_some());
+    }
+
+    /// Replace the selected match and select the next one, or select the next match if
+    /// none is selected. Returns whether the document or the selection changed
+    pub fn replace_find_match(&mut self) -> bool {
+        self.refresh_find_matches();
+        let Some(index) = self.find_bar.current() else {
+            return self.find_step(false);
+        };
+        let range = self.find_bar.matches()[index].clone();
+        let (query, replacement) = (self.find_bar.query_text(), self.find_bar.replacement_text());
+        let options = self.find_bar.search_options();
+
+        let editor = &mut self.tab_manager.active_tab_mut().plugin.editor;
+        let doc = &editor.view.doc;
+        let edits = find::replacements(
+            &doc.read(),
+            &query,
+            &options,
+            &[range.clone()],
+            &replacement,
+        );
+        let replaced_len = edits[0].1.len();
+        editor.input.apply_edits(doc, edits);
+        // Continue after the replacement, so it isn't matched again (replacing one match
+        // after another isn't jumping around)
+        editor
+            .input
+            .select_range(doc, range.start..range.start + replaced_len);
+        self.step_to_match(false, false);
+        true
+    }
+
+    /// Replace every match in the active tab as one undo step
+    /// Returns how many were replaced
+    pub fn replace_all_find_matches(&mut self) -> usize {
+        self.refresh_find_matches();
+        let matches = self.find_bar.matches().to_vec();
+        if matches.is_empty() {
+            return 0;
+        }
+        let (query, replacement) = (self.find_bar.query_text(), self.find_bar.replacement_text());
+        let options = self.find_bar.search_options();
+
+        let editor = &mut self.tab_manager.active_tab_mut().plugin.editor;
+        let doc = &editor.view.doc;
+        let edits = find::replacements(&doc.read(), &query, &options, &matches, &replacement);
+        editor.input.apply_edits(doc, edits);
+
+        self.ui_changed = true;
+        matches.len()
     }
 
     /// Get document to render
@@ -311,6 +644,7 @@ impl EditorLogic {
     /// Returns true if cursor moved (requiring scroll update)
     pub fn on_update(&mut self) -> bool {
         let mut cursor_moved = false;
+        self.sync_jumps();
         let plugin = self.active_plugin_mut();
         // Check if we should send pending syntax updates (debounce timer expired)
         if plugin.editor.input.should_flush() {
@@ -321,6 +655,39 @@ impl EditorLogic {
         // LSP results are now handled in tab.diagnostics.update() in app.rs
         let tab = self.tab_manager.active_tab_mut();
 
+        // Hand selection ranges to the input handler for expand selection
+        if let Some(chains) = tab.diagnostics.take_selection_ranges() {
+            let doc = &tab.plugin.editor.view.doc;
+            let tree = doc.read();
+            let to_byte = |pos: &crate::lsp_service::DocPosition| {
+                tree.point_utf16_to_byte(tiny_tree::PointUtf16::new(
+                    pos.line as u32,
+                    pos.column as u32,
+                ))
+            };
+            let ranges = chains
+                .iter()
+                .flatten()
+                .map(|(start, end)| to_byte(start)..to_byte(end))
+                .collect();
+            drop(tree);
+            let version = doc.version();
+            tab.plugin
+                .editor
+                .input
+                .set_lsp_selection_ranges(version, ranges);
+        }
+
+        // Folding ranges replace the syntax tree's for this document version
+        if let Some(ranges) = tab.diagnostics.take_folding_ranges() {
+            let version = tab.plugin.editor.view.doc.version();
+            let ranges = ranges
+                .into_iter()
+                .map(|(first, last)| crate::folds::FoldRange::new(first as u32, last as u32))
+                .collect();
+            tab.plugin.editor.input.set_lsp_fold_ranges(version, ranges);
+        }
+
         // Apply pending text edits from code actions
         let edits = match tab.diagnostics.take_text_edits() {
             Some(e) => e,
@@ -469,8 +836,21 @@ impl EditorLogic {
         Ok(())
     }
 
+    /// Forward jump origins recorded by the active editor into the global jump list
+    pub fn sync_jumps(&mut self) {
+        let tab = self.tab_manager.active_tab_mut();
+        let path = tab.plugin.file_path.clone();
+        for position in tab.plugin.editor.input.take_jumps() {
+            self.global_nav_history.checkpoint_if_changed(history::FileLocation {
+                path: path.clone(),
+                position,
+            });
+        }
+    }
+
     /// Navigate back in global history (across files)
     pub fn navigate_back(&mut self) -> Result<bool> {
+        self.sync_jumps();
         let current_location = history::FileLocation {
             path: self.active_editor()?.file_path.clone(),
             position: self
@@ -488,6 +868,7 @@ impl EditorLogic {
 
     /// Navigate forward in global history (across files)
     pub fn navigate_forward(&mut self) -> Result<bool> {
+        self.sync_jumps();
         let current_location = history::FileLocation {
             path: self.active_editor()?.file_path.clone(),
             position: self
@@ -503,6 +884,84 @@ impl EditorLogic {
         self.navigate_to_location(target)
     }
 
+    /// Complete a pending mark command with the given mark name
+    pub fn complete_mark_command(&mut self, name: char) -> Result<bool> {
+        let Some(command) = self.pending_mark.take() else {
+            return Ok(false);
+        };
+        if !marks::is_valid_mark(name) {
+            return Ok(false);
+        }
+
+        match command {
+            marks::MarkCommand::Set => {
+                if marks::is_global_mark(name) {
+                    // Global marks resolve through their file, so unsaved buffers can't hold one
+                    let plugin = self.active_editor()?;
+                    let mark = marks::GlobalMark {
+                        path: plugin
+                            .file_path
+                            .clone()
+                            .context("Save the file before setting a global mark")?,
+                        position: plugin
+                            .editor
+                            .input
+                            .primary_cursor_doc_pos(&plugin.editor.view.doc),
+                    };
+                    self.global_marks.insert(name, mark);
+                }
+                let plugin = self.active_plugin_mut();
+                plugin.editor.input.set_mark(&plugin.editor.view.doc, name);
+                Ok(false)
+            }
+            marks::MarkCommand::Goto { exact } => {
+                if marks::is_global_mark(name) {
+                    let mark = self
+                        .global_marks
+                        .get(&name)
+                        .cloned()
+                        .with_context(|| format!("Mark '{}' is not set", name))?;
+                    self.record_navigation()?;
+                    self.tab_manager
+                        .open_file(mark.path)
+                        .context("Failed to open file for mark")?;
+                } else {
+                    self.record_navigation()?;
+                }
+
+                let plugin = self.active_plugin_mut();
+                let doc = &plugin.editor.view.doc;
+                let position = match plugin.editor.input.mark_position(doc, name, exact) {
+                    Some(pos) => pos,
+                    // Global mark whose tab was closed - fall back to the stored location
+                    None => self
+                        .global_marks
+                        .get(&name)
+                        .map(|mark| mark.position)
+                        .with_context(|| format!("Mark '{}' is not set", name))?,
+                };
+                self.active_plugin_mut().editor.input.set_cursor(position);
+                self.ui_changed = true;
+                self.cursor_needs_centering = true;
+                Ok(true)
+            }
+        }
+    }
+
+    /// Move to an older/newer entry in the active buffer's change list
+    pub fn goto_change(&mut self, older: bool) -> Result<bool> {
+        let plugin = self.active_plugin_mut();
+        let moved = plugin
+            .editor
+            .input
+            .goto_change(&plugin.editor.view.doc, older);
+        if moved {
+            self.ui_changed = true;
+            self.cursor_needs_centering = true;
+        }
+        Ok(moved)
+    }
+
     /// Navigate to a specific file and position
     fn navigate_to_location(&mut self, location: history::FileLocation) -> Result<bool> {
         // Open file if needed (without recording - we're already in a navigation)
@@ -605,6 +1064,44 @@ impl EditorLogic {
         true
     }
 
+    /// Ask the language server for selection ranges at each cursor (for expand selection)
+    pub fn request_selection_ranges(&mut self) {
+        let tab = self.tab_manager.active_tab_mut();
+        let doc = &tab.plugin.editor.view.doc;
+        if !tab.plugin.editor.input.needs_lsp_selection_ranges(doc) {
+            return;
+        }
+
+        let tree = doc.read();
+        let positions: Vec<(usize, usize)> = tab
+            .plugin
+            .editor
+            .input
+            .selections()
+            .iter()
+            .map(|sel| {
+                let point = tree.offset_to_point_utf16(tree.doc_pos_to_byte(sel.cursor));
+                (point.row as usize, point.column as usize)
+            })
+            .collect();
+        drop(tree);
+
+        tab.diagnostics.request_selection_ranges(&positions);
+    }
+
+    /// Ask the language server for folding ranges of the active document (for fold commands)
+    pub fn request_folding_ranges(&mut self) {
+        let tab = self.tab_manager.active_tab_mut();
+        if tab
+            .plugin
+            .editor
+            .input
+            .needs_lsp_fold_ranges(&tab.plugin.editor.view.doc)
+        {
+            tab.diagnostics.request_folding_ranges();
+        }
+    }
+
     /// Go to definition at current cursor position
     pub fn goto_definition(&mut self) -> Result<()> {
         self.record_navigation()?;
@@ -672,7 +1169,17 @@ impl EditorLogic {
         let path = plugin.file_path.as_ref()
             .context("No file path set")?;
 
-        io::autosave(&plugin.editor.view.doc, path)
+        // Re-read .editorconfig rules (they may have changed since the file was opened)
+        plugin.editorconfig = editorconfig::Properties::for_file(path);
+        let text = plugin.editor.view.doc.read().flatten_to_string();
+        let edits = plugin.editorconfig.save_edits(&text);
+        if !edits.is_empty() {
+            plugin.editor.input.apply_edits(&plugin.editor.view.doc, edits);
+        }
+
+        // Refused, leaving the file as it was, if the charset can't represent the text
+        let charset = plugin.editorconfig.charset.unwrap_or_default();
+        io::autosave_encoded(&plugin.editor.view.doc, path, charset)
             .context("Failed to save file")?;
 
         // Update saved content hash
@@ -725,18 +1232,27 @@ impl EditorLogic {
         let tab_bar = tab_bar_plugin::TabBarPlugin::new();
         let file_picker = file_picker_plugin::FilePickerPlugin::new();
         let grep = grep_plugin::GrepPlugin::new();
+        let command_palette = command_palette_plugin::CommandPalettePlugin::new();
+        let completion = completion_plugin::CompletionPlugin::new();
+        let find_bar = find_bar_plugin::FindBarPlugin::new();
 
         Self {
             tab_manager,
             tab_bar,
             file_picker,
             grep,
+            command_palette,
+            completion,
+            find_bar,
+            word_index: completion::WordIndex::new(),
             widgets_dirty: true,
             extra_text_styles: Vec::new(),
             pending_scroll: None,
             ui_changed: true,
             global_nav_history: history::FileNavigationHistory::with_max_size(50),
             cursor_needs_centering: false,
+            pending_mark: None,
+            global_marks: AHashMap::new(),
         }
     }
 }
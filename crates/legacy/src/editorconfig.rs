@@ -0,0 +1,510 @@
+//! EditorConfig - per-project formatting rules from `.editorconfig` files
+//!
+//! Files are collected walking up from the edited file until one says `root = true`.
+//! Sections closer to the file override those further up, and later sections in a file
+//! override earlier ones. See https://editorconfig.org for the format.
+
+use crate::indentation::IndentStyle;
+use ahash::AHashMap as HashMap;
+use std::ops::Range;
+use std::path::Path;
+
+/// Line ending written on save
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+    Cr,
+}
+
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::CrLf => "\r\n",
+            Self::Cr => "\r",
+        }
+    }
+}
+
+/// File encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Charset {
+    #[default]
+    Utf8,
+    Utf8Bom,
+    Latin1,
+    Utf16Be,
+    Utf16Le,
+}
+
+/// Resolved properties for one file (None = not set, the editor's own settings apply)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Properties {
+    /// `indent_style`: true for tabs, false for spaces
+    pub indent_tabs: Option<bool>,
+    /// `indent_size` in columns (`indent_size = tab` resolves to the tab width)
+    pub indent_size: Option<u32>,
+    pub tab_width: Option<u32>,
+    pub end_of_line: Option<LineEnding>,
+    pub charset: Option<Charset>,
+    pub trim_trailing_whitespace: Option<bool>,
+    pub insert_final_newline: Option<bool>,
+    pub max_line_length: Option<u32>,
+}
+
+impl Properties {
+    /// Properties for a file from the `.editorconfig` files above it
+    pub fn for_file(path: &Path) -> Self {
+        let mut files = Vec::new();
+        let mut dir = path.parent();
+        while let Some(current) = dir {
+            if let Ok(text) = std::fs::read_to_string(current.join(".editorconfig")) {
+                let file = ConfigFile::parse(&text);
+                let root = file.root;
+                files.push((current, file));
+                if root {
+                    break;
+                }
+            }
+            dir = current.parent();
+        }
+
+        let mut pairs = HashMap::new();
+        for (dir, file) in files.iter().rev() {
+            let Ok(relative) = path.strip_prefix(dir) else {
+                continue;
+            };
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            file.collect(&relative, &mut pairs);
+        }
+        Self::from_pairs(&pairs)
+    }
+
+    /// Interpret raw `key = value` pairs (keys and values lowercase)
+    fn from_pairs(pairs: &HashMap<String, String>) -> Self {
+        let get = |key: &str| pairs.get(key).map(String::as_str);
+        let number = |key: &str| get(key)?.parse::<u32>().ok().filter(|&n| n > 0);
+        let flag = |key: &str| match get(key)? {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        };
+
+        let tab_width = number("tab_width");
+        let indent_size = match get("indent_size") {
+            Some("tab") => tab_width,
+            _ => number("indent_size"),
+        };
+        Self {
+            indent_tabs: match get("indent_style") {
+                Some("tab") => Some(true),
+                Some("space") => Some(false),
+                _ => None,
+            },
+            indent_size,
+            // The tab width defaults to the indent size
+            tab_width: tab_width.or(indent_size),
+            end_of_line: match get("end_of_line") {
+                Some("lf") => Some(LineEnding::Lf),
+                Some("crlf") => Some(LineEnding::CrLf),
+                Some("cr") => Some(LineEnding::Cr),
+                _ => None,
+            },
+            charset: match get("charset") {
+                Some("utf-8") => Some(Charset::Utf8),
+                Some("utf-8-bom") => Some(Charset::Utf8Bom),
+                Some("latin1") => Some(Charset::Latin1),
+                Some("utf-16be") => Some(Charset::Utf16Be),
+                Some("utf-16le") => Some(Charset::Utf16Le),
+                _ => None,
+            },
+            trim_trailing_whitespace: flag("trim_trailing_whitespace"),
+            insert_final_newline: flag("insert_final_newline"),
+            max_line_length: number("max_line_length"),
+        }
+    }
+
+    /// Indentation for the file, filling in what's not set from the `detected` style
+    pub fn indentation(&self, detected: Option<IndentStyle>) -> Option<IndentStyle> {
+        let detected_width = match detected {
+            Some(IndentStyle::Spaces(width)) => Some(width),
+            _ => None,
+        };
+        match (self.indent_tabs, self.indent_size) {
+            (Some(true), _) => Some(IndentStyle::Tabs),
+            (Some(false), size) => size.or(detected_width).map(IndentStyle::Spaces),
+            (None, Some(size)) => match detected {
+                Some(IndentStyle::Tabs) => detected,
+                _ => Some(IndentStyle::Spaces(size)),
+            },
+            (None, None) => None,
+        }
+    }
+
+    /// Edits applying the save-time rules (trailing whitespace, line endings and the
+    /// final newline) to a text
+    pub fn save_edits(&self, text: &str) -> Vec<(Range<usize>, String)> {
+        let mut edits = Vec::new();
+        let mut end = text.len();
+
+        if self.insert_final_newline == Some(false) {
+            let content_end = text.trim_end_matches(['\n', '\r']).len();
+            if content_end < end {
+                edits.push((content_end..end, String::new()));
+                end = content_end;
+            }
+        }
+
+        let trim = self.trim_trailing_whitespace == Some(true);
+        let mut start = 0;
+        while start < end {
+            let line_end = text[start..end]
+                .find(['\n', '\r'])
+                .map_or(end, |i| start + i);
+            let ending_len = match &text[line_end..end] {
+                rest if rest.starts_with("\r\n") => 2,
+                "" => 0,
+                _ => 1,
+            };
+
+            if trim {
+                let content = text[start..line_end].trim_end_matches([' ', '\t']);
+                if start + content.len() < line_end {
+                    edits.push((start + content.len()..line_end, String::new()));
+                }
+            }
+            if let Some(eol) = self.end_of_line {
+                let ending = &text[line_end..line_end + ending_len];
+                if !ending.is_empty() && ending != eol.as_str() {
+                    edits.push((line_end..line_end + ending_len, eol.as_str().to_string()));
+                }
+            }
+            start = line_end + ending_len;
+        }
+
+        if self.insert_final_newline == Some(true)
+            && !text.is_empty()
+            && !text.ends_with(['\n', '\r'])
+        {
+            let eol = self.end_of_line.map_or_else(
+                || if text.contains("\r\n") { "\r\n" } else { "\n" },
+                LineEnding::as_str,
+            );
+            edits.push((text.len()..text.len(), eol.to_string()));
+        }
+        edits
+    }
+}
+
+/// One parsed `.editorconfig` file
+#[derive(Debug, Default)]
+struct ConfigFile {
+    root: bool,
+    sections: Vec<(String, Vec<(String, String)>)>,
+}
+
+impl ConfigFile {
+    fn parse(text: &str) -> Self {
+        let mut file = Self::default();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(['#', ';']) {
+                continue;
+            }
+            if let Some(glob) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                file.sections.push((glob.to_string(), Vec::new()));
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let key = key.trim().to_lowercase();
+            let value = value.trim().to_lowercase();
+            match file.sections.last_mut() {
+                Some((_, pairs)) => pairs.push((key, value)),
+                None if key == "root" => file.root = value == "true",
+                None => {}
+            }
+        }
+        file
+    }
+
+    /// Add the pairs of every section matching `path` (relative to this file's directory)
+    fn collect(&self, path: &str, pairs: &mut HashMap<String, String>) {
+        for (glob, section) in &self.sections {
+            if !section_matches(glob, path) {
+                continue;
+            }
+            for (key, value) in section {
+                if value == "unset" {
+                    pairs.remove(key);
+                } else {
+                    pairs.insert(key.clone(), value.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Globs without a slash match the file name in any directory, others match the path
+fn section_matches(glob: &str, path: &str) -> bool {
+    let (glob, target) = match glob.strip_prefix('/') {
+        Some(anchored) => (anchored, path),
+        None if glob.contains('/') => (glob, path),
+        None => (glob, path.rsplit('/').next().unwrap_or(path)),
+    };
+    matches(&tokenize(glob), target)
+}
+
+#[derive(Debug, Clone)]
+enum Token {
+    Char(char),
+    /// `?` - any character but `/`
+    Any,
+    /// `*` - any characters but `/`
+    Star,
+    /// `**` - any characters
+    DoubleStar,
+    /// `[abc]`, `[a-z]`, `[!abc]`
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+    /// `{a,b}`
+    Alternatives(Vec<Vec<Token>>),
+    /// `{1..10}`
+    Numbers(i64, i64),
+}
+
+fn tokenize(glob: &str) -> Vec<Token> {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if i + 1 < chars.len() => {
+                tokens.push(Token::Char(chars[i + 1]));
+                i += 1;
+            }
+            '?' => tokens.push(Token::Any),
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                tokens.push(Token::DoubleStar);
+                i += 1;
+            }
+            '*' => tokens.push(Token::Star),
+            '[' => match chars[i + 1..].iter().position(|&c| c == ']') {
+                Some(len) => {
+                    let mut class = &chars[i + 1..i + 1 + len];
+                    let negated = class.first() == Some(&'!');
+                    if negated {
+                        class = &class[1..];
+                    }
+                    let mut ranges = Vec::new();
+                    let mut j = 0;
+                    while j < class.len() {
+                        if j + 2 < class.len() && class[j + 1] == '-' {
+                            ranges.push((class[j], class[j + 2]));
+                            j += 3;
+                        } else {
+                            ranges.push((class[j], class[j]));
+                            j += 1;
+                        }
+                    }
+                    tokens.push(Token::Class { negated, ranges });
+                    i += len + 1;
+                }
+                None => tokens.push(Token::Char('[')),
+            },
+            '{' => match closing_brace(&chars[i..]) {
+                Some(len) => {
+                    let inner: String = chars[i + 1..i + len].iter().collect();
+                    tokens.extend(brace_tokens(&inner));
+                    i += len;
+                }
+                None => tokens.push(Token::Char('{')),
+            },
+            c => tokens.push(Token::Char(c)),
+        }
+        i += 1;
+    }
+    tokens
+}
+
+/// Offset of the `}` closing the `{` at the start of `chars`
+fn closing_brace(chars: &[char]) -> Option<usize> {
+    let mut depth = 0;
+    let mut escaped = false;
+    for (i, &c) in chars.iter().enumerate() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Tokens for the inside of `{...}`: a number range, alternatives or (for a single
+/// word) the literal text with its braces
+fn brace_tokens(inner: &str) -> Vec<Token> {
+    if let Some((from, to)) = inner.split_once("..") {
+        if let (Ok(from), Ok(to)) = (from.parse(), to.parse()) {
+            return vec![Token::Numbers(from, to)];
+        }
+    }
+
+    let mut alternatives = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in inner.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                alternatives.push(tokenize(&inner[start..i]));
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if alternatives.is_empty() {
+        return tokenize(&format!("\\{{{}\\}}", inner));
+    }
+    alternatives.push(tokenize(&inner[start..]));
+    vec![Token::Alternatives(alternatives)]
+}
+
+fn matches(tokens: &[Token], text: &str) -> bool {
+    let Some((token, rest)) = tokens.split_first() else {
+        return text.is_empty();
+    };
+    let mut chars = text.chars();
+    let first = chars.next();
+    let after = chars.as_str();
+    match token {
+        Token::Char(c) => first == Some(*c) && matches(rest, after),
+        Token::Any => first.is_some_and(|c| c != '/') && matches(rest, after),
+        Token::Star => {
+            let segment_end = text.find('/').unwrap_or(text.len());
+            text[..segment_end]
+                .char_indices()
+                .map(|(i, _)| i)
+                .chain([segment_end])
+                .any(|i| matches(rest, &text[i..]))
+        }
+        Token::DoubleStar => text
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain([text.len()])
+            .any(|i| matches(rest, &text[i..])),
+        Token::Class { negated, ranges } => {
+            first.is_some_and(|c| {
+                let in_class = ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi);
+                c != '/' && in_class != *negated
+            }) && matches(rest, after)
+        }
+        Token::Alternatives(alternatives) => alternatives.iter().any(|alternative| {
+            let mut combined = alternative.clone();
+            combined.extend_from_slice(rest);
+            matches(&combined, text)
+        }),
+        Token::Numbers(from, to) => {
+            let sign = usize::from(text.starts_with('-'));
+            let digits = text[sign..]
+                .find(|c: char| !c.is_ascii_digit())
+                .map_or(text.len(), |i| sign + i);
+            text[..digits]
+                .parse::<i64>()
+                .is_ok_and(|n| *from <= n && n <= *to)
+                && matches(rest, &text[digits..])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn properties(config: &str, path: &str) -> Properties {
+        let mut pairs = HashMap::new();
+        ConfigFile::parse(config).collect(path, &mut pairs);
+        Properties::from_pairs(&pairs)
+    }
+
+    #[test]
+    fn sections_match_globs() {
+        assert!(section_matches("*.rs", "src/main.rs"));
+        assert!(!section_matches("*.rs", "src/main.rs.bak"));
+        assert!(section_matches("src/*.rs", "src/main.rs"));
+        assert!(!section_matches("src/*.rs", "src/bin/main.rs"));
+        assert!(section_matches("/src/**.rs", "src/bin/main.rs"));
+        assert!(section_matches("*.{js,ts}", "app.ts"));
+        assert!(!section_matches("*.{js,ts}", "app.tsx"));
+        assert!(section_matches("file[0-9].txt", "file3.txt"));
+        assert!(!section_matches("file[!0-9].txt", "file3.txt"));
+        assert!(section_matches("part{1..12}.md", "part10.md"));
+        assert!(!section_matches("part{1..12}.md", "part13.md"));
+        assert!(section_matches("{Makefile}", "{Makefile}"));
+    }
+
+    #[test]
+    fn later_sections_override_earlier_ones() {
+        let config = "root = true\n\n[*]\nindent_style = space\nindent_size = 4\n\
+                      end_of_line = lf\n\n[Makefile]\nindent_style = tab\n\n\
+                      [*.md]\ntrim_trailing_whitespace = false\nindent_size = unset\n";
+        assert!(ConfigFile::parse(config).root);
+
+        let rust = properties(config, "src/lib.rs");
+        assert_eq!(rust.indentation(None), Some(IndentStyle::Spaces(4)));
+        assert_eq!(rust.tab_width, Some(4));
+        assert_eq!(rust.end_of_line, Some(LineEnding::Lf));
+
+        let makefile = properties(config, "Makefile");
+        assert_eq!(makefile.indentation(None), Some(IndentStyle::Tabs));
+
+        let markdown = properties(config, "README.md");
+        assert_eq!(markdown.indent_size, None);
+        assert_eq!(markdown.trim_trailing_whitespace, Some(false));
+        assert_eq!(
+            markdown.indentation(Some(IndentStyle::Spaces(2))),
+            Some(IndentStyle::Spaces(2))
+        );
+    }
+
+    #[test]
+    fn save_edits_clean_up_lines() {
+        let apply = |properties: &Properties, text: &str| {
+            let mut result = text.to_string();
+            for (range, replacement) in properties.save_edits(text).into_iter().rev() {
+                result.replace_range(range, &replacement);
+            }
+            result
+        };
+
+        let properties = Properties {
+            trim_trailing_whitespace: Some(true),
+            insert_final_newline: Some(true),
+            end_of_line: Some(LineEnding::Lf),
+            ..Default::default()
+        };
+        assert_eq!(apply(&properties, "a  \r\nb\t\nc "), "a\nb\nc\n");
+
+        let properties = Properties {
+            insert_final_newline: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(apply(&properties, "a\nb\n\n"), "a\nb");
+        assert!(Properties::default().save_edits("a  \r\n").is_empty());
+    }
+}
@@ -51,6 +51,22 @@ pub struct FileGotoData {
     pub column: u64,
 }
 
+/// Mark commands accept the mark name directly; otherwise the next typed char is used
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MarkData {
+    #[serde(default)]
+    pub mark: Option<char>,
+}
+
+/// Indentation for the active file: `spaces` wide (tabs if absent), or detected again
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IndentationData {
+    #[serde(default)]
+    pub spaces: Option<u32>,
+    #[serde(default)]
+    pub detect: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DragScrollData {
     pub delta_x: f64,
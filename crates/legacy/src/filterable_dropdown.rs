@@ -262,6 +262,63 @@ impl<T: Clone> FilterableDropdown<T> {
         // Results can scroll, so don't reset scroll here
     }
 
+    /// Calculate bounds for a results-only popup next to an anchor (e.g. the cursor)
+    /// Opens below the anchor, or above it when there's no room below, and is only as
+    /// wide as the longest item
+    pub fn calculate_anchored_bounds(&mut self, viewport: &Viewport, anchor: Rect) {
+        const PADDING: f32 = 4.0;
+        const MIN_CHARS: usize = 12;
+        let results_padding_x = 4.0;
+
+        let line_height = viewport.metrics.line_height;
+        let max_visible_results = 10;
+        let visible_count = self.items.len().min(max_visible_results).max(1);
+        let results_height = (visible_count as f32) * line_height;
+        let height = results_height + PADDING * 2.0;
+
+        let longest = self
+            .items
+            .iter()
+            .map(|item| (self.format_fn)(item).chars().count())
+            .max()
+            .unwrap_or(0)
+            .max(MIN_CHARS);
+        let screen_width = viewport.logical_size.width.0;
+        let width = (longest as f32 * viewport.metrics.space_width
+            + (results_padding_x + PADDING) * 2.0)
+            .min(screen_width);
+
+        let below = anchor.y.0 + anchor.height.0;
+        let y = if below + height > viewport.logical_size.height.0 && anchor.y.0 >= height {
+            anchor.y.0 - height
+        } else {
+            below
+        };
+        let x = anchor.x.0.min(screen_width - width).max(0.0);
+
+        self.bounds = Rect {
+            x: LogicalPixels(x),
+            y: LogicalPixels(y),
+            width: LogicalPixels(width),
+            height: LogicalPixels(height),
+        };
+
+        let results_bounds_width = width - PADDING * 2.0;
+        self.results.viewport.bounds = tiny_sdk::types::LayoutRect::new(
+            x + PADDING,
+            y + PADDING,
+            results_bounds_width,
+            results_height,
+        );
+        self.results.viewport.logical_size =
+            tiny_sdk::LogicalSize::new(results_bounds_width, results_height);
+        self.results.viewport.scale_factor = viewport.scale_factor;
+        self.results.viewport.metrics = viewport.metrics.clone();
+
+        self.results.padding_x = results_padding_x;
+        self.results.padding_y = 0.0;
+    }
+
     /// Get bounds for rendering
     pub fn bounds(&self) -> Rect {
         self.bounds
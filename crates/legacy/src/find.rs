@@ -0,0 +1,190 @@
+//! In-file find and replace - matches of the find bar's query and the text replacing them
+//!
+//! Searching goes through `tiny_tree`, so case, whole-word and regex matching behave as
+//! they do everywhere else. Empty matches and matches overlapping an earlier one are
+//! dropped, so every match can be stepped through and replaced at once.
+
+use std::ops::Range;
+use tiny_core::tree::{SearchOptions, Tree};
+
+/// Toggles of the find bar
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FindOptions {
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    pub regex: bool,
+    /// Only search the text that was selected when this was turned on
+    pub in_selection: bool,
+}
+
+impl FindOptions {
+    pub fn search_options(&self) -> SearchOptions {
+        SearchOptions {
+            case_sensitive: self.case_sensitive,
+            whole_word: self.whole_word,
+            regex: self.regex,
+            limit: None,
+        }
+    }
+}
+
+/// Matches of `query` in document order, only those inside `scope` if there is one
+pub fn find_matches(
+    tree: &Tree,
+    query: &str,
+    options: &FindOptions,
+    scope: Option<&Range<usize>>,
+) -> Vec<Range<usize>> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let mut matches: Vec<Range<usize>> = Vec::new();
+    for m in tree.search(query, options.search_options()) {
+        let range = m.byte_range;
+        let outside = scope.is_some_and(|scope| range.start < scope.start || range.end > scope.end);
+        let overlaps = matches.last().is_some_and(|last| range.start < last.end);
+        if !range.is_empty() && !outside && !overlaps {
+            matches.push(range);
+        }
+    }
+    matches
+}
+
+/// Index of the first match starting at or after byte `from`, or going `backward`, the
+/// last one starting before it. Wraps around the ends of the document
+pub fn next_match(matches: &[Range<usize>], from: usize, backward: bool) -> Option<usize> {
+    if matches.is_empty() {
+        return None;
+    }
+    let after = matches.partition_point(|m| m.start < from);
+    Some(match (backward, after) {
+        (true, 0) => matches.len() - 1,
+        (true, after) => after - 1,
+        (false, after) if after == matches.len() => 0,
+        (false, after) => after,
+    })
+}
+
+/// Match counter, e.g. "3 of 12" ("? of 12" while none of them is selected)
+pub fn counter_label(current: Option<usize>, total: usize) -> String {
+    match current {
+        _ if total == 0 => "No results".to_string(),
+        Some(index) => format!("{} of {}", index + 1, total),
+        None => format!("? of {}", total),
+    }
+}
+
+/// Text replacing one match: the replacement as typed, or for regex searches with
+/// groups (`$1`, `${name}`) filled in from the match
+pub fn expand_replacement(
+    query: &str,
+    options: &FindOptions,
+    matched: &str,
+    replacement: &str,
+) -> String {
+    if !options.regex {
+        return replacement.to_string();
+    }
+    // Same pattern tiny_tree searched with, anchored to the matched text
+    let mut pattern = query.to_string();
+    if !options.case_sensitive {
+        pattern = format!("(?i){}", pattern);
+    }
+    if options.whole_word {
+        pattern = format!(r"\b{}\b", pattern);
+    }
+    match regex::Regex::new(&format!(r"\A(?:{})\z", pattern)) {
+        Ok(regex) if regex.is_match(matched) => regex.replace(matched, replacement).into_owned(),
+        _ => replacement.to_string(),
+    }
+}
+
+/// Edits replacing each of `matches` in `tree`
+pub fn replacements(
+    tree: &Tree,
+    query: &str,
+    options: &FindOptions,
+    matches: &[Range<usize>],
+    replacement: &str,
+) -> Vec<(Range<usize>, String)> {
+    matches
+        .iter()
+        .map(|range| {
+            let matched = tree.get_text_slice(range.clone());
+            let text = expand_replacement(query, options, &matched, replacement);
+            (range.clone(), text)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_follow_the_toggles() {
+        let tree = Tree::from_str("Foo foo food\nfoo_bar FOO");
+        let mut options = FindOptions::default();
+        assert_eq!(find_matches(&tree, "foo", &options, None).len(), 5);
+
+        options.case_sensitive = true;
+        assert_eq!(
+            find_matches(&tree, "foo", &options, None),
+            vec![4..7, 8..11, 13..16]
+        );
+
+        options.whole_word = true;
+        assert_eq!(find_matches(&tree, "foo", &options, None), vec![4..7]);
+
+        // Only inside the scope, and never empty
+        let options = FindOptions {
+            regex: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            find_matches(&tree, "fo+d?", &options, Some(&(4..12))),
+            vec![4..7, 8..12]
+        );
+        assert!(find_matches(&tree, "x*", &options, None).is_empty());
+        assert!(find_matches(&tree, "", &options, None).is_empty());
+    }
+
+    #[test]
+    fn overlapping_matches_are_dropped() {
+        let tree = Tree::from_str("aaaa");
+        let options = FindOptions::default();
+        assert_eq!(find_matches(&tree, "aa", &options, None), vec![0..2, 2..4]);
+    }
+
+    #[test]
+    fn stepping_through_matches_wraps_around() {
+        let matches = vec![2..4, 10..12, 20..22];
+        assert_eq!(next_match(&matches, 0, false), Some(0));
+        assert_eq!(next_match(&matches, 4, false), Some(1));
+        assert_eq!(next_match(&matches, 21, false), Some(0));
+        assert_eq!(next_match(&matches, 10, true), Some(0));
+        assert_eq!(next_match(&matches, 2, true), Some(2));
+        assert_eq!(next_match(&[], 0, false), None);
+
+        assert_eq!(counter_label(Some(1), 3), "2 of 3");
+        assert_eq!(counter_label(None, 3), "? of 3");
+        assert_eq!(counter_label(None, 0), "No results");
+    }
+
+    #[test]
+    fn regex_replacements_fill_in_groups() {
+        let options = FindOptions {
+            regex: true,
+            ..Default::default()
+        };
+        let tree = Tree::from_str("let x = 1;\nlet y = 2;");
+        let matches = find_matches(&tree, r"let (\w+)", &options, None);
+        let edits = replacements(&tree, r"let (\w+)", &options, &matches, "const ${1}_");
+        assert_eq!(edits[0], (0..5, "const x_".to_string()));
+        assert_eq!(edits[1], (11..16, "const y_".to_string()));
+
+        // Plain searches take the replacement as it is
+        let plain = FindOptions::default();
+        assert_eq!(expand_replacement("x", &plain, "x", "$1"), "$1");
+    }
+}
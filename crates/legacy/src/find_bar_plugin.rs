@@ -0,0 +1,615 @@
+//! Find bar - search and replace in the active file, docked to the top of the editor
+//!
+//! Typing searches as you go, from where the bar was opened; Enter and Shift+Enter step
+//! through the matches, which get selected in the editor. The toggles map to
+//! `tiny_tree::SearchOptions`, and "in selection" limits the search to the text that was
+//! selected when it was turned on.
+
+use crate::coordinates::Viewport;
+use crate::editable_text_view::EditableTextView;
+use crate::find::{self, FindOptions};
+use crate::input::{Event, EventSubscriber, PropagationControl};
+use crate::text_view::TextView;
+use std::ops::Range;
+use std::sync::Arc;
+use tiny_core::tree::{Rect, Tree};
+use tiny_font::SharedFontSystem;
+use tiny_sdk::types::{LayoutRect, RectInstance, RoundedRectInstance};
+use tiny_sdk::LogicalPixels;
+
+/// Toggles next to the query: label, and the event flipping it
+pub const TOGGLES: [(&str, &str); 4] = [
+    ("Aa", "find.toggle_case"),
+    ("\\b", "find.toggle_whole_word"),
+    (".*", "find.toggle_regex"),
+    ("Sel", "find.toggle_in_selection"),
+];
+
+/// Buttons next to the replacement: label, and the event they emit
+const ACTIONS: [(&str, &str); 2] = [("Replace", "find.replace_one"), ("All", "find.replace_all")];
+
+/// Spaces between labels
+const LABEL_GAP: usize = 2;
+
+/// Columns of the toggles and the longest counter ("9999 of 9999")
+const STATUS_COLUMNS: usize = 30;
+
+/// Input field of the find bar
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FindField {
+    Query,
+    Replacement,
+}
+
+/// What the matches were found for
+#[derive(Debug, Clone, PartialEq)]
+struct Searched {
+    doc_id: u64,
+    version: u64,
+    query: String,
+    options: FindOptions,
+    scope: Option<Range<usize>>,
+}
+
+/// Find and replace bar for the active tab
+pub struct FindBarPlugin {
+    pub query: EditableTextView,
+    pub replacement: EditableTextView,
+    /// Toggles and match counter, next to the query
+    status: TextView,
+    /// Replace buttons, next to the replacement
+    actions: TextView,
+    pub visible: bool,
+    /// Whether the replacement row is shown
+    pub replacing: bool,
+    pub options: FindOptions,
+    /// Enter steps backward (opened with vim's `?`)
+    pub backward: bool,
+    /// Searching starts here: typing selects the first match from it
+    pub origin: usize,
+    /// Whole words only for the current query (vim's `*`), without turning the toggle on
+    word_search: bool,
+    /// Field typing goes to, None while the editor has focus
+    focus: Option<FindField>,
+    /// Matches in the active document, and which of them is selected there
+    matches: Vec<Range<usize>>,
+    current: Option<usize>,
+    searched: Option<Searched>,
+    bounds: Rect,
+}
+
+impl FindBarPlugin {
+    pub fn new() -> Self {
+        let placeholder_viewport = Viewport::new(800.0, 600.0, 1.0);
+
+        let mut actions = TextView::empty(placeholder_viewport.clone());
+        actions.set_text(&labels_text(&ACTIONS));
+
+        Self {
+            query: EditableTextView::single_line(placeholder_viewport.clone()),
+            replacement: EditableTextView::single_line(placeholder_viewport.clone()),
+            status: TextView::empty(placeholder_viewport),
+            actions,
+            visible: false,
+            replacing: false,
+            options: FindOptions::default(),
+            backward: false,
+            origin: 0,
+            word_search: false,
+            focus: None,
+            matches: Vec::new(),
+            current: None,
+            searched: None,
+            bounds: Rect::default(),
+        }
+    }
+
+    /// Show the bar with the query focused and selected, so typing replaces it
+    /// `seed` replaces the previous query (e.g. the selected text)
+    pub fn show(&mut self, replacing: bool, backward: bool, seed: Option<&str>, origin: usize) {
+        self.visible = true;
+        self.replacing = replacing;
+        self.backward = backward;
+        self.origin = origin;
+        self.word_search = false;
+        if let Some(seed) = seed {
+            self.query.set_text(seed);
+        }
+        self.query.input.select_all(&self.query.view.doc);
+        self.focus(FindField::Query);
+        self.update_status();
+    }
+
+    pub fn hide(&mut self) {
+        self.visible = false;
+        self.focus = None;
+        self.query.set_focused(false);
+        self.replacement.set_focused(false);
+        self.word_search = false;
+        self.set_in_selection(false);
+        self.matches.clear();
+        self.current = None;
+        self.searched = None;
+    }
+
+    /// Give a field the keyboard focus
+    pub fn focus(&mut self, field: FindField) {
+        let field = if self.replacing {
+            field
+        } else {
+            FindField::Query
+        };
+        self.focus = Some(field);
+        self.query.set_focused(field == FindField::Query);
+        self.replacement
+            .set_focused(field == FindField::Replacement);
+    }
+
+    /// Leave typing to the editor, keeping the bar and its highlights up
+    pub fn blur(&mut self) {
+        self.focus = None;
+        self.query.set_focused(false);
+        self.replacement.set_focused(false);
+    }
+
+    /// Id of the focused input, if the bar has the focus
+    pub fn focused_id(&self) -> Option<u64> {
+        self.focus.map(|field| self.field(field).id)
+    }
+
+    fn field(&self, field: FindField) -> &EditableTextView {
+        match field {
+            FindField::Query => &self.query,
+            FindField::Replacement => &self.replacement,
+        }
+    }
+
+    fn field_mut(&mut self, field: FindField) -> &mut EditableTextView {
+        match field {
+            FindField::Query => &mut self.query,
+            FindField::Replacement => &mut self.replacement,
+        }
+    }
+
+    pub fn query_text(&self) -> String {
+        self.query.text().as_ref().clone()
+    }
+
+    pub fn replacement_text(&self) -> String {
+        self.replacement.text().as_ref().clone()
+    }
+
+    /// Show the bar searching for `word` as a whole word, leaving the toggle as it was
+    pub fn show_word(&mut self, backward: bool, word: &str, origin: usize) {
+        self.show(false, backward, Some(word), origin);
+        self.word_search = true;
+    }
+
+    /// Options the query is searched with: the toggles, and whole words for a word search
+    pub fn search_options(&self) -> FindOptions {
+        FindOptions {
+            whole_word: self.options.whole_word || self.word_search,
+            ..self.options
+        }
+    }
+
+    /// Flip the case, whole word or regex toggle named by its event
+    pub fn toggle(&mut self, event: &str) {
+        let option = match event {
+            "find.toggle_case" => &mut self.options.case_sensitive,
+            "find.toggle_whole_word" => {
+                // A word search shows the toggle on, so flipping it turns it off
+                self.options.whole_word |= std::mem::take(&mut self.word_search);
+                &mut self.options.whole_word
+            }
+            "find.toggle_regex" => &mut self.options.regex,
+            _ => return,
+        };
+        *option = !*option;
+        self.update_status();
+    }
+
+    /// Turn "in selection" on or off. The scope itself is kept by the document's input
+    /// handler, which moves it through every edit
+    pub fn set_in_selection(&mut self, in_selection: bool) {
+        self.options.in_selection = in_selection;
+        self.update_status();
+    }
+
+    /// Search document `doc_id` again if it, the query or the toggles changed since the
+    /// last search. `scope` is only searched while "in selection" is on
+    /// Returns whether the matches were found again
+    pub fn update_matches(
+        &mut self,
+        doc_id: u64,
+        tree: &Tree,
+        scope: Option<Range<usize>>,
+    ) -> bool {
+        let searched = Searched {
+            doc_id,
+            version: tree.version,
+            query: self.query_text(),
+            options: self.search_options(),
+            scope: scope.filter(|_| self.options.in_selection),
+        };
+        if self.searched.as_ref() == Some(&searched) {
+            return false;
+        }
+        self.matches = find::find_matches(
+            tree,
+            &searched.query,
+            &searched.options,
+            searched.scope.as_ref(),
+        );
+        self.current = None;
+        self.searched = Some(searched);
+        self.update_status();
+        true
+    }
+
+    pub fn matches(&self) -> &[Range<usize>] {
+        &self.matches
+    }
+
+    /// The match selected in the editor, if the selection is one
+    pub fn current(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// Count the match equal to the editor's selection as the current one
+    /// Returns whether the counter changed
+    pub fn set_selection(&mut self, selection: Range<usize>) -> bool {
+        let current = self
+            .matches
+            .binary_search_by_key(&selection.start, |m| m.start)
+            .ok()
+            .filter(|&i| self.matches[i] == selection);
+        if current == self.current {
+            return false;
+        }
+        self.current = current;
+        self.update_status();
+        true
+    }
+
+    /// Toggles followed by the match counter (empty without a query)
+    fn update_status(&mut self) {
+        let counter = if self.query.text().is_empty() {
+            String::new()
+        } else {
+            find::counter_label(self.current, self.matches.len())
+        };
+        let text = format!("{}   {}", labels_text(&TOGGLES), counter);
+        if *self.status.text() != text {
+            self.status.set_text(&text);
+        }
+    }
+
+    /// Calculate bounds: top-right corner of the editor, one row per field
+    pub fn calculate_bounds(&mut self, viewport: &Viewport, editor_bounds: LayoutRect) {
+        const MAX_WIDTH: f32 = 640.0;
+        const MARGIN_RIGHT: f32 = 24.0; // Clear of the scrollbar
+        const MARGIN_TOP: f32 = 4.0;
+        const PADDING: f32 = 6.0;
+        const INPUT_PADDING_Y: f32 = 4.0;
+
+        let line_height = viewport.metrics.line_height;
+        let row_height = line_height + INPUT_PADDING_Y * 2.0;
+        let rows = if self.replacing { 2.0 } else { 1.0 };
+        let height = rows * row_height + (rows + 1.0) * PADDING;
+        let width = MAX_WIDTH
+            .min(editor_bounds.width.0 - MARGIN_RIGHT * 2.0)
+            .max(0.0);
+        let x = editor_bounds.x.0 + editor_bounds.width.0 - width - MARGIN_RIGHT;
+        let y = editor_bounds.y.0 + MARGIN_TOP;
+
+        self.bounds = Rect {
+            x: LogicalPixels(x),
+            y: LogicalPixels(y),
+            width: LogicalPixels(width),
+            height: LogicalPixels(height),
+        };
+
+        let status_width = STATUS_COLUMNS as f32 * viewport.metrics.space_width;
+        let input_width = (width - PADDING * 3.0 - status_width).max(0.0);
+        let status_x = x + PADDING * 2.0 + input_width;
+        let second_row_y = y + PADDING * 2.0 + row_height;
+
+        let fields = [
+            (&mut self.query.view, x + PADDING, y + PADDING, input_width),
+            (&mut self.status, status_x, y + PADDING, status_width),
+            (
+                &mut self.replacement.view,
+                x + PADDING,
+                second_row_y,
+                input_width,
+            ),
+            (&mut self.actions, status_x, second_row_y, status_width),
+        ];
+        for (view, x, y, width) in fields {
+            view.viewport.bounds = LayoutRect::new(x, y, width, row_height);
+            view.viewport.logical_size = tiny_sdk::LogicalSize::new(width, row_height);
+            view.viewport.scale_factor = viewport.scale_factor;
+            view.viewport.metrics = viewport.metrics.clone();
+            view.viewport.scroll = tiny_sdk::types::LayoutPos::new(0.0, 0.0);
+            view.padding_x = 0.0;
+            view.padding_y = INPUT_PADDING_Y;
+        }
+    }
+
+    pub fn get_bounds(&self) -> Rect {
+        self.bounds
+    }
+
+    /// Whether a screen position is on the bar
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        let bounds = &self.bounds;
+        self.visible
+            && x >= bounds.x.0
+            && x < bounds.x.0 + bounds.width.0
+            && y >= bounds.y.0
+            && y < bounds.y.0 + bounds.height.0
+    }
+
+    /// Handle a click on the bar: focus the clicked field, or return the event of the
+    /// clicked toggle or button
+    pub fn handle_click(&mut self, x: f32, y: f32, shift: bool) -> Option<&'static str> {
+        let point = tiny_core::tree::Point {
+            x: LogicalPixels(x),
+            y: LogicalPixels(y),
+        };
+        let fields = if self.replacing {
+            &[FindField::Query, FindField::Replacement][..]
+        } else {
+            &[FindField::Query][..]
+        };
+        for &field in fields {
+            if view_contains(&self.field(field).view, x, y) {
+                self.focus(field);
+                self.field_mut(field).handle_click(point, shift, false);
+                return None;
+            }
+        }
+
+        if view_contains(&self.status, x, y) {
+            return label_at(&self.status, &TOGGLES, x);
+        }
+        if self.replacing && view_contains(&self.actions, x, y) {
+            return label_at(&self.actions, &ACTIONS, x);
+        }
+        None
+    }
+
+    /// Collect glyphs for rendering (one batch per field, each with its scissor rect)
+    pub fn collect_glyphs(
+        &mut self,
+        font_system: &Arc<SharedFontSystem>,
+    ) -> Vec<(Vec<tiny_sdk::GlyphInstance>, (u32, u32, u32, u32))> {
+        if !self.visible {
+            return Vec::new();
+        }
+
+        let mut views = vec![&mut self.query.view, &mut self.status];
+        if self.replacing {
+            views.extend([&mut self.replacement.view, &mut self.actions]);
+        }
+
+        let mut result = Vec::new();
+        for view in views {
+            view.update_layout(font_system);
+            let glyphs = view.collect_glyphs(font_system);
+            if !glyphs.is_empty() {
+                result.push((glyphs, view.get_scissor_rect()));
+            }
+        }
+        result
+    }
+
+    /// Collect background rects: input fields, active toggles and input selections
+    pub fn collect_background_rects(&self) -> Vec<RectInstance> {
+        if !self.visible {
+            return Vec::new();
+        }
+
+        const INPUT_BG: u32 = 0x232629FF;
+        const TOGGLE_ON_BG: u32 = 0x2D4A6BFF;
+
+        let mut inputs = vec![&self.query];
+        if self.replacing {
+            inputs.push(&self.replacement);
+        }
+
+        let mut rects: Vec<RectInstance> = inputs
+            .iter()
+            .map(|input| {
+                let bounds = input.view.viewport.bounds;
+                RectInstance {
+                    rect: Rect {
+                        x: bounds.x,
+                        y: bounds.y,
+                        width: bounds.width,
+                        height: bounds.height,
+                    },
+                    color: INPUT_BG,
+                }
+            })
+            .collect();
+
+        let enabled = [
+            self.options.case_sensitive,
+            self.search_options().whole_word,
+            self.options.regex,
+            self.options.in_selection,
+        ];
+        let bounds = self.status.viewport.bounds;
+        let space_width = self.status.viewport.metrics.space_width;
+        for (columns, _) in label_columns(&TOGGLES)
+            .into_iter()
+            .zip(enabled)
+            .filter(|&(_, on)| on)
+        {
+            rects.push(RectInstance {
+                rect: Rect {
+                    x: LogicalPixels(
+                        bounds.x.0 + self.status.padding_x + columns.start as f32 * space_width,
+                    ),
+                    y: bounds.y,
+                    width: LogicalPixels(columns.len() as f32 * space_width),
+                    height: bounds.height,
+                },
+                color: TOGGLE_ON_BG,
+            });
+        }
+
+        for input in inputs {
+            rects.extend(input.collect_background_rects());
+        }
+        rects
+    }
+
+    /// Get rounded rect for the frame with border (SDF rendering)
+    pub fn get_frame_rounded_rect(&self) -> Option<RoundedRectInstance> {
+        if !self.visible {
+            return None;
+        }
+
+        const FRAME_BG: u32 = 0x1A1D1FFF;
+        const BORDER_COLOR: u32 = 0x30343AFF;
+
+        Some(RoundedRectInstance {
+            rect: LayoutRect {
+                x: self.bounds.x,
+                y: self.bounds.y,
+                width: self.bounds.width,
+                height: self.bounds.height,
+            },
+            color: FRAME_BG,
+            border_color: BORDER_COLOR,
+            corner_radius: 4.0,
+            border_width: 1.0,
+        })
+    }
+}
+
+/// Labels separated by gaps
+fn labels_text(labels: &[(&str, &str)]) -> String {
+    labels
+        .iter()
+        .map(|(label, _)| *label)
+        .collect::<Vec<_>>()
+        .join(&" ".repeat(LABEL_GAP))
+}
+
+/// Columns each label takes up in `labels_text`
+fn label_columns(labels: &[(&str, &str)]) -> Vec<Range<usize>> {
+    let mut column = 0;
+    labels
+        .iter()
+        .map(|(label, _)| {
+            let start = column;
+            column += label.chars().count() + LABEL_GAP;
+            start..start + label.chars().count()
+        })
+        .collect()
+}
+
+/// Event of the label under screen position `x` in a view showing `labels_text`
+fn label_at(view: &TextView, labels: &[(&str, &'static str)], x: f32) -> Option<&'static str> {
+    let offset = x - view.viewport.bounds.x.0 - view.padding_x;
+    let column = (offset / view.viewport.metrics.space_width).floor();
+    if column < 0.0 {
+        return None;
+    }
+    label_columns(labels)
+        .into_iter()
+        .zip(labels)
+        .find(|(columns, _)| columns.contains(&(column as usize)))
+        .map(|(_, &(_, event))| event)
+}
+
+fn view_contains(view: &TextView, x: f32, y: f32) -> bool {
+    let bounds = &view.viewport.bounds;
+    x >= bounds.x.0
+        && x < bounds.x.0 + bounds.width.0
+        && y >= bounds.y.0
+        && y < bounds.y.0 + bounds.height.0
+}
+
+impl EventSubscriber for FindBarPlugin {
+    fn handle_event(
+        &mut self,
+        event: &Event,
+        event_bus: &mut crate::input::EventBus,
+    ) -> PropagationControl {
+        if !self.visible {
+            return PropagationControl::Continue; // Not active, pass through
+        }
+
+        use serde_json::json;
+
+        // Escape closes the bar wherever the focus is
+        if event.name == "action.cancel" {
+            self.hide();
+            event_bus.emit("overlay.closed", json!({"source": "find"}), 10, "find");
+            return PropagationControl::Stop;
+        }
+        let Some(field) = self.focus else {
+            return PropagationControl::Continue; // Typing goes to the editor
+        };
+
+        match event.name.as_str() {
+            // Enter steps to the next match, Shift+Enter the other way
+            "editor.insert_newline" | "editor.insert_newline_below" => {
+                let backward = self.backward != (event.name == "editor.insert_newline_below");
+                let step = if backward {
+                    "find.previous"
+                } else {
+                    "find.next"
+                };
+                event_bus.emit(step, json!({}), 10, "find");
+                PropagationControl::Stop
+            }
+            // Enter also arrives as a submit, already handled above
+            "action.submit" => PropagationControl::Stop,
+            // Tab switches between the query and the replacement
+            "editor.insert_tab" => {
+                let other = match field {
+                    FindField::Query => FindField::Replacement,
+                    FindField::Replacement => FindField::Query,
+                };
+                self.focus(other);
+                event_bus.emit("find.focus", json!({}), 10, "find");
+                PropagationControl::Stop
+            }
+            // Handle text editing events in the focused field
+            event_name if event_name.starts_with("editor.") => {
+                let input = self.field_mut(field);
+                let text_before = input.view.text();
+
+                let _action =
+                    input
+                        .input
+                        .handle_event(event, &input.view.doc, &input.view.viewport);
+
+                if input.view.text() != text_before {
+                    if field == FindField::Query {
+                        // Search as you type, for the typed text rather than a word
+                        self.word_search = false;
+                        event_bus.emit("find.query_changed", json!({}), 10, "find");
+                    }
+                    event_bus.emit("ui.redraw", json!({}), 20, "find");
+                }
+                PropagationControl::Stop
+            }
+            _ => PropagationControl::Continue,
+        }
+    }
+
+    fn priority(&self) -> i32 {
+        50 // Below the pickers, which open over the bar
+    }
+
+    fn is_active(&self) -> bool {
+        self.visible
+    }
+}
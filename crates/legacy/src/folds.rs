@@ -0,0 +1,171 @@
+//! Code folding - foldable regions and the set of folded ones
+//!
+//! Foldable regions come from the language server, the syntax tree or indentation.
+//! Folded regions are stored as byte offsets of their first and last line and
+//! shifted through every edit the InputHandler applies, like marks.
+
+use std::ops::Range;
+
+use crate::marks::adjust_offset;
+use tiny_core::tree::Edit;
+
+/// A foldable region: the header line stays visible, the lines after it up to `last` hide
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FoldRange {
+    pub header: u32,
+    pub last: u32,
+}
+
+impl FoldRange {
+    pub fn new(header: u32, last: u32) -> Self {
+        Self { header, last }
+    }
+
+    /// Whether a line is the header or one of the lines the region hides
+    pub fn contains(&self, line: u32) -> bool {
+        self.header <= line && line <= self.last
+    }
+
+    /// Whether `other` lies within this region
+    pub fn encloses(&self, other: &FoldRange) -> bool {
+        self.contains(other.header) && other.last <= self.last
+    }
+
+    /// Lines hidden while folded
+    pub fn hidden_lines(&self) -> Range<u32> {
+        self.header + 1..self.last + 1
+    }
+}
+
+/// Visual width of a line's leading whitespace, None for blank lines
+fn indent_width(line: &str, tab_width: u32) -> Option<u32> {
+    let mut width = 0;
+    for ch in line.chars() {
+        match ch {
+            ' ' => width += 1,
+            '\t' => width = (width / tab_width + 1) * tab_width,
+            '\r' | '\n' => return None,
+            _ => return Some(width),
+        }
+    }
+    None
+}
+
+/// Foldable regions from indentation: a line followed by more deeply indented lines
+/// Blank lines inside a region don't end it, trailing blank lines aren't part of it.
+pub fn indent_fold_ranges(text: &str, tab_width: u32) -> Vec<FoldRange> {
+    let tab_width = tab_width.max(1);
+    let mut ranges = Vec::new();
+    // Open regions as (header line, header indent), innermost last
+    let mut open: Vec<(u32, u32)> = Vec::new();
+    let mut last_content = 0;
+
+    for (line, text) in text.split('\n').enumerate() {
+        let Some(indent) = indent_width(text, tab_width) else {
+            continue;
+        };
+        let line = line as u32;
+        while let Some(&(header, header_indent)) = open.last() {
+            if header_indent < indent {
+                break;
+            }
+            open.pop();
+            if last_content > header {
+                ranges.push(FoldRange::new(header, last_content));
+            }
+        }
+        open.push((line, indent));
+        last_content = line;
+    }
+
+    for (header, _) in open.into_iter().rev() {
+        if last_content > header {
+            ranges.push(FoldRange::new(header, last_content));
+        }
+    }
+
+    ranges.sort_unstable_by_key(|range| range.header);
+    ranges
+}
+
+/// The smallest region containing `line`
+pub fn innermost_at(ranges: &[FoldRange], line: u32) -> Option<FoldRange> {
+    ranges
+        .iter()
+        .filter(|range| range.contains(line))
+        .min_by_key(|range| range.last - range.header)
+        .copied()
+}
+
+/// Folded regions of a buffer, as byte offsets of their header and last line starts
+#[derive(Default, Clone)]
+pub struct FoldSet {
+    folds: Vec<(usize, usize)>,
+}
+
+impl FoldSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.folds.is_empty()
+    }
+
+    /// Fold the region between two line start offsets (no-op if already folded)
+    pub fn insert(&mut self, header: usize, last: usize) {
+        if !self.folds.contains(&(header, last)) {
+            self.folds.push((header, last));
+        }
+    }
+
+    /// Keep only the folds `keep` accepts
+    pub fn retain(&mut self, mut keep: impl FnMut(usize, usize) -> bool) {
+        self.folds.retain(|&(header, last)| keep(header, last));
+    }
+
+    /// Folded regions as `(header, last)` line start offsets
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.folds.iter().copied()
+    }
+
+    /// Shift all folds through an edit
+    pub fn adjust(&mut self, edit: &Edit) {
+        for (header, last) in &mut self.folds {
+            *header = adjust_offset(*header, edit);
+            *last = adjust_offset(*last, edit);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.folds.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indentation_regions_keep_closing_lines_visible() {
+        let text = "fn main() {\n    if x {\n        y();\n    }\n\n    z();\n}\n";
+        assert_eq!(
+            indent_fold_ranges(text, 4),
+            vec![FoldRange::new(0, 5), FoldRange::new(1, 2)]
+        );
+    }
+
+    #[test]
+    fn trailing_blank_lines_are_not_folded() {
+        let text = "def f():\n\treturn 1\n\n\nx = 2\n";
+        assert_eq!(indent_fold_ranges(text, 4), vec![FoldRange::new(0, 1)]);
+    }
+
+    #[test]
+    fn innermost_region_wins() {
+        let ranges = [FoldRange::new(0, 9), FoldRange::new(2, 4)];
+        assert_eq!(innermost_at(&ranges, 3), Some(FoldRange::new(2, 4)));
+        assert_eq!(innermost_at(&ranges, 6), Some(FoldRange::new(0, 9)));
+        assert_eq!(innermost_at(&ranges, 10), None);
+    }
+}
@@ -0,0 +1,145 @@
+//! IME composition - preedit text from an input method until it commits
+//!
+//! Input methods (CJK, compose and dead keys) first send preedit text that is drawn
+//! over the document and replaced as the user types, then commit the final text. This tracks the
+//! composition and tells the focused editor what to do, independent of the windowing
+//! library so it can be driven without a window.
+
+/// Input method event (mirrors winit's `Ime`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImeEvent {
+    Enabled,
+    /// Preedit text with the cursor (or selected clause) as a byte range in it;
+    /// empty text ends the preedit
+    Preedit {
+        text: String,
+        cursor: Option<(usize, usize)>,
+    },
+    Commit(String),
+    Disabled,
+}
+
+/// What the editor should do for an input method event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImeAction {
+    None,
+    /// Show this preedit over the selections, replacing the previous preedit
+    Preedit {
+        text: String,
+        cursor: Option<(usize, usize)>,
+    },
+    /// Drop the preedit (if any) and insert the committed text as one undoable edit
+    Commit(String),
+    /// Drop the preedit without touching the document
+    Cancel,
+}
+
+/// Composition state of the window's input method
+#[derive(Debug, Default)]
+pub struct Composition {
+    enabled: bool,
+    composing: bool,
+}
+
+impl Composition {
+    /// Whether the input method is on (it may consume key presses)
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Whether preedit text is showing (key presses belong to the input method)
+    pub fn is_composing(&self) -> bool {
+        self.composing
+    }
+
+    pub fn handle(&mut self, event: ImeEvent) -> ImeAction {
+        match event {
+            ImeEvent::Enabled => {
+                self.enabled = true;
+                ImeAction::None
+            }
+            ImeEvent::Preedit { text, cursor } if !text.is_empty() => {
+                self.composing = true;
+                ImeAction::Preedit { text, cursor }
+            }
+            ImeEvent::Preedit { .. } => self.end(),
+            ImeEvent::Commit(text) => {
+                self.composing = false;
+                if text.is_empty() {
+                    ImeAction::Cancel
+                } else {
+                    ImeAction::Commit(text)
+                }
+            }
+            ImeEvent::Disabled => {
+                self.enabled = false;
+                self.end()
+            }
+        }
+    }
+
+    fn end(&mut self) -> ImeAction {
+        if std::mem::take(&mut self.composing) {
+            ImeAction::Cancel
+        } else {
+            ImeAction::None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preedit(text: &str) -> ImeEvent {
+        ImeEvent::Preedit {
+            text: text.to_string(),
+            cursor: Some((text.len(), text.len())),
+        }
+    }
+
+    #[test]
+    fn composition_commits() {
+        let mut ime = Composition::default();
+        assert_eq!(ime.handle(ImeEvent::Enabled), ImeAction::None);
+        assert!(ime.is_enabled() && !ime.is_composing());
+
+        assert!(matches!(
+            ime.handle(preedit("n")),
+            ImeAction::Preedit { .. }
+        ));
+        assert!(matches!(
+            ime.handle(preedit("ni")),
+            ImeAction::Preedit { .. }
+        ));
+        assert!(ime.is_composing());
+
+        // The preedit is cleared right before the commit
+        assert_eq!(ime.handle(preedit("")), ImeAction::Cancel);
+        assert_eq!(
+            ime.handle(ImeEvent::Commit("你".to_string())),
+            ImeAction::Commit("你".to_string())
+        );
+        assert!(!ime.is_composing());
+    }
+
+    #[test]
+    fn dead_key_commits_without_preedit() {
+        let mut ime = Composition::default();
+        assert_eq!(
+            ime.handle(ImeEvent::Commit("é".to_string())),
+            ImeAction::Commit("é".to_string())
+        );
+        assert_eq!(ime.handle(preedit("")), ImeAction::None);
+    }
+
+    #[test]
+    fn disabling_cancels_the_preedit() {
+        let mut ime = Composition::default();
+        ime.handle(ImeEvent::Enabled);
+        ime.handle(preedit("´"));
+        assert_eq!(ime.handle(ImeEvent::Disabled), ImeAction::Cancel);
+        assert!(!ime.is_enabled() && !ime.is_composing());
+        assert_eq!(ime.handle(ImeEvent::Disabled), ImeAction::None);
+    }
+}
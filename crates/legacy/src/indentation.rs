@@ -0,0 +1,123 @@
+//! Indentation style - tabs or a number of spaces, detected per file
+//!
+//! Detection looks at how indentation changes from one line to the next: a file
+//! indented with 2 spaces mostly steps by 2, whatever the nesting depth. Lines that
+//! start with a tab count towards tab indentation instead.
+
+/// How one level of indentation is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndentStyle {
+    Tabs,
+    Spaces(u32),
+}
+
+/// Lines looked at when detecting (enough for a reliable histogram)
+const MAX_LINES: usize = 10_000;
+
+/// Widest space indentation considered a level
+const MAX_WIDTH: usize = 8;
+
+impl IndentStyle {
+    /// Style from the editor config
+    pub fn from_config(soft_tabs: bool, tab_width: u32) -> Self {
+        if soft_tabs {
+            Self::Spaces(tab_width.max(1))
+        } else {
+            Self::Tabs
+        }
+    }
+
+    /// Text inserted for one level of indentation
+    pub fn unit(self) -> String {
+        match self {
+            Self::Tabs => "\t".to_string(),
+            Self::Spaces(width) => " ".repeat(width.max(1) as usize),
+        }
+    }
+
+    /// Short description, e.g. for the status of a file
+    pub fn label(self) -> String {
+        match self {
+            Self::Tabs => "Tabs".to_string(),
+            Self::Spaces(width) => format!("{} Spaces", width),
+        }
+    }
+}
+
+/// Detect the indentation style of a text, None if it has no indented lines
+pub fn detect(text: &str) -> Option<IndentStyle> {
+    let mut tab_lines = 0;
+    let mut space_lines = 0;
+    // deltas[n] = times the space indentation grew by n from the previous line
+    let mut deltas = [0usize; MAX_WIDTH + 1];
+    let mut previous = 0;
+
+    for line in text.lines().take(MAX_LINES) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let spaces = line.len() - line.trim_start_matches(' ').len();
+        if line[spaces..].starts_with('\t') {
+            if spaces == 0 {
+                tab_lines += 1;
+            }
+            // Mixed indentation tells us nothing about the width
+            previous = 0;
+            continue;
+        }
+        if spaces > 0 {
+            space_lines += 1;
+        }
+        // Single spaces are alignment (e.g. ` * ` in block comments), not a level
+        if spaces > previous && spaces - previous > 1 && spaces - previous <= MAX_WIDTH {
+            deltas[spaces - previous] += 1;
+        }
+        previous = spaces;
+    }
+
+    if tab_lines == 0 && space_lines == 0 {
+        return None;
+    }
+    if tab_lines >= space_lines {
+        return Some(IndentStyle::Tabs);
+    }
+
+    // Most common step, preferring the smaller width on a tie
+    let (width, count) = deltas
+        .iter()
+        .enumerate()
+        .rev()
+        .max_by_key(|&(_, count)| *count)?;
+    (*count > 0).then_some(IndentStyle::Spaces(width as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_space_width_from_deltas() {
+        let two = "fn a() {\n  if x {\n    if y {\n      z();\n    }\n  }\n}\n";
+        assert_eq!(detect(two), Some(IndentStyle::Spaces(2)));
+
+        // Deep nesting doesn't look like a wider indent
+        let four = "a:\n    b:\n        c:\n            d\n    e\n";
+        assert_eq!(detect(four), Some(IndentStyle::Spaces(4)));
+
+        // Block comment alignment is ignored
+        let comments = "/**\n * doc\n */\nfn a() {\n    b();\n}\n";
+        assert_eq!(detect(comments), Some(IndentStyle::Spaces(4)));
+    }
+
+    #[test]
+    fn detects_tabs() {
+        let text = "fn a() {\n\tb();\n\tif c {\n\t\td();\n\t}\n}\n";
+        assert_eq!(detect(text), Some(IndentStyle::Tabs));
+    }
+
+    #[test]
+    fn unindented_text_has_no_style() {
+        assert_eq!(detect("a\nb\n\nc\n"), None);
+        assert_eq!(detect(""), None);
+    }
+}
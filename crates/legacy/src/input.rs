@@ -2,19 +2,33 @@
 //!
 //! Handles keyboard, mouse, and multi-cursor selections
 
-use crate::coordinates::Viewport;
-use crate::history::{DocumentHistory, DocumentSnapshot, SelectionHistory};
+use crate::comments;
+use crate::completion;
+use crate::config::AutoCloseConfig;
+use crate::confusables;
+use crate::coordinates::{self, DisplayMap, Viewport};
+use crate::folds::{self, FoldRange, FoldSet};
+use crate::history::{DocumentHistory, DocumentSnapshot};
 use crate::input_types::MouseButton;
 use crate::lsp_manager::TextChange;
-use crate::syntax::SyntaxHighlighter;
+use crate::marks::{ChangeList, MarkSet};
+use crate::snippets::{self, Snippet, SnippetLibrary, SnippetSession};
+use crate::surround;
+use crate::syntax::{
+    AutoPair, CommentTokens, Indenter, Languages, SyntaxHighlighter, DEFAULT_AUTO_PAIRS,
+};
 use crate::text_editor_plugin::TextEditorPlugin;
+use crate::transforms::{self, Transform};
+use crate::words::{self, WordConfig};
 use arboard::Clipboard;
 use serde_json::Value;
 use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use tiny_core::tree::{Content, Doc, Edit, Point, SearchOptions};
+use tiny_core::tree::{advance_column, Content, Doc, Edit, Point, SearchOptions, Tree};
 use tiny_sdk::{DocPos, LayoutPos, LayoutRect};
+use unicode_segmentation::UnicodeSegmentation;
 
 /// Actions that can be triggered by input
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -35,6 +49,8 @@ pub enum InputMode {
     Normal,
     /// Vim-like visual mode - text selection
     Visual,
+    /// Overwrite mode - typed characters replace the ones under the cursor
+    Replace,
     /// Custom modes for extensions
     Custom(&'static str),
 }
@@ -45,6 +61,19 @@ impl Default for InputMode {
     }
 }
 
+impl InputMode {
+    /// Mode name as sent to plugins ("insert", "normal", "visual" or the custom name)
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Insert => "insert",
+            Self::Normal => "normal",
+            Self::Visual => "visual",
+            Self::Replace => "replace",
+            Self::Custom(name) => name,
+        }
+    }
+}
+
 /// Selection with cursor and anchor in document coordinates
 #[derive(Clone)]
 pub struct Selection {
@@ -54,6 +83,49 @@ pub struct Selection {
     pub anchor: DocPos,
     /// Unique ID
     pub id: u32,
+    /// Goal column for vertical navigation (None means use current column)
+    pub goal_column: Option<u32>,
+}
+
+/// Input method composition in progress
+/// The preedit is drawn over the document at each selection; the document itself only
+/// changes once the composition commits
+struct ImeSession {
+    /// Text being composed
+    text: String,
+    /// Cursor or selected clause within the preedit (byte range)
+    cursor: Option<(usize, usize)>,
+}
+
+/// Selected text being dragged to a new place
+struct TextDrag {
+    /// Where the press landed (the cursor goes there if the text isn't dropped)
+    origin: DocPos,
+    /// Drop position under the mouse, previewed as a caret
+    target: Option<DocPos>,
+}
+
+/// Rectangular selection in visual columns (tabs expanded to tab stops)
+/// Expanded to one selection per line; lines ending before the left edge are skipped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct BlockSelection {
+    anchor_line: u32,
+    anchor_column: u32,
+    cursor_line: u32,
+    cursor_column: u32,
+}
+
+impl BlockSelection {
+    fn lines(&self) -> std::ops::RangeInclusive<u32> {
+        self.anchor_line.min(self.cursor_line)..=self.anchor_line.max(self.cursor_line)
+    }
+
+    fn columns(&self) -> (u32, u32) {
+        (
+            self.anchor_column.min(self.cursor_column),
+            self.anchor_column.max(self.cursor_column),
+        )
+    }
 }
 
 impl Selection {
@@ -85,6 +157,22 @@ impl Selection {
         }
     }
 
+    /// First and last line the selection covers
+    /// A multi-line selection ending at column 0 doesn't include that last line
+    pub fn line_span(&self) -> (u32, u32) {
+        let start = self.min_pos();
+        let end = if start == self.cursor {
+            self.anchor
+        } else {
+            self.cursor
+        };
+        if end.line > start.line && end.column == 0 {
+            (start.line, end.line - 1)
+        } else {
+            (start.line, end.line)
+        }
+    }
+
     /// Generate rectangles for this selection (1-3 rectangles for multi-line)
     pub fn to_rectangles(&self, doc: &Doc, viewport: &Viewport) -> Vec<LayoutRect> {
         if self.is_cursor() {
@@ -102,10 +190,11 @@ impl Selection {
         let mut rects = vec![LayoutRect::new(0.0, 0.0, 0.0, 0.0)];
 
         let tree = doc.read();
+        let start_layout = viewport.doc_to_layout_with_tree(start, &tree);
+        let end_layout = viewport.doc_to_layout_with_tree(end, &tree);
 
-        if start.line == end.line {
-            let start_layout = viewport.doc_to_layout_with_tree(start, &tree);
-            let end_layout = viewport.doc_to_layout_with_tree(end, &tree);
+        // Rows rather than lines: a soft wrapped line spans several
+        if start_layout.y.0 == end_layout.y.0 {
             rects.push(LayoutRect::new(
                 start_layout.x.0 - 2.0,
                 start_layout.y.0,
@@ -113,8 +202,6 @@ impl Selection {
                 line_height,
             ));
         } else {
-            let start_layout = viewport.doc_to_layout_with_tree(start, &tree);
-            let end_layout = viewport.doc_to_layout_with_tree(end, &tree);
             let viewport_right = viewport.bounds.width.0;
 
             // First line
@@ -126,12 +213,13 @@ impl Selection {
             ));
 
             // Middle lines
-            if end.line > start.line + 1 {
+            if end_layout.y.0 > start_layout.y.0 + line_height {
                 rects.push(LayoutRect::new(
                     0.0,
                     start_layout.y.0 + line_height,
                     viewport.bounds.width.0,
-                    (end.line - start.line - 1) as f32 * line_height,
+                    // Measured in layout: folded lines take no rows, wrapped lines several
+                    end_layout.y.0 - start_layout.y.0 - line_height,
                 ));
             }
 
@@ -318,8 +406,8 @@ pub struct InputHandler {
     next_id: u32,
     /// Clipboard contents
     clipboard: Option<String>,
-    /// Goal column for vertical navigation (None means use current column)
-    goal_column: Option<u32>,
+    /// Word picked by select-next-occurrence from an empty cursor (matched as a whole word)
+    occurrence_word: Option<String>,
     /// Pending edits that haven't been flushed yet
     pending_edits: Vec<Edit>,
     /// Time of last edit for debouncing
@@ -334,15 +422,66 @@ pub struct InputHandler {
     pending_lsp_changes: Vec<TextChange>,
     /// Accumulated edits for syntax token adjustment
     pending_renderer_edits: Vec<tiny_core::tree::Edit>,
+    /// Edits not yet seen by the completion word index (None once the tree was replaced)
+    pending_word_edits: Option<Vec<Edit>>,
     /// History for undo/redo (document + selections)
     history: DocumentHistory,
-    /// Navigation history for cursor positions (Cmd+[/])
-    nav_history: SelectionHistory,
+    /// Jump origins not yet forwarded to the global jump list (drained by EditorLogic)
+    pending_jumps: Vec<DocPos>,
+    /// Buffer-local marks (byte offsets, adjusted through every edit)
+    marks: MarkSet,
+    /// Text the find bar searches while "in selection" is on (adjusted through every edit)
+    find_scope: Option<Range<usize>>,
+    /// Recent edit locations for change-list navigation
+    change_list: ChangeList,
     /// Drag anchor in document coordinates (set when drag starts)
     drag_anchor: Option<DocPos>,
-    /// Selection anchor - when set, cursor movements extend selection from this point
-    /// Set when entering selection mode (shift pressed), cleared when leaving selection mode
-    selection_anchor: Option<DocPos>,
+    /// Selected text being dragged (started by pressing inside a selection)
+    text_drag: Option<TextDrag>,
+    /// Active block selection (cleared by any command that doesn't extend it)
+    block: Option<BlockSelection>,
+    /// Text last copied from a block selection (pasted back as a block)
+    block_clipboard: Option<String>,
+    /// Tab stops of the view, refreshed on every event (for visual columns)
+    tab_stops: u32,
+    /// Word definition for word movement and deletion
+    word_config: Arc<WordConfig>,
+    /// One level of indentation
+    indent_unit: String,
+    /// Close brackets and quotes as they're typed, by language
+    auto_close_pairs: Arc<AutoCloseConfig>,
+    /// Byte offsets of closers inserted by auto-closing (adjusted through every edit)
+    /// Typing one of these steps over it instead of inserting another
+    auto_closed: Vec<usize>,
+    /// Ranges each selection had before being expanded, by selection id (for shrinking)
+    expansion_stacks: Vec<(u32, Vec<Range<usize>>)>,
+    /// Selection ranges from the language server and the document version they're for
+    lsp_selection_ranges: Option<(u64, Vec<Range<usize>>)>,
+    /// Folded regions (byte offsets, adjusted through every edit)
+    folds: FoldSet,
+    /// Lines hidden by the folded regions
+    display_map: DisplayMap,
+    /// Folding ranges from the language server and the document version they're for
+    lsp_fold_ranges: Option<(u64, Vec<FoldRange>)>,
+    /// Foldable regions and the (document, syntax tree) versions they were found for
+    fold_ranges_cache: Option<((u64, u64), Vec<FoldRange>)>,
+    /// Soft wrap column (None = no wrapping) and whether continuation rows keep the indent
+    soft_wrap: Option<(u32, bool)>,
+    /// Document version, soft wrap and tab width the display map was built for
+    display_map_key: (u64, Option<(u32, bool)>, u32),
+    /// Tree the display map's soft wraps were computed for
+    wrapped_tree: Option<Arc<Tree>>,
+    /// Edits applied since the soft wraps were computed, so only the lines they touched
+    /// are wrapped again (None if the tree was replaced, e.g. by undo)
+    pending_wrap_edits: Option<Vec<Edit>>,
+    /// Snippet being filled in (tab stops as byte offsets, adjusted through every edit)
+    snippet: Option<SnippetSession>,
+    /// User snippets expanded by tab, by language
+    snippets: Arc<SnippetLibrary>,
+    /// Input method composition in progress
+    ime: Option<ImeSession>,
+    /// Path of the document, for snippet variables
+    file_path: Option<PathBuf>,
     /// Track click count and timing for double/triple click detection
     last_click_time: Option<Instant>,
     last_click_pos: Option<DocPos>,
@@ -353,6 +492,11 @@ pub struct InputHandler {
     last_checkpoint_time: Option<Instant>,
     /// Current input mode (for vim-like modal editing)
     current_mode: InputMode,
+    /// (typed, overwritten) text of each replace mode keystroke, by selection id
+    /// Backspace restores them until the cursors move
+    overwritten: Vec<(u32, Vec<(String, String)>)>,
+    /// The next typed character replaces the one under each cursor (vim `r`)
+    replace_pending: bool,
     /// Pending scroll delta from drag operations (to be consumed by app)
     pub pending_scroll_delta: Option<(f32, f32)>,
 }
@@ -364,10 +508,11 @@ impl InputHandler {
                 cursor: DocPos::default(),
                 anchor: DocPos::default(),
                 id: 0,
+                goal_column: None,
             }],
             next_id: 1,
             clipboard: None,
-            goal_column: None,
+            occurrence_word: None,
             pending_edits: Vec::new(),
             last_edit_time: None,
             syntax_highlighter: None,
@@ -375,16 +520,43 @@ impl InputHandler {
             has_pending_syntax_update: false,
             pending_lsp_changes: Vec::new(),
             pending_renderer_edits: Vec::new(),
+            pending_word_edits: Some(Vec::new()),
             history: DocumentHistory::new(),
-            nav_history: SelectionHistory::with_max_size(50),
+            pending_jumps: Vec::new(),
+            marks: MarkSet::new(),
+            find_scope: None,
+            change_list: ChangeList::new(),
             drag_anchor: None,
-            selection_anchor: None,
+            text_drag: None,
+            block: None,
+            block_clipboard: None,
+            tab_stops: 4,
+            word_config: Arc::new(WordConfig::default()),
+            indent_unit: "    ".to_string(),
+            auto_close_pairs: Arc::new(AutoCloseConfig::default()),
+            auto_closed: Vec::new(),
+            expansion_stacks: Vec::new(),
+            lsp_selection_ranges: None,
+            folds: FoldSet::new(),
+            display_map: DisplayMap::default(),
+            lsp_fold_ranges: None,
+            fold_ranges_cache: None,
+            soft_wrap: None,
+            display_map_key: (0, None, 0),
+            wrapped_tree: None,
+            pending_wrap_edits: Some(Vec::new()),
+            snippet: None,
+            snippets: Arc::new(SnippetLibrary::default()),
+            ime: None,
+            file_path: None,
             last_click_time: None,
             last_click_pos: None,
             click_count: 0,
             ignore_next_drag: false,
             last_checkpoint_time: None,
             current_mode: InputMode::default(),
+            overwritten: Vec::new(),
+            replace_pending: false,
             pending_scroll_delta: None,
         }
     }
@@ -399,25 +571,139 @@ impl InputHandler {
         self.current_mode = mode;
     }
 
+    /// Set the word definition used by word movement and deletion
+    pub fn set_word_config(&mut self, config: Arc<WordConfig>) {
+        self.word_config = config;
+    }
+
+    /// Set the languages that auto-close brackets and quotes
+    pub fn set_auto_close_pairs(&mut self, config: Arc<AutoCloseConfig>) {
+        self.auto_close_pairs = config;
+    }
+
+    /// Set the user snippets that tab expands
+    pub fn set_snippets(&mut self, snippets: Arc<SnippetLibrary>) {
+        self.snippets = snippets;
+    }
+
+    /// Set the path of the document being edited (for snippet variables like `$TM_FILENAME`)
+    pub fn set_file_path(&mut self, path: Option<PathBuf>) {
+        self.file_path = path;
+    }
+
+    /// Soft wrap lines at a visual column (None to stop wrapping)
+    /// With `indent_continuations` wrapped rows line up with the line's indentation
+    pub fn set_soft_wrap(&mut self, columns: Option<u32>, indent_continuations: bool) {
+        self.soft_wrap = columns.map(|columns| (columns.max(1), indent_continuations));
+    }
+
+    /// Provide language server selection ranges (byte ranges in the given document version)
+    /// Used by expand selection alongside the syntax tree while the document is unchanged
+    pub fn set_lsp_selection_ranges(&mut self, version: u64, ranges: Vec<Range<usize>>) {
+        self.lsp_selection_ranges = Some((version, ranges));
+    }
+
+    /// Whether language server selection ranges are missing for the current document
+    pub fn needs_lsp_selection_ranges(&self, doc: &Doc) -> bool {
+        !matches!(self.lsp_selection_ranges, Some((version, _)) if version == doc.version())
+    }
+
+    /// Provide language server folding ranges (lines in the given document version)
+    /// Used instead of the syntax tree's while the document is unchanged
+    pub fn set_lsp_fold_ranges(&mut self, version: u64, ranges: Vec<FoldRange>) {
+        self.lsp_fold_ranges = Some((version, ranges));
+        self.fold_ranges_cache = None;
+    }
+
+    /// Whether language server folding ranges are missing for the current document
+    pub fn needs_lsp_fold_ranges(&self, doc: &Doc) -> bool {
+        !matches!(self.lsp_fold_ranges, Some((version, _)) if version == doc.version())
+    }
+
+    /// Set the text inserted for one level of indentation
+    pub fn set_indent_unit(&mut self, unit: impl Into<String>) {
+        self.indent_unit = unit.into();
+    }
+
     /// Helper to convert byte offset to DocPos
     fn byte_to_doc_pos(&self, tree: &tiny_core::tree::Tree, byte_offset: usize) -> DocPos {
-        let line = tree.byte_to_line(byte_offset);
-        let line_start_byte = tree.line_to_byte(line).unwrap_or(0);
-        let column = tree
-            .get_text_slice(line_start_byte..byte_offset)
-            .chars()
-            .count() as u32;
-        DocPos {
-            line,
-            column,
-            byte_offset: 0,
-        }
+        doc_pos_at(tree, byte_offset)
     }
 
     /// Handle an event (new event system)
     /// Returns the action that should be taken
     pub fn handle_event(&mut self, event: &Event, doc: &Doc, viewport: &Viewport) -> InputAction {
-        match event.name.as_str() {
+        self.tab_stops = viewport.metrics.tab_stops.max(1);
+        // Motions grow the block while in visual block mode
+        let name = match block_motion(&event.name) {
+            Some(extend) if self.current_mode == InputMode::Visual && self.block.is_some() => {
+                extend
+            }
+            _ => event.name.as_str(),
+        };
+        let ends_block = !keeps_block_selection(name) && self.block.take().is_some();
+        if !matches!(
+            event.name.as_str(),
+            "editor.extend_selection" | "editor.shrink_selection"
+        ) {
+            self.expansion_stacks.clear();
+        }
+        if !matches!(
+            event.name.as_str(),
+            "editor.insert_char" | "editor.insert_space" | "editor.delete_backward"
+        ) {
+            self.overwritten.clear();
+        }
+        if !event.name.starts_with("editor.ime_") {
+            // Anything else keeps the preedit as typed text
+            self.end_composition(doc);
+        }
+        let replace_pending = std::mem::take(&mut self.replace_pending);
+
+        let action = match name {
+            // Overwriting
+            "editor.insert_char" | "editor.insert_space" if replace_pending => {
+                match typed_text(event).chars().next() {
+                    Some(ch) => self.replace_chars(doc, ch),
+                    None => InputAction::None,
+                }
+            }
+            "editor.insert_char" | "editor.insert_space"
+                if self.current_mode == InputMode::Replace =>
+            {
+                match typed_text(event) {
+                    "" => InputAction::None,
+                    text => self.overwrite(doc, text),
+                }
+            }
+            "editor.delete_backward" if self.current_mode == InputMode::Replace => {
+                self.overwrite_backspace(doc)
+            }
+            "editor.toggle_overwrite" => {
+                self.current_mode = if self.current_mode == InputMode::Replace {
+                    InputMode::Insert
+                } else {
+                    InputMode::Replace
+                };
+                InputAction::Redraw
+            }
+            "editor.replace_mode" => {
+                self.current_mode = InputMode::Replace;
+                InputAction::Redraw
+            }
+            "editor.replace_char" => {
+                self.replace_pending = true;
+                InputAction::None
+            }
+            // Escape leaves replace mode before it removes cursors
+            "cursor.remove" if self.current_mode == InputMode::Replace => {
+                self.current_mode = InputMode::Insert;
+                InputAction::Redraw
+            }
+            "cursor.remove" | "visual.escape" if self.current_mode == InputMode::Visual => {
+                self.leave_visual_block()
+            }
+
             // Text insertion
             "editor.insert_char" => {
                 let ch = event
@@ -425,19 +711,107 @@ impl InputHandler {
                     .get("char")
                     .and_then(|v| v.as_str())
                     .unwrap_or("");
-                if !ch.is_empty() {
-                    self.insert_text(doc, ch)
-                } else {
+                let mut chars = ch.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => self.insert_char(doc, c),
+                    (Some(_), Some(_)) => self.insert_text(doc, ch),
+                    _ => InputAction::None,
+                }
+            }
+            "editor.insert_newline" => self.insert_newline(doc),
+            // Text inserted as-is, without auto-pairing (e.g. picked by name)
+            "editor.insert_text" => {
+                let text = event
+                    .data
+                    .get("text")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("");
+                if text.is_empty() {
                     InputAction::None
+                } else {
+                    self.insert_text(doc, text)
                 }
             }
-            "editor.insert_newline" => self.insert_text(doc, "\n"),
-            "editor.insert_tab" => self.insert_text(doc, "\t"),
+            "editor.ime_preedit" => {
+                let text = event.data.get("text").and_then(|v| v.as_str());
+                let cursor = event.data.get("cursor").and_then(|v| {
+                    Some((v.get(0)?.as_u64()? as usize, v.get(1)?.as_u64()? as usize))
+                });
+                self.show_preedit(text.unwrap_or(""), cursor)
+            }
+            "editor.ime_commit" => {
+                let text = event.data.get("text").and_then(|v| v.as_str());
+                self.commit_composition(doc, text.unwrap_or(""))
+            }
+            "editor.ime_cancel" => self.cancel_composition(),
+            "editor.insert_tab" => self.tab(doc),
+            "editor.unindent" if self.snippet.is_some() => self.previous_tab_stop(doc),
+            "editor.unindent" => self.shift_lines(doc, true),
+            "indent.increase" | "indent.line_increase" | "visual.indent" => {
+                self.shift_lines(doc, false)
+            }
+            "indent.decrease" | "indent.line_decrease" | "visual.unindent" => {
+                self.shift_lines(doc, true)
+            }
             "editor.insert_space" => self.insert_text(doc, " "),
 
             // Deletion
             "editor.delete_backward" => self.delete_at_cursor(doc, false),
             "editor.delete_forward" => self.delete_at_cursor(doc, true),
+            "editor.delete_word_left" => self.delete_word(doc, false),
+            "editor.delete_word_right" => self.delete_word(doc, true),
+
+            // Line operations
+            "editor.move_line_up" => self.move_lines(doc, true),
+            "editor.move_line_down" => self.move_lines(doc, false),
+            "editor.duplicate_line" => self.duplicate_lines(doc),
+            "editor.delete_line" => self.delete_lines(doc),
+            "editor.join_lines" => self.join_lines(doc),
+            "editor.insert_newline_above" => self.open_line(doc, true),
+            "editor.insert_newline_below" => self.open_line(doc, false),
+            "editor.split_line" => self.split_line(doc),
+            "editor.toggle_comment" => self.toggle_line_comment(doc),
+            "editor.toggle_block_comment" => self.toggle_block_comment(doc),
+            "editor.auto_indent" => self.reindent_selection(doc),
+            "editor.extend_selection" => self.expand_selection(doc),
+            "editor.shrink_selection" => self.shrink_selection(doc),
+            "editor.surround" => {
+                let body = event.data.get("body").and_then(|v| v.as_str());
+                let block = event.data.get("block").and_then(|v| v.as_bool());
+                match body {
+                    Some(body) => self.surround(doc, body, block.unwrap_or(false)),
+                    None => InputAction::None,
+                }
+            }
+            "editor.unwrap" => self.unwrap_enclosing(doc),
+
+            // Text transforms
+            "editor.transform" => {
+                let name = event.data.get("name").and_then(|v| v.as_str());
+                match name.and_then(transforms::find) {
+                    Some(transform) => self.transform(doc, transform, false),
+                    None => InputAction::None,
+                }
+            }
+            "case.toggle" | "visual.toggle_case" => {
+                self.transform_by_name(doc, "toggle_case", false)
+            }
+            "case.upper" | "visual.upper" => self.transform_by_name(doc, "upper", false),
+            "case.lower" | "visual.lower" => self.transform_by_name(doc, "lower", false),
+            "case.upper_line" => self.transform_by_name(doc, "upper", true),
+            "case.lower_line" => self.transform_by_name(doc, "lower", true),
+
+            // Folding
+            "fold.toggle" => self.toggle_fold_at_cursors(doc),
+            "fold.collapse" => self.fold_at_cursors(doc, false),
+            "fold.expand" => self.unfold_at_cursors(doc, false),
+            "fold.collapse_recursively" => self.fold_at_cursors(doc, true),
+            "fold.expand_recursively" => self.unfold_at_cursors(doc, true),
+            "fold.collapse_all" => {
+                let ranges = self.fold_ranges(doc).to_vec();
+                self.fold(doc, &ranges)
+            }
+            "fold.expand_all" => self.unfold_where(doc, |_| true),
 
             // Clipboard
             "editor.copy" => {
@@ -452,10 +826,42 @@ impl InputHandler {
                 self.paste(doc);
                 InputAction::Redraw
             }
+            // Copy given text rather than the selection (e.g. a detail from a picker)
+            "editor.copy_text" => {
+                let text = event
+                    .data
+                    .get("text")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("");
+                let _ = Clipboard::new().and_then(|mut c| c.set_text(text));
+                self.clipboard = Some(text.to_string());
+                InputAction::None
+            }
+            // Remove invisible and bidi-control characters, replace lookalikes with ASCII
+            "editor.replace_confusables" => {
+                let text = doc.read().flatten_to_string();
+                self.replace_ranges_keeping_selections(doc, confusables::replacements(&text))
+            }
             "editor.select_all" => {
                 self.select_all(doc);
                 InputAction::Redraw
             }
+            "editor.select_next_occurrence" => self.select_next_occurrence(doc),
+            "editor.select_all_occurrences" => self.select_all_occurrences(doc),
+            "editor.unselect_occurrence" => self.unselect_occurrence(),
+
+            // Multiple cursors
+            "cursor.add_above" => self.add_cursor_vertical(doc, -1),
+            "cursor.add_below" => self.add_cursor_vertical(doc, 1),
+            "cursor.add_at_click" => self.add_cursor_at_click(event, doc, viewport),
+            "cursor.remove" => self.remove_secondary_cursors(),
+
+            // Block (column) selection
+            "visual.block_mode" => self.toggle_visual_block(doc),
+            "editor.extend_block_left" => self.extend_block(doc, -1, 0),
+            "editor.extend_block_right" => self.extend_block(doc, 1, 0),
+            "editor.extend_block_up" => self.extend_block(doc, 0, -1),
+            "editor.extend_block_down" => self.extend_block(doc, 0, 1),
 
             // Cursor movement
             "editor.move_left" => self.move_cursor(doc, -1, 0, false),
@@ -470,6 +876,18 @@ impl InputHandler {
             "editor.move_line_end" => self.move_to_line_edge(doc, true, false),
             "editor.extend_line_start" => self.move_to_line_edge(doc, false, true),
             "editor.extend_line_end" => self.move_to_line_edge(doc, true, true),
+            "editor.move_word_left" => self.move_word(doc, false, false),
+            "editor.move_word_right" => self.move_word(doc, true, false),
+            "editor.extend_word_left" => self.move_word(doc, false, true),
+            "editor.extend_word_right" => self.move_word(doc, true, true),
+            "editor.move_line_start_smart" => self.move_to_line_edge_smart(doc, false, false),
+            "editor.move_line_end_smart" => self.move_to_line_edge_smart(doc, true, false),
+            "editor.extend_line_start_smart" => self.move_to_line_edge_smart(doc, false, true),
+            "editor.extend_line_end_smart" => self.move_to_line_edge_smart(doc, true, true),
+            "editor.move_document_start" => self.move_to_document_edge(doc, false, false),
+            "editor.move_document_end" => self.move_to_document_edge(doc, true, false),
+            "editor.extend_document_start" => self.move_to_document_edge(doc, false, true),
+            "editor.extend_document_end" => self.move_to_document_edge(doc, true, true),
             "editor.page_up" => self.page_jump(doc, true, false),
             "editor.page_down" => self.page_jump(doc, false, false),
             "editor.extend_page_up" => self.page_jump(doc, true, true),
@@ -486,12 +904,31 @@ impl InputHandler {
             "mouse.press" => self.handle_mouse_press(event, doc, viewport),
             "mouse.drag" => self.handle_mouse_drag_event(event, doc, viewport),
             "mouse.release" => {
-                self.clear_drag_anchor();
-                InputAction::None
+                let copy = event.data.get("alt").and_then(|v| v.as_bool());
+                if self.end_drag(doc, copy.unwrap_or(false)) {
+                    InputAction::Redraw
+                } else {
+                    InputAction::None
+                }
             }
 
             _ => InputAction::None,
+        };
+
+        // Visual block mode ends with its block (edits and any other command)
+        if ends_block && self.current_mode == InputMode::Visual {
+            self.current_mode = InputMode::Normal;
         }
+
+        // Leaving the current tab stop ends the snippet, and so does undo
+        if let Some(session) = &self.snippet {
+            let cursor = doc.read().doc_pos_to_byte(self.selections[0].cursor);
+            if matches!(action, InputAction::Undo | InputAction::Redo) || !session.contains(cursor)
+            {
+                self.snippet = None;
+            }
+        }
+        action
     }
 
     /// Handle mouse press events
@@ -518,6 +955,22 @@ impl InputHandler {
             y: tiny_sdk::LogicalPixels(y as f32),
         };
 
+        // Clicks in the gutter toggle the fold starting on that line
+        if x < 0.0 {
+            let line = viewport
+                .layout_to_doc_with_tree(
+                    tiny_sdk::LayoutPos {
+                        x: tiny_sdk::LogicalPixels(0.0),
+                        y: tiny_sdk::LogicalPixels(y as f32 + viewport.scroll.y.0),
+                    },
+                    &doc.read(),
+                )
+                .line;
+            if self.toggle_fold_at_line(doc, line) {
+                return InputAction::Redraw;
+            }
+        }
+
         // Store drag anchor in document coordinates (properly clamped to document bounds)
         let tree = doc.read();
         self.drag_anchor = Some(viewport.layout_to_doc_with_tree(
@@ -594,30 +1047,32 @@ impl InputHandler {
         }
     }
 
-    /// Move cursor to a new position, handling selection based on current state
-    fn move_cursor_to(&mut self, new_position: DocPos, extending_selection: bool) {
-        if extending_selection {
-            // When extending, use selection anchor if available, or current cursor if not
-            if self.selection_anchor.is_none() {
-                self.selection_anchor = self.selections.first().map(|sel| sel.anchor);
+    /// Move every cursor with `motion`, collapsing or extending each selection
+    /// Vertical motions keep each selection's goal column, others reset it
+    fn move_cursors(
+        &mut self,
+        doc: &Doc,
+        extending_selection: bool,
+        vertical: bool,
+        motion: impl Fn(&Tree, &Selection) -> DocPos,
+    ) {
+        let tree = doc.read();
+        for sel in &mut self.selections {
+            if vertical {
+                sel.goal_column.get_or_insert(sel.cursor.column);
+            } else {
+                sel.goal_column = None;
             }
 
-            // Update cursor while keeping anchor
-            for sel in &mut self.selections {
-                sel.cursor = new_position;
-                if let Some(anchor) = self.selection_anchor {
-                    sel.anchor = anchor;
-                }
-            }
-        } else {
-            // Not extending - move cursor and collapse selection
-            for sel in &mut self.selections {
-                sel.cursor = new_position;
+            let new_position = motion(&tree, sel);
+            sel.cursor = new_position;
+            if !extending_selection {
                 sel.anchor = new_position;
             }
-            // Clear selection anchor when making a non-extending movement
-            self.selection_anchor = None;
         }
+        drop(tree);
+
+        self.merge_selections(doc);
     }
 
     /// Unified cursor movement
@@ -628,152 +1083,318 @@ impl InputHandler {
         dy: i32,
         extending_selection: bool,
     ) -> InputAction {
-        let tree = doc.read();
-
-        // Handle vertical movement
-        if dy != 0 {
-            if self.goal_column.is_none() && !self.selections.is_empty() {
-                self.goal_column = Some(self.selections[0].cursor.column);
-            }
-
-            // Get current cursor position
-            let mut new_pos = self
-                .selections
-                .first()
-                .map(|s| s.cursor)
-                .unwrap_or_default();
-
-            if dy < 0 && new_pos.line > 0 {
-                new_pos.line -= 1;
-            } else if dy > 0 && tree.line_to_byte(new_pos.line + 1).is_some() {
-                new_pos.line += 1;
-            }
+        // Handle vertical movement (by display row when lines wrap)
+        if dy != 0 && self.display_map.is_wrapped() {
+            self.move_display_rows(doc, dy, extending_selection);
+        } else if dy != 0 {
+            let display_map = self.display_map.clone();
+            self.move_cursors(doc, extending_selection, true, |tree, sel| {
+                // Folded lines are stepped over
+                let mut new_pos = sel.cursor;
+                if dy < 0 && new_pos.line > 0 {
+                    new_pos.line = display_map.visible_line(new_pos.line - 1, false);
+                } else if dy > 0 {
+                    let next = display_map.visible_line(new_pos.line + 1, true);
+                    if tree.line_to_byte(next).is_some() {
+                        new_pos.line = next;
+                    }
+                }
 
-            let line_length = tree.line_char_count(new_pos.line) as u32;
-            new_pos.column = self.goal_column.unwrap_or(new_pos.column).min(line_length);
-            new_pos.byte_offset = 0;
-            self.move_cursor_to(new_pos, extending_selection);
+                let line_length = tree.line_char_count(new_pos.line) as u32;
+                new_pos.column = sel.goal_column.unwrap_or(new_pos.column).min(line_length);
+                new_pos.byte_offset = 0;
+                new_pos
+            });
         }
 
         // Handle horizontal movement
         if dx != 0 {
-            eprintln!("⬅️➡️ move_cursor: horizontal movement dx={}", dx);
-            self.goal_column = None;
+            let display_map = self.display_map.clone();
+            self.move_cursors(doc, extending_selection, false, |tree, sel| {
+                let mut new_pos = sel.cursor;
+                if dx < 0 {
+                    if new_pos.column > 0 {
+                        new_pos.column -= 1;
+                    } else if new_pos.line > 0 {
+                        new_pos.line = display_map.visible_line(new_pos.line - 1, false);
+                        new_pos.column = tree.line_char_count(new_pos.line) as u32;
+                    }
+                } else {
+                    let line_length = tree.line_char_count(new_pos.line) as u32;
+                    let next = display_map.visible_line(new_pos.line + 1, true);
+                    if new_pos.column < line_length {
+                        new_pos.column += 1;
+                    } else if tree.line_to_byte(next).is_some() {
+                        new_pos.line = next;
+                        new_pos.column = 0;
+                    }
+                }
+                new_pos.byte_offset = 0;
+                new_pos
+            });
+        }
 
-            // Get current cursor position
-            let mut new_pos = self
-                .selections
-                .first()
-                .map(|s| s.cursor)
-                .unwrap_or_default();
+        InputAction::Redraw
+    }
 
-            eprintln!(
-                "⬅️➡️ move_cursor: current pos line={}, column={}",
-                new_pos.line, new_pos.column
-            );
-            if dx < 0 {
-                if new_pos.column > 0 {
-                    new_pos.column -= 1;
-                } else if new_pos.line > 0 {
-                    new_pos.line -= 1;
-                    new_pos.column = tree.line_char_count(new_pos.line) as u32;
+    /// Move every cursor up or down one display row of soft wrapped lines
+    /// The goal column is kept as a visual column within the row
+    fn move_display_rows(&mut self, doc: &Doc, dy: i32, extending_selection: bool) {
+        let tree = doc.read();
+        let display_map = &self.display_map;
+        let tab_stops = self.tab_stops;
+        for sel in &mut self.selections {
+            let (row, visual) = display_row_position(&tree, display_map, sel.cursor, tab_stops);
+            let goal = *sel.goal_column.get_or_insert(visual);
+
+            let line = sel.cursor.line;
+            let target = if dy < 0 {
+                if row > 0 {
+                    Some((line, row - 1))
+                } else if line > 0 {
+                    let prev = display_map.visible_line(line - 1, false);
+                    Some((prev, display_map.rows_in_line(prev) - 1))
+                } else {
+                    None
                 }
+            } else if row + 1 < display_map.rows_in_line(line) {
+                Some((line, row + 1))
             } else {
-                let line_length = tree.line_char_count(new_pos.line) as u32;
-                if new_pos.column < line_length {
-                    new_pos.column += 1;
-                } else if tree.line_to_byte(new_pos.line + 1).is_some() {
-                    new_pos.line += 1;
-                    new_pos.column = 0;
-                }
+                let next = display_map.visible_line(line + 1, true);
+                tree.line_to_byte(next).map(|_| (next, 0))
+            };
+            let Some((line, row)) = target else {
+                continue;
+            };
+
+            let new_position =
+                position_on_display_row(&tree, display_map, line, row, goal, tab_stops);
+            sel.cursor = new_position;
+            if !extending_selection {
+                sel.anchor = new_position;
             }
-            new_pos.byte_offset = 0;
-            self.move_cursor_to(new_pos, extending_selection);
         }
+        drop(tree);
 
-        InputAction::Redraw
+        self.merge_selections(doc);
     }
 
     /// Delete at cursor position (forward or backward)
     pub fn delete_at_cursor(&mut self, doc: &Doc, forward: bool) -> InputAction {
         self.save_snapshot_to_history(doc);
 
-        if forward {
-            // Delete key
-            for sel in &self.selections {
-                let tree = doc.read();
-                let range = if !sel.is_cursor() {
-                    sel.byte_range(doc)
-                } else {
-                    let cursor_byte = tree.doc_pos_to_byte(sel.cursor);
-                    if cursor_byte < tree.byte_count() {
+        let tree = doc.read();
+        let replacements: Vec<(Range<usize>, String)> = self
+            .selections
+            .iter()
+            .map(|sel| {
+                if !sel.is_cursor() {
+                    return (sel.byte_range(doc), String::new());
+                }
+
+                let cursor_byte = tree.doc_pos_to_byte(sel.cursor);
+                let line_length = tree.line_char_count(sel.cursor.line) as u32;
+                let range = if forward {
+                    if sel.cursor.column < line_length {
+                        let next = DocPos {
+                            column: sel.cursor.column + 1,
+                            ..sel.cursor
+                        };
+                        cursor_byte..tree.doc_pos_to_byte(next)
+                    } else if cursor_byte < tree.byte_count() {
+                        // Join with the next line
                         cursor_byte..cursor_byte + 1
                     } else {
-                        continue;
+                        cursor_byte..cursor_byte
                     }
+                } else if let Some(pair) = self.pair_around(&tree, sel.cursor, cursor_byte) {
+                    // Backspace between an empty pair removes both halves
+                    pair
+                } else if sel.cursor.column > 0 {
+                    let prev = DocPos {
+                        column: sel.cursor.column - 1,
+                        ..sel.cursor
+                    };
+                    tree.doc_pos_to_byte(prev)..cursor_byte
+                } else if cursor_byte > 0 {
+                    // Join with the previous line
+                    cursor_byte - 1..cursor_byte
+                } else {
+                    cursor_byte..cursor_byte
                 };
-                self.pending_edits.push(Edit::Delete { range });
-            }
-            self.flush_pending_edits(doc);
-        } else {
-            // Backspace - track what we delete to update cursor properly
-            let mut deleted_info = Vec::new();
+                (range, String::new())
+            })
+            .collect();
+        drop(tree);
 
-            for sel in &self.selections {
-                if !sel.is_cursor() {
-                    self.pending_edits.push(Edit::Delete {
-                        range: sel.byte_range(doc),
-                    });
-                    deleted_info.push(None);
-                } else if sel.cursor.line > 0 || sel.cursor.column > 0 {
-                    let tree = doc.read();
+        let new_ranges = self.replace_ranges(doc, &replacements);
+        self.collapse_to_bytes(doc, new_ranges.iter().map(|r| r.start));
+
+        InputAction::Redraw
+    }
+
+    /// Delete from each cursor to the next/previous word boundary
+    /// Selections are deleted as they are
+    fn delete_word(&mut self, doc: &Doc, forward: bool) -> InputAction {
+        self.save_snapshot_to_history(doc);
+
+        let tree = doc.read();
+        let mut replacements: Vec<(Range<usize>, String)> = self
+            .selections
+            .iter()
+            .map(|sel| {
+                let range = if sel.is_cursor() {
                     let cursor_byte = tree.doc_pos_to_byte(sel.cursor);
-                    if cursor_byte > 0 {
-                        let deleted = tree.get_text_slice(cursor_byte - 1..cursor_byte);
-                        // When at column 0, we're deleting a newline - capture the previous line's length NOW
-                        let target_column =
-                            if sel.cursor.column == 0 && deleted == "\n" && sel.cursor.line > 0 {
-                                Some(tree.line_char_count(sel.cursor.line - 1) as u32)
-                            } else {
-                                None
-                            };
-                        deleted_info.push(Some((deleted.clone(), target_column)));
-                        self.pending_edits.push(Edit::Delete {
-                            range: cursor_byte - 1..cursor_byte,
-                        });
+                    let target = tree.doc_pos_to_byte(word_motion(
+                        &tree,
+                        sel.cursor,
+                        forward,
+                        &self.word_config,
+                    ));
+                    cursor_byte.min(target)..cursor_byte.max(target)
+                } else {
+                    sel.byte_range(doc)
+                };
+                (range, String::new())
+            })
+            .collect();
+        drop(tree);
+
+        // Cursors in the same word would delete overlapping ranges - trim them apart
+        let mut order: Vec<usize> = (0..replacements.len()).collect();
+        order.sort_by_key(|&i| replacements[i].0.start);
+        let mut covered = 0;
+        for i in order {
+            let range = &mut replacements[i].0;
+            range.start = range.start.max(covered);
+            range.end = range.end.max(range.start);
+            covered = range.end;
+        }
+
+        let new_ranges = self.replace_ranges(doc, &replacements);
+        self.collapse_to_bytes(doc, new_ranges.iter().map(|r| r.start));
+
+        InputAction::Redraw
+    }
+
+    /// Auto-closing pairs in effect at a byte offset (none when disabled for its language)
+    fn auto_pairs_at(&self, byte: usize) -> &'static [AutoPair] {
+        let Some(highlighter) = &self.syntax_highlighter else {
+            return if self.auto_close_pairs.enabled {
+                DEFAULT_AUTO_PAIRS
+            } else {
+                &[]
+            };
+        };
+        let language = highlighter.language_at(byte);
+        if self.auto_close_pairs.enabled_for(language) {
+            Languages::auto_pairs(language)
+        } else {
+            &[]
+        }
+    }
+
+    /// Byte range of an empty pair (e.g. `(|)`) around a cursor
+    fn pair_around(&self, tree: &Tree, pos: DocPos, byte: usize) -> Option<Range<usize>> {
+        let line_text = tree.line_text_trimmed(pos.line);
+        let mut chars = line_text
+            .chars()
+            .skip((pos.column as usize).checked_sub(1)?);
+        let (prev, next) = (chars.next()?, chars.next()?);
+        self.auto_pairs_at(byte)
+            .iter()
+            .any(|pair| pair.open == prev && pair.close == next)
+            .then(|| byte - prev.len_utf8()..byte + next.len_utf8())
+    }
+
+    /// Type a character at each selection, handling auto-closing pairs
+    /// An opening char wraps a selection in the pair, or inserts both halves when the
+    /// cursor isn't in a string or comment and nothing but whitespace or a closer follows.
+    /// Typing a closer that auto-closing inserted next to the cursor steps over it.
+    fn insert_char(&mut self, doc: &Doc, ch: char) -> InputAction {
+        self.save_snapshot_to_history(doc);
+
+        let tree = doc.read();
+        // Flattened only when a pair may be auto-closed, to check for strings and comments
+        let mut text = None;
+        let mut replacements = Vec::with_capacity(self.selections.len());
+        // New anchor and cursor of each selection, relative to its replacement's start
+        let mut offsets = Vec::with_capacity(self.selections.len());
+        // Selections whose closer was inserted (to be stepped over later) or stepped over
+        let mut closed = Vec::with_capacity(self.selections.len());
+        let mut stepped_over = Vec::new();
+        for sel in &self.selections {
+            let range = sel.byte_range(doc);
+            let pairs = self.auto_pairs_at(range.start);
+            let typed = ch.len_utf8();
+
+            if !sel.is_cursor() {
+                if let Some(pair) = pairs.iter().find(|pair| pair.open == ch) {
+                    let inner = tree.get_text_slice(range.clone());
+                    let end = typed + inner.len();
+                    offsets.push(if sel.min_pos() == sel.cursor {
+                        (end, typed)
                     } else {
-                        deleted_info.push(None);
-                    }
+                        (typed, end)
+                    });
+                    replacements.push((range, format!("{}{}{}", ch, inner, pair.close)));
                 } else {
-                    deleted_info.push(None);
+                    offsets.push((typed, typed));
+                    replacements.push((range, ch.to_string()));
                 }
+                closed.push(false);
+                continue;
             }
 
-            self.flush_pending_edits(doc);
+            let before = tree.get_text_slice(line_start(&tree, sel.cursor.line)..range.start);
+            let next = tree
+                .get_text_slice(range.start..tree.find_line_end_at(range.start))
+                .chars()
+                .next();
+
+            offsets.push((typed, typed));
+            if next == Some(ch) && self.auto_closed.contains(&range.start) {
+                stepped_over.push(range.start);
+                replacements.push((range.start..range.start, String::new()));
+            } else if let Some(pair) = pairs.iter().find(|pair| {
+                pair.open == ch
+                    && should_auto_close(pair, pairs, &before, next)
+                    && !self.syntax_highlighter.as_ref().is_some_and(|highlighter| {
+                        let text = text.get_or_insert_with(|| tree.flatten_to_string());
+                        highlighter.in_string_or_comment(text, range.start)
+                    })
+            }) {
+                replacements.push((range, format!("{}{}", ch, pair.close)));
+                closed.push(true);
+                continue;
+            } else {
+                replacements.push((range, ch.to_string()));
+            }
+            closed.push(false);
+        }
+        drop(tree);
+
+        self.auto_closed
+            .retain(|offset| !stepped_over.contains(offset));
+        let new_ranges = self.replace_ranges(doc, &replacements);
 
-            // Update cursors after backspace
-            for (sel, deleted_info) in self.selections.iter_mut().zip(deleted_info.iter()) {
-                if !sel.is_cursor() {
-                    sel.cursor = sel.min_pos();
-                } else if sel.cursor.column > 0 {
-                    // Simply move back one character, regardless of what it was
-                    sel.cursor.column -= 1;
-                } else if sel.cursor.line > 0 {
-                    sel.cursor.line -= 1;
-                    sel.cursor.column = if let Some((deleted, target_column)) = deleted_info {
-                        if deleted == "\n" {
-                            // Use the pre-captured column position
-                            target_column.unwrap_or(0)
-                        } else {
-                            0
-                        }
-                    } else {
-                        0
-                    };
-                }
-                sel.anchor = sel.cursor;
+        let tree = doc.read();
+        for (((sel, range), (anchor, cursor)), closed) in self
+            .selections
+            .iter_mut()
+            .zip(new_ranges)
+            .zip(offsets)
+            .zip(closed)
+        {
+            sel.anchor = doc_pos_at(&tree, range.start + anchor);
+            sel.cursor = doc_pos_at(&tree, range.start + cursor);
+            sel.goal_column = None;
+            if closed {
+                self.auto_closed.push(range.start + cursor);
             }
         }
+        drop(tree);
+        self.merge_selections(doc);
 
         InputAction::Redraw
     }
@@ -782,106 +1403,2514 @@ impl InputHandler {
     pub fn insert_text(&mut self, doc: &Doc, text: &str) -> InputAction {
         self.save_snapshot_to_history(doc);
 
-        for sel in &self.selections {
-            if !sel.is_cursor() {
-                self.pending_edits.push(Edit::Delete {
-                    range: sel.byte_range(doc),
-                });
-            }
-            let tree = doc.read();
-            let insert_pos = tree.doc_pos_to_byte(sel.min_pos());
-            self.pending_edits.push(Edit::Insert {
-                pos: insert_pos,
-                content: Content::Text(text.to_string()),
-            });
-        }
+        let replacements: Vec<(Range<usize>, String)> = self
+            .selections
+            .iter()
+            .map(|sel| (sel.byte_range(doc), text.to_string()))
+            .collect();
 
-        self.flush_pending_edits(doc);
+        let new_ranges = self.replace_ranges(doc, &replacements);
+        self.collapse_to_bytes(doc, new_ranges.iter().map(|r| r.end));
 
-        // Update cursor positions
-        for sel in &mut self.selections {
-            if !sel.is_cursor() {
-                sel.cursor = sel.min_pos();
-            }
-            match text {
-                "\n" => {
-                    sel.cursor.line += 1;
-                    sel.cursor.column = 0;
-                }
-                // Tab is just one character in the document
-                _ => sel.cursor.column += text.chars().count() as u32,
-            }
-            sel.anchor = sel.cursor;
-        }
         InputAction::Redraw
     }
 
+    /// The word being typed at the primary cursor: (the part before the cursor, the
+    /// whole word). Empty when there's a selection
+    pub fn word_at_cursor(&self, doc: &Doc) -> (String, String) {
+        let Some(sel) = self.selections.first().filter(|sel| sel.is_cursor()) else {
+            return Default::default();
+        };
+        let tree = doc.read();
+        let offset = tree.doc_pos_to_byte(sel.cursor);
+        let before = tree.get_text_slice(line_start(&tree, sel.cursor.line)..offset);
+        let after = tree.get_text_slice(offset..tree.find_line_end_at(offset));
+
+        let prefix = completion::prefix(&before).to_string();
+        let rest: String = after
+            .chars()
+            .take_while(|&ch| completion::is_word_char(ch))
+            .collect();
+        let word = format!("{}{}", prefix, rest);
+        (prefix, word)
+    }
+
+    /// The grapheme under the primary cursor ("\n" at the end of a line, None at the end
+    /// of the document)
+    pub fn grapheme_at_cursor(&self, doc: &Doc) -> Option<String> {
+        let sel = self.selections.first()?;
+        let tree = doc.read();
+        let offset = tree.doc_pos_to_byte(sel.cursor);
+        let rest = tree.get_text_slice(offset..tree.find_line_end_at(offset));
+        match rest.graphemes(true).next() {
+            Some(grapheme) => Some(grapheme.to_string()),
+            None if offset < tree.byte_count() => Some("\n".to_string()),
+            None => None,
+        }
+    }
+
+    /// Replace the part of the word before each cursor with a completion
+    pub fn complete_word(&mut self, doc: &Doc, text: &str) -> InputAction {
+        if self.selections.iter().any(|sel| !sel.is_cursor()) {
+            return InputAction::None;
+        }
+        self.save_snapshot_to_history(doc);
+
+        let tree = doc.read();
+        let replacements: Vec<(Range<usize>, String)> = self
+            .selections
+            .iter()
+            .map(|sel| {
+                let offset = tree.doc_pos_to_byte(sel.cursor);
+                let before = tree.get_text_slice(line_start(&tree, sel.cursor.line)..offset);
+                let prefix = completion::prefix(&before);
+                (offset - prefix.len()..offset, text.to_string())
+            })
+            .collect();
+        drop(tree);
+
+        let new_ranges = self.replace_ranges(doc, &replacements);
+        self.collapse_to_bytes(doc, new_ranges.iter().map(|r| r.end));
+
+        InputAction::Redraw
+    }
+
+    /// IME preedit being composed: its text, the cursor or selected clause within it
+    /// (byte range), and where it is drawn (the start of each selection)
+    pub fn preedit(&self) -> Option<(&str, Option<Range<usize>>, Vec<DocPos>)> {
+        let session = self.ime.as_ref()?;
+        let len = session.text.len();
+        let clause = session
+            .cursor
+            .map(|(start, end)| start.min(len)..end.min(len));
+        let positions = self.selections.iter().map(|sel| sel.min_pos()).collect();
+        Some((session.text.as_str(), clause, positions))
+    }
+
+    /// Show IME preedit text over the selections, replacing the previous preedit
+    /// The document is left alone until the composition commits
+    fn show_preedit(&mut self, text: &str, cursor: Option<(usize, usize)>) -> InputAction {
+        self.ime = Some(ImeSession {
+            text: text.to_string(),
+            cursor,
+        });
+        InputAction::Redraw
+    }
+
+    /// Insert the committed IME text in place of the selections
+    fn commit_composition(&mut self, doc: &Doc, text: &str) -> InputAction {
+        self.ime = None;
+        self.insert_text(doc, text)
+    }
+
+    /// Drop the preedit; nothing was written to the document
+    fn cancel_composition(&mut self) -> InputAction {
+        match self.ime.take() {
+            Some(_) => InputAction::Redraw,
+            None => InputAction::None,
+        }
+    }
+
+    /// Keep the preedit as typed text (e.g. when the user clicks away)
+    fn end_composition(&mut self, doc: &Doc) {
+        if let Some(session) = self.ime.take() {
+            self.insert_text(doc, &session.text);
+        }
+    }
+
+    /// Apply edits made outside the editor (e.g. cleanup on save) as one undo step,
+    /// carrying selections through them
+    pub fn apply_edits(&mut self, doc: &Doc, edits: Vec<(Range<usize>, String)>) -> InputAction {
+        self.replace_ranges_keeping_selections(doc, edits)
+    }
+
+    /// Type over the text under each cursor, one grapheme cluster per typed one
+    /// Nothing is overwritten at the end of a line; selections are replaced as a whole.
+    fn overwrite(&mut self, doc: &Doc, text: &str) -> InputAction {
+        self.save_snapshot_to_history(doc);
+
+        let tree = doc.read();
+        let graphemes = text.graphemes(true).count();
+        let replacements: Vec<(Range<usize>, String)> = self
+            .selections
+            .iter()
+            .map(|sel| {
+                let mut range = sel.byte_range(doc);
+                if sel.is_cursor() {
+                    let line_end = line_content_end(&tree, sel.cursor.line);
+                    let rest = tree.get_text_slice(range.start..line_end);
+                    range.end += grapheme_prefix_len(&rest, graphemes);
+                }
+                (range, text.to_string())
+            })
+            .collect();
+        let overwritten: Vec<String> = replacements
+            .iter()
+            .map(|(range, _)| tree.get_text_slice(range.clone()))
+            .collect();
+        drop(tree);
+
+        let new_ranges = self.replace_ranges(doc, &replacements);
+        for (sel, original) in self.selections.iter().zip(overwritten) {
+            let entry = (text.to_string(), original);
+            match self.overwritten.iter_mut().find(|(id, _)| *id == sel.id) {
+                Some((_, stack)) => stack.push(entry),
+                None => self.overwritten.push((sel.id, vec![entry])),
+            }
+        }
+        self.collapse_to_bytes(doc, new_ranges.iter().map(|r| r.end));
+
+        InputAction::Redraw
+    }
+
+    /// Backspace in replace mode: bring back the text the last keystroke overwrote,
+    /// or just move left past text that was there before replace mode began
+    fn overwrite_backspace(&mut self, doc: &Doc) -> InputAction {
+        self.save_snapshot_to_history(doc);
+
+        let tree = doc.read();
+        let mut replacements = Vec::with_capacity(self.selections.len());
+        // How far each cursor steps back once its replacement is made
+        let mut steps = Vec::with_capacity(self.selections.len());
+        for sel in &self.selections {
+            if !sel.is_cursor() {
+                replacements.push((sel.byte_range(doc), String::new()));
+                steps.push(0);
+                continue;
+            }
+
+            let cursor = tree.doc_pos_to_byte(sel.cursor);
+            let restored = self
+                .overwritten
+                .iter_mut()
+                .find(|(id, _)| *id == sel.id)
+                .and_then(|(_, stack)| {
+                    let (typed, _) = stack.last()?;
+                    let start = cursor.checked_sub(typed.len())?;
+                    if tree.get_text_slice(start..cursor) != *typed {
+                        return None;
+                    }
+                    stack.pop().map(|(_, original)| (start..cursor, original))
+                });
+            match restored {
+                Some(replacement) => {
+                    replacements.push(replacement);
+                    steps.push(0);
+                }
+                None => {
+                    let line_text = tree.line_text_trimmed(sel.cursor.line);
+                    let step = (sel.cursor.column as usize)
+                        .checked_sub(1)
+                        .and_then(|column| line_text.chars().nth(column))
+                        .map_or(0, char::len_utf8);
+                    replacements.push((cursor..cursor, String::new()));
+                    steps.push(step);
+                }
+            }
+        }
+        drop(tree);
+
+        let new_ranges = self.replace_ranges(doc, &replacements);
+        self.collapse_to_bytes(
+            doc,
+            new_ranges
+                .iter()
+                .zip(steps)
+                .map(|(range, step)| range.start - step),
+        );
+
+        InputAction::Redraw
+    }
+
+    /// Replace every character under the cursors (or in the selections) with `ch`,
+    /// keeping line breaks. Cursors stay on the replaced character.
+    fn replace_chars(&mut self, doc: &Doc, ch: char) -> InputAction {
+        self.checkpoint_history(doc);
+
+        let typed = ch.to_string();
+        let tree = doc.read();
+        let replacements: Vec<(Range<usize>, String)> = self
+            .selections
+            .iter()
+            .map(|sel| {
+                let mut range = sel.byte_range(doc);
+                if sel.is_cursor() {
+                    let line_end = line_content_end(&tree, sel.cursor.line);
+                    let rest = tree.get_text_slice(range.start..line_end);
+                    range.end += grapheme_prefix_len(&rest, 1);
+                }
+                let replaced: String = tree
+                    .get_text_slice(range.clone())
+                    .graphemes(true)
+                    .map(|g| {
+                        if matches!(g, "\n" | "\r\n") {
+                            g
+                        } else {
+                            typed.as_str()
+                        }
+                    })
+                    .collect();
+                (range, replaced)
+            })
+            .collect();
+        drop(tree);
+
+        let new_ranges = self.replace_ranges(doc, &replacements);
+        self.collapse_to_bytes(doc, new_ranges.iter().map(|r| r.start));
+
+        InputAction::Redraw
+    }
+
+    /// Replace byte ranges (relative to the current tree) as one batch of edits
+    /// Edits are queued from the end of the document backwards so each stays valid
+    /// while the ones before it are applied. Ranges must not overlap.
+    /// Returns each replacement's byte range in the edited document, in input order
+    fn replace_ranges(
+        &mut self,
+        doc: &Doc,
+        replacements: &[(Range<usize>, String)],
+    ) -> Vec<Range<usize>> {
+        let mut order: Vec<usize> = (0..replacements.len()).collect();
+        order.sort_by_key(|&i| replacements[i].0.start);
+
+        // Where each replacement ends up once everything before it has been applied
+        let mut new_ranges = vec![0..0; replacements.len()];
+        let mut delta: isize = 0;
+        for &i in &order {
+            let (range, text) = &replacements[i];
+            let start = (range.start as isize + delta) as usize;
+            new_ranges[i] = start..start + text.len();
+            delta += text.len() as isize - range.len() as isize;
+        }
+
+        for &i in order.iter().rev() {
+            let (range, text) = &replacements[i];
+            if !range.is_empty() {
+                self.pending_edits.push(Edit::Delete {
+                    range: range.clone(),
+                });
+            }
+            if !text.is_empty() {
+                self.pending_edits.push(Edit::Insert {
+                    pos: range.start,
+                    content: Content::Text(text.clone()),
+                });
+            }
+        }
+
+        self.flush_pending_edits(doc);
+        new_ranges
+    }
+
+    /// Collapse each selection (in order) to a cursor at the given byte offset
+    fn collapse_to_bytes(&mut self, doc: &Doc, offsets: impl Iterator<Item = usize>) {
+        let tree = doc.read();
+        for (sel, offset) in self.selections.iter_mut().zip(offsets) {
+            let pos = doc_pos_at(&tree, offset);
+            sel.cursor = pos;
+            sel.anchor = pos;
+            sel.goal_column = None;
+        }
+        drop(tree);
+
+        self.merge_selections(doc);
+    }
+
+    /// Tab: move to the next snippet tab stop, indent selected lines, expand a snippet
+    /// prefix or insert indentation
+    fn tab(&mut self, doc: &Doc) -> InputAction {
+        if self.snippet.is_some() {
+            return self.next_tab_stop(doc);
+        }
+        if self
+            .selections
+            .iter()
+            .any(|sel| sel.anchor.line != sel.cursor.line)
+        {
+            return self.shift_lines(doc, false);
+        }
+        match self.snippet_before_cursors(doc) {
+            Some((body, ranges)) => self.insert_snippet(doc, &body, ranges),
+            None => self.insert_indent(doc),
+        }
+    }
+
+    /// Insert a tab, or with soft tabs the spaces up to the next indentation stop
+    fn insert_indent(&mut self, doc: &Doc) -> InputAction {
+        if self.indent_unit.contains('\t') {
+            let unit = self.indent_unit.clone();
+            return self.insert_text(doc, &unit);
+        }
+
+        self.save_snapshot_to_history(doc);
+
+        let step = (self.indent_unit.len() as u32).max(1);
+        let tree = doc.read();
+        let replacements: Vec<(Range<usize>, String)> = self
+            .selections
+            .iter()
+            .map(|sel| {
+                let start = sel.min_pos();
+                let line_text = tree.line_text_trimmed(start.line);
+                let column = visual_column(&line_text, start.column, self.tab_stops);
+                let spaces = step - column % step;
+                (sel.byte_range(doc), " ".repeat(spaces as usize))
+            })
+            .collect();
+        drop(tree);
+
+        let new_ranges = self.replace_ranges(doc, &replacements);
+        self.collapse_to_bytes(doc, new_ranges.iter().map(|r| r.end));
+
+        InputAction::Redraw
+    }
+
+    /// Indent or outdent every line touched by a selection by one level
+    /// Indenting skips blank lines; outdenting removes a leading tab or the spaces back
+    /// to the previous indentation stop.
+    fn shift_lines(&mut self, doc: &Doc, outdent: bool) -> InputAction {
+        let step = if self.indent_unit.contains('\t') {
+            self.tab_stops
+        } else {
+            (self.indent_unit.len() as u32).max(1)
+        } as usize;
+
+        let tree = doc.read();
+        let mut edits = Vec::new();
+        for (first, last) in self.selected_line_blocks() {
+            for line in first..=last {
+                let start = line_start(&tree, line);
+                let line_text = tree.line_text_trimmed(line);
+                if outdent {
+                    let spaces = line_text.len() - line_text.trim_start_matches(' ').len();
+                    let remove = if spaces > 0 {
+                        (spaces - 1) % step + 1
+                    } else if line_text.starts_with('\t') {
+                        1
+                    } else {
+                        0
+                    };
+                    if remove > 0 {
+                        edits.push((start..start + remove, String::new()));
+                    }
+                } else if !line_text.trim().is_empty() {
+                    edits.push((start..start, self.indent_unit.clone()));
+                }
+            }
+        }
+        drop(tree);
+
+        self.replace_ranges_keeping_selections(doc, edits)
+    }
+
+    /// Select the next tab stop of the active snippet (the final stop ends it)
+    fn next_tab_stop(&mut self, doc: &Doc) -> InputAction {
+        let Some(session) = &mut self.snippet else {
+            return InputAction::None;
+        };
+        let ranges = session.next().to_vec();
+        if session.is_last() {
+            self.snippet = None;
+        }
+        self.select_byte_ranges(doc, &ranges);
+        InputAction::Redraw
+    }
+
+    /// Select the previous tab stop of the active snippet
+    fn previous_tab_stop(&mut self, doc: &Doc) -> InputAction {
+        let Some(session) = &mut self.snippet else {
+            return InputAction::None;
+        };
+        let ranges = session.prev().to_vec();
+        self.select_byte_ranges(doc, &ranges);
+        InputAction::Redraw
+    }
+
+    /// Replace all selections with the given byte ranges, the first one primary
+    fn select_byte_ranges(&mut self, doc: &Doc, ranges: &[Range<usize>]) {
+        if ranges.is_empty() {
+            return;
+        }
+        let tree = doc.read();
+        let selections = ranges
+            .iter()
+            .map(|range| {
+                let selection = Selection {
+                    cursor: doc_pos_at(&tree, range.end),
+                    anchor: doc_pos_at(&tree, range.start),
+                    id: self.next_id,
+                    goal_column: None,
+                };
+                self.next_id += 1;
+                selection
+            })
+            .collect();
+        drop(tree);
+
+        self.selections = selections;
+        self.merge_selections(doc);
+    }
+
+    /// The user snippet whose prefix every cursor ends on, with the prefix ranges it replaces
+    fn snippet_before_cursors(&self, doc: &Doc) -> Option<(String, Vec<(Range<usize>, String)>)> {
+        if self.selections.iter().any(|sel| !sel.is_cursor()) {
+            return None;
+        }
+        let language = self.syntax_highlighter.as_ref()?.language();
+
+        let tree = doc.read();
+        let before_cursor = |sel: &Selection| {
+            let offset = tree.doc_pos_to_byte(sel.cursor);
+            let start = line_start(&tree, sel.cursor.line);
+            (offset, tree.get_text_slice(start..offset))
+        };
+        let (_, primary_before) = before_cursor(&self.selections[0]);
+        let snippet = self.snippets.find(language, &primary_before)?;
+
+        let mut ranges = Vec::with_capacity(self.selections.len());
+        for sel in &self.selections {
+            let (offset, before) = before_cursor(sel);
+            if !before.ends_with(&snippet.prefix) {
+                return None;
+            }
+            ranges.push((offset - snippet.prefix.len()..offset, String::new()));
+        }
+        Some((snippet.body.clone(), ranges))
+    }
+
+    /// Insert a snippet in place of each range, with the text `$TM_SELECTED_TEXT` stands
+    /// for there, and select its first tab stop. Each copy is fitted to its line's
+    /// indentation, and the copies' tab stops are linked so typing fills them all.
+    fn insert_snippet(
+        &mut self,
+        doc: &Doc,
+        body: &str,
+        ranges: Vec<(Range<usize>, String)>,
+    ) -> InputAction {
+        self.checkpoint_history(doc);
+
+        let clipboard = if body.contains("CLIPBOARD") {
+            Clipboard::new()
+                .ok()
+                .and_then(|mut c| c.get_text().ok())
+                .or_else(|| self.clipboard.clone())
+        } else {
+            None
+        };
+
+        let tree = doc.read();
+        let expanded: Vec<Snippet> = ranges
+            .iter()
+            .map(|(range, selected)| {
+                let line = tree.byte_to_line(range.start);
+                let line_text = tree.line_text_trimmed(line);
+                let resolve = |name: &str| match name {
+                    "TM_SELECTED_TEXT" => Some(selected.clone()),
+                    "TM_CURRENT_LINE" => Some(line_text.clone()),
+                    "TM_LINE_INDEX" => Some(line.to_string()),
+                    "TM_LINE_NUMBER" => Some((line + 1).to_string()),
+                    "CLIPBOARD" => clipboard.clone(),
+                    _ => file_variable(name, self.file_path.as_deref()?),
+                };
+                snippets::parse(body, resolve)
+                    .indented(leading_whitespace(&line_text), &self.indent_unit)
+            })
+            .collect();
+        drop(tree);
+
+        let replacements: Vec<(Range<usize>, String)> = ranges
+            .into_iter()
+            .zip(&expanded)
+            .map(|((range, _), snippet)| (range, snippet.text.clone()))
+            .collect();
+        let inserted = self.replace_ranges(doc, &replacements);
+
+        // Link the copies: stop N of every copy becomes one stop
+        let stop_count = expanded
+            .iter()
+            .map(|s| s.tab_stops.len())
+            .max()
+            .unwrap_or(0);
+        let stops: Vec<Vec<Range<usize>>> = (0..stop_count)
+            .map(|i| {
+                let mut ranges: Vec<Range<usize>> = expanded
+                    .iter()
+                    .zip(&inserted)
+                    .filter_map(|(snippet, at)| Some((snippet.tab_stops.get(i)?, at.start)))
+                    .flat_map(|(stop, start)| {
+                        stop.ranges
+                            .iter()
+                            .map(move |range| start + range.start..start + range.end)
+                    })
+                    .collect();
+                ranges.sort_by_key(|range| range.start);
+                ranges
+            })
+            .collect();
+
+        // Choices come from the first copy that has them (the copies are the same body)
+        let choices: Vec<Vec<String>> = (0..stop_count)
+            .map(|i| {
+                expanded
+                    .iter()
+                    .filter_map(|snippet| snippet.tab_stops.get(i))
+                    .map(|stop| stop.choices.clone())
+                    .find(|choices| !choices.is_empty())
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        if let Some(first) = stops.first() {
+            self.select_byte_ranges(doc, first);
+        }
+        self.snippet = SnippetSession::new(stops).map(|session| session.with_choices(choices));
+        InputAction::Redraw
+    }
+
+    /// Choices of the snippet tab stop being filled in (`${1|a,b|}`), if it has any
+    pub fn snippet_choices(&self) -> Option<&[String]> {
+        let choices = self.snippet.as_ref()?.choices();
+        (!choices.is_empty()).then_some(choices)
+    }
+
+    /// Replace every range of the current snippet tab stop with one of its choices,
+    /// keeping them selected so another choice can replace it again
+    pub fn choose_snippet_choice(&mut self, doc: &Doc, choice: &str) -> InputAction {
+        let Some(session) = &self.snippet else {
+            return InputAction::None;
+        };
+        let replacements: Vec<(Range<usize>, String)> = session
+            .current()
+            .iter()
+            .map(|range| (range.clone(), choice.to_string()))
+            .collect();
+
+        self.save_snapshot_to_history(doc);
+        let ranges = self.replace_ranges(doc, &replacements);
+        self.select_byte_ranges(doc, &ranges);
+        InputAction::Redraw
+    }
+
+    /// Insert a completion item, expanding it as a snippet if it is one
+    /// The text the item replaces is taken relative to the primary cursor and replaced
+    /// the same way at every cursor.
+    pub fn insert_completion(
+        &mut self,
+        doc: &Doc,
+        item: &lsp_types::CompletionItem,
+    ) -> InputAction {
+        use lsp_types::{CompletionTextEdit, InsertTextFormat};
+
+        let (text, range) = match &item.text_edit {
+            Some(CompletionTextEdit::Edit(edit)) => (edit.new_text.as_str(), Some(edit.range)),
+            Some(CompletionTextEdit::InsertAndReplace(edit)) => {
+                (edit.new_text.as_str(), Some(edit.insert))
+            }
+            None => (item.insert_text.as_deref().unwrap_or(&item.label), None),
+        };
+
+        let tree = doc.read();
+        let primary = tree.doc_pos_to_byte(self.selections[0].cursor);
+        let byte_at = |position: lsp_types::Position| {
+            let (line, column) = tree.point_utf16_to_doc_pos(tiny_tree::PointUtf16::new(
+                position.line,
+                position.character,
+            ));
+            tree.line_to_byte(line).unwrap_or(0) + column as usize
+        };
+        // Bytes replaced before and after each cursor
+        let (before, after) = match range {
+            Some(range) => (
+                primary.saturating_sub(byte_at(range.start)),
+                byte_at(range.end).saturating_sub(primary),
+            ),
+            None => {
+                let start = line_start(&tree, self.selections[0].cursor.line);
+                let prefix = tree.get_text_slice(start..primary);
+                let word = prefix
+                    .chars()
+                    .rev()
+                    .take_while(|&c| c == '_' || c.is_alphanumeric())
+                    .map(char::len_utf8)
+                    .sum::<usize>();
+                (word, 0)
+            }
+        };
+        let len = tree.byte_count();
+        let ranges: Vec<(Range<usize>, String)> = self
+            .selections
+            .iter()
+            .map(|sel| {
+                let cursor = tree.doc_pos_to_byte(sel.cursor);
+                let anchor = tree.doc_pos_to_byte(sel.anchor);
+                let selected = tree.get_text_slice(cursor.min(anchor)..cursor.max(anchor));
+                let range = cursor.min(anchor).saturating_sub(before)
+                    ..(cursor.max(anchor) + after).min(len);
+                (range, selected)
+            })
+            .collect();
+        drop(tree);
+
+        if item.insert_text_format == Some(InsertTextFormat::SNIPPET) {
+            return self.insert_snippet(doc, text, ranges);
+        }
+
+        self.save_snapshot_to_history(doc);
+        let replacements: Vec<(Range<usize>, String)> = ranges
+            .into_iter()
+            .map(|(range, _)| (range, text.to_string()))
+            .collect();
+        let new_ranges = self.replace_ranges(doc, &replacements);
+        self.collapse_to_bytes(doc, new_ranges.iter().map(|r| r.end));
+        InputAction::Redraw
+    }
+
+    /// Wrap each selection in a snippet body, with `$TM_SELECTED_TEXT` where the selection
+    /// goes. Block templates wrap the whole lines the selection touches.
+    fn surround(&mut self, doc: &Doc, body: &str, block: bool) -> InputAction {
+        let tree = doc.read();
+        let mut ranges: Vec<(Range<usize>, String)> = Vec::with_capacity(self.selections.len());
+        for i in self.selections_in_doc_order() {
+            let mut range = self.selections[i].byte_range(doc);
+            if block {
+                let first = tree.byte_to_line(range.start);
+                let mut last = tree.byte_to_line(range.end);
+                // A selection ending at the start of a line doesn't include that line
+                if last > first && range.end == line_start(&tree, last) {
+                    last -= 1;
+                }
+                let indent = leading_whitespace(&tree.line_text_trimmed(first)).len();
+                range = line_start(&tree, first) + indent..line_content_end(&tree, last);
+            }
+            // Several selections on the same lines wrap them once
+            if block
+                && ranges
+                    .last()
+                    .is_some_and(|(prev, _)| range.start <= prev.end)
+            {
+                continue;
+            }
+            let line_text = tree.line_text_trimmed(tree.byte_to_line(range.start));
+            let selected = surround::selected_text(
+                &tree.get_text_slice(range.clone()),
+                leading_whitespace(&line_text),
+                block,
+            );
+            ranges.push((range, selected));
+        }
+        drop(tree);
+
+        self.insert_snippet(doc, body, ranges)
+    }
+
+    fn transform_by_name(&mut self, doc: &Doc, name: &str, whole_lines: bool) -> InputAction {
+        match transforms::find(name) {
+            Some(transform) => self.transform(doc, transform, whole_lines),
+            None => InputAction::None,
+        }
+    }
+
+    /// Apply a text transform to each selection, or to the word under each cursor, as
+    /// one undo step. Line transforms (and `whole_lines`) widen selections and cursors to
+    /// whole lines. Selections keep covering the transformed text; cursors keep their
+    /// column.
+    fn transform(&mut self, doc: &Doc, transform: &Transform, whole_lines: bool) -> InputAction {
+        let tree = doc.read();
+        let mut ranges: Vec<Range<usize>> = Vec::with_capacity(self.selections.len());
+        for i in self.selections_in_doc_order() {
+            let sel = &self.selections[i];
+            let mut range = sel.byte_range(doc);
+            if transform.lines || whole_lines {
+                range = line_span(&tree, range);
+            } else if sel.is_cursor() {
+                match word_range_at(&tree, sel.cursor) {
+                    Some(word) => range = word,
+                    None => continue,
+                }
+            }
+            match ranges.last_mut() {
+                Some(prev) if range.start <= prev.end => prev.end = prev.end.max(range.end),
+                _ => ranges.push(range),
+            }
+        }
+        let replacements: Vec<(Range<usize>, String)> = ranges
+            .into_iter()
+            .filter_map(|range| {
+                let text = tree.get_text_slice(range.clone());
+                let transformed = (transform.apply)(&text);
+                (transformed != text).then_some((range, transformed))
+            })
+            .collect();
+        if replacements.is_empty() {
+            return InputAction::None;
+        }
+
+        // Positions inside a transformed range keep their char index within it:
+        // (Some(replacement), char index) or (None, mapped byte offset)
+        let old_texts: Vec<String> = replacements
+            .iter()
+            .map(|(range, _)| tree.get_text_slice(range.clone()))
+            .collect();
+        let locate = |offset: usize| {
+            let found = replacements
+                .iter()
+                .position(|(range, _)| range.start <= offset && offset <= range.end);
+            match found {
+                Some(i) => {
+                    let start = replacements[i].0.start;
+                    (Some(i), old_texts[i][..offset - start].chars().count())
+                }
+                None => (None, map_offset(&replacements, offset)),
+            }
+        };
+        let positions: Vec<_> = self
+            .selections
+            .iter()
+            .map(|sel| {
+                (
+                    locate(tree.doc_pos_to_byte(sel.anchor)),
+                    locate(tree.doc_pos_to_byte(sel.cursor)),
+                )
+            })
+            .collect();
+        drop(tree);
+
+        self.checkpoint_history(doc);
+        let new_ranges = self.replace_ranges(doc, &replacements);
+
+        let resolve = |position: (Option<usize>, usize)| match position {
+            (None, offset) => offset,
+            (Some(i), chars) => {
+                let text = &replacements[i].1;
+                let byte = text
+                    .char_indices()
+                    .nth(chars)
+                    .map_or(text.len(), |(b, _)| b);
+                new_ranges[i].start + byte
+            }
+        };
+        let tree = doc.read();
+        for (sel, (anchor, cursor)) in self.selections.iter_mut().zip(positions) {
+            sel.anchor = doc_pos_at(&tree, resolve(anchor));
+            sel.cursor = doc_pos_at(&tree, resolve(cursor));
+            sel.goal_column = None;
+        }
+        drop(tree);
+        self.merge_selections(doc);
+
+        InputAction::Redraw
+    }
+
+    /// Remove the innermost block or call around each selection, keeping its contents,
+    /// and reindent what was inside
+    fn unwrap_enclosing(&mut self, doc: &Doc) -> InputAction {
+        let Some(highlighter) = self.syntax_highlighter.clone() else {
+            return InputAction::None;
+        };
+
+        let tree = doc.read();
+        let text = tree.flatten_to_string();
+        let ranges: Vec<Range<usize>> = self
+            .selections
+            .iter()
+            .map(|sel| sel.byte_range(doc))
+            .collect();
+        let mut unwraps: Vec<(Range<usize>, Range<usize>)> = highlighter
+            .unwrap_ranges(&text, &ranges)
+            .into_iter()
+            .flatten()
+            .map(|(outer, inner)| {
+                // Keep the contents without the whitespace around them
+                let contents = &text[inner.clone()];
+                let start = inner.start + contents.len() - contents.trim_start().len();
+                let end = inner.start + contents.trim_end().len();
+                (outer, start..end.max(start))
+            })
+            .collect();
+        unwraps.sort_by_key(|(outer, _)| outer.start);
+        // Selections in the same construct unwrap it once, nested ones unwrap the outer one
+        unwraps.dedup_by(|next, prev| next.0.start < prev.0.end);
+        if unwraps.is_empty() {
+            return InputAction::None;
+        }
+
+        let replacements: Vec<(Range<usize>, String)> = unwraps
+            .iter()
+            .map(|(outer, inner)| (outer.clone(), text[inner.clone()].to_string()))
+            .collect();
+        // Selections inside the kept text stay on the same characters
+        let map = |offset: usize| match unwraps
+            .iter()
+            .find(|(_, inner)| inner.start <= offset && offset <= inner.end)
+        {
+            Some((outer, inner)) => map_offset(&replacements, outer.start) + offset - inner.start,
+            None => map_offset(&replacements, offset),
+        };
+        let offsets: Vec<(usize, usize)> = self
+            .selections
+            .iter()
+            .map(|sel| {
+                (
+                    map(tree.doc_pos_to_byte(sel.anchor)),
+                    map(tree.doc_pos_to_byte(sel.cursor)),
+                )
+            })
+            .collect();
+        drop(tree);
+
+        self.checkpoint_history(doc);
+        let new_ranges = self.replace_ranges(doc, &replacements);
+
+        let tree = doc.read();
+        for (sel, (anchor, cursor)) in self.selections.iter_mut().zip(offsets) {
+            sel.anchor = doc_pos_at(&tree, anchor);
+            sel.cursor = doc_pos_at(&tree, cursor);
+            sel.goal_column = None;
+        }
+        drop(tree);
+        self.merge_selections(doc);
+
+        let pasted: Vec<(Range<usize>, &str)> = new_ranges
+            .into_iter()
+            .zip(replacements.iter().map(|(_, text)| text.as_str()))
+            .collect();
+        self.reindent_pasted(doc, &pasted);
+        InputAction::Redraw
+    }
+
+    /// Merge overlapping selections and duplicate cursors
+    /// The primary selection stays first, the rest are kept in document order
+    fn merge_selections(&mut self, doc: &Doc) {
+        if self.selections.len() < 2 {
+            return;
+        }
+
+        let tree = doc.read();
+        let primary_id = self.selections[0].id;
+
+        let mut ranged: Vec<(Range<usize>, Selection)> = self
+            .selections
+            .drain(..)
+            .map(|sel| {
+                let cursor = tree.doc_pos_to_byte(sel.cursor);
+                let anchor = tree.doc_pos_to_byte(sel.anchor);
+                (cursor.min(anchor)..cursor.max(anchor), sel)
+            })
+            .collect();
+        ranged.sort_by_key(|(range, _)| range.start);
+
+        let mut merged: Vec<(Range<usize>, Selection)> = Vec::with_capacity(ranged.len());
+        for (range, sel) in ranged {
+            if let Some((last_range, last)) = merged.last_mut() {
+                if range.start < last_range.end || range.start == last_range.start {
+                    if range.end > last_range.end {
+                        // Grow the earlier selection, keeping its direction
+                        let end_pos = if tree.doc_pos_to_byte(sel.cursor) == range.end {
+                            sel.cursor
+                        } else {
+                            sel.anchor
+                        };
+                        if tree.doc_pos_to_byte(last.cursor) >= tree.doc_pos_to_byte(last.anchor) {
+                            last.cursor = end_pos;
+                        } else {
+                            last.anchor = end_pos;
+                        }
+                        last_range.end = range.end;
+                    }
+                    if sel.id == primary_id {
+                        last.id = primary_id;
+                    }
+                    continue;
+                }
+            }
+            merged.push((range, sel));
+        }
+
+        self.selections = merged.into_iter().map(|(_, sel)| sel).collect();
+        if let Some(index) = self.selections.iter().position(|s| s.id == primary_id) {
+            let primary = self.selections.remove(index);
+            self.selections.insert(0, primary);
+        }
+    }
+
+    /// Selection indices in document order
+    fn selections_in_doc_order(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.selections.len()).collect();
+        order.sort_by_key(|&i| {
+            let pos = self.selections[i].min_pos();
+            (pos.line, pos.column)
+        });
+        order
+    }
+
     /// Move cursor to start or end of line
     fn move_to_line_edge(
         &mut self,
         doc: &Doc,
-        to_end: bool,
-        extending_selection: bool,
+        to_end: bool,
+        extending_selection: bool,
+    ) -> InputAction {
+        self.move_cursors(doc, extending_selection, false, |tree, sel| DocPos {
+            line: sel.cursor.line,
+            column: if to_end {
+                tree.line_char_count(sel.cursor.line) as u32
+            } else {
+                0
+            },
+            byte_offset: 0,
+        });
+
+        InputAction::Redraw
+    }
+
+    /// Move each cursor by one word (crossing line breaks at line edges)
+    fn move_word(&mut self, doc: &Doc, forward: bool, extending_selection: bool) -> InputAction {
+        let config = self.word_config.clone();
+        self.move_cursors(doc, extending_selection, false, |tree, sel| {
+            word_motion(tree, sel.cursor, forward, &config)
+        });
+
+        InputAction::Redraw
+    }
+
+    /// Smart line start/end: toggle between the first non-blank (or end of content) and the line edge
+    fn move_to_line_edge_smart(
+        &mut self,
+        doc: &Doc,
+        to_end: bool,
+        extending_selection: bool,
+    ) -> InputAction {
+        self.move_cursors(doc, extending_selection, false, |tree, sel| {
+            let chars: Vec<char> = tree.line_text_trimmed(sel.cursor.line).chars().collect();
+            let (content_edge, line_edge) = if to_end {
+                (words::last_non_blank_end(&chars), chars.len())
+            } else {
+                (words::first_non_blank(&chars), 0)
+            };
+            let column = if sel.cursor.column as usize == content_edge {
+                line_edge
+            } else {
+                content_edge
+            };
+            DocPos {
+                line: sel.cursor.line,
+                column: column as u32,
+                byte_offset: 0,
+            }
+        });
+
+        InputAction::Redraw
+    }
+
+    /// Move to the start or end of the document (all cursors merge into one)
+    fn move_to_document_edge(
+        &mut self,
+        doc: &Doc,
+        to_end: bool,
+        extending_selection: bool,
+    ) -> InputAction {
+        self.record_jump(self.primary_cursor_doc_pos(doc));
+
+        self.move_cursors(doc, extending_selection, false, |tree, _| {
+            if to_end {
+                doc_pos_at(tree, tree.byte_count())
+            } else {
+                DocPos::default()
+            }
+        });
+
+        InputAction::Redraw
+    }
+
+    /// Page navigation (up/down)
+    fn page_jump(&mut self, doc: &Doc, up: bool, extending_selection: bool) -> InputAction {
+        self.record_jump(self.primary_cursor_doc_pos(doc));
+
+        const PAGE_SIZE: u32 = 20;
+
+        // Pages are counted in display lines, folded lines don't count
+        let display_map = self.display_map.clone();
+        self.move_cursors(doc, extending_selection, true, |tree, sel| {
+            let total_lines = tree.line_count();
+            let display_line = display_map.doc_to_display(sel.cursor.line);
+            let target = if up {
+                display_line.saturating_sub(PAGE_SIZE)
+            } else {
+                display_line + PAGE_SIZE
+            };
+            let new_line = display_map.visible_line(
+                display_map
+                    .display_to_doc(target)
+                    .min(total_lines.saturating_sub(1)),
+                false,
+            );
+
+            let line_length = tree.line_char_count(new_line) as u32;
+            DocPos {
+                line: new_line,
+                column: sel
+                    .goal_column
+                    .unwrap_or(sel.cursor.column)
+                    .min(line_length),
+                byte_offset: 0,
+            }
+        });
+
+        InputAction::Redraw
+    }
+
+    /// Line spans of all selections in document order, merging spans that overlap or touch
+    fn selected_line_blocks(&self) -> Vec<(u32, u32)> {
+        let mut spans: Vec<(u32, u32)> = self.selections.iter().map(Selection::line_span).collect();
+        spans.sort_unstable();
+
+        let mut blocks: Vec<(u32, u32)> = Vec::with_capacity(spans.len());
+        for (first, last) in spans {
+            match blocks.last_mut() {
+                Some(block) if first <= block.1 + 1 => block.1 = block.1.max(last),
+                _ => blocks.push((first, last)),
+            }
+        }
+        blocks
+    }
+
+    /// Index of the block containing each selection (in selection order)
+    fn block_of_selections(&self, blocks: &[(u32, u32)]) -> Vec<usize> {
+        self.selections
+            .iter()
+            .map(|sel| {
+                let line = sel.line_span().0;
+                blocks
+                    .iter()
+                    .position(|&(first, last)| first <= line && line <= last)
+                    .unwrap_or(0)
+            })
+            .collect()
+    }
+
+    /// Start a new undo step for a command that should undo on its own
+    fn checkpoint_history(&mut self, doc: &Doc) {
+        self.last_checkpoint_time = None;
+        self.save_snapshot_to_history(doc);
+        // The next edit (e.g. typing) starts its own step too
+        self.last_checkpoint_time = None;
+    }
+
+    /// Swap the selected lines with the line above/below
+    /// Does nothing if any block is already at the document edge
+    fn move_lines(&mut self, doc: &Doc, up: bool) -> InputAction {
+        let blocks = self.selected_line_blocks();
+        let tree = doc.read();
+        let last_line = tree.line_count().saturating_sub(1);
+        let at_edge = blocks.iter().any(
+            |&(first, last)| {
+                if up {
+                    first == 0
+                } else {
+                    last >= last_line
+                }
+            },
+        );
+        if at_edge {
+            return InputAction::None;
+        }
+
+        // Each block and the line it swaps with are rewritten as one range of whole lines
+        let replacements: Vec<(Range<usize>, String)> = blocks
+            .iter()
+            .map(|&(first, last)| {
+                let (from, to) = if up {
+                    (first - 1, last)
+                } else {
+                    (first, last + 1)
+                };
+                let mut lines: Vec<String> = (from..=to)
+                    .map(|line| tree.line_text_trimmed(line))
+                    .collect();
+                if up {
+                    lines.rotate_left(1);
+                } else {
+                    lines.rotate_right(1);
+                }
+                (
+                    line_start(&tree, from)..line_content_end(&tree, to),
+                    lines.join("\n"),
+                )
+            })
+            .collect();
+        drop(tree);
+
+        self.checkpoint_history(doc);
+        self.replace_ranges(doc, &replacements);
+
+        for sel in &mut self.selections {
+            for pos in [&mut sel.cursor, &mut sel.anchor] {
+                pos.line = if up { pos.line - 1 } else { pos.line + 1 };
+                pos.byte_offset = 0;
+            }
+        }
+
+        InputAction::Redraw
+    }
+
+    /// Duplicate the selected lines, keeping the selections on the copy below
+    fn duplicate_lines(&mut self, doc: &Doc) -> InputAction {
+        let blocks = self.selected_line_blocks();
+        let tree = doc.read();
+        let replacements: Vec<(Range<usize>, String)> = blocks
+            .iter()
+            .map(|&(first, last)| {
+                let start = line_start(&tree, first);
+                let text = tree.get_text_slice(start..line_content_end(&tree, last));
+                (start..start, text + "\n")
+            })
+            .collect();
+        drop(tree);
+
+        let owners = self.block_of_selections(&blocks);
+        self.checkpoint_history(doc);
+        self.replace_ranges(doc, &replacements);
+
+        for (sel, &block) in self.selections.iter_mut().zip(&owners) {
+            let (first, last) = blocks[block];
+            for pos in [&mut sel.cursor, &mut sel.anchor] {
+                pos.line += last - first + 1;
+                pos.byte_offset = 0;
+            }
+        }
+
+        InputAction::Redraw
+    }
+
+    /// Delete the selected lines, leaving a cursor where they were
+    fn delete_lines(&mut self, doc: &Doc) -> InputAction {
+        let blocks = self.selected_line_blocks();
+        let tree = doc.read();
+        let last_line = tree.line_count().saturating_sub(1);
+        let replacements: Vec<(Range<usize>, String)> = blocks
+            .iter()
+            .map(|&(first, last)| {
+                let range = if last < last_line {
+                    line_start(&tree, first)..line_start(&tree, last + 1)
+                } else if first > 0 {
+                    // No line break after the last line - take the one before the block
+                    line_content_end(&tree, first - 1)..tree.byte_count()
+                } else {
+                    0..tree.byte_count()
+                };
+                (range, String::new())
+            })
+            .collect();
+        drop(tree);
+
+        let owners = self.block_of_selections(&blocks);
+        self.checkpoint_history(doc);
+        let new_ranges = self.replace_ranges(doc, &replacements);
+        let offsets: Vec<usize> = owners.iter().map(|&i| new_ranges[i].start).collect();
+        self.collapse_to_bytes(doc, offsets.into_iter());
+
+        InputAction::Redraw
+    }
+
+    /// Join each selected line with the next (or all lines of a multi-line selection)
+    /// Indentation of the joined line collapses to a single space
+    fn join_lines(&mut self, doc: &Doc) -> InputAction {
+        let blocks = self.selected_line_blocks();
+        let tree = doc.read();
+        let last_line = tree.line_count().saturating_sub(1);
+
+        let mut replacements: Vec<(Range<usize>, String)> = Vec::new();
+        // Index of the last join in each block (None if there was nothing to join)
+        let mut last_join: Vec<Option<usize>> = Vec::with_capacity(blocks.len());
+        for &(first, last) in &blocks {
+            // A single line joins with the next one
+            let end = if last > first {
+                last
+            } else {
+                (last + 1).min(last_line)
+            };
+            for line in first..end {
+                let text = tree.line_text_trimmed(line);
+                let next = tree.line_text_trimmed(line + 1);
+                let kept = text.trim_end().len().max(leading_whitespace(&text).len());
+                let separator = if text.trim().is_empty() || next.trim().is_empty() {
+                    ""
+                } else {
+                    " "
+                };
+                replacements.push((
+                    line_start(&tree, line) + kept
+                        ..line_start(&tree, line + 1) + leading_whitespace(&next).len(),
+                    separator.to_string(),
+                ));
+            }
+            last_join.push((end > first).then(|| replacements.len() - 1));
+        }
+        drop(tree);
+
+        if replacements.is_empty() {
+            return InputAction::None;
+        }
+
+        let owners = self.block_of_selections(&blocks);
+        self.checkpoint_history(doc);
+        let new_ranges = self.replace_ranges(doc, &replacements);
+
+        // Cursors go to the last join point of their block
+        let tree = doc.read();
+        for (sel, &block) in self.selections.iter_mut().zip(&owners) {
+            if let Some(join) = last_join[block] {
+                let pos = doc_pos_at(&tree, new_ranges[join].start);
+                sel.cursor = pos;
+                sel.anchor = pos;
+                sel.goal_column = None;
+            }
+        }
+        drop(tree);
+        self.merge_selections(doc);
+
+        InputAction::Redraw
+    }
+
+    /// Open a new line above/below each selection, indented like the line it's opened from
+    fn open_line(&mut self, doc: &Doc, above: bool) -> InputAction {
+        let tree = doc.read();
+        let targets: Vec<u32> = self
+            .selections
+            .iter()
+            .map(|sel| {
+                let (first, last) = sel.line_span();
+                if above {
+                    first
+                } else {
+                    last
+                }
+            })
+            .collect();
+        let mut lines = targets.clone();
+        lines.sort_unstable();
+        lines.dedup();
+
+        let replacements: Vec<(Range<usize>, String)> = lines
+            .iter()
+            .map(|&line| {
+                let indent = leading_whitespace(&tree.line_text_trimmed(line)).to_string();
+                if above {
+                    let start = line_start(&tree, line);
+                    (start..start, indent + "\n")
+                } else {
+                    let end = line_content_end(&tree, line);
+                    (end..end, "\n".to_string() + &indent)
+                }
+            })
+            .collect();
+        drop(tree);
+
+        self.checkpoint_history(doc);
+        let new_ranges = self.replace_ranges(doc, &replacements);
+
+        // Cursor after the indentation of the new line
+        let offsets: Vec<usize> = targets
+            .iter()
+            .map(|line| {
+                let index = lines.binary_search(line).unwrap_or(0);
+                let range = &new_ranges[index];
+                if above {
+                    range.end - 1
+                } else {
+                    range.end
+                }
+            })
+            .collect();
+        self.collapse_to_bytes(doc, offsets.into_iter());
+
+        InputAction::Redraw
+    }
+
+    /// Break the line at each cursor, keeping the cursor before the break
+    fn split_line(&mut self, doc: &Doc) -> InputAction {
+        let tree = doc.read();
+        let replacements: Vec<(Range<usize>, String)> = self
+            .selections
+            .iter()
+            .map(|sel| {
+                let line_text = tree.line_text_trimmed(sel.min_pos().line);
+                let indent = leading_whitespace(&line_text);
+                (sel.byte_range(doc), "\n".to_string() + indent)
+            })
+            .collect();
+        drop(tree);
+
+        self.checkpoint_history(doc);
+        let new_ranges = self.replace_ranges(doc, &replacements);
+        self.collapse_to_bytes(doc, new_ranges.iter().map(|r| r.start));
+
+        InputAction::Redraw
+    }
+
+    /// Comment syntax at a byte offset, following injected languages (e.g. markdown code fences)
+    fn comment_tokens_at(&self, byte: usize) -> CommentTokens {
+        self.syntax_highlighter
+            .as_ref()
+            .map(|highlighter| highlighter.comment_tokens_at(byte))
+            .unwrap_or_default()
+    }
+
+    /// Apply replacements as one undo step, carrying selections through them
+    fn replace_ranges_keeping_selections(
+        &mut self,
+        doc: &Doc,
+        replacements: Vec<(Range<usize>, String)>,
+    ) -> InputAction {
+        if replacements.is_empty() {
+            return InputAction::None;
+        }
+
+        self.checkpoint_history(doc);
+        self.replace_ranges_mapping_selections(doc, replacements);
+        InputAction::Redraw
+    }
+
+    /// Apply replacements (relative to the current tree), carrying selections through them
+    fn replace_ranges_mapping_selections(
+        &mut self,
+        doc: &Doc,
+        mut replacements: Vec<(Range<usize>, String)>,
+    ) {
+        if replacements.is_empty() {
+            return;
+        }
+        replacements.sort_by_key(|(range, _)| range.start);
+
+        let tree = doc.read();
+        let offsets: Vec<(usize, usize)> = self
+            .selections
+            .iter()
+            .map(|sel| {
+                (
+                    map_offset(&replacements, tree.doc_pos_to_byte(sel.anchor)),
+                    map_offset(&replacements, tree.doc_pos_to_byte(sel.cursor)),
+                )
+            })
+            .collect();
+        drop(tree);
+
+        self.replace_ranges(doc, &replacements);
+
+        let tree = doc.read();
+        for (sel, (anchor, cursor)) in self.selections.iter_mut().zip(offsets) {
+            sel.anchor = doc_pos_at(&tree, anchor);
+            sel.cursor = doc_pos_at(&tree, cursor);
+            sel.goal_column = None;
+        }
+        drop(tree);
+        self.merge_selections(doc);
+    }
+
+    /// Syntax-based indentation for the current document text, if the language supports it
+    fn indenter(&self, text: &str) -> Option<Indenter> {
+        self.syntax_highlighter.as_ref()?.indenter(text)
+    }
+
+    /// Break the line at each cursor and indent the new line from the syntax tree
+    /// (or like the current line). A closing delimiter right after the cursor moves to
+    /// a line of its own, one level further out.
+    fn insert_newline(&mut self, doc: &Doc) -> InputAction {
+        self.save_snapshot_to_history(doc);
+
+        let tree = doc.read();
+        let text = tree.flatten_to_string();
+        let indenter = self.indenter(&text);
+
+        let mut cursor_offsets = Vec::with_capacity(self.selections.len());
+        let replacements: Vec<(Range<usize>, String)> = self
+            .selections
+            .iter()
+            .map(|sel| {
+                let range = sel.byte_range(doc);
+                let line_end = line_content_end(&tree, tree.byte_to_line(range.end));
+                let rest = &text[range.end..line_end.max(range.end)];
+                let content = range.end + leading_whitespace(rest).len();
+
+                let mut inserted = "\n".to_string();
+                match indenter
+                    .as_ref()
+                    .map(|indenter| indenter.indent_level(&text, range.start, content))
+                {
+                    Some(indent) if indent.outdent && indent.level > 0 => {
+                        inserted += &self.indent_unit.repeat(indent.level as usize);
+                        cursor_offsets.push(inserted.len());
+                        inserted += "\n";
+                        inserted += &self.indent_unit.repeat(indent.level as usize - 1);
+                    }
+                    Some(indent) => {
+                        inserted += &self.indent_unit.repeat(indent.content_level() as usize);
+                        cursor_offsets.push(inserted.len());
+                    }
+                    None => {
+                        let line_text = tree.line_text_trimmed(tree.byte_to_line(range.start));
+                        inserted += leading_whitespace(&line_text);
+                        cursor_offsets.push(inserted.len());
+                    }
+                }
+                (range.start..content, inserted)
+            })
+            .collect();
+        drop(tree);
+
+        let new_ranges = self.replace_ranges(doc, &replacements);
+        self.collapse_to_bytes(
+            doc,
+            new_ranges
+                .iter()
+                .zip(cursor_offsets)
+                .map(|(range, offset)| range.start + offset),
+        );
+
+        InputAction::Redraw
+    }
+
+    /// New indentation for the non-blank lines in `first..=last`
+    /// Without syntax-based indentation, lines starting with the `fallback` indent prefix
+    /// get the replacement prefix instead (keeping their relative indentation)
+    fn reindent_edits(
+        &self,
+        tree: &Tree,
+        text: &str,
+        indenter: Option<&Indenter>,
+        lines: std::ops::RangeInclusive<u32>,
+        fallback: Option<(&str, &str)>,
+    ) -> Vec<(Range<usize>, String)> {
+        let mut edits = Vec::new();
+        for line in lines {
+            let line_text = tree.line_text_trimmed(line);
+            if line_text.trim().is_empty() {
+                continue;
+            }
+
+            let start = line_start(tree, line);
+            let current = leading_whitespace(&line_text);
+            let content = start + current.len();
+            let indent = match (indenter, fallback) {
+                (Some(indenter), _) => self
+                    .indent_unit
+                    .repeat(indenter.indent_level(text, start, content).content_level() as usize),
+                (None, Some((from, to))) => match current.strip_prefix(from) {
+                    Some(rest) => format!("{}{}", to, rest),
+                    None => continue,
+                },
+                (None, None) => continue,
+            };
+
+            if indent != current {
+                edits.push((start..content, indent));
+            }
+        }
+        edits
+    }
+
+    /// Reindent the selected lines (falls back to aligning them with the line above)
+    fn reindent_selection(&mut self, doc: &Doc) -> InputAction {
+        let tree = doc.read();
+        let text = tree.flatten_to_string();
+        let indenter = self.indenter(&text);
+
+        let mut edits = Vec::new();
+        for (first, last) in self.selected_line_blocks() {
+            let first_indent = leading_whitespace(&tree.line_text_trimmed(first)).to_string();
+            let above_indent = (0..first)
+                .rev()
+                .map(|line| tree.line_text_trimmed(line))
+                .find(|line_text| !line_text.trim().is_empty())
+                .map(|line_text| leading_whitespace(&line_text).to_string())
+                .unwrap_or_default();
+            edits.extend(self.reindent_edits(
+                &tree,
+                &text,
+                indenter.as_ref(),
+                first..=last,
+                Some((first_indent.as_str(), above_indent.as_str())),
+            ));
+        }
+        drop(tree);
+
+        self.replace_ranges_keeping_selections(doc, edits)
+    }
+
+    /// Reindent multi-line pasted text to fit where it landed
+    /// `pasted` holds each pasted range (in the current tree) and the text that was pasted
+    fn reindent_pasted(&mut self, doc: &Doc, pasted: &[(Range<usize>, &str)]) {
+        if !pasted.iter().any(|(_, text)| text.contains('\n')) {
+            return;
+        }
+
+        let tree = doc.read();
+        let text = tree.flatten_to_string();
+        let indenter = self.indenter(&text);
+
+        let mut edits = Vec::new();
+        for (range, pasted_text) in pasted {
+            if !pasted_text.contains('\n') {
+                continue;
+            }
+            let first = tree.byte_to_line(range.start);
+            let last = tree.byte_to_line(range.end);
+            let first_line = tree.line_text_trimmed(first);
+
+            if indenter.is_some() {
+                // The first line only moves if the paste started in its indentation
+                let before = &text[line_start(&tree, first)..range.start];
+                let from = if before.trim().is_empty() {
+                    first
+                } else {
+                    first + 1
+                };
+                edits.extend(self.reindent_edits(
+                    &tree,
+                    &text,
+                    indenter.as_ref(),
+                    from..=last,
+                    None,
+                ));
+                continue;
+            }
+
+            // Shift the following lines so the least indented one lines up with the
+            // line the paste landed on
+            let pasted_indent = pasted_text
+                .lines()
+                .skip(1)
+                .filter(|line| !line.trim().is_empty())
+                .map(leading_whitespace)
+                .min_by_key(|indent| indent.len())
+                .unwrap_or("");
+            let landed_indent = leading_whitespace(&first_line);
+            edits.extend(self.reindent_edits(
+                &tree,
+                &text,
+                None,
+                first + 1..=last,
+                Some((pasted_indent, landed_indent)),
+            ));
+        }
+        drop(tree);
+
+        self.replace_ranges_mapping_selections(doc, edits);
+    }
+
+    /// Grow each selection to the next enclosing syntax node (or language server selection
+    /// range), falling back to word, line, paragraph and document in plain text
+    fn expand_selection(&mut self, doc: &Doc) -> InputAction {
+        let tree = doc.read();
+        let text = tree.flatten_to_string();
+        let ranges: Vec<Range<usize>> = self
+            .selections
+            .iter()
+            .map(|sel| sel.byte_range(doc))
+            .collect();
+        let node_ranges = match &self.syntax_highlighter {
+            Some(highlighter) => highlighter.enclosing_node_ranges(&text, &ranges),
+            None => vec![Vec::new(); ranges.len()],
+        };
+        let lsp_ranges: &[Range<usize>] = match &self.lsp_selection_ranges {
+            Some((version, lsp_ranges)) if *version == doc.version() => lsp_ranges,
+            _ => &[],
+        };
+
+        let mut expanded = Vec::with_capacity(ranges.len());
+        for (range, node_ranges) in ranges.iter().zip(node_ranges) {
+            let grows = |candidate: &&Range<usize>| {
+                candidate.start <= range.start
+                    && candidate.end >= range.end
+                    && candidate.len() > range.len()
+                    && candidate.end <= text.len()
+            };
+            let next = lsp_ranges
+                .iter()
+                .chain(&node_ranges)
+                .filter(grows)
+                .min_by_key(|candidate| candidate.len())
+                .cloned()
+                .or_else(|| {
+                    text_object_ranges(&tree, range.clone())
+                        .iter()
+                        .filter(grows)
+                        .min_by_key(|candidate| candidate.len())
+                        .cloned()
+                });
+            expanded.push(next);
+        }
+
+        let mut changed = false;
+        for ((sel, range), next) in self.selections.iter_mut().zip(ranges).zip(expanded) {
+            let Some(next) = next else {
+                continue;
+            };
+            match self
+                .expansion_stacks
+                .iter_mut()
+                .find(|(id, _)| *id == sel.id)
+            {
+                Some((_, stack)) => stack.push(range),
+                None => self.expansion_stacks.push((sel.id, vec![range])),
+            }
+            sel.anchor = doc_pos_at(&tree, next.start);
+            sel.cursor = doc_pos_at(&tree, next.end);
+            sel.goal_column = None;
+            changed = true;
+        }
+        drop(tree);
+
+        if !changed {
+            return InputAction::None;
+        }
+        self.merge_selections(doc);
+        InputAction::Redraw
+    }
+
+    /// Undo the last expand selection of each selection
+    fn shrink_selection(&mut self, doc: &Doc) -> InputAction {
+        let tree = doc.read();
+        let mut changed = false;
+        for sel in &mut self.selections {
+            let Some(previous) = self
+                .expansion_stacks
+                .iter_mut()
+                .find(|(id, _)| *id == sel.id)
+                .and_then(|(_, stack)| stack.pop())
+            else {
+                continue;
+            };
+            sel.anchor = doc_pos_at(&tree, previous.start);
+            sel.cursor = doc_pos_at(&tree, previous.end);
+            sel.goal_column = None;
+            changed = true;
+        }
+        drop(tree);
+
+        if changed {
+            InputAction::Redraw
+        } else {
+            InputAction::None
+        }
+    }
+
+    /// Refresh the display map (folded and soft wrapped lines) for this frame
+    /// Folds containing a cursor line (e.g. after jumping into one) are opened first
+    pub fn update_display_map(&mut self, doc: &Doc) -> &DisplayMap {
+        let mut stale = self.display_map_key != self.display_map_key(doc);
+        if !self.folds.is_empty() {
+            let tree = doc.read();
+            let cursor_lines: Vec<u32> = self.selections.iter().map(|s| s.cursor.line).collect();
+            let folds = self.folds.iter().count();
+            self.folds.retain(|header, last| {
+                let range = FoldRange::new(tree.byte_to_line(header), tree.byte_to_line(last));
+                !cursor_lines
+                    .iter()
+                    .any(|line| range.hidden_lines().contains(line))
+            });
+            stale |= self.folds.iter().count() != folds;
+        }
+        if stale {
+            self.refresh_display_map(doc);
+        }
+        &self.display_map
+    }
+
+    /// Foldable header lines and whether each is folded, for the gutter
+    pub fn fold_markers(&mut self, doc: &Doc) -> Vec<(u32, bool)> {
+        let folded: Vec<u32> = self
+            .folded_ranges(&doc.read())
+            .iter()
+            .map(|range| range.header)
+            .collect();
+        let mut markers: Vec<(u32, bool)> = self
+            .fold_ranges(doc)
+            .iter()
+            .map(|range| (range.header, folded.contains(&range.header)))
+            .collect();
+        for header in folded {
+            if !markers.iter().any(|&(line, _)| line == header) {
+                markers.push((header, true));
+            }
+        }
+        markers.sort_unstable();
+        markers
+    }
+
+    /// Foldable regions of the document: language server ranges for the current version,
+    /// else the folds query of the syntax tree, else indentation
+    fn fold_ranges(&mut self, doc: &Doc) -> &[FoldRange] {
+        let syntax_version = self
+            .syntax_highlighter
+            .as_ref()
+            .map_or(0, |highlighter| highlighter.cached_version());
+        let key = (doc.version(), syntax_version);
+        if !matches!(&self.fold_ranges_cache, Some((cached, _)) if *cached == key) {
+            let tree = doc.read();
+            let mut ranges = match &self.lsp_fold_ranges {
+                Some((version, ranges)) if *version == doc.version() => ranges.clone(),
+                _ => self
+                    .syntax_highlighter
+                    .as_ref()
+                    .and_then(|highlighter| highlighter.fold_ranges())
+                    .map(|(_, ranges)| {
+                        ranges
+                            .into_iter()
+                            .map(|(header, last)| FoldRange::new(header, last))
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_else(|| {
+                        folds::indent_fold_ranges(&tree.flatten_to_string(), self.tab_stops)
+                    }),
+            };
+            let line_count = tree.line_count();
+            ranges.retain(|range| range.last > range.header && range.last < line_count);
+            ranges.sort_unstable_by(|a, b| a.header.cmp(&b.header).then(b.last.cmp(&a.last)));
+            ranges.dedup_by_key(|range| range.header);
+            self.fold_ranges_cache = Some((key, ranges));
+        }
+        self.fold_ranges_cache
+            .as_ref()
+            .map(|(_, ranges)| ranges.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Folded regions as lines, dropping folds an edit collapsed onto one line
+    fn folded_ranges(&mut self, tree: &Tree) -> Vec<FoldRange> {
+        self.folds
+            .retain(|header, last| tree.byte_to_line(last) > tree.byte_to_line(header));
+        self.folds
+            .iter()
+            .map(|(header, last)| {
+                FoldRange::new(tree.byte_to_line(header), tree.byte_to_line(last))
+            })
+            .collect()
+    }
+
+    fn display_map_key(&self, doc: &Doc) -> (u64, Option<(u32, bool)>, u32) {
+        (doc.version(), self.soft_wrap, self.tab_stops)
+    }
+
+    fn refresh_display_map(&mut self, doc: &Doc) {
+        let key = self.display_map_key(doc);
+        let same_wrapping = (key.1, key.2) == (self.display_map_key.1, self.display_map_key.2);
+        self.display_map_key = key;
+        let edits = self.pending_wrap_edits.replace(Vec::new());
+        let wrapped_tree = self.wrapped_tree.take();
+        if self.folds.is_empty() && self.display_map.is_empty() && self.soft_wrap.is_none() {
+            return;
+        }
+
+        let tree = doc.read();
+        let hidden = self
+            .folded_ranges(&tree)
+            .iter()
+            .map(FoldRange::hidden_lines)
+            .collect();
+        let mut display_map = DisplayMap::new(hidden);
+
+        if let Some((columns, indent_continuations)) = self.soft_wrap {
+            let tab_stops = self.tab_stops;
+            let wrap = |line: u32, line_text: &str| {
+                let wraps = coordinates::wrap_line(
+                    line_text.trim_end_matches('\r'),
+                    columns,
+                    tab_stops,
+                    indent_continuations,
+                );
+                (!wraps.breaks.is_empty()).then_some((line, wraps))
+            };
+
+            // Only the lines edited since the last refresh are wrapped again
+            let rewrapped = match (wrapped_tree, edits) {
+                (Some(old_tree), Some(edits)) if same_wrapping && self.display_map.is_wrapped() => {
+                    rewrap_changed_lines(
+                        self.display_map.wrapped_lines(),
+                        &old_tree,
+                        &tree,
+                        &edits,
+                        |line| wrap(line, &tree.line_text_trimmed(line)),
+                    )
+                }
+                _ => None,
+            };
+            let wraps = rewrapped.unwrap_or_else(|| {
+                let text = tree.flatten_to_string();
+                text.split('\n')
+                    .enumerate()
+                    .filter_map(|(line, line_text)| wrap(line as u32, line_text))
+                    .collect()
+            });
+            display_map = display_map.with_wraps(wraps, tree.line_count());
+            self.wrapped_tree = Some(tree);
+        }
+        self.display_map = display_map;
+    }
+
+    /// Fold regions, moving cursors inside them to the header line
+    fn fold(&mut self, doc: &Doc, ranges: &[FoldRange]) -> InputAction {
+        if ranges.is_empty() {
+            return InputAction::None;
+        }
+        let tree = doc.read();
+        for range in ranges {
+            self.folds.insert(
+                line_start(&tree, range.header),
+                line_start(&tree, range.last),
+            );
+        }
+        drop(tree);
+        self.refresh_display_map(doc);
+
+        let tree = doc.read();
+        let mut moved = false;
+        for sel in &mut self.selections {
+            let Some(hidden) = self.display_map.hidden_range_at(sel.cursor.line) else {
+                continue;
+            };
+            let line = hidden.start - 1;
+            let column = sel.cursor.column.min(tree.line_char_count(line) as u32);
+            sel.cursor = DocPos {
+                line,
+                column,
+                byte_offset: 0,
+            };
+            sel.anchor = sel.cursor;
+            sel.goal_column = None;
+            moved = true;
+        }
+        drop(tree);
+        if moved {
+            self.merge_selections(doc);
+        }
+        InputAction::Redraw
+    }
+
+    /// Fold the innermost unfolded region around each cursor, and with `recursive`
+    /// every region inside it too
+    fn fold_at_cursors(&mut self, doc: &Doc, recursive: bool) -> InputAction {
+        let folded = self.folded_ranges(&doc.read());
+        let cursor_lines: Vec<u32> = self.selections.iter().map(|s| s.cursor.line).collect();
+        let ranges = self.fold_ranges(doc);
+
+        let mut to_fold = Vec::new();
+        for line in cursor_lines {
+            let unfolded: Vec<FoldRange> = ranges
+                .iter()
+                .filter(|range| !folded.contains(range))
+                .copied()
+                .collect();
+            let Some(target) = folds::innermost_at(&unfolded, line) else {
+                continue;
+            };
+            if recursive {
+                to_fold.extend(ranges.iter().filter(|range| target.encloses(range)));
+            } else {
+                to_fold.push(target);
+            }
+        }
+        self.fold(doc, &to_fold)
+    }
+
+    /// Unfold the folds headed at each cursor's line, and with `recursive`
+    /// every fold inside the region around the cursor
+    fn unfold_at_cursors(&mut self, doc: &Doc, recursive: bool) -> InputAction {
+        let cursor_lines: Vec<u32> = self.selections.iter().map(|s| s.cursor.line).collect();
+        let regions: Vec<FoldRange> = if recursive {
+            let ranges = self.fold_ranges(doc);
+            cursor_lines
+                .iter()
+                .filter_map(|&line| folds::innermost_at(ranges, line))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        self.unfold_where(doc, |fold| {
+            cursor_lines.contains(&fold.header)
+                || regions.iter().any(|region| region.encloses(&fold))
+        })
+    }
+
+    /// Unfold the folds `pred` accepts
+    fn unfold_where(&mut self, doc: &Doc, pred: impl Fn(FoldRange) -> bool) -> InputAction {
+        let tree = doc.read();
+        let before = self.folds.iter().count();
+        self.folds.retain(|header, last| {
+            !pred(FoldRange::new(
+                tree.byte_to_line(header),
+                tree.byte_to_line(last),
+            ))
+        });
+        let changed = self.folds.iter().count() != before;
+        drop(tree);
+
+        if !changed {
+            return InputAction::None;
+        }
+        self.refresh_display_map(doc);
+        InputAction::Redraw
+    }
+
+    /// Unfold when a cursor is on a folded header, fold otherwise
+    fn toggle_fold_at_cursors(&mut self, doc: &Doc) -> InputAction {
+        let folded = self.folded_ranges(&doc.read());
+        let on_fold = self
+            .selections
+            .iter()
+            .any(|sel| folded.iter().any(|range| range.header == sel.cursor.line));
+        if on_fold {
+            self.unfold_at_cursors(doc, false)
+        } else {
+            self.fold_at_cursors(doc, false)
+        }
+    }
+
+    /// Toggle the fold headed at a line (gutter click), false if the line isn't foldable
+    fn toggle_fold_at_line(&mut self, doc: &Doc, line: u32) -> bool {
+        let folded = self.folded_ranges(&doc.read());
+        if folded.iter().any(|range| range.header == line) {
+            self.unfold_where(doc, |fold| fold.header == line);
+            return true;
+        }
+        let Some(range) = self
+            .fold_ranges(doc)
+            .iter()
+            .find(|range| range.header == line)
+            .copied()
+        else {
+            return false;
+        };
+        self.fold(doc, &[range]);
+        true
+    }
+
+    /// Toggle line comments on the selected lines
+    /// Each block of lines uses the comment syntax of the language it's in;
+    /// languages without line comments wrap the lines in a block comment instead
+    fn toggle_line_comment(&mut self, doc: &Doc) -> InputAction {
+        let tree = doc.read();
+        let mut replacements = Vec::new();
+        for (first, last) in self.selected_line_blocks() {
+            let lines: Vec<String> = (first..=last)
+                .map(|line| tree.line_text_trimmed(line))
+                .collect();
+            let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+            let Some((content_line, indent)) = comments::first_content(&lines) else {
+                continue;
+            };
+
+            let tokens =
+                self.comment_tokens_at(line_start(&tree, first + content_line as u32) + indent);
+            if let Some(token) = tokens.line {
+                for edit in comments::toggle_line_comments(&lines, token) {
+                    let start = line_start(&tree, first + edit.line as u32);
+                    replacements
+                        .push((start + edit.range.start..start + edit.range.end, edit.text));
+                }
+            } else if let Some((open, close)) = tokens.block {
+                let start = line_start(&tree, first);
+                let text = tree.get_text_slice(start..line_content_end(&tree, last));
+                for (range, text) in comments::toggle_block_comment(&text, open, close) {
+                    replacements.push((start + range.start..start + range.end, text));
+                }
+            }
+        }
+        drop(tree);
+
+        self.replace_ranges_keeping_selections(doc, replacements)
+    }
+
+    /// Toggle a block comment around each selection (or the cursor's line)
+    /// Falls back to line comments for languages without block comments
+    fn toggle_block_comment(&mut self, doc: &Doc) -> InputAction {
+        let tree = doc.read();
+        let mut ranges: Vec<Range<usize>> = self
+            .selections
+            .iter()
+            .map(|sel| {
+                if sel.is_cursor() {
+                    line_start(&tree, sel.cursor.line)..line_content_end(&tree, sel.cursor.line)
+                } else {
+                    sel.byte_range(doc)
+                }
+            })
+            .collect();
+        ranges.sort_by_key(|range| range.start);
+        ranges.dedup_by(|range, previous| range.start < previous.end || range == previous);
+
+        let mut replacements = Vec::new();
+        for range in ranges {
+            let text = tree.get_text_slice(range.clone());
+            let content_start = range.start + (text.len() - text.trim_start().len());
+            match self.comment_tokens_at(content_start).block {
+                Some((open, close)) => {
+                    for (edit, text) in comments::toggle_block_comment(&text, open, close) {
+                        replacements.push((range.start + edit.start..range.start + edit.end, text));
+                    }
+                }
+                None => {
+                    drop(tree);
+                    return self.toggle_line_comment(doc);
+                }
+            }
+        }
+        drop(tree);
+
+        self.replace_ranges_keeping_selections(doc, replacements)
+    }
+
+    /// Add a cursor on the line above/below the outermost cursor (at its goal column)
+    fn add_cursor_vertical(&mut self, doc: &Doc, dy: i32) -> InputAction {
+        let tree = doc.read();
+        let edge = if dy < 0 {
+            self.selections
+                .iter()
+                .min_by_key(|s| (s.cursor.line, s.cursor.column))
+        } else {
+            self.selections
+                .iter()
+                .max_by_key(|s| (s.cursor.line, s.cursor.column))
+        };
+        let Some(edge) = edge.cloned() else {
+            return InputAction::None;
+        };
+
+        let line = if dy < 0 {
+            edge.cursor
+                .line
+                .checked_sub(1)
+                .map(|l| self.display_map.visible_line(l, false))
+        } else {
+            Some(self.display_map.visible_line(edge.cursor.line + 1, true))
+                .filter(|&l| tree.line_to_byte(l).is_some())
+        };
+        let Some(line) = line else {
+            return InputAction::None;
+        };
+
+        let goal = edge.goal_column.unwrap_or(edge.cursor.column);
+        let pos = DocPos {
+            line,
+            column: goal.min(tree.line_char_count(line) as u32),
+            byte_offset: 0,
+        };
+        drop(tree);
+
+        self.add_selection(doc, pos, pos, Some(goal));
+        InputAction::Redraw
+    }
+
+    /// Add a cursor at the mouse position (or remove the cursor already there)
+    fn add_cursor_at_click(
+        &mut self,
+        event: &Event,
+        doc: &Doc,
+        viewport: &Viewport,
     ) -> InputAction {
-        self.goal_column = None;
+        let (Some(x), Some(y)) = (
+            event.data.get("x").and_then(|v| v.as_f64()),
+            event.data.get("y").and_then(|v| v.as_f64()),
+        ) else {
+            return InputAction::None;
+        };
+
         let tree = doc.read();
+        let doc_pos = viewport.layout_to_doc_with_tree(
+            LayoutPos {
+                x: tiny_sdk::LogicalPixels(x as f32 + viewport.scroll.x.0),
+                y: tiny_sdk::LogicalPixels(y as f32 + viewport.scroll.y.0),
+            },
+            &tree,
+        );
+        drop(tree);
+
+        let existing = self.selections.iter().position(|s| {
+            s.is_cursor() && s.cursor.line == doc_pos.line && s.cursor.column == doc_pos.column
+        });
+        match existing {
+            Some(index) if self.selections.len() > 1 => {
+                self.selections.remove(index);
+            }
+            Some(_) => return InputAction::None,
+            None => self.add_selection(doc, doc_pos, doc_pos, None),
+        }
+        InputAction::Redraw
+    }
+
+    /// Drop every selection except the primary
+    fn remove_secondary_cursors(&mut self) -> InputAction {
+        self.snippet = None;
+        if self.selections.len() < 2 {
+            return InputAction::None;
+        }
+        self.clear_selections();
+        InputAction::Redraw
+    }
+
+    /// Add a selection as the new primary and merge it with any it overlaps
+    fn add_selection(
+        &mut self,
+        doc: &Doc,
+        anchor: DocPos,
+        cursor: DocPos,
+        goal_column: Option<u32>,
+    ) {
+        self.selections.insert(
+            0,
+            Selection {
+                cursor,
+                anchor,
+                id: self.next_id,
+                goal_column,
+            },
+        );
+        self.next_id += 1;
+        self.merge_selections(doc);
+    }
 
-        let sel = match self.selections.first() {
-            Some(sel) => sel,
-            None => return InputAction::None,
+    /// Text and search options for occurrence selection, based on the primary selection
+    fn occurrence_pattern(&self, doc: &Doc) -> Option<(String, SearchOptions)> {
+        let primary = self.selections.first().filter(|s| !s.is_cursor())?;
+        let pattern = doc.read().get_text_slice(primary.byte_range(doc));
+        let options = SearchOptions {
+            whole_word: self.occurrence_word.as_deref() == Some(pattern.as_str()),
+            ..Default::default()
         };
+        Some((pattern, options))
+    }
 
-        let new_pos = DocPos {
-            line: sel.cursor.line,
-            column: if to_end {
-                tree.line_char_count(sel.cursor.line) as u32
+    /// Expand every empty cursor to the word under it
+    /// Returns false if the primary cursor isn't on a word
+    fn select_words_at_cursors(&mut self, doc: &Doc) -> bool {
+        let tree = doc.read();
+        let Some(primary_word) = self
+            .selections
+            .first()
+            .and_then(|s| word_range_at(&tree, s.cursor))
+        else {
+            return false;
+        };
+
+        for sel in &mut self.selections {
+            if !sel.is_cursor() {
+                continue;
+            }
+            if let Some(range) = word_range_at(&tree, sel.cursor) {
+                sel.anchor = doc_pos_at(&tree, range.start);
+                sel.cursor = doc_pos_at(&tree, range.end);
+                sel.goal_column = None;
+            }
+        }
+        self.occurrence_word = Some(tree.get_text_slice(primary_word));
+        drop(tree);
+
+        self.merge_selections(doc);
+        true
+    }
+
+    /// Add the next occurrence of the primary selection's text (wrapping around)
+    /// An empty cursor first selects the word under it
+    fn select_next_occurrence(&mut self, doc: &Doc) -> InputAction {
+        if self.selections.first().map_or(true, |s| s.is_cursor()) {
+            return if self.select_words_at_cursors(doc) {
+                InputAction::Redraw
             } else {
-                0
-            },
-            byte_offset: 0,
+                InputAction::None
+            };
+        }
+
+        let Some((pattern, options)) = self.occurrence_pattern(doc) else {
+            return InputAction::None;
+        };
+
+        let tree = doc.read();
+        let selected: Vec<Range<usize>> =
+            self.selections.iter().map(|s| s.byte_range(doc)).collect();
+
+        // Search after the most recently added occurrence, skipping ones already selected
+        let mut from = selected[0].end;
+        let mut wrapped = false;
+        let found = loop {
+            match tree.search_next(&pattern, from, options.clone()) {
+                Some(m) if selected.contains(&m.byte_range) => from = m.byte_range.end,
+                Some(m) => break Some(m),
+                None if !wrapped => {
+                    wrapped = true;
+                    from = 0;
+                }
+                None => break None,
+            }
+        };
+        let Some(found) = found else {
+            return InputAction::None;
+        };
+
+        let anchor = doc_pos_at(&tree, found.byte_range.start);
+        let cursor = doc_pos_at(&tree, found.byte_range.end);
+        drop(tree);
+
+        self.add_selection(doc, anchor, cursor, None);
+        InputAction::Redraw
+    }
+
+    /// Select every occurrence of the primary selection's text (or the word under the cursor)
+    fn select_all_occurrences(&mut self, doc: &Doc) -> InputAction {
+        if self.selections.first().map_or(true, |s| s.is_cursor()) {
+            self.selections.truncate(1);
+            if !self.select_words_at_cursors(doc) {
+                return InputAction::None;
+            }
+        }
+
+        let Some((pattern, options)) = self.occurrence_pattern(doc) else {
+            return InputAction::None;
         };
-        self.move_cursor_to(new_pos, extending_selection);
+        let primary_range = self.selections[0].byte_range(doc);
+
+        let tree = doc.read();
+        let mut selections: Vec<Selection> = Vec::new();
+        for m in tree.search(&pattern, options) {
+            let selection = Selection {
+                cursor: doc_pos_at(&tree, m.byte_range.end),
+                anchor: doc_pos_at(&tree, m.byte_range.start),
+                id: self.next_id,
+                goal_column: None,
+            };
+            self.next_id += 1;
+
+            // Keep the original primary as primary
+            if m.byte_range == primary_range {
+                selections.insert(0, selection);
+            } else {
+                selections.push(selection);
+            }
+        }
+        drop(tree);
+
+        if selections.is_empty() {
+            return InputAction::None;
+        }
+        self.selections = selections;
+        self.merge_selections(doc);
+        InputAction::Redraw
+    }
+
+    /// Remove the most recently added occurrence (the primary selection)
+    /// The newest remaining one becomes primary, so repeated unselects walk back in order
+    fn unselect_occurrence(&mut self) -> InputAction {
+        if self.selections.len() < 2 {
+            return InputAction::None;
+        }
+        self.selections.remove(0);
+        // Ids grow as selections are added, while merging keeps them in document order
+        if let Some(newest) = (0..self.selections.len()).max_by_key(|&i| self.selections[i].id) {
+            let primary = self.selections.remove(newest);
+            self.selections.insert(0, primary);
+        }
+        InputAction::Redraw
+    }
+
+    /// Grow or shrink the block selection by moving its cursor corner
+    /// Starts a block from the primary selection when none is active
+    fn extend_block(&mut self, doc: &Doc, dx: i32, dy: i32) -> InputAction {
+        let tree = doc.read();
+        let tab_stops = self.tab_stops;
+        let mut block = self.block.unwrap_or_else(|| {
+            let sel = &self.selections[0];
+            let column_of = |pos: DocPos| {
+                visual_column(&tree.line_text_trimmed(pos.line), pos.column, tab_stops)
+            };
+            BlockSelection {
+                anchor_line: sel.anchor.line,
+                anchor_column: column_of(sel.anchor),
+                cursor_line: sel.cursor.line,
+                cursor_column: column_of(sel.cursor),
+            }
+        });
+
+        let last_line = tree.line_count().saturating_sub(1);
+        block.cursor_line = block.cursor_line.saturating_add_signed(dy).min(last_line);
+
+        // Columns can go past short lines, but not past the widest line in the block
+        let widest = block
+            .lines()
+            .map(|line| line_visual_width(&tree.line_text_trimmed(line), tab_stops))
+            .max()
+            .unwrap_or(0);
+        block.cursor_column = block
+            .cursor_column
+            .saturating_add_signed(dx)
+            .min(widest.max(block.anchor_column));
+        drop(tree);
+
+        self.set_block_selection(doc, block);
+        InputAction::Redraw
+    }
+
+    /// Enter visual block mode with a block at the primary selection, or leave it
+    fn toggle_visual_block(&mut self, doc: &Doc) -> InputAction {
+        if self.current_mode == InputMode::Visual && self.block.is_some() {
+            return self.leave_visual_block();
+        }
+        self.current_mode = InputMode::Visual;
+        self.extend_block(doc, 0, 0)
+    }
 
+    /// Back to normal mode, keeping a cursor where the block's cursor corner was
+    fn leave_visual_block(&mut self) -> InputAction {
+        self.block = None;
+        self.current_mode = InputMode::Normal;
+        self.selections.truncate(1);
+        self.selections[0].anchor = self.selections[0].cursor;
         InputAction::Redraw
     }
 
-    /// Page navigation (up/down)
-    fn page_jump(&mut self, doc: &Doc, up: bool, extending_selection: bool) -> InputAction {
-        self.nav_history
-            .checkpoint_if_changed(self.primary_cursor_doc_pos(doc));
-        if self.goal_column.is_none() && !self.selections.is_empty() {
-            self.goal_column = Some(self.selections[0].cursor.column);
+    /// Replace all selections with one selection per line of the block
+    /// The selection on the cursor's line becomes the primary
+    fn set_block_selection(&mut self, doc: &Doc, block: BlockSelection) {
+        let tree = doc.read();
+        let (left, right) = block.columns();
+        let mut selections = Vec::new();
+
+        for line in block.lines() {
+            let text = tree.line_text_trimmed(line);
+            let char_count = text.chars().count() as u32;
+            let left_column = match char_column_at(&text, left, self.tab_stops) {
+                Some(column) => column,
+                // A zero-width block keeps a cursor at the end of short lines
+                None if left == right => char_count,
+                None => continue,
+            };
+            let right_column = char_column_at(&text, right, self.tab_stops).unwrap_or(char_count);
+
+            let (anchor_column, cursor_column) = if block.anchor_column <= block.cursor_column {
+                (left_column, right_column)
+            } else {
+                (right_column, left_column)
+            };
+            let pos = |column: u32| DocPos {
+                line,
+                column,
+                byte_offset: 0,
+            };
+            selections.push(Selection {
+                cursor: pos(cursor_column),
+                anchor: pos(anchor_column),
+                id: self.next_id,
+                goal_column: None,
+            });
+            self.next_id += 1;
+        }
+
+        if selections.is_empty() {
+            // Every line ends before the block - fall back to a cursor on the cursor line
+            let pos = DocPos {
+                line: block.cursor_line,
+                column: tree.line_char_count(block.cursor_line) as u32,
+                byte_offset: 0,
+            };
+            selections.push(Selection {
+                cursor: pos,
+                anchor: pos,
+                id: self.next_id,
+                goal_column: None,
+            });
+            self.next_id += 1;
         }
 
-        const PAGE_SIZE: u32 = 20;
+        if let Some(index) = selections
+            .iter()
+            .position(|s| s.cursor.line == block.cursor_line)
+        {
+            let primary = selections.remove(index);
+            selections.insert(0, primary);
+        }
 
-        let sel = match self.selections.first() {
-            Some(sel) => sel,
-            None => return InputAction::None,
-        };
+        self.selections = selections;
+        self.block = Some(block);
+    }
 
+    /// Text covered by the active block, one row per line (short lines give empty rows)
+    fn block_text(&self, doc: &Doc) -> Option<String> {
+        let block = self.block?;
         let tree = doc.read();
-        let total_lines = tree.line_count();
+        let (left, right) = block.columns();
+
+        let rows: Vec<String> = block
+            .lines()
+            .map(|line| {
+                let text = tree.line_text_trimmed(line);
+                let Some(start) = char_column_at(&text, left, self.tab_stops) else {
+                    return String::new();
+                };
+                let end = char_column_at(&text, right, self.tab_stops)
+                    .unwrap_or(text.chars().count() as u32);
+                text.chars()
+                    .skip(start as usize)
+                    .take(end.saturating_sub(start) as usize)
+                    .collect()
+            })
+            .collect();
 
-        let new_line = if up {
-            sel.cursor.line.saturating_sub(PAGE_SIZE)
-        } else {
-            (sel.cursor.line + PAGE_SIZE).min(total_lines.saturating_sub(1))
-        };
+        Some(rows.join("\n"))
+    }
 
-        let line_length = tree.line_char_count(new_line) as u32;
-        let new_pos = DocPos {
-            line: new_line,
-            column: self
-                .goal_column
-                .unwrap_or(sel.cursor.column)
-                .min(line_length),
-            byte_offset: 0,
-        };
+    /// Paste block text at the primary cursor: row N goes to the same visual column N lines down
+    /// Short lines are padded with spaces and missing lines are appended
+    fn paste_block(&mut self, doc: &Doc, text: &str) {
+        let tree = doc.read();
+        let cursor = self.selections[0].cursor;
+        let column = visual_column(
+            &tree.line_text_trimmed(cursor.line),
+            cursor.column,
+            self.tab_stops,
+        );
+        let last_line = tree.line_count().saturating_sub(1);
+        let end = tree.byte_count();
+
+        let mut replacements: Vec<(Range<usize>, String)> = Vec::new();
+        let mut appended = String::new();
+        for (i, row) in text.split('\n').enumerate() {
+            let line = cursor.line + i as u32;
+            if line > last_line {
+                appended.push('\n');
+                appended.push_str(&" ".repeat(column as usize));
+                appended.push_str(row);
+                continue;
+            }
+
+            let line_start = tree.line_to_byte(line).unwrap_or(end);
+            let line_text = tree.line_text_trimmed(line);
+            let (offset, padding) = match char_column_at(&line_text, column, self.tab_stops) {
+                Some(char_column) => (
+                    line_text
+                        .char_indices()
+                        .nth(char_column as usize)
+                        .map_or(line_text.len(), |(b, _)| b),
+                    0,
+                ),
+                None => (
+                    line_text.len(),
+                    column - line_visual_width(&line_text, self.tab_stops),
+                ),
+            };
+            let pos = line_start + offset;
+            replacements.push((pos..pos, " ".repeat(padding as usize) + row));
+        }
+        drop(tree);
 
-        self.move_cursor_to(new_pos, extending_selection);
+        if !appended.is_empty() {
+            // The last line may already get a row inserted at the document end
+            match replacements.last_mut() {
+                Some((range, row)) if range.start == end => row.push_str(&appended),
+                _ => replacements.push((end..end, appended)),
+            }
+        }
 
-        InputAction::Redraw
+        let new_ranges = self.replace_ranges(doc, &replacements);
+        self.selections.truncate(1);
+        self.collapse_to_bytes(doc, new_ranges.first().map(|r| r.start).into_iter());
     }
 
     /// Set the syntax highlighter for InputEdit coordination
@@ -912,6 +3941,12 @@ impl InputHandler {
         std::mem::take(&mut self.pending_renderer_edits)
     }
 
+    /// Get and clear the edits applied since the last call, None if the tree was
+    /// replaced wholesale since (undo/redo) and has to be re-indexed
+    pub fn take_word_edits(&mut self) -> Option<Vec<Edit>> {
+        self.pending_word_edits.replace(Vec::new())
+    }
+
     pub fn pending_edits_count(&self) -> usize {
         self.pending_edits.len()
     }
@@ -925,6 +3960,7 @@ impl InputHandler {
             cursor: pos,
             anchor: pos,
             id: 0,
+            goal_column: None,
         }];
     }
 
@@ -953,16 +3989,9 @@ impl InputHandler {
 
         let text_after = doc.read().flatten_to_string();
 
-        // If we have multiple edits, we can't send them all to tree-sitter
-        // (it only accepts one InputEdit at a time), so reset the tree and do a fresh parse
-        if self.pending_text_edits.len() == 1 {
-            // Single edit - use incremental parsing
-            let edit = &self.pending_text_edits[0];
-            syntax_hl.request_update_with_edit(&text_after, doc.version(), Some(edit.clone()));
-        } else {
-            // Multiple edits - reset tree and do fresh parse
-            syntax_hl.request_update_with_reset(&text_after, doc.version(), None, true);
-        }
+        // The parser thread applies the edits to its tree in order before re-parsing
+        let edits = std::mem::take(&mut self.pending_text_edits);
+        syntax_hl.request_update_with_edits(&text_after, doc.version(), edits);
 
         // Clear the pending syntax updates
         self.pending_text_edits.clear();
@@ -1003,6 +4032,12 @@ impl InputHandler {
 
             // Track edits for renderer (syntax token adjustment)
             self.pending_renderer_edits.push(edit.clone());
+            if let Some(edits) = &mut self.pending_word_edits {
+                edits.push(edit.clone());
+            }
+            if let Some(edits) = &mut self.pending_wrap_edits {
+                edits.push(edit.clone());
+            }
 
             // Apply incremental edit to renderer for stable typing
             renderer
@@ -1010,13 +4045,23 @@ impl InputHandler {
                 .map(|r| r.apply_incremental_edit(edit));
         }
 
-        // Apply all pending edits
+        // Apply all pending edits, keeping marks and change list in sync
         for edit in self.pending_edits.drain(..) {
+            self.marks.adjust(&edit);
+            self.folds.adjust(&edit);
+            if let Some(session) = &mut self.snippet {
+                session.adjust(&edit);
+            }
+            self.change_list.adjust(&edit);
+            adjust_auto_closed(&mut self.auto_closed, &edit);
+            adjust_find_scope(&mut self.find_scope, &edit);
+            self.change_list.record(crate::marks::edit_start(&edit));
             doc.edit(edit);
         }
 
         // Flush document to create new tree snapshot
         doc.flush();
+        self.refresh_display_map(doc);
 
         // Update metadata
         self.last_edit_time = Some(Instant::now());
@@ -1038,9 +4083,9 @@ impl InputHandler {
         if button != MouseButton::Left {
             return false;
         }
+        self.end_composition(doc);
 
         let current_pos = self.primary_cursor_doc_pos(doc);
-        self.goal_column = None;
 
         let layout_pos = LayoutPos {
             x: pos.x + viewport.scroll.x,
@@ -1057,9 +4102,9 @@ impl InputHandler {
             doc_pos.line, doc_pos.column
         );
 
-        // Save to nav history if jumping >5 lines
+        // Save to jump list if jumping >5 lines
         if current_pos.line.abs_diff(doc_pos.line) > 5 {
-            self.nav_history.checkpoint_if_changed(current_pos);
+            self.record_jump(current_pos);
         }
 
         // Detect double/triple click
@@ -1104,7 +4149,19 @@ impl InputHandler {
         }
 
         // Normal click handling
-        if shift_held {
+        if shift_held && alt_held {
+            // Shift+alt: start a block selection at the click point (dragging extends it)
+            drop(tree);
+            let column = visual_column_at_x(viewport, layout_pos.x.0);
+            let block = BlockSelection {
+                anchor_line: doc_pos.line,
+                anchor_column: column,
+                cursor_line: doc_pos.line,
+                cursor_column: column,
+            };
+            self.set_block_selection(doc, block);
+            return true;
+        } else if shift_held {
             // Shift-click: extend selection from current position to click point
             let anchor = self.selections.first().map_or(doc_pos, |sel| {
                 if sel.anchor != sel.cursor {
@@ -1116,27 +4173,32 @@ impl InputHandler {
                 }
             });
 
-            // Don't set selection_anchor here - that's only for keyboard-based selection extension
             self.selections = vec![Selection {
                 cursor: doc_pos,
                 anchor,
                 id: self.next_id,
+                goal_column: None,
             }];
         } else if alt_held {
             // Alt-click: add a new cursor
-            self.selections.push(Selection {
-                cursor: doc_pos,
-                anchor: doc_pos,
-                id: self.next_id,
+            self.add_selection(doc, doc_pos, doc_pos, None);
+            return true;
+        } else if self.click_count == 1 && self.selection_contains(doc, doc_pos) {
+            // Pressing inside a selection starts dragging its text
+            self.drag_anchor = None;
+            self.text_drag = Some(TextDrag {
+                origin: doc_pos,
+                target: None,
             });
+            return true;
         } else {
             // Regular click: start fresh selection at click point
-            self.selection_anchor = None; // Clear any existing selection mode
             self.drag_anchor = None; // Clear any leftover drag anchor
             self.selections = vec![Selection {
                 cursor: doc_pos,
                 anchor: doc_pos,
                 id: self.next_id,
+                goal_column: None,
             }];
         }
         self.next_id += 1;
@@ -1148,7 +4210,7 @@ impl InputHandler {
         &mut self,
         doc: &Doc,
         viewport: &Viewport,
-        _from: Point, // Unused - we use the stored drag_anchor
+        from: Point, // Only used for the visual column of a block selection's anchor
         to: Point,
         alt_held: bool,
     ) -> (bool, Option<(f32, f32)>) {
@@ -1175,21 +4237,39 @@ impl InputHandler {
             },
             &tree,
         );
+        drop(tree);
 
-        // Update selection using the anchor we stored at click time
-        // This ensures consistent selection behavior regardless of drag direction
-        let selection = Selection {
-            cursor: end_doc,
-            anchor: anchor_doc, // Always use the original click position as anchor
-            id: self.next_id,
-        };
-
-        if alt_held {
-            self.selections.push(selection);
+        if let Some(drag) = &mut self.text_drag {
+            // Dragging selected text only moves the drop caret
+            drag.target = Some(end_doc);
+        } else if alt_held {
+            // Alt-drag (shift+alt, since alt+click adds a cursor): block selection in visual columns, which may go past short lines
+            let (anchor_line, anchor_column) = match self.block {
+                Some(block) => (block.anchor_line, block.anchor_column),
+                None => (
+                    anchor_doc.line,
+                    visual_column_at_x(viewport, from.x.0 + viewport.scroll.x.0),
+                ),
+            };
+            let block = BlockSelection {
+                anchor_line,
+                anchor_column,
+                cursor_line: end_doc.line,
+                cursor_column: visual_column_at_x(viewport, to.x.0 + viewport.scroll.x.0),
+            };
+            self.set_block_selection(doc, block);
         } else {
-            self.selections = vec![selection];
+            // Update selection using the anchor we stored at click time
+            // This ensures consistent selection behavior regardless of drag direction
+            self.block = None;
+            self.selections = vec![Selection {
+                cursor: end_doc,
+                anchor: anchor_doc, // Always use the original click position as anchor
+                id: self.next_id,
+                goal_column: None,
+            }];
+            self.next_id += 1;
         }
-        self.next_id += 1;
 
         // Calculate scroll delta based on mouse position relative to VIEWPORT edges
         // Only scroll when mouse is outside the text area
@@ -1228,22 +4308,32 @@ impl InputHandler {
     }
 
     /// Copy selection to clipboard
+    /// Multiple selections are joined with newlines in document order
+    /// A block selection is copied row by row, including empty rows for short lines
     pub fn copy(&mut self, doc: &Doc) {
-        let sel = match self.selections.first().filter(|s| !s.is_cursor()) {
-            Some(sel) => sel,
-            None => return,
+        let block_text = self.block_text(doc);
+        let selected = match &block_text {
+            Some(text) => text.clone(),
+            None => {
+                let tree = doc.read();
+                let parts: Vec<String> = self
+                    .selections_in_doc_order()
+                    .into_iter()
+                    .map(|i| &self.selections[i])
+                    .filter(|sel| !sel.is_cursor())
+                    .map(|sel| tree.get_text_slice(sel.byte_range(doc)))
+                    .collect();
+
+                if parts.is_empty() {
+                    return;
+                }
+                parts.join("\n")
+            }
         };
 
-        let text = doc.read().flatten_to_string();
-        let range = sel.byte_range(doc);
-
-        if range.end > text.len() {
-            return;
-        }
-
-        let selected = &text[range];
-        self.clipboard = Some(selected.to_string());
-        let _ = Clipboard::new().and_then(|mut c| c.set_text(selected));
+        let _ = Clipboard::new().and_then(|mut c| c.set_text(selected.as_str()));
+        self.clipboard = Some(selected);
+        self.block_clipboard = block_text;
     }
 
     /// Cut selection to clipboard
@@ -1251,23 +4341,18 @@ impl InputHandler {
         self.copy(doc);
         self.save_snapshot_to_history(doc);
 
-        for sel in &self.selections {
-            if !sel.is_cursor() {
-                self.pending_edits.push(Edit::Delete {
-                    range: sel.byte_range(doc),
-                });
-            }
-        }
-
-        self.flush_pending_edits(doc);
+        let replacements: Vec<(Range<usize>, String)> = self
+            .selections
+            .iter()
+            .map(|sel| (sel.byte_range(doc), String::new()))
+            .collect();
 
-        for sel in &mut self.selections {
-            sel.cursor = sel.min_pos();
-            sel.anchor = sel.cursor;
-        }
+        let new_ranges = self.replace_ranges(doc, &replacements);
+        self.collapse_to_bytes(doc, new_ranges.iter().map(|r| r.start));
     }
 
     /// Paste from clipboard
+    /// With one clipboard line per selection, each selection gets its own line
     pub fn paste(&mut self, doc: &Doc) {
         let text = Clipboard::new()
             .ok()
@@ -1281,27 +4366,36 @@ impl InputHandler {
 
         self.save_snapshot_to_history(doc);
 
-        for sel in &self.selections {
-            if !sel.is_cursor() {
-                self.pending_edits.push(Edit::Delete {
-                    range: sel.byte_range(doc),
-                });
-            }
-            let tree = doc.read();
-            self.pending_edits.push(Edit::Insert {
-                pos: tree.doc_pos_to_byte(sel.min_pos()),
-                content: Content::Text(text.clone()),
-            });
+        // Text copied from a block goes back in as a block at a single cursor
+        if self.selections.len() == 1
+            && self.selections[0].is_cursor()
+            && self.block_clipboard.as_deref() == Some(text.as_str())
+        {
+            self.paste_block(doc, &text);
+            return;
         }
 
-        self.flush_pending_edits(doc);
+        let mut replacements: Vec<(Range<usize>, String)> = self
+            .selections
+            .iter()
+            .map(|sel| (sel.byte_range(doc), text.clone()))
+            .collect();
 
-        let advance_chars = text.chars().count() as u32;
-        for sel in &mut self.selections {
-            sel.cursor = sel.min_pos();
-            sel.cursor.column += advance_chars;
-            sel.anchor = sel.cursor;
+        let lines: Vec<&str> = text.lines().collect();
+        if self.selections.len() > 1 && lines.len() == self.selections.len() {
+            for (i, line) in self.selections_in_doc_order().into_iter().zip(lines) {
+                replacements[i].1 = line.to_string();
+            }
         }
+
+        let new_ranges = self.replace_ranges(doc, &replacements);
+        self.collapse_to_bytes(doc, new_ranges.iter().map(|r| r.end));
+
+        let pasted: Vec<(Range<usize>, &str)> = new_ranges
+            .into_iter()
+            .zip(replacements.iter().map(|(_, text)| text.as_str()))
+            .collect();
+        self.reindent_pasted(doc, &pasted);
     }
 
     /// Select all text
@@ -1316,6 +4410,7 @@ impl InputHandler {
             },
             anchor: DocPos::default(),
             id: self.next_id,
+            goal_column: None,
         }];
         self.next_id += 1;
     }
@@ -1373,6 +4468,7 @@ impl InputHandler {
             cursor: word_end,
             anchor: word_start,
             id: self.next_id,
+            goal_column: None,
         }];
         self.next_id += 1;
     }
@@ -1406,6 +4502,7 @@ impl InputHandler {
             cursor: line_end,
             anchor: line_start,
             id: self.next_id,
+            goal_column: None,
         }];
         self.next_id += 1;
     }
@@ -1422,6 +4519,82 @@ impl InputHandler {
         }
     }
 
+    /// Whether a position is inside one of the (non-empty) selections
+    fn selection_contains(&self, doc: &Doc, pos: DocPos) -> bool {
+        let offset = doc.read().doc_pos_to_byte(pos);
+        self.selections
+            .iter()
+            .any(|sel| !sel.is_cursor() && sel.byte_range(doc).contains(&offset))
+    }
+
+    /// Drop caret of the text being dragged, if it's over the document
+    pub fn drop_caret(&self) -> Option<DocPos> {
+        self.text_drag.as_ref().and_then(|drag| drag.target)
+    }
+
+    /// End a mouse drag, dropping dragged text at the drop caret as one undo step
+    /// The text moves unless `copy` (alt held); dropping it onto itself just places
+    /// the cursor. Returns whether the selections or document changed
+    pub fn end_drag(&mut self, doc: &Doc, copy: bool) -> bool {
+        self.clear_drag_anchor();
+        let Some(drag) = self.text_drag.take() else {
+            return false;
+        };
+
+        let tree = doc.read();
+        let sources: Vec<Range<usize>> = self
+            .selections_in_doc_order()
+            .into_iter()
+            .map(|i| self.selections[i].byte_range(doc))
+            .filter(|range| !range.is_empty())
+            .collect();
+        let target = drag
+            .target
+            .map(|pos| tree.doc_pos_to_byte(pos))
+            .filter(|&offset| {
+                !sources
+                    .iter()
+                    .any(|range| range.start <= offset && offset <= range.end)
+            });
+        let Some(target) = target else {
+            drop(tree);
+            let pos = drag.target.unwrap_or(drag.origin);
+            self.selections = vec![Selection {
+                cursor: pos,
+                anchor: pos,
+                id: self.next_id,
+                goal_column: None,
+            }];
+            self.next_id += 1;
+            return true;
+        };
+
+        // Several selections are dropped together, one per line (like copy)
+        let text: Vec<String> = sources
+            .iter()
+            .map(|range| tree.get_text_slice(range.clone()))
+            .collect();
+        drop(tree);
+        let mut replacements = vec![(target..target, text.join("\n"))];
+        if !copy {
+            replacements.extend(sources.into_iter().map(|range| (range, String::new())));
+        }
+
+        self.checkpoint_history(doc);
+        let dropped = self.replace_ranges(doc, &replacements)[0].clone();
+
+        // The dropped text ends up selected
+        let tree = doc.read();
+        self.selections = vec![Selection {
+            cursor: doc_pos_at(&tree, dropped.end),
+            anchor: doc_pos_at(&tree, dropped.start),
+            id: self.next_id,
+            goal_column: None,
+        }];
+        self.next_id += 1;
+        true
+    }
+
     /// Clear the drag anchor (called when mouse button is released)
     pub fn clear_drag_anchor(&mut self) {
         self.drag_anchor = None;
@@ -1447,9 +4620,93 @@ impl InputHandler {
             cursor: pos,
             anchor: pos,
             id: self.next_id,
+            goal_column: None,
+        }];
+        self.next_id += 1;
+    }
+
+    /// Select a byte range of the document, dropping the other selections (e.g. a search match)
+    pub fn select_range(&mut self, doc: &Doc, range: Range<usize>) {
+        let tree = doc.read();
+        self.selections = vec![Selection {
+            cursor: doc_pos_at(&tree, range.end),
+            anchor: doc_pos_at(&tree, range.start),
+            id: self.next_id,
+            goal_column: None,
         }];
         self.next_id += 1;
-        self.goal_column = None;
+    }
+
+    /// Text of the primary selection, None if it's empty
+    pub fn selected_text(&self, doc: &Doc) -> Option<String> {
+        let sel = self.selections.first().filter(|sel| !sel.is_cursor())?;
+        Some(doc.read().get_text_slice(sel.byte_range(doc)))
+    }
+
+    /// Byte range of the whole word under (or just before) the primary cursor
+    pub fn word_range_at_cursor(&self, doc: &Doc) -> Option<Range<usize>> {
+        let sel = self.selections.first()?;
+        word_range_at(&doc.read(), sel.cursor)
+    }
+
+    /// Record a jump origin for the jump list
+    pub fn record_jump(&mut self, pos: DocPos) {
+        if self.pending_jumps.last() != Some(&pos) {
+            self.pending_jumps.push(pos);
+        }
+    }
+
+    /// Take jump origins recorded since the last call
+    pub fn take_jumps(&mut self) -> Vec<DocPos> {
+        std::mem::take(&mut self.pending_jumps)
+    }
+
+    /// Set a buffer-local mark at the primary cursor
+    pub fn set_mark(&mut self, doc: &Doc, name: char) {
+        let offset = self.primary_cursor_doc_pos(doc).byte_offset;
+        self.marks.set(name, offset);
+    }
+
+    /// Text the find bar searches while "in selection" is on
+    pub fn find_scope(&self) -> Option<Range<usize>> {
+        self.find_scope.clone()
+    }
+
+    pub fn set_find_scope(&mut self, scope: Option<Range<usize>>) {
+        self.find_scope = scope;
+    }
+
+    /// Current position of a mark, clamped to the document
+    /// `exact` keeps the column, otherwise the first non-blank of the mark's line
+    pub fn mark_position(&self, doc: &Doc, name: char, exact: bool) -> Option<DocPos> {
+        let offset = self.marks.get(name)?;
+        let tree = doc.read();
+        let mut pos = self.byte_to_doc_pos(&tree, offset.min(tree.byte_count()));
+        if !exact {
+            let line_text = tree.line_text(pos.line);
+            pos.column = line_text
+                .chars()
+                .take_while(|c| *c == ' ' || *c == '\t')
+                .count() as u32;
+        }
+        pos.byte_offset = tree.doc_pos_to_byte(pos);
+        Some(pos)
+    }
+
+    /// Move the primary cursor to an older (or newer) change location
+    pub fn goto_change(&mut self, doc: &Doc, older: bool) -> bool {
+        let offset = if older {
+            self.change_list.older()
+        } else {
+            self.change_list.newer()
+        };
+        let Some(offset) = offset else {
+            return false;
+        };
+        let tree = doc.read();
+        let pos = self.byte_to_doc_pos(&tree, offset.min(tree.byte_count()));
+        self.set_cursor(pos);
+        true
     }
 
     /// Get selection data for plugins: cursor position and selection positions
@@ -1516,6 +4773,22 @@ impl InputHandler {
         }
     }
 
+    /// Shift marks, folds and the change list when the tree is swapped wholesale
+    /// (undo/redo), as the edit flush does for ordinary edits
+    fn adjust_for_tree_swap(&mut self, before: &str, after: &str) {
+        let Some(edit) = crate::marks::diff_edit(before, after) else {
+            return;
+        };
+        self.marks.adjust(&edit);
+        self.folds.adjust(&edit);
+        if let Some(session) = &mut self.snippet {
+            session.adjust(&edit);
+        }
+        self.change_list.adjust(&edit);
+        adjust_auto_closed(&mut self.auto_closed, &edit);
+        adjust_find_scope(&mut self.find_scope, &edit);
+    }
+
     /// Perform undo operation
     pub fn undo(&mut self, doc: &Doc) -> bool {
         self.flush_pending_edits(doc);
@@ -1529,19 +4802,23 @@ impl InputHandler {
             None => return false,
         };
 
+        let before = doc.read().flatten_to_string();
         doc.replace_tree(prev.tree.clone());
+        let text = doc.read().flatten_to_string();
+        self.adjust_for_tree_swap(&before, &text);
         self.selections = prev.selections;
         self.next_id = self.selections.iter().map(|s| s.id).max().unwrap_or(0) + 1;
 
         // Clear accumulated renderer edits - they're invalid for the undone tree
         self.pending_renderer_edits.clear();
+        self.pending_word_edits = None;
+        self.pending_wrap_edits = None;
 
         // Reset checkpoint time so next edit starts a new undo group
         self.last_checkpoint_time = None;
 
         // Request syntax update after undo (tree has changed significantly)
         if let Some(ref syntax_hl) = self.syntax_highlighter {
-            let text = doc.read().flatten_to_string();
             syntax_hl.request_update_with_reset(&text, doc.version(), None, true);
         }
 
@@ -1614,19 +4891,23 @@ impl InputHandler {
             None => return false,
         };
 
+        let before = doc.read().flatten_to_string();
         doc.replace_tree(next.tree.clone());
+        let text = doc.read().flatten_to_string();
+        self.adjust_for_tree_swap(&before, &text);
         self.selections = next.selections;
         self.next_id = self.selections.iter().map(|s| s.id).max().unwrap_or(0) + 1;
 
         // Clear accumulated renderer edits - they're invalid for the redone tree
         self.pending_renderer_edits.clear();
+        self.pending_word_edits = None;
+        self.pending_wrap_edits = None;
 
         // Reset checkpoint time so next edit starts a new undo group
         self.last_checkpoint_time = None;
 
         // Request syntax update after redo (tree has changed significantly)
         if let Some(ref syntax_hl) = self.syntax_highlighter {
-            let text = doc.read().flatten_to_string();
             syntax_hl.request_update_with_reset(&text, doc.version(), None, true);
         }
 
@@ -1634,6 +4915,391 @@ impl InputHandler {
     }
 }
 
+/// Convert a byte offset to a DocPos (line + char column)
+fn doc_pos_at(tree: &Tree, byte_offset: usize) -> DocPos {
+    let line = tree.byte_to_line(byte_offset);
+    let line_start_byte = tree.line_to_byte(line).unwrap_or(0);
+    let column = tree
+        .get_text_slice(line_start_byte..byte_offset)
+        .chars()
+        .count() as u32;
+    DocPos {
+        line,
+        column,
+        byte_offset: 0,
+    }
+}
+
+/// Position one word before/after `pos`, moving to the adjacent line at line edges
+fn word_motion(tree: &Tree, pos: DocPos, forward: bool, config: &WordConfig) -> DocPos {
+    let chars: Vec<char> = tree.line_text_trimmed(pos.line).chars().collect();
+    let column = (pos.column as usize).min(chars.len());
+
+    let (line, column) = if forward {
+        if column < chars.len() {
+            (pos.line, config.next_boundary(&chars, column))
+        } else if tree.line_to_byte(pos.line + 1).is_some() {
+            (pos.line + 1, 0)
+        } else {
+            (pos.line, column)
+        }
+    } else if column > 0 {
+        (pos.line, config.prev_boundary(&chars, column))
+    } else if pos.line > 0 {
+        (pos.line - 1, tree.line_char_count(pos.line - 1))
+    } else {
+        (pos.line, 0)
+    };
+
+    DocPos {
+        line,
+        column: column as u32,
+        byte_offset: 0,
+    }
+}
+
+/// Where a byte offset ends up after replacements (sorted, relative to the same text)
+/// Offsets inside a replaced range move to its start
+fn map_offset(replacements: &[(Range<usize>, String)], offset: usize) -> usize {
+    let mut mapped = offset as isize;
+    for (range, text) in replacements {
+        if range.end <= offset {
+            mapped += text.len() as isize - range.len() as isize;
+        } else if range.start < offset {
+            mapped -= (offset - range.start) as isize;
+        }
+    }
+    mapped as usize
+}
+
+/// Whether typing `pair.open` after `before` (the line up to the cursor) and in front of
+/// `next` should also insert `pair.close`
+fn should_auto_close(
+    pair: &AutoPair,
+    pairs: &[AutoPair],
+    before: &str,
+    next: Option<char>,
+) -> bool {
+    // Don't pair in front of a word, e.g. `(|foo`
+    let before_boundary = next.map_or(true, |c| {
+        c.is_whitespace()
+            || matches!(c, ',' | ';' | ':')
+            || pairs
+                .iter()
+                .any(|other| other.close == c && other.open != other.close)
+    });
+    if !before_boundary || pair.suppressed_after(before) {
+        return false;
+    }
+
+    let prev = before.chars().next_back();
+    // A quote right after a word (or another quote) is an apostrophe or a closing quote
+    pair.open != pair.close
+        || prev.map_or(true, |c| !c.is_alphanumeric() && c != '_' && c != pair.open)
+}
+
+/// Soft wraps after `edits` turned `old` into `new`, wrapping only the lines they touched
+/// again. None if the edits don't explain the new tree
+fn rewrap_changed_lines(
+    wrapped: &[(u32, coordinates::LineWraps)],
+    old: &Tree,
+    new: &Tree,
+    edits: &[Edit],
+    wrap: impl Fn(u32) -> Option<(u32, coordinates::LineWraps)>,
+) -> Option<Vec<(u32, coordinates::LineWraps)>> {
+    if edits.is_empty() {
+        return (old.version == new.version).then(|| wrapped.to_vec());
+    }
+    let (old_span, new_span) = completion::changed_span(edits)?;
+    if old_span.end > old.byte_count()
+        || new_span.end > new.byte_count()
+        || old.byte_count() - old_span.len() != new.byte_count() - new_span.len()
+    {
+        return None;
+    }
+
+    // The end of the document is on the last line
+    let line_at = |tree: &Tree, byte: usize| {
+        if byte < tree.byte_count() {
+            tree.byte_to_line(byte)
+        } else {
+            tree.line_count()
+        }
+    };
+    let old_lines = line_at(old, old_span.start)..=line_at(old, old_span.end);
+    let new_lines = line_at(new, new_span.start)..=line_at(new, new_span.end);
+    let shift = *new_lines.end() as i64 - *old_lines.end() as i64;
+    let mut lines: Vec<_> = wrapped
+        .iter()
+        .filter(|(line, _)| line < old_lines.start())
+        .cloned()
+        .collect();
+    lines.extend(new_lines.filter_map(wrap));
+    lines.extend(
+        wrapped
+            .iter()
+            .filter(|(line, _)| line > old_lines.end())
+            .map(|(line, wraps)| ((*line as i64 + shift) as u32, wraps.clone())),
+    );
+    Some(lines)
+}
+
+/// Move auto-inserted closers through an edit, forgetting the ones it removes
+fn adjust_auto_closed(offsets: &mut Vec<usize>, edit: &Edit) {
+    offsets.retain_mut(|offset| {
+        let removed = match edit {
+            Edit::Insert { .. } => false,
+            Edit::Delete { range } | Edit::Replace { range, .. } => range.contains(offset),
+        };
+        *offset = crate::marks::adjust_offset(*offset, edit);
+        !removed
+    });
+}
+
+/// Move the find scope through an edit
+/// Text replaced at its start stays inside it, so replacing the first match keeps it searched
+fn adjust_find_scope(scope: &mut Option<Range<usize>>, edit: &Edit) {
+    if let Some(range) = scope {
+        let start = match edit {
+            Edit::Insert { pos, .. } if *pos == range.start => range.start,
+            _ => crate::marks::adjust_offset(range.start, edit),
+        };
+        let end = crate::marks::adjust_offset(range.end, edit).max(start);
+        *range = start..end;
+    }
+}
+
+/// Byte offset where a line starts (document end past the last line)
+fn line_start(tree: &Tree, line: u32) -> usize {
+    tree.line_to_byte(line).unwrap_or(tree.byte_count())
+}
+
+/// Byte offset of the end of a line's text, before its line break
+fn line_content_end(tree: &Tree, line: u32) -> usize {
+    line_start(tree, line) + tree.line_text_trimmed(line).len()
+}
+
+/// Byte range of the whole lines a range touches, without the last line's break
+/// A range ending at the start of a line doesn't touch that line
+fn line_span(tree: &Tree, range: Range<usize>) -> Range<usize> {
+    let first = tree.byte_to_line(range.start);
+    let mut last = tree.byte_to_line(range.end);
+    if last > first && range.end == line_start(tree, last) {
+        last -= 1;
+    }
+    line_start(tree, first)..line_content_end(tree, last)
+}
+
+/// Leading spaces and tabs of a line
+fn leading_whitespace(text: &str) -> &str {
+    &text[..text.len() - text.trim_start_matches([' ', '\t']).len()]
+}
+
+/// Byte length of the first `count` grapheme clusters of `text`
+fn grapheme_prefix_len(text: &str, count: usize) -> usize {
+    text.graphemes(true).take(count).map(str::len).sum()
+}
+
+/// Text typed by an insert event (`editor.insert_space` carries no data)
+fn typed_text(event: &Event) -> &str {
+    if event.name == "editor.insert_space" {
+        return " ";
+    }
+    event
+        .data
+        .get("char")
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+}
+
+/// Snippet variables describing the document's file
+fn file_variable(name: &str, path: &Path) -> Option<String> {
+    let text = |s: Option<&std::ffi::OsStr>| s.map(|s| s.to_string_lossy().into_owned());
+    match name {
+        "TM_FILENAME" => text(path.file_name()),
+        "TM_FILENAME_BASE" => text(path.file_stem()),
+        "TM_DIRECTORY" => text(path.parent().map(|p| p.as_os_str())),
+        "TM_FILEPATH" => Some(path.to_string_lossy().into_owned()),
+        _ => None,
+    }
+}
+
+/// Events that leave an active block selection in place
+fn keeps_block_selection(name: &str) -> bool {
+    name.starts_with("editor.extend_block_")
+        || matches!(
+            name,
+            "editor.copy" | "editor.cut" | "mouse.drag" | "mouse.release" | "visual.block_mode"
+        )
+}
+
+/// Block-extending equivalent of a cursor motion (visual block mode)
+fn block_motion(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "editor.move_left" => "editor.extend_block_left",
+        "editor.move_right" => "editor.extend_block_right",
+        "editor.move_up" => "editor.extend_block_up",
+        "editor.move_down" => "editor.extend_block_down",
+        _ => return None,
+    })
+}
+
+/// Visual column of a char column within a line
+fn visual_column(line_text: &str, char_column: u32, tab_stops: u32) -> u32 {
+    line_text
+        .chars()
+        .take(char_column as usize)
+        .fold(0, |column, ch| advance_column(column, ch, tab_stops))
+}
+
+/// Display row of a position within its soft wrapped line,
+/// and its visual column on that row (continuation rows start at the wrap indent)
+fn display_row_position(
+    tree: &Tree,
+    display_map: &DisplayMap,
+    pos: DocPos,
+    tab_stops: u32,
+) -> (u32, u32) {
+    let line_text = tree.line_text_trimmed(pos.line);
+    let visual = visual_column(&line_text, pos.column, tab_stops);
+    let Some(wraps) = display_map.line_wraps(pos.line) else {
+        return (0, visual);
+    };
+    let row = wraps.row_of(pos.column);
+    if row == 0 {
+        return (0, visual);
+    }
+    let row_start = visual_column(&line_text, wraps.breaks[row as usize - 1], tab_stops);
+    (row, visual - row_start + wraps.indent)
+}
+
+/// Position on a display row of a soft wrapped line nearest to a visual column on that row
+/// Continuation rows other than the last end before their last character
+fn position_on_display_row(
+    tree: &Tree,
+    display_map: &DisplayMap,
+    line: u32,
+    row: u32,
+    visual: u32,
+    tab_stops: u32,
+) -> DocPos {
+    let line_text = tree.line_text_trimmed(line);
+    let line_chars = line_text.chars().count() as u32;
+    let (columns, indent, last_row) = match display_map.line_wraps(line) {
+        Some(wraps) => (
+            wraps.row_columns(row, line_chars),
+            if row > 0 { wraps.indent } else { 0 },
+            row as usize == wraps.breaks.len(),
+        ),
+        None => (0..line_chars, 0, true),
+    };
+
+    let row_start = visual_column(&line_text, columns.start, tab_stops);
+    let target = row_start + visual.saturating_sub(indent);
+    let last = if last_row {
+        columns.end
+    } else {
+        columns.end.saturating_sub(1).max(columns.start)
+    };
+    let column = char_column_at(&line_text, target, tab_stops)
+        .unwrap_or(line_chars)
+        .clamp(columns.start, last);
+    DocPos {
+        line,
+        column,
+        byte_offset: 0,
+    }
+}
+
+/// Visual width of a whole line
+fn line_visual_width(line_text: &str, tab_stops: u32) -> u32 {
+    visual_column(line_text, u32::MAX, tab_stops)
+}
+
+/// Visual column nearest to a layout x position (not clamped to any line)
+fn visual_column_at_x(viewport: &Viewport, layout_x: f32) -> u32 {
+    (layout_x / viewport.metrics.space_width).round().max(0.0) as u32
+}
+
+/// Char column of the first character starting at or after a visual column
+/// None if the line ends before reaching it
+fn char_column_at(line_text: &str, visual: u32, tab_stops: u32) -> Option<u32> {
+    let mut column = 0;
+    let mut chars = 0;
+    for ch in line_text.chars() {
+        if column >= visual {
+            return Some(chars);
+        }
+        column = advance_column(column, ch, tab_stops);
+        chars += 1;
+    }
+    (column >= visual).then_some(chars)
+}
+
+/// Plain text ranges around a byte range for expand selection:
+/// word, line (without indentation), whole lines, paragraph and document
+fn text_object_ranges(tree: &Tree, range: Range<usize>) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    if let Some(word) = word_range_at(tree, doc_pos_at(tree, range.start)) {
+        ranges.push(word);
+    }
+
+    let first = tree.byte_to_line(range.start);
+    let last = tree.byte_to_line(range.end);
+    let start = line_start(tree, first);
+    let end = line_content_end(tree, last);
+    let indent = leading_whitespace(&tree.line_text_trimmed(first)).len();
+    ranges.push(start + indent..end);
+    ranges.push(start..end);
+
+    let is_blank = |line: u32| tree.line_text_trimmed(line).trim().is_empty();
+    let mut paragraph_first = first;
+    while paragraph_first > 0 && !is_blank(paragraph_first - 1) {
+        paragraph_first -= 1;
+    }
+    let mut paragraph_last = last;
+    while paragraph_last + 1 < tree.line_count() && !is_blank(paragraph_last + 1) {
+        paragraph_last += 1;
+    }
+    ranges.push(line_start(tree, paragraph_first)..line_content_end(tree, paragraph_last));
+
+    ranges.push(0..tree.byte_count());
+    ranges
+}
+
+/// Byte range of the identifier-like word under (or just before) a position
+fn word_range_at(tree: &Tree, pos: DocPos) -> Option<Range<usize>> {
+    let line_start = tree.line_to_byte(pos.line)?;
+    let line_text = tree.line_text(pos.line);
+    let chars: Vec<(usize, char)> = line_text.char_indices().collect();
+    let is_word = |i: usize| {
+        chars
+            .get(i)
+            .map_or(false, |&(_, c)| c.is_alphanumeric() || c == '_')
+    };
+
+    let column = pos.column as usize;
+    let index = if is_word(column) {
+        column
+    } else if column > 0 && is_word(column - 1) {
+        column - 1
+    } else {
+        return None;
+    };
+
+    let mut start = index;
+    while start > 0 && is_word(start - 1) {
+        start -= 1;
+    }
+    let mut end = index + 1;
+    while is_word(end) {
+        end += 1;
+    }
+
+    let byte_at = |i: usize| chars.get(i).map_or(line_text.len(), |&(b, _)| b);
+    Some(line_start + byte_at(start)..line_start + byte_at(end))
+}
+
 /// Handle input actions at the plugin level
 /// Returns true if the action was handled and requires a redraw
 /// Note: InputAction::Save should be handled by the caller since it needs EditorLogic
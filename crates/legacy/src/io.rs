@@ -2,21 +2,81 @@
 //!
 //! Load and save documents
 
+use crate::editorconfig::{self, Charset};
 use std::fs;
 use std::io;
 use std::path::Path;
 use tiny_core::tree::Doc;
 
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
 /// Load document from file
 /// Handles both text and binary files by replacing invalid UTF-8 with �
 /// Uses simdutf8 for fast validation
 pub fn load(path: &Path) -> io::Result<Doc> {
     let bytes = fs::read(path)?;
-    let content = match simdutf8::basic::from_utf8(&bytes) {
-        Ok(s) => s.to_string(),
-        Err(_) => String::from_utf8_lossy(&bytes).into_owned(),
+    let charset = editorconfig::Properties::for_file(path).charset;
+    Ok(Doc::from_str(&decode(&bytes, charset.unwrap_or_default())))
+}
+
+/// Decode file contents in the given charset (skipping its byte order mark)
+pub fn decode(bytes: &[u8], charset: Charset) -> String {
+    let utf16 = |bytes: &[u8], bom: [u8; 2], from: fn([u8; 2]) -> u16| {
+        let units: Vec<u16> = bytes
+            .strip_prefix(&bom)
+            .unwrap_or(bytes)
+            .chunks_exact(2)
+            .map(|pair| from([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
     };
-    Ok(Doc::from_str(&content))
+    match charset {
+        Charset::Utf8 => decode_utf8(bytes),
+        Charset::Utf8Bom => decode_utf8(bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes)),
+        Charset::Latin1 => bytes.iter().map(|&b| b as char).collect(),
+        Charset::Utf16Be => utf16(bytes, [0xFE, 0xFF], u16::from_be_bytes),
+        Charset::Utf16Le => utf16(bytes, [0xFF, 0xFE], u16::from_le_bytes),
+    }
+}
+
+fn decode_utf8(bytes: &[u8]) -> String {
+    match simdutf8::basic::from_utf8(bytes) {
+        Ok(s) => s.to_string(),
+        Err(_) => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// Encode text for writing (UTF-16 gets a byte order mark)
+/// Fails on the first character latin1 can't represent, rather than losing it
+pub fn encode(text: &str, charset: Charset) -> io::Result<Vec<u8>> {
+    Ok(match charset {
+        Charset::Utf8 => text.as_bytes().to_vec(),
+        Charset::Utf8Bom => [UTF8_BOM, text.as_bytes()].concat(),
+        Charset::Latin1 => {
+            let mut bytes = Vec::with_capacity(text.len());
+            for (offset, c) in text.char_indices() {
+                let byte = u8::try_from(c).map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "{c:?} (U+{:04X}) at byte {offset} can't be written as latin1",
+                            c as u32
+                        ),
+                    )
+                })?;
+                bytes.push(byte);
+            }
+            bytes
+        }
+        Charset::Utf16Be => [0xFE, 0xFF]
+            .into_iter()
+            .chain(text.encode_utf16().flat_map(u16::to_be_bytes))
+            .collect(),
+        Charset::Utf16Le => [0xFF, 0xFE]
+            .into_iter()
+            .chain(text.encode_utf16().flat_map(u16::to_le_bytes))
+            .collect(),
+    })
 }
 
 /// Save document to file
@@ -27,8 +87,28 @@ pub fn save(doc: &Doc, path: &Path) -> io::Result<()> {
 
 /// Auto-save to temporary file
 pub fn autosave(doc: &Doc, path: &Path) -> io::Result<()> {
+    autosave_encoded(doc, path, Charset::Utf8)
+}
+
+/// Auto-save to temporary file in the given encoding
+/// Nothing is written if the text can't be encoded
+pub fn autosave_encoded(doc: &Doc, path: &Path, charset: Charset) -> io::Result<()> {
     let tmp_path = path.with_extension("tmp");
-    save(doc, &tmp_path)?;
+    let content = doc.read().flatten_to_string();
+    fs::write(&tmp_path, encode(&content, charset)?)?;
     fs::rename(tmp_path, path)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latin1_refuses_characters_it_cant_represent() {
+        assert_eq!(encode("café", Charset::Latin1).unwrap(), b"caf\xE9");
+        let error = encode("5 €", Charset::Latin1).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("U+20AC"));
+    }
+}
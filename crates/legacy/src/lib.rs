@@ -9,29 +9,46 @@ pub mod gpu_ffi_host;
 pub mod accelerator;
 #[cfg(feature = "winit")]
 pub mod app;
+pub mod command_palette_plugin;
+pub mod comments;
+pub mod completion;
+pub mod completion_plugin;
 pub mod config;
+pub mod confusables;
 pub mod diagnostics_manager;
 pub mod editor_logic;
 pub mod event_data;
 pub mod file_picker_plugin;
 pub mod filterable_dropdown;
+pub mod find;
+pub mod find_bar_plugin;
+pub mod folds;
 pub mod overlay_picker;
 pub mod grep_plugin;
 pub mod history;
+pub mod ime;
+pub mod indentation;
 pub mod input;
 pub mod input_types;
 pub mod io;
 pub mod line_numbers_plugin;
 pub mod lsp_manager;
 pub mod lsp_service;
+pub mod marks;
 pub use diagnostics_plugin;
 pub mod editable_text_view;
+pub mod editorconfig;
 pub mod render;
 pub mod scrollbar_plugin;
 pub mod shortcuts;
+pub mod snippets;
+pub mod surround;
 pub mod tab_bar_plugin;
 pub mod tab_manager;
 pub mod text_editor_plugin;
+pub mod transforms;
+pub mod unicode_info;
+pub mod words;
 
 // Import UI components from tiny-ui
 pub use tiny_ui::{
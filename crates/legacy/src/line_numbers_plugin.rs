@@ -3,7 +3,10 @@
 use tiny_core::tree::{Doc, Rect};
 use tiny_font::SharedFontSystem;
 use tiny_sdk::LogicalPixels;
-use tiny_ui::{coordinates::Viewport, text_view::TextView};
+use tiny_ui::{
+    coordinates::{DisplayMap, Viewport},
+    text_view::TextView,
+};
 
 /// Wrapper to make Doc pointer Send + Sync
 #[derive(Copy, Clone, PartialEq, Eq)]
@@ -26,6 +29,10 @@ pub struct LineNumbersPlugin {
     text_view: TextView,
     /// Cache total lines to avoid regenerating text
     last_total_lines: Option<u32>,
+    /// Lines hidden by folds (no row in the gutter) or wrapped (blank rows after the number)
+    display_map: DisplayMap,
+    /// Foldable header lines and whether they're folded
+    fold_markers: Vec<(u32, bool)>,
 }
 
 impl LineNumbersPlugin {
@@ -44,6 +51,8 @@ impl LineNumbersPlugin {
                 .with_width(tiny_ui::text_view::SizeConstraint::HugContents)
                 .with_height(tiny_ui::text_view::SizeConstraint::FillContainer),
             last_total_lines: None,
+            display_map: DisplayMap::default(),
+            fold_markers: Vec::new(),
         }
     }
 
@@ -57,6 +66,15 @@ impl LineNumbersPlugin {
         }
     }
 
+    /// Set the folded and wrapped lines and the fold chevrons to show
+    pub fn set_display_map(&mut self, display_map: DisplayMap, fold_markers: Vec<(u32, bool)>) {
+        if self.display_map != display_map || self.fold_markers != fold_markers {
+            self.display_map = display_map;
+            self.fold_markers = fold_markers;
+            self.last_total_lines = None;
+        }
+    }
+
     /// Enable or disable line numbers
     pub fn set_enabled(&mut self, enabled: bool) {
         self.enabled = enabled;
@@ -97,10 +115,15 @@ impl LineNumbersPlugin {
             let max_line_num = total_lines;
             let max_width = max_line_num.to_string().len();
 
-            // Generate ALL line numbers with right-alignment padding
+            // Generate line numbers of all visible lines with right-alignment padding,
+            // followed by a chevron on foldable lines
             let mut line_numbers_text = String::new();
-            for line_num in 0..total_lines as usize {
-                if line_num > 0 {
+            let mut markers = self.fold_markers.iter().peekable();
+            for line_num in 0..total_lines {
+                if self.display_map.is_hidden(line_num) {
+                    continue;
+                }
+                if !line_numbers_text.is_empty() {
                     line_numbers_text.push('\n');
                 }
                 let num_str = (line_num + 1).to_string();
@@ -110,6 +133,18 @@ impl LineNumbersPlugin {
                     line_numbers_text.push(' ');
                 }
                 line_numbers_text.push_str(&num_str);
+
+                while markers.next_if(|(line, _)| *line < line_num).is_some() {}
+                line_numbers_text.push_str(match markers.peek() {
+                    Some((line, true)) if *line == line_num => " ▸",
+                    Some((line, false)) if *line == line_num => " ▾",
+                    _ => "  ",
+                });
+
+                // Continuation rows of a wrapped line have no number
+                for _ in 1..self.display_map.rows_in_line(line_num) {
+                    line_numbers_text.push('\n');
+                }
             }
 
             // Update TextView content
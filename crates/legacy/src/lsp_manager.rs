@@ -109,6 +109,10 @@ pub enum LspRequest {
         line: u32,
         character: u32,
     },
+    SelectionRange {
+        positions: Vec<(u32, u32)>,
+    },
+    FoldingRange,
     CodeAction {
         line: u32,
         character: u32,
@@ -152,6 +156,18 @@ pub struct GotoDefinitionUpdate {
     pub locations: Vec<Location>,
 }
 
+/// Selection ranges from LSP server (one per requested position, in order)
+#[derive(Debug, Clone)]
+pub struct SelectionRangeUpdate {
+    pub ranges: Vec<lsp_types::SelectionRange>,
+}
+
+/// Folding ranges for the whole document from LSP server
+#[derive(Debug, Clone)]
+pub struct FoldingRangeUpdate {
+    pub ranges: Vec<lsp_types::FoldingRange>,
+}
+
 /// Code actions available at a position
 #[derive(Debug, Clone)]
 pub struct CodeActionUpdate {
@@ -174,6 +190,8 @@ pub enum LspResponse {
     Hover(HoverUpdate),
     Symbols(SymbolsUpdate),
     GotoDefinition(GotoDefinitionUpdate),
+    SelectionRange(SelectionRangeUpdate),
+    FoldingRange(FoldingRangeUpdate),
     CodeAction(CodeActionUpdate),
     TextEdit(TextEditUpdate),
     Error(String),
@@ -342,6 +360,16 @@ impl LspManager {
         let _ = self.tx.send(LspRequest::GotoDefinition { line, character });
     }
 
+    /// Request selection ranges (expand selection) at positions (UTF-16 columns)
+    pub fn request_selection_ranges(&self, positions: Vec<(u32, u32)>) {
+        let _ = self.tx.send(LspRequest::SelectionRange { positions });
+    }
+
+    /// Request folding ranges for the current document
+    pub fn request_folding_ranges(&self) {
+        let _ = self.tx.send(LspRequest::FoldingRange);
+    }
+
     /// Request code actions at position
     pub fn request_code_action(&self, line: u32, character: u32) {
         let diagnostics = self.state.read().ok()
@@ -632,6 +660,8 @@ enum PendingRequest {
         line: u32,
         character: u32,
     },
+    SelectionRange,
+    FoldingRange,
     CodeAction {
         line: u32,
         character: u32,
@@ -1071,6 +1101,43 @@ fn run_lsp_client(
                             }
                         }
                     }
+                    LspRequest::SelectionRange { positions } => {
+                        if init_state.load(Ordering::SeqCst) == INIT_COMPLETE {
+                            if let Some(current_uri) = current_file.read().ok().and_then(|g| g.clone()) {
+                                // Positions are already in UTF-16 code units
+                                let positions = positions
+                                    .into_iter()
+                                    .map(|(line, character)| Position { line, character })
+                                    .collect();
+                                let request_id = send_selection_range(
+                                    &mut stdin,
+                                    &mut next_id,
+                                    &current_uri,
+                                    positions,
+                                )?;
+                                track_request(
+                                    &pending_requests,
+                                    request_id,
+                                    PendingRequest::SelectionRange,
+                                    0,
+                                );
+                            }
+                        }
+                    }
+                    LspRequest::FoldingRange => {
+                        if init_state.load(Ordering::SeqCst) == INIT_COMPLETE {
+                            if let Some(current_uri) = current_file.read().ok().and_then(|g| g.clone()) {
+                                let request_id =
+                                    send_folding_range(&mut stdin, &mut next_id, &current_uri)?;
+                                track_request(
+                                    &pending_requests,
+                                    request_id,
+                                    PendingRequest::FoldingRange,
+                                    0,
+                                );
+                            }
+                        }
+                    }
                     LspRequest::CodeAction {
                         line,
                         character,
@@ -1435,6 +1502,30 @@ fn handle_lsp_message(
                         }
                     }
                 }
+                Some(PendingRequest::SelectionRange) => {
+                    if let Some(result) = resp.result {
+                        if let Ok(ranges) =
+                            serde_json::from_value::<Vec<lsp_types::SelectionRange>>(result)
+                        {
+                            if !ranges.is_empty() {
+                                let _ = response_tx.send(LspResponse::SelectionRange(
+                                    SelectionRangeUpdate { ranges },
+                                ));
+                            }
+                        }
+                    }
+                }
+                Some(PendingRequest::FoldingRange) => {
+                    if let Some(result) = resp.result {
+                        if let Ok(ranges) =
+                            serde_json::from_value::<Vec<lsp_types::FoldingRange>>(result)
+                        {
+                            let _ = response_tx.send(LspResponse::FoldingRange(
+                                FoldingRangeUpdate { ranges },
+                            ));
+                        }
+                    }
+                }
                 Some(PendingRequest::CodeAction { .. }) => {
                     if let Some(result) = resp.result {
                         if let Ok(code_actions) =
@@ -1552,6 +1643,14 @@ fn send_initialize<W: std::io::Write>(
             will_save_wait_until: Some(false),
             did_save: Some(false),
         }),
+        selection_range: Some(lsp_types::SelectionRangeClientCapabilities {
+            dynamic_registration: Some(false),
+        }),
+        folding_range: Some(lsp_types::FoldingRangeClientCapabilities {
+            dynamic_registration: Some(false),
+            line_folding_only: Some(true),
+            ..Default::default()
+        }),
         ..Default::default()
     });
 
@@ -1778,6 +1877,52 @@ fn send_goto_definition<W: std::io::Write>(
     Ok(request_id)
 }
 
+fn send_selection_range<W: std::io::Write>(
+    writer: &mut W,
+    next_id: &mut u64,
+    uri: &Uri,
+    positions: Vec<Position>,
+) -> Result<u64, Box<dyn std::error::Error>> {
+    let request_id = *next_id;
+    *next_id += 1;
+
+    let msg = lsp_server::Message::Request(lsp_server::Request {
+        id: lsp_server::RequestId::from(request_id as i32),
+        method: "textDocument/selectionRange".to_string(),
+        params: serde_json::to_value(lsp_types::SelectionRangeParams {
+            text_document: lsp_types::TextDocumentIdentifier { uri: uri.clone() },
+            positions,
+            work_done_progress_params: lsp_types::WorkDoneProgressParams::default(),
+            partial_result_params: lsp_types::PartialResultParams::default(),
+        })?,
+    });
+
+    msg.write(writer)?;
+    Ok(request_id)
+}
+
+fn send_folding_range<W: std::io::Write>(
+    writer: &mut W,
+    next_id: &mut u64,
+    uri: &Uri,
+) -> Result<u64, Box<dyn std::error::Error>> {
+    let request_id = *next_id;
+    *next_id += 1;
+
+    let msg = lsp_server::Message::Request(lsp_server::Request {
+        id: lsp_server::RequestId::from(request_id as i32),
+        method: "textDocument/foldingRange".to_string(),
+        params: serde_json::to_value(lsp_types::FoldingRangeParams {
+            text_document: lsp_types::TextDocumentIdentifier { uri: uri.clone() },
+            work_done_progress_params: lsp_types::WorkDoneProgressParams::default(),
+            partial_result_params: lsp_types::PartialResultParams::default(),
+        })?,
+    });
+
+    msg.write(writer)?;
+    Ok(request_id)
+}
+
 fn send_code_action<W: std::io::Write>(
     writer: &mut W,
     next_id: &mut u64,
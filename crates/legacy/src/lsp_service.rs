@@ -45,12 +45,19 @@ pub struct TextEdit {
     pub new_text: String,
 }
 
+/// Nested ranges around a position (start, end), innermost first
+pub type SelectionRangeChain = Vec<(DocPosition, DocPosition)>;
+
 /// LSP feature results
 #[derive(Debug, Clone)]
 pub enum LspResult {
     Diagnostics(Vec<ParsedDiagnostic>),
     Hover(Option<HoverInfo>),
     GoToDefinition(Vec<LocationRef>),
+    /// Ranges around each requested position, in request order
+    SelectionRanges(Vec<SelectionRangeChain>),
+    /// Foldable line ranges (first line, last line), both inclusive
+    FoldingRanges(Vec<(usize, usize)>),
     FindReferences(Vec<LocationRef>),
     DocumentSymbols(Vec<lsp_types::DocumentSymbol>),
     CodeActions(Vec<CodeAction>),
@@ -186,6 +193,33 @@ impl LspService {
                             .collect();
                         results.push(LspResult::GoToDefinition(location_refs));
                     }
+                    crate::lsp_manager::LspResponse::SelectionRange(selection_range_update) => {
+                        let chains = selection_range_update
+                            .ranges
+                            .into_iter()
+                            .map(|range| {
+                                let mut chain = Vec::new();
+                                let mut current = Some(Box::new(range));
+                                while let Some(selection_range) = current {
+                                    chain.push((
+                                        DocPosition::from(selection_range.range.start),
+                                        DocPosition::from(selection_range.range.end),
+                                    ));
+                                    current = selection_range.parent;
+                                }
+                                chain
+                            })
+                            .collect();
+                        results.push(LspResult::SelectionRanges(chains));
+                    }
+                    crate::lsp_manager::LspResponse::FoldingRange(folding_range_update) => {
+                        let ranges = folding_range_update
+                            .ranges
+                            .into_iter()
+                            .map(|range| (range.start_line as usize, range.end_line as usize))
+                            .collect();
+                        results.push(LspResult::FoldingRanges(ranges));
+                    }
                     crate::lsp_manager::LspResponse::CodeAction(code_action_update) => {
                         let actions: Vec<CodeAction> = code_action_update
                             .actions
@@ -262,6 +296,25 @@ impl LspService {
         }
     }
 
+    /// Request folding ranges for the whole document
+    pub fn request_folding_ranges(&self) {
+        if let Some(ref lsp) = self.lsp_manager {
+            lsp.request_folding_ranges();
+        }
+    }
+
+    /// Request selection ranges (for expand selection) at positions
+    pub fn request_selection_ranges(&self, positions: &[DocPosition]) {
+        if let Some(ref lsp) = self.lsp_manager {
+            lsp.request_selection_ranges(
+                positions
+                    .iter()
+                    .map(|pos| (pos.line as u32, pos.column as u32))
+                    .collect(),
+            );
+        }
+    }
+
     /// Request document symbols
     pub fn request_document_symbols(&self) {
         if let Some(ref lsp) = self.lsp_manager {
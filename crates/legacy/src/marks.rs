@@ -0,0 +1,329 @@
+//! Marks and change list - named and recent positions that survive edits
+//!
+//! Positions are stored as byte offsets and shifted through every edit the
+//! InputHandler applies, so a mark keeps pointing at the same text.
+
+use ahash::AHashMap as HashMap;
+use std::path::PathBuf;
+use tiny_core::tree::{Content, Edit};
+use tiny_sdk::DocPos;
+
+/// Maximum number of entries kept in the change list
+const CHANGE_LIST_MAX: usize = 100;
+
+/// Edits closer than this (in bytes) to the newest change replace it instead of adding an entry
+const CHANGE_MERGE_DISTANCE: usize = 64;
+
+/// Pending mark command waiting for the mark name (vim `m`, `'` and `` ` ``)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkCommand {
+    /// Set a mark at the primary cursor
+    Set,
+    /// Jump to a mark - `exact` keeps the column, otherwise first non-blank of the line
+    Goto { exact: bool },
+}
+
+/// Valid mark names are ASCII letters
+pub fn is_valid_mark(name: char) -> bool {
+    name.is_ascii_alphabetic()
+}
+
+/// Uppercase marks are global (file-spanning), lowercase marks are buffer-local
+pub fn is_global_mark(name: char) -> bool {
+    name.is_ascii_uppercase()
+}
+
+/// Uppercase mark, resolved in the buffer of its file whichever tab is active
+#[derive(Debug, Clone)]
+pub struct GlobalMark {
+    pub path: PathBuf,
+    /// Position when set, used once the file's tab is closed
+    pub position: DocPos,
+}
+
+fn content_len(content: &Content) -> usize {
+    match content {
+        Content::Text(s) => s.len(),
+        Content::Spatial(_) => 0,
+    }
+}
+
+/// Shift a byte offset through an edit
+/// Offsets inside deleted/replaced text collapse to the start of the edit
+pub fn adjust_offset(offset: usize, edit: &Edit) -> usize {
+    match edit {
+        Edit::Insert { pos, content } => {
+            if offset >= *pos {
+                offset + content_len(content)
+            } else {
+                offset
+            }
+        }
+        Edit::Delete { range } => {
+            if offset >= range.end {
+                offset - range.len()
+            } else if offset > range.start {
+                range.start
+            } else {
+                offset
+            }
+        }
+        Edit::Replace { range, content } => {
+            if offset >= range.end {
+                offset - range.len() + content_len(content)
+            } else if offset > range.start {
+                range.start
+            } else {
+                offset
+            }
+        }
+    }
+}
+
+/// Single edit turning `before` into `after` (the span between their common prefix and suffix)
+/// Used where the document is swapped wholesale (undo/redo) instead of edited
+pub fn diff_edit(before: &str, after: &str) -> Option<Edit> {
+    if before == after {
+        return None;
+    }
+    let mut prefix = before
+        .bytes()
+        .zip(after.bytes())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !before.is_char_boundary(prefix) || !after.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+    let max_suffix = before.len().min(after.len()) - prefix;
+    let mut suffix = before
+        .bytes()
+        .rev()
+        .zip(after.bytes().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !before.is_char_boundary(before.len() - suffix)
+        || !after.is_char_boundary(after.len() - suffix)
+    {
+        suffix -= 1;
+    }
+
+    let range = prefix..before.len() - suffix;
+    let inserted = &after[prefix..after.len() - suffix];
+    Some(match (range.is_empty(), inserted.is_empty()) {
+        (true, _) => Edit::Insert {
+            pos: prefix,
+            content: Content::Text(inserted.to_string()),
+        },
+        (false, true) => Edit::Delete { range },
+        (false, false) => Edit::Replace {
+            range,
+            content: Content::Text(inserted.to_string()),
+        },
+    })
+}
+
+/// Start offset of an edit (where the change happened)
+pub fn edit_start(edit: &Edit) -> usize {
+    match edit {
+        Edit::Insert { pos, .. } => *pos,
+        Edit::Delete { range } | Edit::Replace { range, .. } => range.start,
+    }
+}
+
+/// Named positions within a single buffer
+#[derive(Default, Clone)]
+pub struct MarkSet {
+    marks: HashMap<char, usize>,
+}
+
+impl MarkSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set (or move) a mark
+    pub fn set(&mut self, name: char, offset: usize) {
+        self.marks.insert(name, offset);
+    }
+
+    /// Get a mark's current byte offset
+    pub fn get(&self, name: char) -> Option<usize> {
+        self.marks.get(&name).copied()
+    }
+
+    /// Remove a mark
+    pub fn remove(&mut self, name: char) -> Option<usize> {
+        self.marks.remove(&name)
+    }
+
+    /// Shift all marks through an edit
+    pub fn adjust(&mut self, edit: &Edit) {
+        for offset in self.marks.values_mut() {
+            *offset = adjust_offset(*offset, edit);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.marks.clear();
+    }
+}
+
+/// Recent edit locations for `g;` / `g,` navigation (oldest first)
+#[derive(Clone)]
+pub struct ChangeList {
+    entries: Vec<usize>,
+    /// Position while navigating (None = not navigating, next `older` starts at newest)
+    index: Option<usize>,
+}
+
+impl ChangeList {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            index: None,
+        }
+    }
+
+    /// Record a change location, merging with the newest entry if it's close by
+    pub fn record(&mut self, offset: usize) {
+        self.index = None;
+
+        if let Some(last) = self.entries.last_mut() {
+            if last.abs_diff(offset) <= CHANGE_MERGE_DISTANCE {
+                *last = offset;
+                return;
+            }
+        }
+
+        self.entries.push(offset);
+        if self.entries.len() > CHANGE_LIST_MAX {
+            self.entries.remove(0);
+        }
+    }
+
+    /// Shift all entries through an edit
+    pub fn adjust(&mut self, edit: &Edit) {
+        for offset in &mut self.entries {
+            *offset = adjust_offset(*offset, edit);
+        }
+    }
+
+    /// Step to an older change
+    pub fn older(&mut self) -> Option<usize> {
+        let next = match self.index {
+            None => self.entries.len().checked_sub(1)?,
+            Some(i) => i.checked_sub(1)?,
+        };
+        self.index = Some(next);
+        self.entries.get(next).copied()
+    }
+
+    /// Step to a newer change
+    pub fn newer(&mut self) -> Option<usize> {
+        let next = self.index? + 1;
+        if next >= self.entries.len() {
+            return None;
+        }
+        self.index = Some(next);
+        self.entries.get(next).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl Default for ChangeList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(pos: usize, text: &str) -> Edit {
+        Edit::Insert {
+            pos,
+            content: Content::Text(text.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_marks_survive_edits() {
+        let mut marks = MarkSet::new();
+        marks.set('a', 10);
+        marks.set('b', 2);
+
+        marks.adjust(&insert(5, "hello"));
+        assert_eq!(marks.get('a'), Some(15));
+        assert_eq!(marks.get('b'), Some(2));
+
+        marks.adjust(&Edit::Delete { range: 0..4 });
+        assert_eq!(marks.get('a'), Some(11));
+        assert_eq!(marks.get('b'), Some(0));
+    }
+
+    #[test]
+    fn test_mark_inside_replaced_text_collapses() {
+        let mut marks = MarkSet::new();
+        marks.set('a', 6);
+        marks.adjust(&Edit::Replace {
+            range: 4..8,
+            content: Content::Text("xy".to_string()),
+        });
+        assert_eq!(marks.get('a'), Some(4));
+    }
+
+    #[test]
+    fn test_diff_edit_spans_the_changed_text() {
+        assert!(diff_edit("same", "same").is_none());
+        assert!(matches!(
+            diff_edit("hello world", "hello brave world"),
+            Some(Edit::Insert { pos: 6, .. })
+        ));
+        assert!(matches!(
+            diff_edit("hello brave world", "hello world"),
+            Some(Edit::Delete { range }) if range == (6..12)
+        ));
+
+        // Never splits a multi-byte character
+        let Some(Edit::Replace { range, content }) = diff_edit("aé", "aè") else {
+            panic!("expected a replace");
+        };
+        assert_eq!(range, 1..3);
+        assert!(matches!(content, Content::Text(ref s) if s == "è"));
+
+        // Marks after an undone insert move back
+        let mut marks = MarkSet::new();
+        marks.set('a', 12);
+        marks.adjust(&diff_edit("hello brave world", "hello world").unwrap());
+        assert_eq!(marks.get('a'), Some(6));
+    }
+
+    #[test]
+    fn test_change_list_navigation() {
+        let mut changes = ChangeList::new();
+        changes.record(0);
+        changes.record(10); // Merged with 0 (close by)
+        changes.record(500);
+        changes.record(1000);
+        assert_eq!(changes.len(), 3);
+
+        assert_eq!(changes.older(), Some(1000));
+        assert_eq!(changes.older(), Some(500));
+        assert_eq!(changes.older(), Some(10));
+        assert_eq!(changes.older(), None);
+        assert_eq!(changes.newer(), Some(500));
+
+        // Recording resets navigation to the newest entry
+        changes.record(2000);
+        assert_eq!(changes.older(), Some(2000));
+    }
+}
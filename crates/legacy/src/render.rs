@@ -22,7 +22,21 @@ use tiny_sdk::{PaintContext, Paintable};
 const FILE_EXPLORER_WIDTH: f32 = 0.0;
 const STATUS_BAR_HEIGHT: f32 = 0.0;
 const TAB_BAR_HEIGHT: f32 = 30.0;
+/// Token placeholder glyphs are drawn with (comment color: they aren't part of the text)
+const PLACEHOLDER_TOKEN: u8 = 6;
 const FOO: f32 = 0.0;
+/// Fill behind IME preedit text, hiding the document text it is drawn over
+const PREEDIT_BACKGROUND: u32 = 0x232629FF;
+
+/// IME preedit text drawn over the document, not part of it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Preedit {
+    pub text: String,
+    /// Where a copy of the text is drawn (one per selection)
+    pub positions: Vec<tiny_sdk::DocPos>,
+    /// Underlines over byte ranges of `text` (only `EffectType::Underline` is drawn)
+    pub underlines: Vec<crate::text_effects::TextEffect>,
+}
 
 // Plugin state synchronization
 #[derive(Clone, Debug)]
@@ -122,6 +136,9 @@ pub struct Renderer {
     pub tab_bar_plugin: Option<*mut crate::tab_bar_plugin::TabBarPlugin>,
     pub file_picker_plugin: Option<*mut crate::file_picker_plugin::FilePickerPlugin>,
     pub grep_plugin: Option<*mut crate::grep_plugin::GrepPlugin>,
+    pub command_palette_plugin: Option<*mut crate::command_palette_plugin::CommandPalettePlugin>,
+    pub completion_plugin: Option<*mut crate::completion_plugin::CompletionPlugin>,
+    pub find_bar_plugin: Option<*mut crate::find_bar_plugin::FindBarPlugin>,
     /// Title bar height (logical pixels, for macOS transparent titlebar)
     title_bar_height: f32,
     /// Tab bar height (logical pixels, calculated dynamically based on font size)
@@ -132,6 +149,18 @@ pub struct Renderer {
     accumulated_glyphs: Vec<GlyphInstance>,
     /// Text decoration rectangles (underline, strikethrough)
     text_decoration_rects: Vec<tiny_sdk::types::RectInstance>,
+    /// IME preedit shown over the document
+    preedit: Option<Preedit>,
+    /// Preedit backgrounds and underlines, drawn over the main text
+    preedit_rects: Vec<tiny_sdk::types::RectInstance>,
+    /// Preedit glyphs, drawn over the preedit backgrounds
+    preedit_glyphs: Vec<GlyphInstance>,
+    /// Backgrounds behind document byte ranges (e.g. suspicious characters)
+    background_effects: Vec<crate::text_effects::TextEffect>,
+    /// Glyphs drawn over invisible characters, by document byte offset
+    placeholder_glyphs: Vec<(usize, char)>,
+    /// Where dragged text would be dropped (drawn as a caret)
+    drop_caret: Option<tiny_sdk::DocPos>,
     /// Line number glyphs (rendered separately)
     line_number_glyphs: Vec<GlyphInstance>,
     /// Tab bar glyphs (rendered separately)
@@ -150,6 +179,24 @@ pub struct Renderer {
     grep_rects: Vec<tiny_sdk::types::RectInstance>,
     /// Grep rounded rect frame
     grep_rounded_rect: Option<tiny_sdk::types::RoundedRectInstance>,
+    /// Command palette glyphs with their scissor rects
+    command_palette_glyphs: Vec<(Vec<GlyphInstance>, (u32, u32, u32, u32))>,
+    /// Command palette background rectangle
+    command_palette_rects: Vec<tiny_sdk::types::RectInstance>,
+    /// Command palette rounded rect frame
+    command_palette_rounded_rect: Option<tiny_sdk::types::RoundedRectInstance>,
+    /// Completion popup glyphs with their scissor rects
+    completion_glyphs: Vec<(Vec<GlyphInstance>, (u32, u32, u32, u32))>,
+    /// Completion popup selection highlight
+    completion_rects: Vec<tiny_sdk::types::RectInstance>,
+    /// Completion popup rounded rect frame
+    completion_rounded_rect: Option<tiny_sdk::types::RoundedRectInstance>,
+    /// Find bar glyphs with their scissor rects
+    find_bar_glyphs: Vec<(Vec<GlyphInstance>, (u32, u32, u32, u32))>,
+    /// Find bar input backgrounds and active toggles
+    find_bar_rects: Vec<tiny_sdk::types::RectInstance>,
+    /// Find bar rounded rect frame
+    find_bar_rounded_rect: Option<tiny_sdk::types::RoundedRectInstance>,
     /// Scrollbar plugin for main editor
     pub scrollbar_plugin: crate::scrollbar_plugin::ScrollbarPlugin,
     /// Scrollbar rounded rects
@@ -165,6 +212,9 @@ pub struct Renderer {
     /// Track visibility changes to auto-set ui_dirty (prevents stale rendering)
     last_file_picker_visible: bool,
     last_grep_visible: bool,
+    last_command_palette_visible: bool,
+    last_completion_visible: bool,
+    last_find_bar_visible: bool,
 }
 
 unsafe impl Send for Renderer {}
@@ -198,12 +248,21 @@ impl Renderer {
             tab_bar_plugin: None,
             file_picker_plugin: None,
             grep_plugin: None,
+            command_palette_plugin: None,
+            completion_plugin: None,
+            find_bar_plugin: None,
             title_bar_height,
             tab_bar_height: TAB_BAR_HEIGHT, // Will be updated dynamically
             // Default editor bounds - updated in update_viewport
             editor_bounds: tiny_sdk::types::LayoutRect::new(0.0, 0.0, 800.0, 600.0),
             accumulated_glyphs: Vec::new(),
             text_decoration_rects: Vec::new(),
+            preedit: None,
+            preedit_rects: Vec::new(),
+            preedit_glyphs: Vec::new(),
+            background_effects: Vec::new(),
+            placeholder_glyphs: Vec::new(),
+            drop_caret: None,
             line_number_glyphs: Vec::new(),
             tab_bar_glyphs: Vec::new(),
             tab_bar_rects: Vec::new(),
@@ -213,6 +272,15 @@ impl Renderer {
             grep_glyphs: Vec::new(), // Vec of (glyphs, scissor_rect) tuples
             grep_rects: Vec::new(),
             grep_rounded_rect: None,
+            command_palette_glyphs: Vec::new(), // Vec of (glyphs, scissor_rect) tuples
+            command_palette_rects: Vec::new(),
+            command_palette_rounded_rect: None,
+            completion_glyphs: Vec::new(),
+            completion_rects: Vec::new(),
+            completion_rounded_rect: None,
+            find_bar_glyphs: Vec::new(),
+            find_bar_rects: Vec::new(),
+            find_bar_rounded_rect: None,
             scrollbar_plugin: crate::scrollbar_plugin::ScrollbarPlugin::new(),
             scrollbar_rects: Vec::new(),
             glyphs_dirty: true,
@@ -222,6 +290,9 @@ impl Renderer {
             last_viewport_size: (0.0, 0.0),
             last_file_picker_visible: false,
             last_grep_visible: false,
+            last_command_palette_visible: false,
+            last_completion_visible: false,
+            last_find_bar_visible: false,
         }
     }
 
@@ -268,6 +339,47 @@ impl Renderer {
         }
     }
 
+    pub fn set_tab_stops(&mut self, tab_stops: u32) {
+        self.viewport.metrics.tab_stops = tab_stops.max(1);
+        self.layout_dirty = true;
+        self.glyphs_dirty = true;
+        self.ui_dirty = true;
+    }
+
+    /// Show (or hide) IME preedit text over the document
+    pub fn set_preedit(&mut self, preedit: Option<Preedit>) {
+        if self.preedit != preedit {
+            self.preedit = preedit;
+            self.glyphs_dirty = true;
+        }
+    }
+
+    /// Fill behind byte ranges of the document (only `EffectType::Background` is drawn)
+    /// Higher priority effects are drawn over lower ones
+    pub fn set_background_effects(&mut self, mut effects: Vec<crate::text_effects::TextEffect>) {
+        effects.sort_by_key(|effect| effect.range.start);
+        if self.background_effects != effects {
+            self.background_effects = effects;
+            self.glyphs_dirty = true;
+        }
+    }
+
+    /// Draw a glyph over the character at each byte offset (e.g. for zero-width characters)
+    pub fn set_placeholder_glyphs(&mut self, placeholders: Vec<(usize, char)>) {
+        if self.placeholder_glyphs != placeholders {
+            self.placeholder_glyphs = placeholders;
+            self.glyphs_dirty = true;
+        }
+    }
+
+    /// Show (or hide) the caret previewing where dragged text will drop
+    pub fn set_drop_caret(&mut self, pos: Option<tiny_sdk::DocPos>) {
+        if self.drop_caret != pos {
+            self.drop_caret = pos;
+            self.glyphs_dirty = true;
+        }
+    }
+
     pub fn set_gpu_renderer(&mut self, gpu_renderer: &GpuRenderer) {
         if self.gpu_renderer.is_none() {
             self.gpu_renderer = Some(gpu_renderer as *const _);
@@ -632,6 +744,24 @@ impl Renderer {
         self.grep_plugin = Some(plugin as *mut _);
     }
 
+    pub fn set_command_palette_plugin(
+        &mut self,
+        plugin: &mut crate::command_palette_plugin::CommandPalettePlugin,
+    ) {
+        self.command_palette_plugin = Some(plugin as *mut _);
+    }
+
+    pub fn set_completion_plugin(
+        &mut self,
+        plugin: &mut crate::completion_plugin::CompletionPlugin,
+    ) {
+        self.completion_plugin = Some(plugin as *mut _);
+    }
+
+    pub fn set_find_bar_plugin(&mut self, plugin: &mut crate::find_bar_plugin::FindBarPlugin) {
+        self.find_bar_plugin = Some(plugin as *mut _);
+    }
+
     /// Mark UI as dirty (call when tabs change, file picker opens, etc.)
     pub fn mark_ui_dirty(&mut self) {
         self.ui_dirty = true;
@@ -682,6 +812,16 @@ impl Renderer {
         self.cached_doc_version = 0;
     }
 
+    /// Hide folded lines of the active document (relayouts when the folds change)
+    pub fn set_display_map(&mut self, display_map: crate::coordinates::DisplayMap) {
+        if self.viewport.display_map != display_map {
+            self.viewport.display_map = display_map;
+            self.layout_dirty = true;
+            self.glyphs_dirty = true;
+            self.line_numbers_dirty = true;
+        }
+    }
+
     pub fn clear_edit_deltas(&mut self) {
         self.text_renderer.syntax_state.edit_deltas.clear();
     }
@@ -820,6 +960,12 @@ impl Renderer {
             self.collect_file_picker_glyphs();
             self.grep_glyphs.clear();
             self.collect_grep_glyphs();
+            self.command_palette_glyphs.clear();
+            self.collect_command_palette_glyphs();
+            self.completion_glyphs.clear();
+            self.collect_completion_glyphs();
+            self.find_bar_glyphs.clear();
+            self.collect_find_bar_glyphs();
             self.ui_dirty = false;
         }
 
@@ -846,6 +992,18 @@ impl Renderer {
             .grep_plugin
             .map(|ptr| unsafe { (*ptr).visible })
             .unwrap_or(false);
+        let command_palette_visible = self
+            .command_palette_plugin
+            .map(|ptr| unsafe { (*ptr).visible })
+            .unwrap_or(false);
+        let completion_visible = self
+            .completion_plugin
+            .map(|ptr| unsafe { (*ptr).visible })
+            .unwrap_or(false);
+        let find_bar_visible = self
+            .find_bar_plugin
+            .map(|ptr| unsafe { (*ptr).visible })
+            .unwrap_or(false);
 
         // When file picker becomes hidden, immediately clear all its render data
         if self.last_file_picker_visible && !file_picker_visible {
@@ -861,12 +1019,39 @@ impl Renderer {
             self.grep_rounded_rect = None;
         }
 
+        // When the command palette becomes hidden, immediately clear all its render data
+        if self.last_command_palette_visible && !command_palette_visible {
+            self.command_palette_glyphs.clear();
+            self.command_palette_rects.clear();
+            self.command_palette_rounded_rect = None;
+        }
+
+        // When the completion popup becomes hidden, immediately clear all its render data
+        if self.last_completion_visible && !completion_visible {
+            self.completion_glyphs.clear();
+            self.completion_rects.clear();
+            self.completion_rounded_rect = None;
+        }
+
+        // When the find bar becomes hidden, immediately clear all its render data
+        if self.last_find_bar_visible && !find_bar_visible {
+            self.find_bar_glyphs.clear();
+            self.find_bar_rects.clear();
+            self.find_bar_rounded_rect = None;
+        }
+
         if file_picker_visible != self.last_file_picker_visible
             || grep_visible != self.last_grep_visible
+            || command_palette_visible != self.last_command_palette_visible
+            || completion_visible != self.last_completion_visible
+            || find_bar_visible != self.last_find_bar_visible
         {
             self.ui_dirty = true;
             self.last_file_picker_visible = file_picker_visible;
             self.last_grep_visible = grep_visible;
+            self.last_command_palette_visible = command_palette_visible;
+            self.last_completion_visible = completion_visible;
+            self.last_find_bar_visible = find_bar_visible;
         }
 
         let visible_range = self.viewport.visible_byte_range_with_tree(tree);
@@ -952,6 +1137,29 @@ impl Renderer {
                 })));
             }
 
+            // IME preedit over the main text (z_index = 1)
+            if !self.preedit_rects.is_empty() {
+                let gpu_ptr = self.gpu_renderer;
+                let rects = self.preedit_rects.clone();
+                let glyphs = self.preedit_glyphs.clone();
+                let scale = self.viewport.scale_factor;
+                paint_ops.push((1, Box::new(move |pass| {
+                    if let Some(gpu_ptr) = gpu_ptr {
+                        let gpu_mut = unsafe { &mut *(gpu_ptr as *mut GpuRenderer) };
+                        gpu_mut.draw_rects(pass, &rects, scale);
+                        gpu_mut.draw_glyphs(
+                            pass,
+                            &glyphs,
+                            tiny_core::gpu::DrawConfig {
+                                buffer_name: "preedit",
+                                use_themed: true,
+                                scissor: None,
+                            },
+                        );
+                    }
+                })));
+            }
+
             // Collect ALL plugins from active tab (generic - works with any plugin structure)
             if let Some(tab_mgr) = tab_manager {
                 if let Some(tab) = tab_mgr.active_tab() {
@@ -1078,6 +1286,94 @@ impl Renderer {
                 }
             }
 
+            // === DRAW FIND BAR (docked over the editor, under the popups) ===
+            if let Some(rounded_rect) = self.find_bar_rounded_rect {
+                pass.set_scissor_rect(0, 0, target_w, target_h);
+                if let Some(gpu) = self.gpu_renderer {
+                    unsafe {
+                        let gpu_renderer = &mut *(gpu as *mut GpuRenderer);
+                        gpu_renderer.draw_rounded_rects(pass, &[rounded_rect], scale);
+                    }
+                }
+            }
+            if !self.find_bar_rects.is_empty() {
+                pass.set_scissor_rect(0, 0, target_w, target_h);
+                if let Some(gpu) = self.gpu_renderer {
+                    unsafe {
+                        let gpu_renderer = &mut *(gpu as *mut GpuRenderer);
+                        gpu_renderer.draw_rects(pass, &self.find_bar_rects, scale);
+                    }
+                }
+            }
+            if !self.find_bar_glyphs.is_empty() {
+                if let Some(gpu) = self.gpu_renderer {
+                    unsafe {
+                        let gpu_renderer = &mut *(gpu as *mut GpuRenderer);
+                        gpu_renderer.draw_glyphs_batched(
+                            pass,
+                            &self.find_bar_glyphs,
+                            "find_bar",
+                            true,
+                        );
+                    }
+                }
+            }
+            // Paint the cursor/selection plugins of the focused find bar field
+            if let Some(plugin_ptr) = self.find_bar_plugin {
+                let plugin = unsafe { &mut *plugin_ptr };
+                let visible = plugin.visible;
+                let fields = [(&mut plugin.query, 103), (&mut plugin.replacement, 104)];
+                for (input, widget_id) in fields {
+                    if !visible || !input.is_focused() {
+                        continue;
+                    }
+                    // Sync plugin state right before painting
+                    input.sync_plugins();
+
+                    let input_viewport = tiny_sdk::types::WidgetViewport {
+                        bounds: input.view.viewport.bounds,
+                        scroll: tiny_sdk::LayoutPos::new(0.0, 0.0), // Scroll already applied in view coords
+                        content_margin: tiny_sdk::types::LayoutPos::new(0.0, 0.0),
+                        widget_id,
+                    };
+                    pass.set_scissor_rect(0, 0, target_w, target_h);
+                    self.paint_editable_view_plugins(input, input_viewport, pass);
+                }
+            }
+
+            // === DRAW COMPLETION POPUP (over the editor, under the pickers) ===
+            if let Some(rounded_rect) = self.completion_rounded_rect {
+                pass.set_scissor_rect(0, 0, target_w, target_h);
+                if let Some(gpu) = self.gpu_renderer {
+                    unsafe {
+                        let gpu_renderer = &mut *(gpu as *mut GpuRenderer);
+                        gpu_renderer.draw_rounded_rects(pass, &[rounded_rect], scale);
+                    }
+                }
+            }
+            if !self.completion_rects.is_empty() {
+                pass.set_scissor_rect(0, 0, target_w, target_h);
+                if let Some(gpu) = self.gpu_renderer {
+                    unsafe {
+                        let gpu_renderer = &mut *(gpu as *mut GpuRenderer);
+                        gpu_renderer.draw_rects(pass, &self.completion_rects, scale);
+                    }
+                }
+            }
+            if !self.completion_glyphs.is_empty() {
+                if let Some(gpu) = self.gpu_renderer {
+                    unsafe {
+                        let gpu_renderer = &mut *(gpu as *mut GpuRenderer);
+                        gpu_renderer.draw_glyphs_batched(
+                            pass,
+                            &self.completion_glyphs,
+                            "completion",
+                            true,
+                        );
+                    }
+                }
+            }
+
             // === DRAW FILE PICKER OVERLAY (on top of everything) ===
             // Render rounded frame with border first
             if let Some(rounded_rect) = self.file_picker_rounded_rect {
@@ -1188,6 +1484,64 @@ impl Renderer {
                     );
                 }
             }
+
+            // === DRAW COMMAND PALETTE OVERLAY (on top of everything) ===
+            // Render rounded frame with border first
+            if let Some(rounded_rect) = self.command_palette_rounded_rect {
+                pass.set_scissor_rect(0, 0, target_w, target_h);
+                if let Some(gpu) = self.gpu_renderer {
+                    unsafe {
+                        let gpu_renderer = &mut *(gpu as *mut GpuRenderer);
+                        gpu_renderer.draw_rounded_rects(pass, &[rounded_rect], scale);
+                    }
+                }
+            }
+            // Render background rects (input/results backgrounds)
+            if !self.command_palette_rects.is_empty() {
+                pass.set_scissor_rect(0, 0, target_w, target_h);
+                if let Some(gpu) = self.gpu_renderer {
+                    unsafe {
+                        let gpu_renderer = &mut *(gpu as *mut GpuRenderer);
+                        gpu_renderer.draw_rects(pass, &self.command_palette_rects, scale);
+                    }
+                }
+            }
+            // Draw command palette text with proper scissor rects for each view
+            if !self.command_palette_glyphs.is_empty() {
+                if let Some(gpu) = self.gpu_renderer {
+                    unsafe {
+                        let gpu_renderer = &mut *(gpu as *mut GpuRenderer);
+                        gpu_renderer.draw_glyphs_batched(
+                            pass,
+                            &self.command_palette_glyphs,
+                            "command_palette",
+                            true,
+                        );
+                    }
+                }
+            }
+            // Paint command palette input's cursor/selection plugins
+            if let Some(plugin_ptr) = self.command_palette_plugin {
+                let plugin = unsafe { &mut *plugin_ptr };
+                if plugin.visible {
+                    // Sync plugin state right before painting
+                    plugin.input_mut().sync_plugins();
+
+                    let input_bounds = plugin.picker.dropdown.input.view.viewport.bounds;
+                    let input_viewport = tiny_sdk::types::WidgetViewport {
+                        bounds: input_bounds,
+                        scroll: tiny_sdk::LayoutPos::new(0.0, 0.0), // Scroll already applied in view coords
+                        content_margin: tiny_sdk::types::LayoutPos::new(0.0, 0.0),
+                        widget_id: 102,
+                    };
+                    pass.set_scissor_rect(0, 0, target_w, target_h);
+                    self.paint_editable_view_plugins(
+                        &plugin.picker.dropdown.input,
+                        input_viewport,
+                        pass,
+                    );
+                }
+            }
         }
 
         // Update uniforms if needed
@@ -1491,8 +1845,85 @@ impl Renderer {
         }
     }
 
+    fn collect_command_palette_glyphs(&mut self) {
+        if let Some(plugin_ptr) = self.command_palette_plugin {
+            let plugin = unsafe { &mut *plugin_ptr };
+
+            if !plugin.visible {
+                self.command_palette_glyphs.clear();
+                self.command_palette_rects.clear();
+                self.command_palette_rounded_rect = None;
+                return;
+            }
+
+            // Calculate bounds before collecting glyphs
+            plugin.calculate_bounds(&self.viewport);
+
+            // Get rounded rect frame with border
+            self.command_palette_rounded_rect = plugin.get_frame_rounded_rect();
+
+            // Collect text buffer background rects (includes highlight)
+            self.command_palette_rects = plugin.collect_background_rects();
+
+            let font_system = self
+                .font_system
+                .as_ref()
+                .expect("Font system not initialized - call set_font_system first");
+
+            // Collect glyphs with per-view scissor rects
+            self.command_palette_glyphs = plugin.collect_glyphs(font_system);
+        }
+    }
+
+    fn collect_completion_glyphs(&mut self) {
+        if let Some(plugin_ptr) = self.completion_plugin {
+            let plugin = unsafe { &mut *plugin_ptr };
+
+            if !plugin.visible {
+                self.completion_glyphs.clear();
+                self.completion_rects.clear();
+                self.completion_rounded_rect = None;
+                return;
+            }
+
+            // Calculate bounds (next to the cursor) before collecting glyphs
+            plugin.calculate_bounds(&self.viewport);
+            self.completion_rounded_rect = plugin.get_frame_rounded_rect();
+            self.completion_rects = plugin.collect_background_rects();
+
+            let font_system = self
+                .font_system
+                .as_ref()
+                .expect("Font system not initialized - call set_font_system first");
+            self.completion_glyphs = plugin.collect_glyphs(font_system);
+        }
+    }
+
+    fn collect_find_bar_glyphs(&mut self) {
+        if let Some(plugin_ptr) = self.find_bar_plugin {
+            let plugin = unsafe { &mut *plugin_ptr };
+
+            if !plugin.visible {
+                self.find_bar_glyphs.clear();
+                self.find_bar_rects.clear();
+                self.find_bar_rounded_rect = None;
+                return;
+            }
+
+            // Bounds follow the editor (calculated with the other overlays each frame)
+            self.find_bar_rounded_rect = plugin.get_frame_rounded_rect();
+            self.find_bar_rects = plugin.collect_background_rects();
+
+            let font_system = self
+                .font_system
+                .as_ref()
+                .expect("Font system not initialized - call set_font_system first");
+            self.find_bar_glyphs = plugin.collect_glyphs(font_system);
+        }
+    }
+
     /// Get theme color for a token_id as packed u32 (RGBA8)
-    fn get_token_color(&self, token_id: u8) -> u32 {
+    pub(crate) fn get_token_color(&self, token_id: u8) -> u32 {
         if let Some(ref theme) = self.theme {
             if let Some(style) = theme.get_token_style(token_id) {
                 if let Some(color) = style.colors.first() {
@@ -1701,12 +2132,210 @@ impl Renderer {
         decoration_rects
     }
 
+    /// Preedit backgrounds, underlines and glyphs at each of its positions
+    fn collect_preedit(
+        &self,
+        tree: &Tree,
+    ) -> (Vec<tiny_sdk::types::RectInstance>, Vec<GlyphInstance>) {
+        use crate::text_effects::{EffectType, UnderlineStyle};
+        use tiny_sdk::types::{LayoutRect, RectInstance};
+
+        let (Some(preedit), Some(font_system)) = (&self.preedit, self.font_system.as_ref()) else {
+            return (Vec::new(), Vec::new());
+        };
+        let scale = self.viewport.scale_factor;
+        let font_size = self.viewport.metrics.font_size;
+        let layout = font_system.layout_text_scaled(&preedit.text, font_size, scale);
+        // Logical width of the preedit up to a byte offset
+        let width_to = |byte: usize| {
+            let prefix = preedit.text.get(..byte).unwrap_or(&preedit.text);
+            font_system
+                .layout_text_scaled(prefix, font_size, scale)
+                .width
+                / scale
+        };
+        let underlines: Vec<(f32, f32, f32, u32)> = preedit
+            .underlines
+            .iter()
+            .filter_map(|effect| {
+                let EffectType::Underline { color, style } = &effect.effect else {
+                    return None;
+                };
+                let thickness = (font_size * 0.1).max(1.0 / scale);
+                let height = match style {
+                    UnderlineStyle::Double => thickness * 2.0,
+                    _ => thickness,
+                };
+                let start = width_to(effect.range.start);
+                Some((start, width_to(effect.range.end) - start, height, *color))
+            })
+            .collect();
+
+        let mut rects = Vec::new();
+        let mut glyphs = Vec::new();
+        for &pos in &preedit.positions {
+            let at = self.viewport.doc_to_layout_with_tree(pos, tree);
+            let x = at.x.0 - self.viewport.scroll.x.0 + self.editor_bounds.x.0;
+            let y = at.y.0 - self.viewport.scroll.y.0 + self.editor_bounds.y.0;
+            rects.push(RectInstance {
+                rect: LayoutRect::new(
+                    x,
+                    y,
+                    layout.width / scale,
+                    self.viewport.metrics.line_height,
+                ),
+                color: PREEDIT_BACKGROUND,
+            });
+            let underline_y = y + self.viewport.metrics.baseline + font_size * 0.1;
+            rects.extend(
+                underlines
+                    .iter()
+                    .map(|&(start, width, height, color)| RectInstance {
+                        rect: LayoutRect::new(x + start, underline_y, width, height),
+                        color,
+                    }),
+            );
+            glyphs.extend(layout.glyphs.iter().map(|g| GlyphInstance {
+                pos: LayoutPos::new(x * scale + g.pos.x.0, y * scale + g.pos.y.0),
+                tex_coords: g.tex_coords,
+                token_id: 0,
+                relative_pos: 0.0,
+                shader_id: 0,
+                format: 0,
+                atlas_index: g.atlas_index,
+                _padding: 0,
+            }));
+        }
+        (rects, glyphs)
+    }
+
+    /// Screen cell of a document glyph: (x, line top, width), at least half a space wide
+    /// so zero-width characters have room for a placeholder
+    fn glyph_cell(
+        &self,
+        glyph: &tiny_ui::text_renderer::UnifiedGlyph,
+        search_hint: &mut usize,
+    ) -> Option<(f32, f32, f32)> {
+        let line_idx = self
+            .text_renderer
+            .line_cache
+            .find_by_y_position_hint(glyph.layout_pos.y.0, *search_hint)?;
+        *search_hint = line_idx;
+        let line_info = self.text_renderer.line_cache.get(line_idx)?;
+        let x = glyph.layout_pos.x.0 - self.viewport.scroll.x.0 + self.editor_bounds.x.0;
+        let y = line_info.y_position - self.viewport.scroll.y.0 + self.editor_bounds.y.0;
+        let width = (glyph.physical_width / self.viewport.scale_factor)
+            .max(self.viewport.metrics.space_width * 0.5);
+        Some((x, y, width))
+    }
+
+    /// Rects for the background effects, one per glyph they cover, in one pass over the
+    /// effects (sorted by start) and the glyphs (in document order)
+    fn collect_effect_backgrounds(
+        &self,
+        glyphs: &[&tiny_ui::text_renderer::UnifiedGlyph],
+    ) -> Vec<tiny_sdk::types::RectInstance> {
+        use crate::text_effects::{EffectType, TextEffect};
+        use tiny_sdk::types::{LayoutRect, RectInstance};
+
+        let mut glyphs = glyphs.to_vec();
+        glyphs.sort_by_key(|glyph| glyph.char_byte_offset);
+
+        // (priority, rect), so higher priority effects can be drawn last
+        let mut rects: Vec<(u8, RectInstance)> = Vec::new();
+        let mut search_hint = 0usize;
+        let mut effects = self.background_effects.iter().peekable();
+        let mut active: Vec<&TextEffect> = Vec::new();
+        for glyph in glyphs {
+            let offset = glyph.char_byte_offset;
+            while let Some(effect) = effects.next_if(|effect| effect.range.start <= offset) {
+                active.push(effect);
+            }
+            active.retain(|effect| offset < effect.range.end);
+            if active.is_empty() {
+                continue;
+            }
+            let Some((x, y, width)) = self.glyph_cell(glyph, &mut search_hint) else {
+                continue;
+            };
+            for effect in &active {
+                if let EffectType::Background(color) = effect.effect {
+                    let rect = LayoutRect::new(x, y, width, self.viewport.metrics.line_height);
+                    rects.push((effect.priority, RectInstance { rect, color }));
+                }
+            }
+        }
+        rects.sort_by_key(|(priority, _)| *priority);
+        rects.into_iter().map(|(_, rect)| rect).collect()
+    }
+
+    /// Placeholder glyphs, centered in the cells of the characters they stand for
+    fn collect_placeholder_glyphs(
+        &self,
+        glyphs: &[&tiny_ui::text_renderer::UnifiedGlyph],
+    ) -> Vec<GlyphInstance> {
+        let Some(font_system) = self.font_system.as_ref() else {
+            return Vec::new();
+        };
+        let scale = self.viewport.scale_factor;
+        let mut instances = Vec::new();
+        let mut search_hint = 0usize;
+        for &(offset, placeholder) in &self.placeholder_glyphs {
+            let Some(glyph) = glyphs.iter().find(|g| g.char_byte_offset == offset) else {
+                continue;
+            };
+            let Some((x, y, width)) = self.glyph_cell(glyph, &mut search_hint) else {
+                continue;
+            };
+            let layout = font_system.layout_text_scaled(
+                &placeholder.to_string(),
+                self.viewport.metrics.font_size,
+                scale,
+            );
+            let left = x * scale + (width * scale - layout.width) / 2.0;
+            instances.extend(layout.glyphs.iter().map(|g| GlyphInstance {
+                pos: LayoutPos::new(left + g.pos.x.0, y * scale + g.pos.y.0),
+                tex_coords: g.tex_coords,
+                token_id: PLACEHOLDER_TOKEN,
+                relative_pos: 0.0,
+                shader_id: 0,
+                format: 0,
+                atlas_index: g.atlas_index,
+                _padding: 0,
+            }));
+        }
+        instances
+    }
+
     fn collect_main_text_glyphs(&mut self, tree: &Tree, visible_range: std::ops::Range<usize>) {
         let visible_glyphs = self.text_renderer.get_visible_glyphs_with_style();
 
+        // Backgrounds first so decorations draw over them
+        let background_rects = self.collect_effect_backgrounds(&visible_glyphs);
+        self.text_decoration_rects.extend(background_rects);
+
         // Collect decoration rectangles (underline, strikethrough)
         let decoration_rects = self.collect_text_decorations(&visible_glyphs);
         self.text_decoration_rects.extend(decoration_rects);
+        let (preedit_rects, preedit_glyphs) = self.collect_preedit(tree);
+        self.preedit_rects = preedit_rects;
+        self.preedit_glyphs = preedit_glyphs;
+
+        if let Some(pos) = self.drop_caret {
+            let layout = self.viewport.doc_to_layout_with_tree(pos, tree);
+            let x = layout.x.0 - self.viewport.scroll.x.0 + self.editor_bounds.x.0;
+            let y = layout.y.0 - self.viewport.scroll.y.0 + self.editor_bounds.y.0;
+            let color = self.get_token_color(0);
+            self.text_decoration_rects.push(tiny_sdk::types::RectInstance {
+                rect: tiny_sdk::types::LayoutRect::new(
+                    x,
+                    y,
+                    2.0,
+                    self.viewport.metrics.line_height,
+                ),
+                color,
+            });
+        }
 
         let glyph_instances: Vec<_> = visible_glyphs
             .iter()
@@ -1734,6 +2363,8 @@ impl Renderer {
             .collect();
 
         self.accumulated_glyphs.extend(glyph_instances);
+        let placeholders = self.collect_placeholder_glyphs(&visible_glyphs);
+        self.accumulated_glyphs.extend(placeholders);
     }
 
 
@@ -1768,9 +2399,12 @@ impl Renderer {
 
         // Scrollbar visibility is controlled by hover state (updated in app.rs)
 
-        // Calculate content height
-        let content_height =
-            self.text_renderer.line_cache.len() as f32 * self.viewport.metrics.line_height;
+        // Calculate content height (folded lines take no space)
+        let display_lines = self
+            .viewport
+            .display_map
+            .display_line_count(self.text_renderer.line_cache.len() as u32);
+        let content_height = display_lines as f32 * self.viewport.metrics.line_height;
 
         // Convert editor_bounds to Rect
         let editor_rect = tiny_core::tree::Rect {
@@ -0,0 +1,740 @@
+//! Snippets - LSP/TextMate snippet bodies expanded into linked tab stops
+//!
+//! A body like `fn ${1:name}($2) {\n\t$0\n}` is parsed into plain text plus the byte
+//! ranges of its tab stops. While a snippet is active its stops are shifted through
+//! every edit the InputHandler applies, like marks, and tab/shift+tab cycle through them.
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::path::Path;
+
+use ahash::AHashMap as HashMap;
+use serde::Deserialize;
+
+use crate::marks::adjust_offset;
+use tiny_core::tree::Edit;
+
+/// A tab stop: every place it appears (mirrors are edited together) and its choices
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TabStop {
+    pub index: u32,
+    pub ranges: Vec<Range<usize>>,
+    pub choices: Vec<String>,
+}
+
+/// An expanded snippet: the text to insert and its tab stops in visiting order
+/// (`$1`, `$2`, ... then `$0`, which defaults to the end of the text)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    pub text: String,
+    pub tab_stops: Vec<TabStop>,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Text(String),
+    TabStop {
+        index: u32,
+        children: Vec<Node>,
+        choices: Vec<String>,
+    },
+    Variable {
+        name: String,
+        default: Option<Vec<Node>>,
+    },
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(body: &str) -> Self {
+        Self {
+            chars: body.chars().collect(),
+            pos: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, ch: char) -> bool {
+        if self.peek() == Some(ch) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Parse nodes up to the end of the body, or up to (and past) a closing `}`
+    fn nodes(&mut self, nested: bool) -> Vec<Node> {
+        let mut nodes = Vec::new();
+        let mut text = String::new();
+        while let Some(ch) = self.peek() {
+            if nested && ch == '}' {
+                self.pos += 1;
+                break;
+            }
+            self.pos += 1;
+            match ch {
+                '\\' => match self.peek() {
+                    Some(next @ ('$' | '}' | '\\')) => {
+                        self.pos += 1;
+                        text.push(next);
+                    }
+                    _ => text.push('\\'),
+                },
+                '$' => {
+                    let start = self.pos;
+                    match self.dollar() {
+                        Some(node) => {
+                            if !text.is_empty() {
+                                nodes.push(Node::Text(std::mem::take(&mut text)));
+                            }
+                            nodes.push(node);
+                        }
+                        None => {
+                            self.pos = start;
+                            text.push('$');
+                        }
+                    }
+                }
+                _ => text.push(ch),
+            }
+        }
+        if !text.is_empty() {
+            nodes.push(Node::Text(text));
+        }
+        nodes
+    }
+
+    fn number(&mut self) -> Option<u32> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        digits.parse().ok()
+    }
+
+    fn variable_name(&mut self) -> Option<String> {
+        let first = self.peek()?;
+        if !(first == '_' || first.is_ascii_alphabetic()) {
+            return None;
+        }
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c == '_' || c.is_ascii_alphanumeric())
+        {
+            self.pos += 1;
+        }
+        Some(self.chars[start..self.pos].iter().collect())
+    }
+
+    /// Parse what follows a `$` - None if it isn't a tab stop or variable
+    fn dollar(&mut self) -> Option<Node> {
+        if let Some(index) = self.number() {
+            return Some(Node::TabStop {
+                index,
+                children: Vec::new(),
+                choices: Vec::new(),
+            });
+        }
+        if let Some(name) = self.variable_name() {
+            return Some(Node::Variable {
+                name,
+                default: None,
+            });
+        }
+        if !self.eat('{') {
+            return None;
+        }
+
+        if let Some(index) = self.number() {
+            if self.eat('}') {
+                return Some(Node::TabStop {
+                    index,
+                    children: Vec::new(),
+                    choices: Vec::new(),
+                });
+            }
+            if self.eat(':') {
+                let children = self.nodes(true);
+                return Some(Node::TabStop {
+                    index,
+                    children,
+                    choices: Vec::new(),
+                });
+            }
+            if self.eat('|') {
+                let choices = self.choices()?;
+                return Some(Node::TabStop {
+                    index,
+                    children: Vec::new(),
+                    choices,
+                });
+            }
+            if self.eat('/') {
+                self.skip_transform()?;
+                return Some(Node::TabStop {
+                    index,
+                    children: Vec::new(),
+                    choices: Vec::new(),
+                });
+            }
+            return None;
+        }
+
+        let name = self.variable_name()?;
+        if self.eat('}') {
+            return Some(Node::Variable {
+                name,
+                default: None,
+            });
+        }
+        if self.eat(':') {
+            let default = self.nodes(true);
+            return Some(Node::Variable {
+                name,
+                default: Some(default),
+            });
+        }
+        if self.eat('/') {
+            self.skip_transform()?;
+            return Some(Node::Variable {
+                name,
+                default: None,
+            });
+        }
+        None
+    }
+
+    /// Parse `a,b,c|}` after `${N|`
+    fn choices(&mut self) -> Option<Vec<String>> {
+        let mut choices = Vec::new();
+        let mut choice = String::new();
+        loop {
+            let ch = self.peek()?;
+            self.pos += 1;
+            match ch {
+                '\\' => match self.peek() {
+                    Some(next @ ('$' | '}' | '\\' | ',' | '|')) => {
+                        self.pos += 1;
+                        choice.push(next);
+                    }
+                    _ => choice.push('\\'),
+                },
+                ',' => choices.push(std::mem::take(&mut choice)),
+                '|' => {
+                    choices.push(choice);
+                    return self.eat('}').then_some(choices);
+                }
+                _ => choice.push(ch),
+            }
+        }
+    }
+
+    /// Skip `regex/format/options}` after `${N/` - transforms aren't applied
+    fn skip_transform(&mut self) -> Option<()> {
+        let mut slashes = 1;
+        while let Some(ch) = self.peek() {
+            self.pos += 1;
+            match ch {
+                '\\' => self.pos += 1,
+                '/' => slashes += 1,
+                '}' if slashes >= 3 => return Some(()),
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+/// Collects the text and tab stop ranges while walking the parsed body
+struct Expander<'a, F> {
+    resolve: &'a F,
+    /// Text of the first placeholder for each tab stop, inserted at its mirrors
+    defaults: HashMap<u32, String>,
+    text: String,
+    stops: BTreeMap<u32, (Vec<Range<usize>>, Vec<String>)>,
+}
+
+impl<F: Fn(&str) -> Option<String>> Expander<'_, F> {
+    /// Plain text of nodes, without recording tab stops
+    fn plain(&self, nodes: &[Node]) -> String {
+        let mut text = String::new();
+        for node in nodes {
+            match node {
+                Node::Text(t) => text.push_str(t),
+                Node::TabStop {
+                    index,
+                    children,
+                    choices,
+                } => {
+                    if let Some(choice) = choices.first() {
+                        text.push_str(choice);
+                    } else if !children.is_empty() {
+                        text.push_str(&self.plain(children));
+                    } else if let Some(default) = self.defaults.get(index) {
+                        text.push_str(default);
+                    }
+                }
+                Node::Variable { name, default } => match self.variable(name, default) {
+                    Ok(value) => text.push_str(&value),
+                    Err(default) => text.push_str(&self.plain(default)),
+                },
+            }
+        }
+        text
+    }
+
+    /// A variable's value, or the default nodes to expand in its place
+    fn variable<'n>(
+        &self,
+        name: &str,
+        default: &'n Option<Vec<Node>>,
+    ) -> Result<String, &'n [Node]> {
+        let value = (self.resolve)(name);
+        match (value, default) {
+            (Some(value), _) if !value.is_empty() => Ok(value),
+            (_, Some(default)) => Err(default),
+            (Some(value), None) => Ok(value),
+            (None, None) => Ok(name.to_string()),
+        }
+    }
+
+    fn collect_defaults(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            match node {
+                Node::TabStop {
+                    index,
+                    children,
+                    choices,
+                } => {
+                    if !self.defaults.contains_key(index)
+                        && (!children.is_empty() || !choices.is_empty())
+                    {
+                        let default = self.plain(std::slice::from_ref(node));
+                        self.defaults.insert(*index, default);
+                    }
+                    self.collect_defaults(children);
+                }
+                Node::Variable {
+                    default: Some(default),
+                    ..
+                } => self.collect_defaults(default),
+                _ => {}
+            }
+        }
+    }
+
+    fn expand(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            match node {
+                Node::Text(t) => self.text.push_str(t),
+                Node::TabStop {
+                    index,
+                    children,
+                    choices,
+                } => {
+                    let start = self.text.len();
+                    if let Some(choice) = choices.first() {
+                        self.text.push_str(choice);
+                    } else if !children.is_empty() {
+                        self.expand(children);
+                    } else if let Some(default) = self.defaults.get(index) {
+                        let default = default.clone();
+                        self.text.push_str(&default);
+                    }
+                    let stop = self.stops.entry(*index).or_default();
+                    stop.0.push(start..self.text.len());
+                    if stop.1.is_empty() {
+                        stop.1 = choices.clone();
+                    }
+                }
+                Node::Variable { name, default } => match self.variable(name, default) {
+                    Ok(value) => self.text.push_str(&value),
+                    Err(default) => self.expand(default),
+                },
+            }
+        }
+    }
+}
+
+/// Parse a snippet body and expand it
+/// `resolve` provides variable values (`TM_FILENAME`, ...); unknown variables expand to
+/// their default, or their name if they have none.
+pub fn parse(body: &str, resolve: impl Fn(&str) -> Option<String>) -> Snippet {
+    let nodes = Parser::new(body).nodes(false);
+    let mut expander = Expander {
+        resolve: &resolve,
+        defaults: HashMap::default(),
+        text: String::new(),
+        stops: BTreeMap::new(),
+    };
+    expander.collect_defaults(&nodes);
+    expander.expand(&nodes);
+
+    let mut tab_stops: Vec<TabStop> = expander
+        .stops
+        .into_iter()
+        .map(|(index, (ranges, choices))| TabStop {
+            index,
+            ranges,
+            choices,
+        })
+        .collect();
+    // $0 is the final stop, at the end of the text if the body doesn't place it
+    match tab_stops.first() {
+        Some(stop) if stop.index == 0 => {
+            let last = tab_stops.remove(0);
+            tab_stops.push(last);
+        }
+        _ => {
+            let end = expander.text.len();
+            tab_stops.push(TabStop {
+                index: 0,
+                ranges: vec![end..end],
+                choices: Vec::new(),
+            });
+        }
+    }
+
+    Snippet {
+        text: expander.text,
+        tab_stops,
+    }
+}
+
+impl Snippet {
+    /// Fit the snippet to the line it's inserted on: lines after the first get the
+    /// line's indentation and tabs become one indent unit each
+    pub fn indented(&self, indent: &str, indent_unit: &str) -> Snippet {
+        if !self.text.contains(['\n', '\t']) {
+            return self.clone();
+        }
+
+        let mut text = String::with_capacity(self.text.len());
+        // New offset of every byte offset of the original text (and its end)
+        // An offset right after a newline or tab lands after what replaced it
+        let mut offsets = Vec::with_capacity(self.text.len() + 1);
+        for ch in self.text.chars() {
+            let before = text.len();
+            match ch {
+                '\n' => {
+                    text.push('\n');
+                    text.push_str(indent);
+                }
+                '\t' => text.push_str(indent_unit),
+                _ => text.push(ch),
+            }
+            offsets.push(before);
+            offsets.extend(std::iter::repeat(text.len()).take(ch.len_utf8() - 1));
+        }
+        offsets.push(text.len());
+
+        let map = |offset: usize| offsets[offset];
+        let tab_stops = self
+            .tab_stops
+            .iter()
+            .map(|stop| TabStop {
+                index: stop.index,
+                ranges: stop
+                    .ranges
+                    .iter()
+                    .map(|range| map(range.start)..map(range.end))
+                    .collect(),
+                choices: stop.choices.clone(),
+            })
+            .collect();
+
+        Snippet { text, tab_stops }
+    }
+}
+
+/// An expanded snippet being filled in: tab stop ranges in the document, their choices
+/// and the current stop
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnippetSession {
+    stops: Vec<Vec<Range<usize>>>,
+    choices: Vec<Vec<String>>,
+    current: usize,
+}
+
+impl SnippetSession {
+    /// Start at the first stop - None if there's nothing to visit but the final stop
+    pub fn new(stops: Vec<Vec<Range<usize>>>) -> Option<Self> {
+        (stops.len() > 1).then_some(Self {
+            stops,
+            choices: Vec::new(),
+            current: 0,
+        })
+    }
+
+    /// Set the choices of each stop (`${1|a,b|}`), in the same order as the stops
+    pub fn with_choices(mut self, choices: Vec<Vec<String>>) -> Self {
+        self.choices = choices;
+        self
+    }
+
+    /// Ranges of the current stop, in document order
+    pub fn current(&self) -> &[Range<usize>] {
+        &self.stops[self.current]
+    }
+
+    /// Choices offered for the current stop (empty if it has none)
+    pub fn choices(&self) -> &[String] {
+        self.choices.get(self.current).map_or(&[], Vec::as_slice)
+    }
+
+    /// Whether the current stop is the final one (after which the session ends)
+    pub fn is_last(&self) -> bool {
+        self.current + 1 == self.stops.len()
+    }
+
+    /// Move to the next stop
+    pub fn next(&mut self) -> &[Range<usize>] {
+        self.current = (self.current + 1).min(self.stops.len() - 1);
+        self.current()
+    }
+
+    /// Move to the previous stop
+    pub fn prev(&mut self) -> &[Range<usize>] {
+        self.current = self.current.saturating_sub(1);
+        self.current()
+    }
+
+    /// Whether an offset is inside (or at the edge of) one of the current stop's ranges
+    pub fn contains(&self, offset: usize) -> bool {
+        self.current()
+            .iter()
+            .any(|range| range.start <= offset && offset <= range.end)
+    }
+
+    /// Shift every stop through an edit
+    /// Text inserted at the start of a stop becomes part of it, so typing into an
+    /// empty placeholder grows it.
+    pub fn adjust(&mut self, edit: &Edit) {
+        for range in self.stops.iter_mut().flatten() {
+            let start = match edit {
+                Edit::Insert { pos, .. } if *pos == range.start => range.start,
+                _ => adjust_offset(range.start, edit),
+            };
+            let end = adjust_offset(range.end, edit).max(start);
+            *range = start..end;
+        }
+    }
+}
+
+/// A user snippet: typing `prefix` and pressing tab inserts `body`
+#[derive(Debug, Clone, Deserialize)]
+pub struct SnippetDefinition {
+    pub prefix: String,
+    #[serde(deserialize_with = "deserialize_body")]
+    pub body: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// Bodies are a string or a list of lines
+fn deserialize_body<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Body {
+        Text(String),
+        Lines(Vec<String>),
+    }
+    Ok(match Body::deserialize(deserializer)? {
+        Body::Text(text) => text,
+        Body::Lines(lines) => lines.join("\n"),
+    })
+}
+
+/// User snippets by language name
+#[derive(Debug, Clone, Default)]
+pub struct SnippetLibrary {
+    languages: HashMap<String, Vec<SnippetDefinition>>,
+}
+
+impl SnippetLibrary {
+    /// Load `<language>.snippets.toml` files from a directory (the one init.toml is in)
+    /// Each file is a table of named snippets with `prefix`, `body` and `description`.
+    pub fn load(dir: &Path) -> Self {
+        let mut library = Self::default();
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return library;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Some(language) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(".snippets.toml"))
+            else {
+                continue;
+            };
+            let Ok(content) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            match library.add_toml(language, &content) {
+                Ok(()) => {}
+                Err(e) => eprintln!("Failed to load snippets from {}: {}", path.display(), e),
+            }
+        }
+        library
+    }
+
+    /// Add a language's snippets from TOML text
+    pub fn add_toml(&mut self, language: &str, content: &str) -> Result<(), toml::de::Error> {
+        let table: BTreeMap<String, SnippetDefinition> = toml::from_str(content)?;
+        let snippets = self.languages.entry(language.to_string()).or_default();
+        snippets.extend(table.into_values());
+        snippets.sort_by(|a, b| b.prefix.len().cmp(&a.prefix.len()));
+        Ok(())
+    }
+
+    /// Snippets for a language, longest prefix first
+    pub fn for_language(&self, language: &str) -> &[SnippetDefinition] {
+        self.languages
+            .get(language)
+            .map(|snippets| snippets.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// The snippet whose prefix the text before the cursor ends with
+    /// The prefix must start at a word boundary, so `xfn` doesn't expand `fn`.
+    pub fn find(&self, language: &str, before_cursor: &str) -> Option<&SnippetDefinition> {
+        self.for_language(language).iter().find(|snippet| {
+            !snippet.prefix.is_empty()
+                && before_cursor.ends_with(&snippet.prefix)
+                && !before_cursor[..before_cursor.len() - snippet.prefix.len()]
+                    .chars()
+                    .next_back()
+                    .is_some_and(|c| c == '_' || c.is_alphanumeric())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tiny_core::tree::Content;
+
+    fn expand(body: &str) -> Snippet {
+        parse(body, |name| {
+            (name == "TM_FILENAME").then(|| "main.rs".to_string())
+        })
+    }
+
+    fn stop_ranges(snippet: &Snippet) -> Vec<(u32, Vec<Range<usize>>)> {
+        snippet
+            .tab_stops
+            .iter()
+            .map(|stop| (stop.index, stop.ranges.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn tab_stops_visit_in_order_with_final_stop_last() {
+        let snippet = expand("fn ${1:name}($2) {\n\t$0\n}");
+        assert_eq!(snippet.text, "fn name() {\n\t\n}");
+        assert_eq!(
+            stop_ranges(&snippet),
+            vec![(1, vec![3..7]), (2, vec![8..8]), (0, vec![13..13])]
+        );
+    }
+
+    #[test]
+    fn final_stop_defaults_to_the_end() {
+        let snippet = expand("a$1b");
+        assert_eq!(
+            stop_ranges(&snippet),
+            vec![(1, vec![1..1]), (0, vec![2..2])]
+        );
+    }
+
+    #[test]
+    fn mirrors_share_the_placeholder_text() {
+        let snippet = expand("$1 = ${1:x};");
+        assert_eq!(snippet.text, "x = x;");
+        assert_eq!(stop_ranges(&snippet)[0], (1, vec![0..1, 4..5]));
+    }
+
+    #[test]
+    fn nested_placeholders_and_choices() {
+        let snippet = expand("${1:a ${2:b}} ${3|one,two|}");
+        assert_eq!(snippet.text, "a b one");
+        assert_eq!(
+            stop_ranges(&snippet)[..3],
+            [(1, vec![0..3]), (2, vec![2..3]), (3, vec![4..7])]
+        );
+        assert_eq!(snippet.tab_stops[2].choices, vec!["one", "two"]);
+    }
+
+    #[test]
+    fn variables_escapes_and_stray_dollars() {
+        assert_eq!(expand("// $TM_FILENAME").text, "// main.rs");
+        assert_eq!(expand("${TM_SELECTED_TEXT:none}").text, "none");
+        assert_eq!(expand("$UNKNOWN").text, "UNKNOWN");
+        assert_eq!(expand("\\$1 \\} costs $ 5").text, "$1 } costs $ 5");
+        assert_eq!(expand("${1/(.*)/$1/}x").text, "x");
+    }
+
+    #[test]
+    fn indenting_shifts_tab_stops() {
+        let snippet = expand("{\n\t$1\n}").indented("  ", "    ");
+        assert_eq!(snippet.text, "{\n      \n  }");
+        assert_eq!(
+            stop_ranges(&snippet),
+            vec![(1, vec![8..8]), (0, vec![12..12])]
+        );
+    }
+
+    #[test]
+    fn session_grows_the_stop_being_typed_into() {
+        let mut session = SnippetSession::new(vec![vec![3..3, 10..10], vec![20..20]]).unwrap();
+        session.adjust(&Edit::Insert {
+            pos: 3,
+            content: Content::Text("ab".into()),
+        });
+        assert_eq!(session.current(), &[3..5, 12..12]);
+        assert!(session.contains(5));
+        session.next();
+        assert!(session.is_last());
+        assert_eq!(session.current(), &[22..22]);
+        assert_eq!(session.prev(), &[3..5, 12..12]);
+    }
+
+    #[test]
+    fn session_offers_the_current_stops_choices() {
+        let choices = vec![vec![], vec!["one".to_string(), "two".to_string()]];
+        let mut session = SnippetSession::new(vec![vec![0..1], vec![2..5], vec![6..6]])
+            .unwrap()
+            .with_choices(choices);
+        assert!(session.choices().is_empty());
+        session.next();
+        assert_eq!(session.choices(), ["one", "two"]);
+        session.next();
+        assert!(session.choices().is_empty());
+    }
+
+    #[test]
+    fn library_matches_prefixes_at_word_boundaries() {
+        let mut library = SnippetLibrary::default();
+        library
+            .add_toml(
+                "rust",
+                "[function]\nprefix = \"fn\"\nbody = [\"fn $1() {\", \"\\t$0\", \"}\"]\n",
+            )
+            .unwrap();
+        let snippet = library.find("rust", "    fn").unwrap();
+        assert_eq!(snippet.body, "fn $1() {\n\t$0\n}");
+        assert!(library.find("rust", "xfn").is_none());
+        assert!(library.find("python", "fn").is_none());
+    }
+}
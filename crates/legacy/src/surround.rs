@@ -0,0 +1,129 @@
+//! Surround with - wrapping selections in pairs or language templates
+//!
+//! Templates are snippet bodies with `$TM_SELECTED_TEXT` where the selection goes, so
+//! placeholders like an `if` condition are selected after wrapping and tab moves on.
+
+/// A way to wrap a selection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SurroundTemplate {
+    pub label: &'static str,
+    pub body: &'static str,
+    /// Wraps whole lines, which are indented one level inside the template
+    pub block: bool,
+}
+
+const fn inline(label: &'static str, body: &'static str) -> SurroundTemplate {
+    SurroundTemplate {
+        label,
+        body,
+        block: false,
+    }
+}
+
+const fn block(label: &'static str, body: &'static str) -> SurroundTemplate {
+    SurroundTemplate {
+        label,
+        body,
+        block: true,
+    }
+}
+
+const PAIRS: &[SurroundTemplate] = &[
+    inline("( )", "($TM_SELECTED_TEXT)"),
+    inline("[ ]", "[$TM_SELECTED_TEXT]"),
+    inline("{ }", "{$TM_SELECTED_TEXT}"),
+    inline("\" \"", "\"$TM_SELECTED_TEXT\""),
+    inline("' '", "'$TM_SELECTED_TEXT'"),
+    inline("` `", "`$TM_SELECTED_TEXT`"),
+    inline("< >", "<$TM_SELECTED_TEXT>"),
+];
+
+const RUST: &[SurroundTemplate] = &[
+    block("if", "if ${1:condition} {\n\t$TM_SELECTED_TEXT\n}"),
+    block(
+        "if let",
+        "if let ${1:Some(value)} = ${2:expr} {\n\t$TM_SELECTED_TEXT\n}",
+    ),
+    block(
+        "for",
+        "for ${1:item} in ${2:iter} {\n\t$TM_SELECTED_TEXT\n}",
+    ),
+    block("while", "while ${1:condition} {\n\t$TM_SELECTED_TEXT\n}"),
+    block("loop", "loop {\n\t$TM_SELECTED_TEXT\n}"),
+    block("unsafe {}", "unsafe {\n\t$TM_SELECTED_TEXT\n}"),
+    block("{} block", "{\n\t$TM_SELECTED_TEXT\n}"),
+    inline("match", "match $TM_SELECTED_TEXT {\n\t${1:_} => $0,\n}"),
+    inline("Some()", "Some($TM_SELECTED_TEXT)"),
+    inline("Ok()", "Ok($TM_SELECTED_TEXT)"),
+    inline("Err()", "Err($TM_SELECTED_TEXT)"),
+    inline("Box::new()", "Box::new($TM_SELECTED_TEXT)"),
+    inline("dbg!()", "dbg!($TM_SELECTED_TEXT)"),
+    inline("&", "&$TM_SELECTED_TEXT"),
+];
+
+const WGSL: &[SurroundTemplate] = &[
+    block("if", "if ${1:condition} {\n\t$TM_SELECTED_TEXT\n}"),
+    block(
+        "for",
+        "for (${1:var i = 0u}; ${2:i < n}; ${3:i++}) {\n\t$TM_SELECTED_TEXT\n}",
+    ),
+    block("loop", "loop {\n\t$TM_SELECTED_TEXT\n}"),
+];
+
+/// Templates offered for a language: its own first, then brackets and quotes
+pub fn templates(language: &str) -> Vec<SurroundTemplate> {
+    let own: &[SurroundTemplate] = match language {
+        "rust" => RUST,
+        "wgsl" => WGSL,
+        _ => &[],
+    };
+    own.iter().chain(PAIRS).copied().collect()
+}
+
+/// Selected text as it goes into a template at `indent` (the indentation of the
+/// selection's first line): later lines lose that indentation, since the snippet
+/// engine adds it back, and block templates indent every line one more level
+pub fn selected_text(text: &str, indent: &str, block: bool) -> String {
+    let mut lines = text.split('\n');
+    let mut result = lines.next().unwrap_or("").to_string();
+    for line in lines {
+        result.push('\n');
+        let line = line.strip_prefix(indent).unwrap_or(line.trim_start());
+        if block && !line.is_empty() {
+            result.push('\t');
+        }
+        result.push_str(line);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn language_templates_come_before_pairs() {
+        let rust = templates("rust");
+        assert_eq!(rust[0].label, "if");
+        assert!(rust.iter().any(|t| t.label == "Some()" && !t.block));
+        assert_eq!(templates("toml"), PAIRS.to_vec());
+    }
+
+    #[test]
+    fn block_lines_are_indented_relative_to_the_first() {
+        let text = "let a = 1;\n    if a {\n        b();\n    }\n\n    c();";
+        assert_eq!(
+            selected_text(text, "    ", true),
+            "let a = 1;\n\tif a {\n\t    b();\n\t}\n\n\tc();"
+        );
+    }
+
+    #[test]
+    fn inline_lines_lose_the_shared_indentation() {
+        assert_eq!(
+            selected_text("foo(\n        x,\n    )", "    ", false),
+            "foo(\n    x,\n)"
+        );
+        assert_eq!(selected_text("a", "    ", false), "a");
+    }
+}
@@ -1,7 +1,8 @@
 //! Tab Manager - manages multiple open files
 
-use crate::coordinates::Viewport;
+use crate::coordinates::{LineMode, Viewport};
 use crate::diagnostics_manager::DiagnosticsManager;
+use crate::indentation::{self, IndentStyle};
 use crate::line_numbers_plugin::LineNumbersPlugin;
 use crate::scroll::Scrollable;
 use crate::text_editor_plugin::TextEditorPlugin;
@@ -20,6 +21,9 @@ pub struct Tab {
     /// The ACTUAL syntax highlighter Arc (shared with InputHandler)
     /// This is the source of truth for parse results
     pub syntax_arc: Option<std::sync::Arc<crate::syntax::SyntaxHighlighter>>,
+    /// Indentation detected from the file's content or chosen for it
+    /// (None = use the configured default)
+    pub indentation: Option<IndentStyle>,
 }
 
 impl Tab {
@@ -51,6 +55,10 @@ impl Tab {
                 arc.as_ref() as *const _, arc.language());
         }
 
+        // .editorconfig settings win, detection fills in what they leave open
+        let detected = indentation::detect(&plugin.editor.view.doc.read().flatten_to_string());
+        let indentation = plugin.editorconfig.indentation(detected).or(detected);
+
         Self {
             plugin,
             line_numbers: LineNumbersPlugin::new(),
@@ -59,6 +67,7 @@ impl Tab {
             display_name,
             scroll_position: Point::default(),
             syntax_arc,
+            indentation,
         }
     }
 
@@ -328,15 +337,20 @@ impl Scrollable for Tab {
         let tree = doc.read();
         let line_count = tree.line_count();
 
-        // Use actual line height from viewport metrics
-        let content_height = (line_count as f32) * viewport.metrics.line_height;
+        // Use actual line height from viewport metrics (folded lines hide, wrapped ones grow)
+        let display_lines = viewport.display_map.display_line_count(line_count);
+        let content_height = (display_lines as f32) * viewport.metrics.line_height;
 
         // Calculate maximum line width from document
         let mut max_width = 0.0f32;
-        for line_idx in 0..line_count {
-            let line_text = tree.line_text(line_idx);
-            let line_width = (line_text.len() as f32) * viewport.metrics.space_width;
-            max_width = max_width.max(line_width);
+        if let LineMode::SoftWrap { wrap_width } = viewport.line_mode {
+            max_width = wrap_width;
+        } else {
+            for line_idx in 0..line_count {
+                let line_text = tree.line_text(line_idx);
+                let line_width = (line_text.len() as f32) * viewport.metrics.space_width;
+                max_width = max_width.max(line_width);
+            }
         }
 
         Rect {
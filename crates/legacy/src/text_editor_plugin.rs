@@ -5,7 +5,9 @@
 use crate::{
     coordinates::Viewport,
     editable_text_view::{EditMode, EditableTextView},
+    editorconfig,
     input::{Event, EventSubscriber, InputAction, PropagationControl, Selection},
+    io,
     syntax::SyntaxHighlighter,
     text_effects::TextStyleProvider,
 };
@@ -33,6 +35,8 @@ pub struct TextEditorPlugin {
     // File management
     pub file_path: Option<PathBuf>,
     pub last_saved_content_hash: u64,
+    /// Rules from the `.editorconfig` files above the file
+    pub editorconfig: editorconfig::Properties,
 
     // Cmd+hover for go-to-definition preview (line, start_col, end_col)
     pub cmd_hover_range: Option<(u32, u32, u32)>,
@@ -60,6 +64,7 @@ impl TextEditorPlugin {
             show_line_numbers: true,
             file_path: None,
             last_saved_content_hash: 0,
+            editorconfig: editorconfig::Properties::default(),
             cmd_hover_range: None,
         }
     }
@@ -102,13 +107,13 @@ impl TextEditorPlugin {
         F: Fn(&str) + Send + Sync + 'static,
     {
         let bytes = std::fs::read(&path)?;
-        let content = match simdutf8::basic::from_utf8(&bytes) {
-            Ok(s) => s.to_string(),
-            Err(_) => String::from_utf8_lossy(&bytes).into_owned(),
-        };
+        let properties = editorconfig::Properties::for_file(&path);
+        let content = io::decode(&bytes, properties.charset.unwrap_or_default());
         let doc = Doc::from_str(&content);
         let mut editor = Self::new(doc);
         editor.file_path = Some(path.clone());
+        editor.editorconfig = properties;
+        editor.editor.input.set_file_path(Some(path.clone()));
 
         // Calculate saved content hash (file was just loaded)
         use ahash::AHasher;
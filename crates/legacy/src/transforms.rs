@@ -0,0 +1,372 @@
+//! Text transforms - case conversions and line operations applied to selections
+//!
+//! Each transform is a pure `&str -> String` function. The editor applies it to every
+//! selection (or the word under a cursor) and commits the results as one undo step.
+
+use std::cmp::Ordering;
+
+/// A named text transform
+#[derive(Clone, Copy)]
+pub struct Transform {
+    /// Name used in `editor.transform` events
+    pub name: &'static str,
+    /// Label shown in the command palette
+    pub label: &'static str,
+    /// Works on whole lines (a selection is widened to the lines it touches)
+    pub lines: bool,
+    pub apply: fn(&str) -> String,
+}
+
+const fn text(name: &'static str, label: &'static str, apply: fn(&str) -> String) -> Transform {
+    Transform {
+        name,
+        label,
+        lines: false,
+        apply,
+    }
+}
+
+const fn lines(name: &'static str, label: &'static str, apply: fn(&str) -> String) -> Transform {
+    Transform {
+        name,
+        label,
+        lines: true,
+        apply,
+    }
+}
+
+/// All transforms, in the order the palette lists them
+pub const TRANSFORMS: &[Transform] = &[
+    text("upper", "Uppercase", upper),
+    text("lower", "Lowercase", lower),
+    text("toggle_case", "Toggle Case", toggle_case),
+    text("title", "Title Case", title_case),
+    text("snake", "snake_case", snake_case),
+    text("camel", "camelCase", camel_case),
+    text("pascal", "PascalCase", pascal_case),
+    text("kebab", "kebab-case", kebab_case),
+    text("constant", "CONSTANT_CASE", constant_case),
+    lines("sort", "Sort Lines", sort_lines),
+    lines(
+        "sort_case_insensitive",
+        "Sort Lines (Case Insensitive)",
+        sort_lines_case_insensitive,
+    ),
+    lines("sort_numeric", "Sort Lines (Numeric)", sort_lines_numeric),
+    lines("unique", "Unique Lines", unique_lines),
+    lines("reverse", "Reverse Lines", reverse_lines),
+    lines("shuffle", "Shuffle Lines", shuffle_lines),
+    lines(
+        "trim_trailing",
+        "Trim Trailing Whitespace",
+        trim_trailing_whitespace,
+    ),
+];
+
+/// Look up a transform by name
+pub fn find(name: &str) -> Option<&'static Transform> {
+    TRANSFORMS.iter().find(|transform| transform.name == name)
+}
+
+fn upper(text: &str) -> String {
+    text.to_uppercase()
+}
+
+fn lower(text: &str) -> String {
+    text.to_lowercase()
+}
+
+fn toggle_case(text: &str) -> String {
+    text.chars()
+        .flat_map(|c| -> Box<dyn Iterator<Item = char>> {
+            if c.is_uppercase() {
+                Box::new(c.to_lowercase())
+            } else {
+                Box::new(c.to_uppercase())
+            }
+        })
+        .collect()
+}
+
+/// First letter of every whitespace separated word uppercase, the rest lowercase
+fn title_case(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut word_start = true;
+    for c in text.chars() {
+        if word_start {
+            result.extend(c.to_uppercase());
+        } else {
+            result.extend(c.to_lowercase());
+        }
+        word_start = c.is_whitespace();
+    }
+    result
+}
+
+/// Words of an identifier: split at separators, lower to upper case changes and the
+/// end of an acronym (`HTTPServer` is `HTTP` `Server`)
+fn identifier_words(text: &str) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut words = Vec::new();
+    let mut word = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+        if let Some(prev) = word.chars().last() {
+            let next = chars.get(i + 1).copied();
+            let boundary = c.is_uppercase()
+                && (prev.is_lowercase()
+                    || prev.is_numeric()
+                    || (prev.is_uppercase() && next.is_some_and(char::is_lowercase)));
+            if boundary {
+                words.push(std::mem::take(&mut word));
+            }
+        }
+        word.push(c);
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.flat_map(char::to_lowercase))
+            .collect(),
+        None => String::new(),
+    }
+}
+
+/// Convert each identifier in a text, keeping everything between them. Hyphens between
+/// letters or digits join an identifier, so `foo-bar` converts as one.
+fn convert_identifiers(text: &str, convert: impl Fn(Vec<String>) -> String) -> String {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let joins = |i: usize| match chars[i].1 {
+        '-' => {
+            i > 0
+                && is_word(chars[i - 1].1)
+                && chars.get(i + 1).is_some_and(|&(_, c)| c.is_alphanumeric())
+        }
+        c => is_word(c),
+    };
+
+    let mut result = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let start = i;
+        let identifier = joins(i);
+        while i < chars.len() && joins(i) == identifier {
+            i += 1;
+        }
+        let end = chars.get(i).map_or(text.len(), |&(byte, _)| byte);
+        let span = &text[chars[start].0..end];
+        let words = identifier_words(span);
+        if identifier && !words.is_empty() {
+            result.push_str(&convert(words));
+        } else {
+            result.push_str(span);
+        }
+    }
+    result
+}
+
+fn snake_case(text: &str) -> String {
+    convert_identifiers(text, |words| words.join("_").to_lowercase())
+}
+
+fn constant_case(text: &str) -> String {
+    convert_identifiers(text, |words| words.join("_").to_uppercase())
+}
+
+fn kebab_case(text: &str) -> String {
+    convert_identifiers(text, |words| words.join("-").to_lowercase())
+}
+
+fn pascal_case(text: &str) -> String {
+    convert_identifiers(text, |words| words.iter().map(|w| capitalize(w)).collect())
+}
+
+fn camel_case(text: &str) -> String {
+    convert_identifiers(text, |words| {
+        words
+            .iter()
+            .enumerate()
+            .map(|(i, w)| {
+                if i == 0 {
+                    w.to_lowercase()
+                } else {
+                    capitalize(w)
+                }
+            })
+            .collect()
+    })
+}
+
+/// Apply a function to the lines of a text, keeping its line endings
+fn map_lines(text: &str, f: impl FnOnce(&mut Vec<&str>)) -> String {
+    let ending = if text.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut lines: Vec<&str> = text
+        .split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect();
+    f(&mut lines);
+    lines.join(ending)
+}
+
+/// Compare with runs of digits as numbers (`file2` before `file10`)
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (Some(ca), Some(cb)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len());
+        };
+        let ordering = if ca.is_ascii_digit() && cb.is_ascii_digit() {
+            let digits =
+                |s: &str| s.len() - s.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            let (na, nb) = (&a[..digits(a)], &b[..digits(b)]);
+            a = &a[na.len()..];
+            b = &b[nb.len()..];
+            let (ta, tb) = (na.trim_start_matches('0'), nb.trim_start_matches('0'));
+            ta.len().cmp(&tb.len()).then_with(|| ta.cmp(tb))
+        } else {
+            a = &a[ca.len_utf8()..];
+            b = &b[cb.len_utf8()..];
+            ca.cmp(&cb)
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+fn sort_lines(text: &str) -> String {
+    map_lines(text, |lines| lines.sort_by(|a, b| natural_cmp(a, b)))
+}
+
+fn sort_lines_case_insensitive(text: &str) -> String {
+    map_lines(text, |lines| {
+        lines.sort_by(|a, b| natural_cmp(&a.to_lowercase(), &b.to_lowercase()))
+    })
+}
+
+/// Number a line starts with (after whitespace), if any
+fn leading_number(line: &str) -> Option<f64> {
+    let line = line.trim_start();
+    let end = line
+        .char_indices()
+        .find(|&(i, c)| !(c.is_ascii_digit() || c == '.' || (i == 0 && (c == '-' || c == '+'))))
+        .map_or(line.len(), |(i, _)| i);
+    (1..=end).rev().find_map(|end| line[..end].parse().ok())
+}
+
+/// Sort by each line's leading number, lines without one first
+fn sort_lines_numeric(text: &str) -> String {
+    map_lines(text, |lines| {
+        lines.sort_by(|a, b| {
+            let (na, nb) = (leading_number(a), leading_number(b));
+            na.partial_cmp(&nb).unwrap_or(Ordering::Equal)
+        })
+    })
+}
+
+/// Drop repeated lines, keeping the first of each
+fn unique_lines(text: &str) -> String {
+    map_lines(text, |lines| {
+        let mut seen = std::collections::HashSet::new();
+        lines.retain(|line| seen.insert(*line));
+    })
+}
+
+fn reverse_lines(text: &str) -> String {
+    map_lines(text, |lines| lines.reverse())
+}
+
+fn shuffle_lines(text: &str) -> String {
+    let mut state = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0x9E37_79B9_7F4A_7C15, |d| d.as_nanos() as u64)
+        | 1;
+    // xorshift64 is plenty for shuffling a handful of lines
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    map_lines(text, |lines| {
+        for i in (1..lines.len()).rev() {
+            lines.swap(i, (next() % (i as u64 + 1)) as usize);
+        }
+    })
+}
+
+fn trim_trailing_whitespace(text: &str) -> String {
+    map_lines(text, |lines| {
+        for line in lines.iter_mut() {
+            *line = line.trim_end();
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(name: &str, text: &str) -> String {
+        (find(name).unwrap().apply)(text)
+    }
+
+    #[test]
+    fn identifier_cases() {
+        assert_eq!(
+            apply("snake", "parseHTTPResponse2Fast"),
+            "parse_http_response2_fast"
+        );
+        assert_eq!(apply("camel", "  foo_bar-baz qux\n"), "  fooBarBaz qux\n");
+        assert_eq!(apply("snake", "let fooBar = 1;"), "let foo_bar = 1;");
+        assert_eq!(apply("kebab", "a - b, x-y"), "a - b, x-y");
+        assert_eq!(apply("constant", "__"), "__");
+        assert_eq!(apply("pascal", "foo_bar"), "FooBar");
+        assert_eq!(apply("kebab", "FooBar"), "foo-bar");
+        assert_eq!(apply("constant", "maxValue"), "MAX_VALUE");
+        assert_eq!(apply("title", "the QUICK brown-fox"), "The Quick Brown-fox");
+        assert_eq!(apply("toggle_case", "aBc"), "AbC");
+    }
+
+    #[test]
+    fn sorting_lines() {
+        assert_eq!(
+            apply("sort", "file10\nfile2\nFile1"),
+            "File1\nfile2\nfile10"
+        );
+        assert_eq!(apply("sort_case_insensitive", "b\nC\na"), "a\nb\nC");
+        assert_eq!(
+            apply("sort_numeric", "10 x\n-2 y\nz\n3.5"),
+            "z\n-2 y\n3.5\n10 x"
+        );
+        assert_eq!(apply("sort", "b\r\na"), "a\r\nb");
+    }
+
+    #[test]
+    fn line_operations() {
+        assert_eq!(apply("unique", "a\nb\na\nc\nb"), "a\nb\nc");
+        assert_eq!(apply("reverse", "a\nb\nc"), "c\nb\na");
+        assert_eq!(apply("trim_trailing", "a  \n\tb\t\n"), "a\n\tb\n");
+
+        let mut shuffled: Vec<String> = apply("shuffle", "1\n2\n3\n4\n5")
+            .split('\n')
+            .map(String::from)
+            .collect();
+        shuffled.sort();
+        assert_eq!(shuffled.join("\n"), "1\n2\n3\n4\n5");
+    }
+}
@@ -0,0 +1,122 @@
+//! Unicode inspector - what a grapheme is made of, and labels for picking characters by name
+
+use tiny_font::{NameSource, NamedChar};
+
+/// Name of a code point: its Unicode name, its Nerd Font glyph name, or a placeholder
+pub fn char_name(ch: char) -> String {
+    if let Some(name) = tiny_font::unicode_name(ch) {
+        return name;
+    }
+    if let Some(name) = tiny_font::nerd_font_name(ch) {
+        return format!("{} (Nerd Font)", name);
+    }
+    if ch.is_control() {
+        return "<control>".to_string();
+    }
+    "<unnamed>".to_string()
+}
+
+/// Characters that take no space, or whose space looks like any other
+fn is_invisible(ch: char) -> bool {
+    ch.is_control()
+        || (ch.is_whitespace() && ch != ' ')
+        || matches!(ch,
+            '\u{00AD}' |                 // Soft hyphen
+            '\u{200B}'..='\u{200F}' |   // Zero width spaces and joiners, direction marks
+            '\u{202A}'..='\u{202E}' |   // Bidi embeddings and overrides
+            '\u{2060}'..='\u{206F}' |   // Word joiner, invisible operators, bidi isolates
+            '\u{FE00}'..='\u{FE0F}' |   // Variation selectors
+            '\u{FEFF}' |                 // Byte order mark
+            '\u{E0000}'..='\u{E007F}'   // Tags
+        )
+}
+
+/// Escaped form of a character, so invisible ones can be seen
+fn printable(ch: char) -> String {
+    if is_invisible(ch) {
+        ch.escape_unicode().to_string()
+    } else {
+        ch.to_string()
+    }
+}
+
+/// Details of a grapheme, one line each: a summary, then every code point
+pub fn describe(grapheme: &str) -> Vec<String> {
+    let count = grapheme.chars().count();
+    let mut lines = vec![format!(
+        "{} code point{}, {} UTF-8 bytes, {} UTF-16 units",
+        count,
+        if count == 1 { "" } else { "s" },
+        grapheme.len(),
+        grapheme.encode_utf16().count()
+    )];
+
+    let emoji = tiny_font::contains_emoji(grapheme);
+    let nerd_font = tiny_font::contains_nerd_symbols(grapheme);
+    lines.push(format!(
+        "Emoji: {}, Nerd Font symbol: {}",
+        if emoji { "yes" } else { "no" },
+        if nerd_font { "yes" } else { "no" }
+    ));
+
+    lines.extend(
+        grapheme
+            .chars()
+            .map(|ch| format!("U+{:04X}  {}  {}", ch as u32, printable(ch), char_name(ch))),
+    );
+    lines
+}
+
+/// Picker label of a named character, e.g. "😀  U+1F600  GRINNING FACE"
+pub fn picker_label(named: &NamedChar) -> String {
+    let marker = match named.source {
+        NameSource::Unicode => "",
+        NameSource::NerdFont => "  (Nerd Font)",
+    };
+    format!(
+        "{}  U+{:04X}  {}{}",
+        printable(named.ch),
+        named.ch as u32,
+        named.name,
+        marker
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_code_points_and_lengths() {
+        let lines = describe("é");
+        assert_eq!(lines[0], "1 code point, 2 UTF-8 bytes, 1 UTF-16 units");
+        assert_eq!(lines[1], "Emoji: no, Nerd Font symbol: no");
+        assert_eq!(lines[2], "U+00E9  é  LATIN SMALL LETTER E WITH ACUTE");
+
+        // Thumbs up with a skin tone: two code points outside the BMP
+        let lines = describe("👍🏽");
+        assert_eq!(lines[0], "2 code points, 8 UTF-8 bytes, 4 UTF-16 units");
+        assert_eq!(lines[1], "Emoji: yes, Nerd Font symbol: no");
+        assert_eq!(lines[2], "U+1F44D  👍  THUMBS UP SIGN");
+    }
+
+    #[test]
+    fn invisible_and_nerd_font_chars() {
+        assert_eq!(
+            describe("\u{200B}")[2],
+            "U+200B  \\u{200b}  ZERO WIDTH SPACE"
+        );
+        assert_eq!(describe("\t")[2], "U+0009  \\u{9}  <control>");
+        assert_eq!(char_name('\u{F004}'), "fa-heart (Nerd Font)");
+    }
+
+    #[test]
+    fn picker_labels() {
+        let named = NamedChar {
+            ch: '\u{F004}',
+            name: "heart",
+            source: NameSource::NerdFont,
+        };
+        assert_eq!(picker_label(&named), "\u{F004}  U+F004  heart  (Nerd Font)");
+    }
+}
@@ -1,7 +1,8 @@
 //! Winit adapter - converts winit events to internal semantic events
 
 use crate::accelerator::{Modifiers, Trigger};
-use winit::event::Modifiers as WinitModifiers;
+use crate::ime::ImeEvent;
+use winit::event::{Ime, Modifiers as WinitModifiers};
 use winit::keyboard::{Key, NamedKey};
 
 /// Convert winit modifiers to our Modifiers
@@ -32,6 +33,7 @@ pub fn convert_key(key: &Key) -> Option<Trigger> {
                 NamedKey::Tab => "Tab",
                 NamedKey::Backspace => "Backspace",
                 NamedKey::Delete => "Delete",
+                NamedKey::Insert => "Insert",
                 NamedKey::Escape => "Escape",
                 NamedKey::ArrowUp => "ArrowUp",
                 NamedKey::ArrowDown => "ArrowDown",
@@ -54,3 +56,16 @@ pub fn convert_key(key: &Key) -> Option<Trigger> {
         _ => None,
     }
 }
+
+/// Convert a winit input method event to our ImeEvent
+pub fn convert_ime(ime: &Ime) -> ImeEvent {
+    match ime {
+        Ime::Enabled => ImeEvent::Enabled,
+        Ime::Preedit(text, cursor) => ImeEvent::Preedit {
+            text: text.clone(),
+            cursor: *cursor,
+        },
+        Ime::Commit(text) => ImeEvent::Commit(text.clone()),
+        Ime::Disabled => ImeEvent::Disabled,
+    }
+}
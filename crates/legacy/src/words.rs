@@ -0,0 +1,151 @@
+//! Word boundaries for word-wise cursor movement and deletion
+//!
+//! A word is a run of characters of the same class (word, separator). Which
+//! characters count as separators is configurable, and sub-word mode adds
+//! stops at camelCase humps and after underscores.
+
+/// Default separator characters (everything else that isn't whitespace is a word char)
+pub const DEFAULT_WORD_SEPARATORS: &str = "`~!@#$%^&*()-=+[{]}\\|;:'\",.<>/?";
+
+/// How words are split for movement and deletion
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WordConfig {
+    /// Characters treated as punctuation rather than part of a word
+    pub separators: String,
+    /// Also stop inside words at camelCase humps and underscores
+    pub subword: bool,
+}
+
+impl Default for WordConfig {
+    fn default() -> Self {
+        Self {
+            separators: DEFAULT_WORD_SEPARATORS.to_string(),
+            subword: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Whitespace,
+    Separator,
+    Word,
+}
+
+impl WordConfig {
+    fn class(&self, ch: char) -> CharClass {
+        if ch.is_whitespace() {
+            CharClass::Whitespace
+        } else if self.separators.contains(ch) {
+            CharClass::Separator
+        } else {
+            CharClass::Word
+        }
+    }
+
+    /// Whether a word ends between `chars[i - 1]` and `chars[i]`
+    fn is_boundary(&self, chars: &[char], i: usize) -> bool {
+        let (prev, ch) = (chars[i - 1], chars[i]);
+        let class = self.class(ch);
+        if self.class(prev) != class {
+            return true;
+        }
+        if !self.subword || class != CharClass::Word {
+            return false;
+        }
+
+        // fooBar | foo_bar | HTTPServer -> HTTP|Server
+        (prev == '_' && ch != '_')
+            || ((prev.is_lowercase() || prev.is_ascii_digit()) && ch.is_uppercase())
+            || (prev.is_uppercase()
+                && ch.is_uppercase()
+                && chars.get(i + 1).map_or(false, |next| next.is_lowercase()))
+    }
+
+    /// Char column of the end of the next word at or after `column`
+    /// Returns `chars.len()` when no word follows on the line
+    pub fn next_boundary(&self, chars: &[char], column: usize) -> usize {
+        let mut i = column.min(chars.len());
+        while i < chars.len() && self.class(chars[i]) == CharClass::Whitespace {
+            i += 1;
+        }
+        if i < chars.len() {
+            i += 1;
+            while i < chars.len() && !self.is_boundary(chars, i) {
+                i += 1;
+            }
+        }
+        i
+    }
+
+    /// Char column of the start of the word before `column`
+    /// Returns 0 when no word precedes it on the line
+    pub fn prev_boundary(&self, chars: &[char], column: usize) -> usize {
+        let mut i = column.min(chars.len());
+        while i > 0 && self.class(chars[i - 1]) == CharClass::Whitespace {
+            i -= 1;
+        }
+        if i > 0 {
+            i -= 1;
+            while i > 0 && !self.is_boundary(chars, i) {
+                i -= 1;
+            }
+        }
+        i
+    }
+}
+
+/// Char column of the first non-whitespace character (line length if blank)
+pub fn first_non_blank(chars: &[char]) -> usize {
+    chars
+        .iter()
+        .position(|ch| !ch.is_whitespace())
+        .unwrap_or(chars.len())
+}
+
+/// Char column just after the last non-whitespace character (0 if blank)
+pub fn last_non_blank_end(chars: &[char]) -> usize {
+    chars
+        .iter()
+        .rposition(|ch| !ch.is_whitespace())
+        .map_or(0, |i| i + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(s: &str) -> Vec<char> {
+        s.chars().collect()
+    }
+
+    #[test]
+    fn test_word_boundaries() {
+        let config = WordConfig::default();
+        let line = chars("let foo.bar = 1;");
+        assert_eq!(config.next_boundary(&line, 0), 3);
+        assert_eq!(config.next_boundary(&line, 3), 7);
+        assert_eq!(config.next_boundary(&line, 7), 8);
+        assert_eq!(config.prev_boundary(&line, 11), 8);
+        assert_eq!(config.prev_boundary(&line, 8), 7);
+        assert_eq!(config.prev_boundary(&line, 3), 0);
+    }
+
+    #[test]
+    fn test_subword_boundaries() {
+        let config = WordConfig {
+            subword: true,
+            ..WordConfig::default()
+        };
+        let line = chars("parseHTTPServer foo_bar");
+        assert_eq!(config.next_boundary(&line, 0), 5);
+        assert_eq!(config.next_boundary(&line, 5), 9);
+        assert_eq!(config.next_boundary(&line, 9), 15);
+        assert_eq!(config.next_boundary(&line, 15), 20);
+        assert_eq!(config.prev_boundary(&line, 23), 20);
+        assert_eq!(config.prev_boundary(&line, 15), 9);
+
+        let plain = WordConfig::default();
+        assert_eq!(plain.next_boundary(&line, 0), 15);
+    }
+}
@@ -0,0 +1,167 @@
+//! Soft wrapping and the display rows it maps document lines to
+
+use serde_json::{json, Value};
+use std::time::Instant;
+use tiny_editor::coordinates::{wrap_line, DisplayMap, DocPos, LineWraps, Viewport};
+use tiny_editor::input::{Event, InputHandler};
+use tiny_editor::Doc;
+
+fn event(name: &str, data: Value) -> Event {
+    Event {
+        name: name.to_string(),
+        data,
+        priority: 0,
+        timestamp: Instant::now(),
+        source: "test".to_string(),
+    }
+}
+
+/// Send an event and apply the edits it queued
+fn send(input: &mut InputHandler, doc: &Doc, name: &str, data: Value) {
+    let viewport = Viewport::new(800.0, 600.0, 1.0);
+    input.handle_event(&event(name, data), doc, &viewport);
+    input.flush_pending_edits(doc);
+}
+
+fn cursor_at(input: &mut InputHandler, doc: &Doc, byte: usize) {
+    let tree = doc.read();
+    let line = tree.byte_to_line(byte);
+    let line_start = tree.line_to_byte(line).unwrap_or(0);
+    let column = tree.get_text_slice(line_start..byte).chars().count() as u32;
+    input.set_cursor_for_test(DocPos {
+        line,
+        column,
+        byte_offset: byte,
+    });
+}
+
+fn cursors(input: &InputHandler, doc: &Doc) -> Vec<usize> {
+    let tree = doc.read();
+    input
+        .selections_for_test()
+        .iter()
+        .map(|s| tree.doc_pos_to_byte(s.cursor))
+        .collect()
+}
+
+/// Input handler wrapping `doc` at `columns`, with its display map built
+fn wrapped_input(doc: &Doc, columns: u32) -> InputHandler {
+    let mut input = InputHandler::new();
+    input.set_soft_wrap(Some(columns), true);
+    input.update_display_map(doc);
+    input
+}
+
+#[test]
+fn test_wrap_line_breaks_after_whitespace() {
+    assert_eq!(
+        wrap_line("hello world again", 10, 4, false).breaks,
+        vec![6, 12]
+    );
+    // Whitespace hangs past the wrap column
+    assert_eq!(wrap_line("hello     world", 8, 4, false).breaks, vec![10]);
+    assert!(wrap_line("fits", 10, 4, false).breaks.is_empty());
+}
+
+#[test]
+fn test_wrap_line_breaks_long_words() {
+    assert_eq!(wrap_line("abcdefghijklmnop", 10, 4, false).breaks, vec![10]);
+    assert_eq!(
+        wrap_line("ab abcdefghijklmnop", 5, 4, false).breaks,
+        vec![3, 8, 13, 18]
+    );
+}
+
+#[test]
+fn test_wrap_line_measures_tabs_and_indents_continuations() {
+    // The tab takes 4 columns, so "cd" doesn't fit on the first row
+    assert_eq!(wrap_line("\tab cd", 6, 4, false).breaks, vec![4]);
+
+    let wraps = wrap_line("    foo bar baz qux", 12, 4, true);
+    assert_eq!(
+        wraps,
+        LineWraps {
+            breaks: vec![12],
+            indent: 4,
+        }
+    );
+    assert_eq!(wrap_line("    foo bar baz qux", 12, 4, false).indent, 0);
+    // Indentation leaving less than half the width isn't repeated
+    assert_eq!(wrap_line("        foo bar baz", 12, 4, true).indent, 0);
+}
+
+#[test]
+fn test_display_map_with_wraps_counts_rows() {
+    let wraps = LineWraps {
+        breaks: vec![4, 8],
+        indent: 0,
+    };
+    let map = DisplayMap::new(Vec::new()).with_wraps(vec![(1, wraps)], 4);
+
+    assert!(map.is_wrapped());
+    assert_eq!(map.rows_in_line(1), 3);
+    assert_eq!(map.doc_to_display(2), 4);
+    assert_eq!(map.display_line_count(4), 6);
+    assert_eq!(map.display_row(1, 0), 1);
+    assert_eq!(map.display_row(1, 5), 2);
+    assert_eq!(map.display_row(1, 8), 3);
+    assert_eq!(map.display_row(3, 2), 5);
+    assert_eq!(map.display_to_doc(3), 1);
+    assert_eq!(map.display_to_doc(4), 2);
+}
+
+#[test]
+fn test_display_map_with_wraps_skips_hidden_lines() {
+    let wraps = |breaks| LineWraps { breaks, indent: 0 };
+    let map = DisplayMap::new(vec![2..4])
+        .with_wraps(vec![(1, wraps(vec![4])), (2, wraps(vec![4, 8]))], 5);
+
+    // Lines 2 and 3 are folded into line 1, so line 2's wraps take no rows
+    assert_eq!(map.doc_to_display(4), 3);
+    assert_eq!(map.display_row(2, 8), 1);
+    assert_eq!(map.display_row(1, 6), 2);
+    assert_eq!(map.display_to_doc(3), 4);
+    assert_eq!(map.display_line_count(5), 4);
+}
+
+#[test]
+fn test_moving_down_and_up_steps_through_display_rows() {
+    let doc = Doc::from_str("aaaa bbbb cccc\nxy");
+    let mut input = wrapped_input(&doc, 5);
+    cursor_at(&mut input, &doc, 2);
+
+    send(&mut input, &doc, "editor.move_down", json!({}));
+    assert_eq!(cursors(&input, &doc), vec![7]);
+    send(&mut input, &doc, "editor.move_down", json!({}));
+    assert_eq!(cursors(&input, &doc), vec![12]);
+
+    // The next line is shorter than the goal column
+    send(&mut input, &doc, "editor.move_down", json!({}));
+    assert_eq!(cursors(&input, &doc), vec![17]);
+
+    // The goal column is kept on the way back
+    send(&mut input, &doc, "editor.move_up", json!({}));
+    assert_eq!(cursors(&input, &doc), vec![12]);
+    send(&mut input, &doc, "editor.move_up", json!({}));
+    send(&mut input, &doc, "editor.move_up", json!({}));
+    assert_eq!(cursors(&input, &doc), vec![2]);
+}
+
+#[test]
+fn test_edits_rewrap_like_a_fresh_display_map() {
+    let doc = Doc::from_str("one two three\nfour\nfive six seven eight\nnine");
+    let mut input = wrapped_input(&doc, 8);
+
+    let steps: [(usize, &str, Value); 4] = [
+        (4, "editor.insert_char", json!({ "char": "x" })),
+        (14, "editor.insert_newline", json!({})),
+        (20, "editor.delete_backward", json!({})),
+        (0, "editor.delete_line", json!({})),
+    ];
+    for (byte, name, data) in steps {
+        cursor_at(&mut input, &doc, byte);
+        send(&mut input, &doc, name, data);
+        let fresh = wrapped_input(&doc, 8).update_display_map(&doc).clone();
+        assert_eq!(input.update_display_map(&doc), &fresh, "after {}", name);
+    }
+}
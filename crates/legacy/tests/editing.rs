@@ -0,0 +1,639 @@
+//! Editing commands driven through InputHandler events
+
+use serde_json::{json, Value};
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::Instant;
+use tiny_editor::config::AutoCloseConfig;
+use tiny_editor::coordinates::{DocPos, Viewport};
+use tiny_editor::input::{Event, InputAction, InputHandler, InputMode};
+use tiny_editor::syntax::SyntaxHighlighter;
+use tiny_editor::{Doc, Edit};
+
+fn event(name: &str, data: Value) -> Event {
+    Event {
+        name: name.to_string(),
+        data,
+        priority: 0,
+        timestamp: Instant::now(),
+        source: "test".to_string(),
+    }
+}
+
+/// Send an event and apply the edits it queued
+fn send(input: &mut InputHandler, doc: &Doc, name: &str, data: Value) -> InputAction {
+    let viewport = Viewport::new(800.0, 600.0, 1.0);
+    let action = input.handle_event(&event(name, data), doc, &viewport);
+    input.flush_pending_edits(doc);
+    action
+}
+
+/// Type text at every selection, one character at a time
+fn type_text(input: &mut InputHandler, doc: &Doc, text: &str) {
+    for ch in text.chars() {
+        let data = json!({ "char": ch.to_string() });
+        send(input, doc, "editor.insert_char", data);
+    }
+}
+
+fn cursor_at(input: &mut InputHandler, doc: &Doc, byte: usize) {
+    let tree = doc.read();
+    let line = tree.byte_to_line(byte);
+    let line_start = tree.line_to_byte(line).unwrap_or(0);
+    let column = tree.get_text_slice(line_start..byte).chars().count() as u32;
+    input.set_cursor_for_test(DocPos {
+        line,
+        column,
+        byte_offset: byte,
+    });
+}
+
+/// Byte ranges of the selections, primary first
+fn selected(input: &InputHandler, doc: &Doc) -> Vec<Range<usize>> {
+    input
+        .selections_for_test()
+        .iter()
+        .map(|s| s.byte_range(doc))
+        .collect()
+}
+
+fn text(doc: &Doc) -> String {
+    doc.read().flatten_to_string().to_string()
+}
+
+#[test]
+fn test_select_next_occurrence_adds_the_following_match() {
+    let doc = Doc::from_str("foo bar foo baz foo");
+    let mut input = InputHandler::new();
+    cursor_at(&mut input, &doc, 1);
+
+    // An empty cursor first selects the word under it
+    send(&mut input, &doc, "editor.select_next_occurrence", json!({}));
+    assert_eq!(selected(&input, &doc), vec![0..3]);
+
+    send(&mut input, &doc, "editor.select_next_occurrence", json!({}));
+    send(&mut input, &doc, "editor.select_next_occurrence", json!({}));
+    assert_eq!(selected(&input, &doc)[0], 16..19);
+    assert_eq!(selected(&input, &doc).len(), 3);
+
+    // Occurrences already selected are skipped, so there's nothing left to add
+    assert_eq!(
+        send(&mut input, &doc, "editor.select_next_occurrence", json!({})),
+        InputAction::None
+    );
+    assert_eq!(selected(&input, &doc).len(), 3);
+}
+
+#[test]
+fn test_select_next_occurrence_wraps_and_matches_whole_words() {
+    let doc = Doc::from_str("foo food foo");
+    let mut input = InputHandler::new();
+    cursor_at(&mut input, &doc, 10);
+
+    send(&mut input, &doc, "editor.select_next_occurrence", json!({}));
+    send(&mut input, &doc, "editor.select_next_occurrence", json!({}));
+    assert_eq!(selected(&input, &doc)[0], 0..3);
+    assert_eq!(
+        send(&mut input, &doc, "editor.select_next_occurrence", json!({})),
+        InputAction::None
+    );
+}
+
+#[test]
+fn test_unselect_occurrence_makes_the_newest_remaining_primary() {
+    let doc = Doc::from_str("foo bar foo baz foo");
+    let mut input = InputHandler::new();
+    cursor_at(&mut input, &doc, 1);
+    for _ in 0..3 {
+        send(&mut input, &doc, "editor.select_next_occurrence", json!({}));
+    }
+
+    send(&mut input, &doc, "editor.unselect_occurrence", json!({}));
+    assert_eq!(selected(&input, &doc), vec![8..11, 0..3]);
+    send(&mut input, &doc, "editor.unselect_occurrence", json!({}));
+    assert_eq!(selected(&input, &doc), vec![0..3]);
+
+    // The last selection stays
+    assert_eq!(
+        send(&mut input, &doc, "editor.unselect_occurrence", json!({})),
+        InputAction::None
+    );
+
+    // Selecting again continues after the primary
+    send(&mut input, &doc, "editor.select_next_occurrence", json!({}));
+    assert_eq!(selected(&input, &doc)[0], 8..11);
+}
+
+#[test]
+fn test_select_all_occurrences_keeps_the_primary() {
+    let doc = Doc::from_str("a foo b foo c foo");
+    let mut input = InputHandler::new();
+    cursor_at(&mut input, &doc, 9);
+
+    send(&mut input, &doc, "editor.select_all_occurrences", json!({}));
+    let ranges = selected(&input, &doc);
+    assert_eq!(ranges[0], 8..11);
+    assert_eq!(ranges.len(), 3);
+
+    type_text(&mut input, &doc, "x");
+    assert_eq!(text(&doc), "a x b x c x");
+}
+
+#[test]
+fn test_visual_block_mode_grows_with_motions() {
+    let doc = Doc::from_str("abcd\nab\nabcd");
+    let mut input = InputHandler::new();
+    cursor_at(&mut input, &doc, 1);
+
+    send(&mut input, &doc, "visual.block_mode", json!({}));
+    assert_eq!(input.current_mode(), InputMode::Visual);
+    send(&mut input, &doc, "editor.move_down", json!({}));
+    send(&mut input, &doc, "editor.move_down", json!({}));
+    send(&mut input, &doc, "editor.move_right", json!({}));
+
+    // One selection per line, the cursor's line is primary
+    assert_eq!(selected(&input, &doc), vec![9..10, 1..2, 6..7]);
+
+    // Typing replaces every line of the block and ends visual mode
+    type_text(&mut input, &doc, "X");
+    assert_eq!(text(&doc), "aXcd\naX\naXcd");
+    assert_eq!(input.current_mode(), InputMode::Normal);
+}
+
+#[test]
+fn test_visual_block_mode_skips_short_lines() {
+    let doc = Doc::from_str("abcd\na\nabcd");
+    let mut input = InputHandler::new();
+    cursor_at(&mut input, &doc, 2);
+
+    send(&mut input, &doc, "visual.block_mode", json!({}));
+    send(&mut input, &doc, "editor.move_right", json!({}));
+    send(&mut input, &doc, "editor.move_down", json!({}));
+    send(&mut input, &doc, "editor.move_down", json!({}));
+    assert_eq!(selected(&input, &doc), vec![9..10, 2..3]);
+}
+
+#[test]
+fn test_leaving_visual_block_mode_keeps_one_cursor() {
+    let doc = Doc::from_str("abcd\nabcd");
+    let mut input = InputHandler::new();
+    cursor_at(&mut input, &doc, 0);
+
+    send(&mut input, &doc, "visual.block_mode", json!({}));
+    send(&mut input, &doc, "editor.move_down", json!({}));
+    send(&mut input, &doc, "editor.move_right", json!({}));
+    send(&mut input, &doc, "cursor.remove", json!({}));
+    assert_eq!(input.current_mode(), InputMode::Normal);
+    assert_eq!(selected(&input, &doc), vec![6..6]);
+
+    // Outside visual mode motions move the cursor again
+    send(&mut input, &doc, "editor.move_right", json!({}));
+    assert_eq!(selected(&input, &doc), vec![7..7]);
+
+    // The same key toggles the mode off
+    send(&mut input, &doc, "visual.block_mode", json!({}));
+    send(&mut input, &doc, "visual.block_mode", json!({}));
+    assert_eq!(input.current_mode(), InputMode::Normal);
+    assert_eq!(selected(&input, &doc).len(), 1);
+}
+
+/// Cursor byte offsets, primary first
+fn cursors(input: &InputHandler, doc: &Doc) -> Vec<usize> {
+    let tree = doc.read();
+    input
+        .selections_for_test()
+        .iter()
+        .map(|s| tree.doc_pos_to_byte(s.cursor))
+        .collect()
+}
+
+#[test]
+fn test_move_line_swaps_with_its_neighbour() {
+    let doc = Doc::from_str("a\nb\nc");
+    let mut input = InputHandler::new();
+    cursor_at(&mut input, &doc, 0);
+
+    send(&mut input, &doc, "editor.move_line_down", json!({}));
+    assert_eq!(text(&doc), "b\na\nc");
+    assert_eq!(cursors(&input, &doc), vec![2]);
+
+    send(&mut input, &doc, "editor.move_line_up", json!({}));
+    assert_eq!(text(&doc), "a\nb\nc");
+
+    // Nothing moves past the document edge
+    assert_eq!(
+        send(&mut input, &doc, "editor.move_line_up", json!({})),
+        InputAction::None
+    );
+    assert_eq!(text(&doc), "a\nb\nc");
+}
+
+#[test]
+fn test_duplicate_and_delete_lines() {
+    let doc = Doc::from_str("one\ntwo");
+    let mut input = InputHandler::new();
+    cursor_at(&mut input, &doc, 1);
+
+    send(&mut input, &doc, "editor.duplicate_line", json!({}));
+    assert_eq!(text(&doc), "one\none\ntwo");
+    assert_eq!(cursors(&input, &doc), vec![5]);
+
+    // The last line takes the line break before it
+    cursor_at(&mut input, &doc, 9);
+    send(&mut input, &doc, "editor.delete_line", json!({}));
+    assert_eq!(text(&doc), "one\none");
+    assert_eq!(cursors(&input, &doc), vec![7]);
+}
+
+#[test]
+fn test_join_lines_collapses_indentation() {
+    let doc = Doc::from_str("foo\n    bar\n\nbaz");
+    let mut input = InputHandler::new();
+    cursor_at(&mut input, &doc, 0);
+
+    send(&mut input, &doc, "editor.join_lines", json!({}));
+    assert_eq!(text(&doc), "foo bar\n\nbaz");
+    assert_eq!(cursors(&input, &doc), vec![3]);
+
+    // Empty lines join without a separator
+    send(&mut input, &doc, "editor.join_lines", json!({}));
+    assert_eq!(text(&doc), "foo bar\nbaz");
+}
+
+#[test]
+fn test_open_and_split_lines_keep_indentation() {
+    let doc = Doc::from_str("  ab");
+    let mut input = InputHandler::new();
+    cursor_at(&mut input, &doc, 3);
+
+    send(&mut input, &doc, "editor.split_line", json!({}));
+    assert_eq!(text(&doc), "  a\n  b");
+    assert_eq!(cursors(&input, &doc), vec![3]);
+
+    send(&mut input, &doc, "editor.insert_newline_below", json!({}));
+    assert_eq!(text(&doc), "  a\n  \n  b");
+    assert_eq!(cursors(&input, &doc), vec![6]);
+
+    cursor_at(&mut input, &doc, 0);
+    send(&mut input, &doc, "editor.insert_newline_above", json!({}));
+    assert_eq!(text(&doc), "  \n  a\n  \n  b");
+    assert_eq!(cursors(&input, &doc), vec![2]);
+}
+
+/// Rust source with the cursor at its end, highlighted so auto-closing follows Rust's pairs
+fn rust_input(source: &str) -> (Doc, InputHandler) {
+    let doc = Doc::from_str(source);
+    let mut input = InputHandler::new();
+    input.set_syntax_highlighter(Arc::new(SyntaxHighlighter::new_rust()));
+    cursor_at(&mut input, &doc, source.len());
+    (doc, input)
+}
+
+#[test]
+fn test_quotes_starting_lifetimes_are_not_closed() {
+    for source in [
+        "fn f(x: &",
+        "fn f<T: ",
+        "struct S<'a, ",
+        "impl<T> S<T> where T: Clone + ",
+        "fn f<'a: ",
+    ] {
+        let (doc, mut input) = rust_input(source);
+        type_text(&mut input, &doc, "'");
+        assert_eq!(text(&doc), format!("{}'", source));
+    }
+
+    // Char literals still get both quotes
+    for source in ["let c = ", "f(a, ", "Foo { sep: ", "if a < b && c == "] {
+        let (doc, mut input) = rust_input(source);
+        type_text(&mut input, &doc, "'");
+        assert_eq!(text(&doc), format!("{}''", source));
+    }
+}
+
+#[test]
+fn test_typing_an_auto_inserted_closer_steps_over_it() {
+    let (doc, mut input) = rust_input("");
+    type_text(&mut input, &doc, "f(");
+    assert_eq!(text(&doc), "f()");
+
+    type_text(&mut input, &doc, "[1");
+    assert_eq!(text(&doc), "f([1])");
+    type_text(&mut input, &doc, "])");
+    assert_eq!(text(&doc), "f([1])");
+    assert_eq!(cursors(&input, &doc), vec![6]);
+}
+
+#[test]
+fn test_typing_a_closer_that_was_already_there_inserts_it() {
+    let (doc, mut input) = rust_input("f(x)");
+    cursor_at(&mut input, &doc, 3);
+    type_text(&mut input, &doc, ")");
+    assert_eq!(text(&doc), "f(x))");
+
+    // An auto-inserted closer is forgotten once it's deleted
+    let (doc, mut input) = rust_input("");
+    type_text(&mut input, &doc, "(");
+    send(&mut input, &doc, "editor.delete_forward", json!({}));
+    type_text(&mut input, &doc, "x)");
+    assert_eq!(text(&doc), "(x)");
+    cursor_at(&mut input, &doc, 2);
+    type_text(&mut input, &doc, ")");
+    assert_eq!(text(&doc), "(x))");
+}
+
+#[test]
+fn test_auto_closing_can_be_turned_off_for_a_language() {
+    let (doc, mut input) = rust_input("");
+    input.set_auto_close_pairs(Arc::new(AutoCloseConfig {
+        enabled: true,
+        languages: [("rust".to_string(), false)].into_iter().collect(),
+    }));
+    type_text(&mut input, &doc, "(\"");
+    assert_eq!(text(&doc), "(\"");
+}
+
+#[test]
+fn test_snippet_completions_expand_into_tab_stops() {
+    let doc = Doc::from_str("let x = fo");
+    let mut input = InputHandler::new();
+    cursor_at(&mut input, &doc, 10);
+
+    let item = lsp_types::CompletionItem {
+        label: "format!".to_string(),
+        insert_text: Some("format!(\"${1:fmt}\", $2)".to_string()),
+        insert_text_format: Some(lsp_types::InsertTextFormat::SNIPPET),
+        ..Default::default()
+    };
+    input.insert_completion(&doc, &item);
+    input.flush_pending_edits(&doc);
+    assert_eq!(text(&doc), "let x = format!(\"fmt\", )");
+    // The first tab stop's placeholder is selected
+    assert_eq!(selected(&input, &doc), vec![17..20]);
+}
+
+#[test]
+fn test_snippet_choices_replace_every_linked_range() {
+    let doc = Doc::from_str("");
+    let mut input = InputHandler::new();
+    cursor_at(&mut input, &doc, 0);
+
+    let item = lsp_types::CompletionItem {
+        label: "let".to_string(),
+        insert_text: Some("let ${1|a,bb|} = $1;$0".to_string()),
+        insert_text_format: Some(lsp_types::InsertTextFormat::SNIPPET),
+        ..Default::default()
+    };
+    input.insert_completion(&doc, &item);
+    assert_eq!(text(&doc), "let a = a;");
+    assert_eq!(input.snippet_choices().unwrap(), ["a", "bb"]);
+
+    input.choose_snippet_choice(&doc, "bb");
+    assert_eq!(text(&doc), "let bb = bb;");
+    assert_eq!(selected(&input, &doc), vec![4..6, 9..11]);
+
+    // The stop keeps its choices until tab moves on
+    input.choose_snippet_choice(&doc, "a");
+    assert_eq!(text(&doc), "let a = a;");
+    send(&mut input, &doc, "editor.insert_tab", json!({}));
+    assert_eq!(input.snippet_choices(), None);
+    assert_eq!(cursors(&input, &doc), vec![10]);
+}
+
+#[test]
+fn test_completions_replace_their_edit_range() {
+    let doc = Doc::from_str("x.len");
+    let mut input = InputHandler::new();
+    cursor_at(&mut input, &doc, 4);
+
+    let position = |character| lsp_types::Position::new(0, character);
+    let item = lsp_types::CompletionItem {
+        label: "len()".to_string(),
+        text_edit: Some(lsp_types::CompletionTextEdit::Edit(lsp_types::TextEdit {
+            range: lsp_types::Range::new(position(2), position(5)),
+            new_text: "len()".to_string(),
+        })),
+        ..Default::default()
+    };
+    input.insert_completion(&doc, &item);
+    input.flush_pending_edits(&doc);
+    assert_eq!(text(&doc), "x.len()");
+    assert_eq!(cursors(&input, &doc), vec![7]);
+}
+
+#[test]
+fn test_overwrite_replaces_grapheme_clusters() {
+    let doc = Doc::from_str("añb e\u{301}👍🏽!");
+    let mut input = InputHandler::new();
+    cursor_at(&mut input, &doc, 0);
+    send(&mut input, &doc, "editor.toggle_overwrite", json!({}));
+    assert_eq!(input.current_mode(), InputMode::Replace);
+
+    // `ñ` is two bytes, `e` with its combining accent and the toned emoji are one
+    // grapheme each
+    type_text(&mut input, &doc, "xyz yo");
+    assert_eq!(text(&doc), "xyz yo!");
+    assert_eq!(cursors(&input, &doc), vec![6]);
+}
+
+#[test]
+fn test_overwrite_inserts_at_the_end_of_a_line() {
+    let doc = Doc::from_str("ab\ncd");
+    let mut input = InputHandler::new();
+    cursor_at(&mut input, &doc, 1);
+    send(&mut input, &doc, "editor.toggle_overwrite", json!({}));
+
+    // The line break is never overwritten
+    type_text(&mut input, &doc, "xyz");
+    assert_eq!(text(&doc), "axyz\ncd");
+    assert_eq!(cursors(&input, &doc), vec![4]);
+}
+
+#[test]
+fn test_overwrite_backspace_restores_the_original_text() {
+    let doc = Doc::from_str("añb\nañb");
+    let mut input = InputHandler::new();
+    cursor_at(&mut input, &doc, 0);
+    send(&mut input, &doc, "cursor.add_below", json!({}));
+    send(&mut input, &doc, "editor.toggle_overwrite", json!({}));
+
+    type_text(&mut input, &doc, "xyzw");
+    assert_eq!(text(&doc), "xyzw\nxyzw");
+
+    for _ in 0..4 {
+        send(&mut input, &doc, "editor.delete_backward", json!({}));
+    }
+    assert_eq!(text(&doc), "añb\nañb");
+    let mut positions = cursors(&input, &doc);
+    positions.sort_unstable();
+    assert_eq!(positions, vec![0, 5]);
+
+    // Text that was there before typing is stepped over, not deleted
+    cursor_at(&mut input, &doc, 3);
+    send(&mut input, &doc, "editor.delete_backward", json!({}));
+    assert_eq!(text(&doc), "añb\nañb");
+    assert_eq!(cursors(&input, &doc), vec![1]);
+}
+
+#[test]
+fn test_transform_converts_the_word_under_the_cursor() {
+    let doc = Doc::from_str("let fooBar = 1;");
+    let mut input = InputHandler::new();
+    cursor_at(&mut input, &doc, 6);
+
+    let data = json!({ "name": "snake" });
+    send(&mut input, &doc, "editor.transform", data);
+    assert_eq!(text(&doc), "let foo_bar = 1;");
+    assert_eq!(cursors(&input, &doc), vec![6]);
+
+    // Line transforms still take the whole line
+    send(&mut input, &doc, "case.upper_line", json!({}));
+    assert_eq!(text(&doc), "LET FOO_BAR = 1;");
+}
+
+#[test]
+fn test_ime_preedit_is_drawn_over_the_document() {
+    let doc = Doc::from_str("ab");
+    let mut input = InputHandler::new();
+    cursor_at(&mut input, &doc, 1);
+
+    let data = json!({ "text": "にほ", "cursor": [3, 6] });
+    send(&mut input, &doc, "editor.ime_preedit", data);
+    let data = json!({ "text": "にほん", "cursor": [0, 9] });
+    send(&mut input, &doc, "editor.ime_preedit", data);
+    assert_eq!(text(&doc), "ab");
+    assert!(input.take_renderer_edits().is_empty());
+
+    let (preedit, clause, positions) = input.preedit().unwrap();
+    assert_eq!(preedit, "にほん");
+    assert_eq!(clause, Some(0..9));
+    assert_eq!(positions.len(), 1);
+    assert_eq!((positions[0].line, positions[0].column), (0, 1));
+
+    send(&mut input, &doc, "editor.ime_cancel", json!({}));
+    assert!(input.preedit().is_none());
+    assert_eq!(text(&doc), "ab");
+    assert!(input.take_renderer_edits().is_empty());
+}
+
+#[test]
+fn test_ime_commit_inserts_once() {
+    let doc = Doc::from_str("ab");
+    let mut input = InputHandler::new();
+    cursor_at(&mut input, &doc, 1);
+
+    let data = json!({ "text": "にほ", "cursor": null });
+    send(&mut input, &doc, "editor.ime_preedit", data);
+    let data = json!({ "text": "日本" });
+    send(&mut input, &doc, "editor.ime_commit", data);
+
+    assert!(input.preedit().is_none());
+    assert_eq!(text(&doc), "a日本b");
+    assert_eq!(cursors(&input, &doc), vec![7]);
+    let edits = input.take_renderer_edits();
+    assert_eq!(edits.len(), 1);
+    assert!(matches!(edits[0], Edit::Insert { pos: 1, .. }));
+
+    assert!(input.undo(&doc));
+    assert_eq!(text(&doc), "ab");
+}
+
+#[test]
+fn test_ime_preedit_is_kept_when_typing_moves_on() {
+    let doc = Doc::from_str("ab");
+    let mut input = InputHandler::new();
+    cursor_at(&mut input, &doc, 2);
+
+    let data = json!({ "text": "é", "cursor": null });
+    send(&mut input, &doc, "editor.ime_preedit", data);
+    send(&mut input, &doc, "editor.insert_newline", json!({}));
+    assert!(input.preedit().is_none());
+    assert_eq!(text(&doc), "abé\n");
+}
+
+/// Select `range` of a single line document, anchored at its start
+fn select(input: &mut InputHandler, doc: &Doc, range: Range<usize>) {
+    cursor_at(input, doc, range.end);
+    let anchor = DocPos {
+        line: 0,
+        column: range.start as u32,
+        byte_offset: range.start,
+    };
+    input.selections_mut_for_test()[0].anchor = anchor;
+}
+
+/// Press at one column of the first line, drag to another and release there
+fn drag(input: &mut InputHandler, doc: &Doc, from: u32, to: u32, copy: bool) {
+    let space_width = Viewport::new(800.0, 600.0, 1.0).metrics.space_width;
+    let x = |column: u32| (column as f32 + 0.5) * space_width;
+    let y = 5.0;
+    send(input, doc, "mouse.press", json!({ "x": x(from), "y": y }));
+    let data = json!({ "from_x": x(from), "from_y": y, "to_x": x(to), "to_y": y });
+    send(input, doc, "mouse.drag", data);
+    assert_eq!(input.drop_caret().map(|pos| pos.column), Some(to));
+    send(input, doc, "mouse.release", json!({ "alt": copy }));
+    assert_eq!(input.drop_caret(), None);
+}
+
+#[test]
+fn test_dropping_text_onto_itself_places_the_cursor() {
+    let doc = Doc::from_str("one two three");
+    let mut input = InputHandler::new();
+    select(&mut input, &doc, 4..7);
+
+    drag(&mut input, &doc, 5, 6, false);
+    assert_eq!(text(&doc), "one two three");
+    assert_eq!(cursors(&input, &doc), vec![6]);
+    assert_eq!(selected(&input, &doc), vec![6..6]);
+}
+
+#[test]
+fn test_dragging_text_moves_it_before_or_after_itself() {
+    let doc = Doc::from_str("one two three");
+    let mut input = InputHandler::new();
+    select(&mut input, &doc, 4..7);
+    drag(&mut input, &doc, 5, 0, false);
+    assert_eq!(text(&doc), "twoone  three");
+    assert_eq!(selected(&input, &doc), vec![0..3]);
+
+    // A new handler, so the press doesn't count as a double click
+    let doc = Doc::from_str("one two three");
+    let mut input = InputHandler::new();
+    select(&mut input, &doc, 4..7);
+    drag(&mut input, &doc, 5, 13, false);
+    assert_eq!(text(&doc), "one  threetwo");
+    assert_eq!(selected(&input, &doc), vec![10..13]);
+}
+
+#[test]
+fn test_alt_dragging_text_copies_it() {
+    let doc = Doc::from_str("one two three");
+    let mut input = InputHandler::new();
+    select(&mut input, &doc, 4..7);
+
+    drag(&mut input, &doc, 5, 0, true);
+    assert_eq!(text(&doc), "twoone two three");
+    assert_eq!(selected(&input, &doc), vec![0..3]);
+}
+
+#[test]
+fn test_find_scope_moves_through_edits() {
+    let doc = Doc::from_str("x\nfoo bar foo\ny");
+    let mut input = InputHandler::new();
+    input.set_find_scope(Some(2..13));
+
+    // Replacing matches inside the scope resizes it, even at its edges
+    input.apply_edits(&doc, vec![(2..5, "quux".into()), (10..13, "q".into())]);
+    assert_eq!(text(&doc), "x\nquux bar q\ny");
+    assert_eq!(input.find_scope(), Some(2..12));
+
+    // Undo moves it back along with the text
+    assert!(input.undo(&doc));
+    assert_eq!(text(&doc), "x\nfoo bar foo\ny");
+    assert_eq!(input.find_scope(), Some(2..13));
+
+    // Typing before it shifts it
+    cursor_at(&mut input, &doc, 0);
+    type_text(&mut input, &doc, "ab");
+    assert_eq!(input.find_scope(), Some(4..15));
+}
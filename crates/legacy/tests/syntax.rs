@@ -232,3 +232,110 @@ fn main() {
         }
     }
 }
+
+/// Indent level of the line starting at `line_start` (content after its indentation)
+fn indent_of(indenter: &Indenter, text: &str, line_start: usize) -> (u32, bool) {
+    let content = line_start + text[line_start..].len() - text[line_start..].trim_start().len();
+    let level = indenter.indent_level(text, line_start, content);
+    (level.level, level.outdent)
+}
+
+#[test]
+fn test_indenter_follows_nested_nodes() {
+    let highlighter = SyntaxHighlighter::new_rust();
+    let text = "fn main() {\n    foo(\n        1,\n    );\n}\n";
+    // Nothing parsed in the background yet - the indenter parses right away
+    let indenter = highlighter
+        .indenter(text)
+        .expect("rust has an indents query");
+
+    let lines: Vec<usize> = std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    assert_eq!(indent_of(&indenter, text, lines[0]), (0, false));
+    assert_eq!(indent_of(&indenter, text, lines[1]), (1, false));
+    assert_eq!(indent_of(&indenter, text, lines[2]), (2, false));
+    assert_eq!(indent_of(&indenter, text, lines[3]), (2, true));
+    assert_eq!(indent_of(&indenter, text, lines[4]), (1, true));
+
+    // A line break about to be inserted between braces
+    let text = "fn main() {}";
+    let indenter = highlighter.indenter(text).unwrap();
+    let level = indenter.indent_level(text, 11, 11);
+    assert_eq!((level.level, level.outdent), (1, true));
+}
+
+#[test]
+fn test_indenter_does_not_depend_on_background_parsing() {
+    let highlighter = SyntaxHighlighter::new_rust();
+    let before = "fn main() {\n}\n";
+    let after = "fn main() {\n    if x {\n        y();\n    }\n}\n";
+    let expected =
+        |indenter: &Indenter| indent_of(indenter, after, after.find("        y").unwrap());
+
+    let fresh = highlighter.indenter(after).unwrap();
+    assert_eq!(expected(&fresh), (2, false));
+
+    // Same answer once an older text was parsed in the background
+    highlighter.request_update(before, 1);
+    std::thread::sleep(std::time::Duration::from_millis(300));
+    let reused = highlighter.indenter(after).unwrap();
+    assert_eq!(expected(&reused), (2, false));
+}
+
+#[test]
+fn test_indenter_for_wasm_grammars() {
+    let highlighter = SyntaxHighlighter::new_toml();
+    let text = "a = [\n  1,\n]\n";
+    let indenter = highlighter
+        .indenter(text)
+        .expect("toml has an indents query");
+    assert_eq!(indent_of(&indenter, text, 6), (1, false));
+    assert_eq!(indent_of(&indenter, text, 11), (1, true));
+}
+
+/// What unwrapping at the first `needle` in `text` removes and keeps, as text
+fn unwrap_at<'a>(text: &'a str, needle: &str) -> Option<(&'a str, &'a str)> {
+    let highlighter = SyntaxHighlighter::new_rust();
+    let start = text.find(needle).unwrap();
+    let [target] = highlighter
+        .unwrap_ranges(text, &[start..start + needle.len()])
+        .try_into()
+        .unwrap();
+    target.map(|(outer, inner)| (&text[outer], &text[inner]))
+}
+
+#[test]
+fn test_unwrap_ranges_of_blocks() {
+    let text = "fn main() {\n    { let x = 1; }\n}\n";
+    assert_eq!(
+        unwrap_at(text, "let x"),
+        Some(("{ let x = 1; }", " let x = 1; "))
+    );
+
+    let text = "fn main() {\n    if ready { go }\n}\n";
+    assert_eq!(unwrap_at(text, "go"), Some(("if ready { go }", " go ")));
+}
+
+#[test]
+fn test_unwrap_ranges_of_call_arguments() {
+    let text = "fn main() {\n    run(Some(value));\n}\n";
+    assert_eq!(unwrap_at(text, "value"), Some(("Some(value)", "value")));
+    assert_eq!(unwrap_at(text, "Some"), Some(("Some(value)", "value")));
+    assert_eq!(
+        unwrap_at(text, "run"),
+        Some(("run(Some(value))", "Some(value)"))
+    );
+}
+
+#[test]
+fn test_unwrap_ranges_refuse_if_else() {
+    // Unwrapping either branch would delete the other one
+    let text = "fn main() {\n    loop { if ready { go } else { wait } }\n}\n";
+    assert_eq!(unwrap_at(text, "go"), None);
+    assert_eq!(unwrap_at(text, "wait"), None);
+
+    let text = "fn main() {\n    if a { x } else if b { y }\n}\n";
+    assert_eq!(unwrap_at(text, "x"), None);
+    assert_eq!(unwrap_at(text, "y"), None);
+}
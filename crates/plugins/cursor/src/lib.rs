@@ -1,4 +1,7 @@
 //! Cursor Plugin - Blinking text cursor with customizable appearance
+//!
+//! Draws one caret per selection (primary + secondary styles) with a shape
+//! chosen per input mode (bar, block, underline, hollow block when unfocused)
 
 use ahash::AHasher;
 use serde::Deserialize;
@@ -13,38 +16,114 @@ use tiny_sdk::{
         BindGroupId, BindGroupLayoutId, BufferId, PipelineId, ShaderModuleId,
         VertexAttributeDescriptor, VertexFormat,
     },
-    CachedBuffer, Capability, Configurable, Initializable, LayoutPos, Library, PaintContext,
-    Paintable, Plugin, PluginError, SetupContext, Updatable, UpdateContext, ViewportInfo,
+    CachedBuffer, Capability, CaretInfo, Configurable, Initializable, LayoutPos, Library,
+    PaintContext, Paintable, Plugin, PluginError, SetupContext, Updatable, UpdateContext, ViewPos,
+    ViewportInfo,
 };
 
+/// Maximum number of carets drawn (vertex buffer is sized for this many)
+const MAX_CARETS: usize = 256;
+
+/// Vertices per caret (hollow block = 4 quads)
+const MAX_VERTICES_PER_CARET: usize = 24;
+
 /// API exposed by cursor plugin
 pub struct CursorAPI {
-    position: LayoutPos,
+    carets: Vec<CaretInfo>,
 }
 
 impl CursorAPI {
     pub fn new() -> Self {
         Self {
-            position: LayoutPos::new(0.0, 0.0),
+            carets: vec![CaretInfo::new(ViewPos::new(0.0, 0.0), 0.0, true)],
         }
     }
 
+    /// Set a single (primary) caret
     pub fn set_position(&mut self, pos: LayoutPos) {
-        self.position = pos;
+        self.carets = vec![CaretInfo::new(ViewPos::new(pos.x.0, pos.y.0), 0.0, true)];
     }
 
+    /// Primary caret position (first caret if none is flagged primary)
     pub fn get_position(&self) -> LayoutPos {
-        self.position
+        self.carets
+            .iter()
+            .find(|c| c.is_primary())
+            .or(self.carets.first())
+            .map_or(LayoutPos::new(0.0, 0.0), |c| {
+                LayoutPos::new(c.pos.x.0, c.pos.y.0)
+            })
+    }
+
+    pub fn set_carets(&mut self, carets: Vec<CaretInfo>) {
+        self.carets = carets;
+    }
+
+    pub fn carets(&self) -> &[CaretInfo] {
+        &self.carets
+    }
+}
+
+/// Caret shape
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CursorShape {
+    /// Thin vertical bar before the character
+    Bar,
+    /// Filled cell over the character
+    Block,
+    /// Line under the character
+    Underline,
+    /// Outlined cell (used when the view isn't focused)
+    HollowBlock,
+}
+
+impl CursorShape {
+    /// Parse a shape name from config ("bar", "block", "underline", "hollow")
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "bar" | "line" => Some(Self::Bar),
+            "block" => Some(Self::Block),
+            "underline" => Some(Self::Underline),
+            "hollow" | "hollow_block" => Some(Self::HollowBlock),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Bar => "bar",
+            Self::Block => "block",
+            Self::Underline => "underline",
+            Self::HollowBlock => "hollow",
+        }
     }
 }
 
 /// Cursor appearance configuration
 #[derive(Debug, Clone)]
 pub struct CursorStyle {
+    /// Primary caret color (RGBA)
     pub color: u32,
+    /// Secondary (multi-cursor) caret color (RGBA)
+    pub secondary_color: u32,
+    /// Bar width (and hollow block border width)
     pub width: f32,
     pub height_scale: f32,
     pub x_offset: f32,
+    /// Underline thickness
+    pub underline_height: f32,
+    /// Alpha multiplier for filled blocks so the character stays readable
+    pub block_alpha: f32,
+}
+
+/// Shape used for each input mode
+#[derive(Debug, Clone)]
+pub struct CursorShapes {
+    pub insert: CursorShape,
+    pub normal: CursorShape,
+    pub visual: CursorShape,
+    pub replace: CursorShape,
+    pub unfocused: CursorShape,
 }
 
 /// Configuration loaded from plugin.toml
@@ -54,6 +133,7 @@ pub struct CursorConfig {
     pub blink_rate: f32,
     pub solid_duration_ms: u64,
     pub style: CursorStyle,
+    pub shapes: CursorShapes,
 }
 
 impl Default for CursorConfig {
@@ -64,9 +144,19 @@ impl Default for CursorConfig {
             solid_duration_ms: 500,
             style: CursorStyle {
                 color: 0xE1E1E1FF,
+                secondary_color: 0xE1E1E1A0,
                 width: 2.0,
                 height_scale: 1.0,
                 x_offset: 0.0,
+                underline_height: 2.0,
+                block_alpha: 0.6,
+            },
+            shapes: CursorShapes {
+                insert: CursorShape::Bar,
+                normal: CursorShape::Block,
+                visual: CursorShape::Block,
+                replace: CursorShape::Underline,
+                unfocused: CursorShape::HollowBlock,
             },
         }
     }
@@ -83,8 +173,13 @@ pub struct CursorPlugin {
     // Current state
     blink_phase: f32,
 
+    // Input mode of the host view (selects the caret shape)
+    mode: String,
+    // Whether the host view has keyboard focus
+    focused: bool,
+
     // Activity tracking for smart blinking
-    last_position: Option<LayoutPos>,
+    last_carets_hash: u64,
     last_active_ms: AtomicU64,
     program_start: Instant,
 
@@ -100,7 +195,6 @@ pub struct CursorPlugin {
     // Uniform cache to avoid redundant writes
     last_visibility: std::sync::atomic::AtomicBool,
     last_viewport_size: std::sync::atomic::AtomicU64, // packed width|height as u32s
-    last_color: std::sync::atomic::AtomicU32,
 }
 
 impl CursorPlugin {
@@ -112,7 +206,9 @@ impl CursorPlugin {
             config: CursorConfig::default(),
             api: CursorAPI::new(),
             blink_phase: 0.0,
-            last_position: None,
+            mode: "insert".to_string(),
+            focused: true,
+            last_carets_hash: 0,
             last_active_ms: AtomicU64::new(0),
             program_start: Instant::now(),
             viewport: ViewportInfo {
@@ -134,26 +230,41 @@ impl CursorPlugin {
             custom_pipeline_id: None,
             last_visibility: std::sync::atomic::AtomicBool::new(false),
             last_viewport_size: std::sync::atomic::AtomicU64::new(0),
-            last_color: std::sync::atomic::AtomicU32::new(0),
         }
     }
 
-    /// Update cursor position
+    /// Update cursor position (single caret)
     pub fn set_position(&mut self, x: f32, y: f32) {
-        let new_pos = LayoutPos::new(x, y);
-
-        // Check if cursor moved
-        if self
-            .last_position
-            .map_or(true, |p| p.x.0 != new_pos.x.0 || p.y.0 != new_pos.y.0)
-        {
-            self.last_position = Some(new_pos);
-            // Update last activity time
+        self.set_carets(vec![CaretInfo::new(ViewPos::new(x, y), 0.0, true)]);
+    }
+
+    /// Update all carets (primary + secondary)
+    pub fn set_carets(&mut self, carets: Vec<CaretInfo>) {
+        let mut hasher = AHasher::default();
+        bytemuck::cast_slice::<CaretInfo, u8>(&carets).hash(&mut hasher);
+        let carets_hash = hasher.finish();
+
+        // Any caret moving counts as activity (keeps carets solid while typing)
+        if carets_hash != self.last_carets_hash {
+            self.last_carets_hash = carets_hash;
             let now_ms = self.program_start.elapsed().as_millis() as u64;
             self.last_active_ms.store(now_ms, Ordering::Relaxed);
         }
 
-        self.api.set_position(new_pos);
+        self.api.set_carets(carets);
+    }
+
+    /// Shape for the current mode and focus state
+    pub fn current_shape(&self) -> CursorShape {
+        if !self.focused {
+            return self.config.shapes.unfocused;
+        }
+        match self.mode.as_str() {
+            "normal" => self.config.shapes.normal,
+            "visual" => self.config.shapes.visual,
+            "replace" => self.config.shapes.replace,
+            _ => self.config.shapes.insert,
+        }
     }
 
     /// Calculate current cursor visibility based on blink state
@@ -177,52 +288,98 @@ impl CursorPlugin {
         }
     }
 
-    /// Create vertex data for cursor rectangle at a specific position
-    fn create_vertices_at_position(
+    /// Create vertex data for one caret at a specific position (physical pixels)
+    fn create_caret_vertices(
         &self,
         viewport: &tiny_sdk::ViewportInfo,
-        position: tiny_sdk::LayoutPos,
-    ) -> Vec<CursorVertex> {
+        position: LayoutPos,
+        caret: &CaretInfo,
+        shape: CursorShape,
+        vertices: &mut Vec<CursorVertex>,
+    ) {
+        let style = &self.config.style;
         // Use viewport's line height (in logical pixels)
-        let line_height = viewport.line_height * self.config.style.height_scale;
+        let line_height = viewport.line_height * style.height_scale;
 
         // Position from host is in logical VIEW pixels (already accounts for scroll)
         // Shader expects physical pixels, so we need to scale
         let scale = viewport.scale_factor;
 
-        let x = (position.x.0 + self.config.style.x_offset) * scale;
+        let x = (position.x.0 + style.x_offset) * scale;
         let y = position.y.0 * scale;
-        let w = self.config.style.width * scale;
         let h = line_height * scale;
+        let cell_width = if caret.char_width > 0.0 {
+            caret.char_width
+        } else {
+            viewport.font_size * 0.6
+        } * scale;
+        let thickness = style.width.max(1.0) * scale;
 
-        // Create two triangles for a quad (no color - it's in uniforms)
-        vec![
-            CursorVertex { position: [x, y] },
-            CursorVertex {
-                position: [x + w, y],
-            },
-            CursorVertex {
-                position: [x, y + h],
-            },
-            CursorVertex {
-                position: [x + w, y],
-            },
-            CursorVertex {
-                position: [x + w, y + h],
-            },
-            CursorVertex {
-                position: [x, y + h],
-            },
-        ]
+        let color = if caret.is_primary() {
+            style.color
+        } else {
+            style.secondary_color
+        };
+
+        let mut quad = |x: f32, y: f32, w: f32, h: f32, color: u32| {
+            vertices.extend_from_slice(&[
+                CursorVertex {
+                    position: [x, y],
+                    color,
+                },
+                CursorVertex {
+                    position: [x + w, y],
+                    color,
+                },
+                CursorVertex {
+                    position: [x, y + h],
+                    color,
+                },
+                CursorVertex {
+                    position: [x + w, y],
+                    color,
+                },
+                CursorVertex {
+                    position: [x + w, y + h],
+                    color,
+                },
+                CursorVertex {
+                    position: [x, y + h],
+                    color,
+                },
+            ]);
+        };
+
+        match shape {
+            CursorShape::Bar => quad(x, y, style.width * scale, h, color),
+            CursorShape::Block => quad(x, y, cell_width, h, scale_alpha(color, style.block_alpha)),
+            CursorShape::Underline => {
+                let underline = style.underline_height * scale;
+                quad(x, y + h - underline, cell_width, underline, color)
+            }
+            CursorShape::HollowBlock => {
+                quad(x, y, cell_width, thickness, color);
+                quad(x, y + h - thickness, cell_width, thickness, color);
+                quad(x, y, thickness, h, color);
+                quad(x + cell_width - thickness, y, thickness, h, color);
+            }
+        }
     }
 }
 
-/// Vertex data for cursor rendering (position only - color is uniform)
+/// Multiply the alpha byte of an RGBA color
+fn scale_alpha(color: u32, factor: f32) -> u32 {
+    let alpha = ((color & 0xFF) as f32 * factor.clamp(0.0, 1.0)) as u32;
+    (color & 0xFFFF_FF00) | alpha
+}
+
+/// Vertex data for cursor rendering (color per vertex - primary/secondary carets differ)
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Pod, Zeroable)]
 #[bytemuck(crate = "self::bytemuck")]
 struct CursorVertex {
     position: [f32; 2],
+    color: u32,
 }
 
 /// Cursor uniforms for shader
@@ -231,8 +388,8 @@ struct CursorVertex {
 #[bytemuck(crate = "self::bytemuck")]
 struct CursorUniforms {
     viewport_size: [f32; 2],
-    color: u32,
     alpha: f32,
+    _padding: f32,
 }
 
 // === Plugin Trait Implementation ===
@@ -251,9 +408,9 @@ tiny_sdk::plugin! {
 
 impl Initializable for CursorPlugin {
     fn setup(&mut self, _ctx: &mut SetupContext) -> Result<(), PluginError> {
-        // Create vertex buffer with caching built-in (6 vertices for a quad)
+        // Create vertex buffer with caching built-in (sized for MAX_CARETS hollow blocks)
         let vertex_size = std::mem::size_of::<CursorVertex>();
-        let buffer_size = (vertex_size * 6) as u64;
+        let buffer_size = (vertex_size * MAX_VERTICES_PER_CARET * MAX_CARETS) as u64;
         self.vertex_buffer = Some(CachedBuffer::new(
             buffer_size,
             wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
@@ -278,12 +435,19 @@ impl Initializable for CursorPlugin {
             shader,
             shader,
             pipeline_layout,
-            8, // stride
-            &[VertexAttributeDescriptor {
-                offset: 0,
-                location: 0,
-                format: VertexFormat::Float32x2,
-            }],
+            12, // stride
+            &[
+                VertexAttributeDescriptor {
+                    offset: 0,
+                    location: 0,
+                    format: VertexFormat::Float32x2,
+                },
+                VertexAttributeDescriptor {
+                    offset: 8,
+                    location: 1,
+                    format: VertexFormat::Uint32,
+                },
+            ],
         ));
 
         eprintln!("Cursor plugin setup complete");
@@ -326,6 +490,49 @@ impl Library for CursorPlugin {
                     Err(PluginError::Other("Invalid args for set_position".into()))
                 }
             }
+            "set_carets" => {
+                // Format: count (u32), then CaretInfo array
+                if args.len() < 4 {
+                    return Err(PluginError::Other("Invalid args: too short".into()));
+                }
+                let count_bytes: [u8; 4] = args[0..4]
+                    .try_into()
+                    .map_err(|_| PluginError::Other("Invalid count".into()))?;
+                let count = u32::from_le_bytes(count_bytes) as usize;
+
+                let caret_size = std::mem::size_of::<CaretInfo>();
+                let expected_size = 4 + count * caret_size;
+                if args.len() < expected_size {
+                    return Err(PluginError::Other(
+                        "Invalid args: incomplete caret data".into(),
+                    ));
+                }
+
+                let carets = args[4..expected_size]
+                    .chunks_exact(caret_size)
+                    .map(bytemuck::pod_read_unaligned::<CaretInfo>)
+                    .collect();
+                self.set_carets(carets);
+                Ok(Vec::new())
+            }
+            "set_mode" => {
+                // Input mode name as UTF-8 ("insert", "normal", "visual", custom)
+                let mode = std::str::from_utf8(args)
+                    .map_err(|_| PluginError::Other("Invalid mode name".into()))?;
+                if self.mode != mode {
+                    self.mode = mode.to_string();
+                }
+                Ok(Vec::new())
+            }
+            "set_focused" => {
+                if args.len() == std::mem::size_of::<u32>() {
+                    let focused: u32 = bytemuck::pod_read_unaligned(args);
+                    self.focused = focused != 0;
+                    Ok(Vec::new())
+                } else {
+                    Err(PluginError::Other("Invalid args for set_focused".into()))
+                }
+            }
             "set_viewport_info" => {
                 if args.len() >= std::mem::size_of::<ViewportInfo>() {
                     let viewport: &ViewportInfo =
@@ -351,45 +558,38 @@ impl Paintable for CursorPlugin {
     }
 
     fn paint(&self, ctx: &PaintContext, render_pass: &mut wgpu::RenderPass) {
-        // Get cursor position from our API (in editor-local coordinates)
-        let mut pos = self.api.get_position();
-
-        // Transform to screen coordinates by adding widget viewport bounds (already includes padding)
-        if let Some(ref widget_viewport) = ctx.widget_viewport {
-            pos = LayoutPos::new(
-                pos.x.0 + widget_viewport.bounds.x.0,
-                pos.y.0 + widget_viewport.bounds.y.0,
-            );
+        let shape = self.current_shape();
+        let carets = self.api.carets();
+
+        // Build vertices for every caret (editor-local -> screen by adding widget bounds, already includes padding)
+        let mut vertices = Vec::with_capacity(carets.len().min(MAX_CARETS) * 6);
+        for caret in carets.iter().take(MAX_CARETS) {
+            let mut pos = LayoutPos::new(caret.pos.x.0, caret.pos.y.0);
+            if let Some(ref widget_viewport) = ctx.widget_viewport {
+                pos = LayoutPos::new(
+                    pos.x.0 + widget_viewport.bounds.x.0,
+                    pos.y.0 + widget_viewport.bounds.y.0,
+                );
+            }
+            self.create_caret_vertices(&ctx.viewport, pos, caret, shape, &mut vertices);
+        }
+        if vertices.is_empty() {
+            return;
         }
 
-        // Create cache key from geometry (not visibility - that's in uniforms)
-        let cache_key = (
-            pos.x.0.to_bits(),
-            pos.y.0.to_bits(),
-            self.config.style.width.to_bits(),
-            self.config.style.height_scale.to_bits(),
-            self.config.style.x_offset.to_bits(),
-            ctx.viewport.scale_factor.to_bits(),
-            ctx.viewport.line_height.to_bits(),
-        );
-
-        // Write vertices only if geometry changed - simplified to one line!
+        // Write vertices only if geometry changed (visibility is in uniforms)
         if let Some(ref vertex_buffer) = self.vertex_buffer {
-            let vertices = self.create_vertices_at_position(&ctx.viewport, pos);
-            if vertices.is_empty() {
-                return;
-            }
-            vertex_buffer.write_if_changed(bytemuck::cast_slice(&vertices), &cache_key);
+            let data: &[u8] = bytemuck::cast_slice(&vertices);
+            vertex_buffer.write_if_changed(data, &data);
         } else {
             eprintln!("Cursor vertex buffer not initialized");
             return;
         }
 
-        // Update uniforms only when they change (viewport, color, visibility alpha)
+        // Update uniforms only when they change (viewport, visibility alpha)
         let visible = self.calculate_visibility();
         let viewport_width = ctx.viewport.physical_size.width;
         let viewport_height = ctx.viewport.physical_size.height;
-        let color = self.config.style.color;
 
         // Pack viewport size for atomic comparison
         let packed_viewport = ((viewport_width as u64) << 32) | (viewport_height as u64);
@@ -397,15 +597,14 @@ impl Paintable for CursorPlugin {
         // Check if any uniform values changed
         let visibility_changed = self.last_visibility.load(Ordering::Relaxed) != visible;
         let viewport_changed = self.last_viewport_size.load(Ordering::Relaxed) != packed_viewport;
-        let color_changed = self.last_color.load(Ordering::Relaxed) != color;
 
-        if visibility_changed || viewport_changed || color_changed {
+        if visibility_changed || viewport_changed {
             let alpha = if visible { 1.0 } else { 0.0 };
 
             let uniforms = CursorUniforms {
                 viewport_size: [viewport_width as f32, viewport_height as f32],
-                color,
                 alpha,
+                _padding: 0.0,
             };
 
             if let Some(uniform_buffer) = self.uniform_buffer {
@@ -416,11 +615,9 @@ impl Paintable for CursorPlugin {
             self.last_visibility.store(visible, Ordering::Relaxed);
             self.last_viewport_size
                 .store(packed_viewport, Ordering::Relaxed);
-            self.last_color.store(color, Ordering::Relaxed);
         }
 
-        // Always draw
-        let vertex_count = 6; // 2 triangles = 6 vertices
+        let vertex_count = vertices.len() as u32;
 
         // Draw with our custom pipeline and bind group
         if let Some(ref vertex_buffer) = self.vertex_buffer {
@@ -430,7 +627,7 @@ impl Paintable for CursorPlugin {
                 if let Some(ref gpu_ctx) = ctx.gpu_context {
                     // Use our custom pipeline
                     gpu_ctx.set_pipeline(render_pass, pipeline_id);
-                    // Use our own uniform bind group (with color/alpha)
+                    // Use our own uniform bind group (with blink alpha)
                     gpu_ctx.set_bind_group(render_pass, 0, uniform_bind_group);
                     // Set our vertex buffer
                     gpu_ctx.set_vertex_buffer(render_pass, 0, vertex_buffer.buffer_id());
@@ -464,6 +661,22 @@ impl Configurable for CursorPlugin {
             height_scale: f32,
             #[serde(default = "default_x_offset")]
             x_offset: f32,
+            #[serde(default = "default_secondary_color")]
+            secondary_color: u32,
+            #[serde(default = "default_underline_height")]
+            underline_height: f32,
+            #[serde(default = "default_block_alpha")]
+            block_alpha: f32,
+            #[serde(default = "default_shape_insert")]
+            shape_insert: String,
+            #[serde(default = "default_shape_normal")]
+            shape_normal: String,
+            #[serde(default = "default_shape_visual")]
+            shape_visual: String,
+            #[serde(default = "default_shape_replace")]
+            shape_replace: String,
+            #[serde(default = "default_shape_unfocused")]
+            shape_unfocused: String,
         }
 
         fn default_blink_enabled() -> bool {
@@ -487,6 +700,30 @@ impl Configurable for CursorPlugin {
         fn default_x_offset() -> f32 {
             0.0
         }
+        fn default_secondary_color() -> u32 {
+            0xE1E1E1A0
+        }
+        fn default_underline_height() -> f32 {
+            2.0
+        }
+        fn default_block_alpha() -> f32 {
+            0.6
+        }
+        fn default_shape_insert() -> String {
+            "bar".to_string()
+        }
+        fn default_shape_normal() -> String {
+            "block".to_string()
+        }
+        fn default_shape_visual() -> String {
+            "block".to_string()
+        }
+        fn default_shape_replace() -> String {
+            "underline".to_string()
+        }
+        fn default_shape_unfocused() -> String {
+            "hollow".to_string()
+        }
 
         // Parse TOML value first (handles syntax errors gracefully)
         let toml_value: toml::Value = match toml::from_str(config_data) {
@@ -511,6 +748,14 @@ impl Configurable for CursorPlugin {
                 color: default_color(),
                 height_scale: default_height_scale(),
                 x_offset: default_x_offset(),
+                secondary_color: default_secondary_color(),
+                underline_height: default_underline_height(),
+                block_alpha: default_block_alpha(),
+                shape_insert: default_shape_insert(),
+                shape_normal: default_shape_normal(),
+                shape_visual: default_shape_visual(),
+                shape_replace: default_shape_replace(),
+                shape_unfocused: default_shape_unfocused(),
             });
 
             // Apply parsed values
@@ -521,6 +766,26 @@ impl Configurable for CursorPlugin {
             self.config.style.color = temp_config.color;
             self.config.style.height_scale = temp_config.height_scale;
             self.config.style.x_offset = temp_config.x_offset;
+            self.config.style.secondary_color = temp_config.secondary_color;
+            self.config.style.underline_height = temp_config.underline_height;
+            self.config.style.block_alpha = temp_config.block_alpha;
+
+            // Unknown (or missing) shape names fall back to the defaults
+            let shape = |name: &str, default: CursorShape| {
+                CursorShape::parse(name).unwrap_or_else(|| {
+                    if !name.is_empty() {
+                        eprintln!("⚠️  Unknown cursor shape '{}'. Using default.", name);
+                    }
+                    default
+                })
+            };
+            self.config.shapes = CursorShapes {
+                insert: shape(&temp_config.shape_insert, CursorShape::Bar),
+                normal: shape(&temp_config.shape_normal, CursorShape::Block),
+                visual: shape(&temp_config.shape_visual, CursorShape::Block),
+                replace: shape(&temp_config.shape_replace, CursorShape::Underline),
+                unfocused: shape(&temp_config.shape_unfocused, CursorShape::HollowBlock),
+            };
 
             eprintln!(
                 "Cursor: plugin config updated: width={}, color={:#010x}, blink_rate={}",
@@ -537,14 +802,22 @@ impl Configurable for CursorPlugin {
 
     fn get_config(&self) -> Option<String> {
         // Convert current config back to TOML
-        format!("[config]\nblink_enabled = {}\nblink_rate = {}\nsolid_duration_ms = {}\nwidth = {}\ncolor = {:#010x}\nheight_scale = {}\nx_offset = {}",
+        format!("[config]\nblink_enabled = {}\nblink_rate = {}\nsolid_duration_ms = {}\nwidth = {}\ncolor = {:#010x}\nsecondary_color = {:#010x}\nheight_scale = {}\nx_offset = {}\nunderline_height = {}\nblock_alpha = {}\nshape_insert = \"{}\"\nshape_normal = \"{}\"\nshape_visual = \"{}\"\nshape_replace = \"{}\"\nshape_unfocused = \"{}\"",
                 self.config.blink_enabled,
                 self.config.blink_rate,
                 self.config.solid_duration_ms,
                 self.config.style.width,
                 self.config.style.color,
+                self.config.style.secondary_color,
                 self.config.style.height_scale,
-                self.config.style.x_offset).into()
+                self.config.style.x_offset,
+                self.config.style.underline_height,
+                self.config.style.block_alpha,
+                self.config.shapes.insert.name(),
+                self.config.shapes.normal.name(),
+                self.config.shapes.visual.name(),
+                self.config.shapes.replace.name(),
+                self.config.shapes.unfocused.name()).into()
     }
 }
 
@@ -566,11 +839,16 @@ impl CursorPlugin {
         self
     }
 
-    /// Get current cursor position
+    /// Get current (primary) cursor position
     pub fn position(&self) -> LayoutPos {
         self.api.get_position()
     }
 
+    /// Get all carets
+    pub fn carets(&self) -> &[CaretInfo] {
+        self.api.carets()
+    }
+
     /// Check if cursor is currently visible
     pub fn is_visible(&self) -> bool {
         self.calculate_visibility()
@@ -1,16 +1,18 @@
 // Vertex shader
 struct VertexInput {
     @location(0) position: vec2<f32>,
+    @location(1) color: u32,
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
+    @location(0) @interpolate(flat) color: u32,
 }
 
 struct Uniforms {
     viewport_size: vec2<f32>,
-    color: u32,
     alpha: f32,  // 0.0 = invisible (blinking), 1.0 = visible
+    _padding: f32,
 }
 
 @group(0) @binding(0)
@@ -25,6 +27,7 @@ fn vs_main(input: VertexInput) -> VertexOutput {
     let y = 1.0 - (input.position.y / uniforms.viewport_size.y) * 2.0;
 
     out.clip_position = vec4<f32>(x, y, 0.0, 1.0);
+    out.color = input.color;
 
     return out;
 }
@@ -32,11 +35,12 @@ fn vs_main(input: VertexInput) -> VertexOutput {
 // Fragment shader
 @fragment
 fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
-    // Unpack color from u32
-    let r = f32((uniforms.color >> 24u) & 0xFFu) / 255.0;
-    let g = f32((uniforms.color >> 16u) & 0xFFu) / 255.0;
-    let b = f32((uniforms.color >> 8u) & 0xFFu) / 255.0;
+    // Unpack RGBA color from u32 (primary/secondary caret color per vertex)
+    let r = f32((input.color >> 24u) & 0xFFu) / 255.0;
+    let g = f32((input.color >> 16u) & 0xFFu) / 255.0;
+    let b = f32((input.color >> 8u) & 0xFFu) / 255.0;
+    let a = f32(input.color & 0xFFu) / 255.0;
 
     // Apply visibility alpha (for blinking)
-    return vec4<f32>(r, g, b, uniforms.alpha);
+    return vec4<f32>(r, g, b, a * uniforms.alpha);
 }
\ No newline at end of file
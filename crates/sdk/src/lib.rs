@@ -18,9 +18,9 @@ pub use crate::traits::{
 
 // Re-export all types
 pub use crate::types::{
-    ByteRange, Color, DocPos, GlyphInstance, GlyphInstances, LayoutPos, LayoutRect, LogicalPixels,
-    LogicalPos, LogicalRect, LogicalSize, PhysicalPixels, PhysicalPos, PhysicalSize, PhysicalSizeF,
-    ViewPos, ViewRect, ViewportInfo, WidgetViewport,
+    ByteRange, CaretInfo, Color, DocPos, GlyphInstance, GlyphInstances, LayoutPos, LayoutRect,
+    LogicalPixels, LogicalPos, LogicalRect, LogicalSize, PhysicalPixels, PhysicalPos, PhysicalSize,
+    PhysicalSizeF, ViewPos, ViewRect, ViewportInfo, WidgetViewport,
 };
 
 // Re-export services
@@ -451,6 +451,37 @@ impl WidgetViewport {
     }
 }
 
+// === Caret Info for Plugins ===
+
+/// Caret sent to cursor plugins (`set_carets`), one per selection
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default, Pod, Zeroable)]
+pub struct CaretInfo {
+    /// Top-left of the caret cell in view coordinates (padding applied)
+    pub pos: ViewPos,
+    /// Width of the character under the caret (for block/underline shapes)
+    pub char_width: f32,
+    /// Bit flags (see `CaretInfo::PRIMARY`)
+    pub flags: u32,
+}
+
+impl CaretInfo {
+    /// The primary selection's caret
+    pub const PRIMARY: u32 = 1;
+
+    pub fn new(pos: ViewPos, char_width: f32, primary: bool) -> Self {
+        Self {
+            pos,
+            char_width,
+            flags: if primary { Self::PRIMARY } else { 0 },
+        }
+    }
+
+    pub fn is_primary(&self) -> bool {
+        self.flags & Self::PRIMARY != 0
+    }
+}
+
 // === Viewport Info for Plugins ===
 
 /// Simplified viewport information for plugins
@@ -367,6 +367,17 @@ impl std::fmt::Debug for Content {
     }
 }
 
+/// Visual column after `ch` at `column`: tabs advance to the next multiple of
+/// `tab_width`, everything else by one
+pub fn advance_column(column: u32, ch: char, tab_width: u32) -> u32 {
+    if ch == '\t' {
+        let tab_width = tab_width.max(1);
+        (column / tab_width + 1) * tab_width
+    } else {
+        column + 1
+    }
+}
+
 pub type Rect = LayoutRect;
 pub type Point = LayoutPos;
 
@@ -1016,31 +1027,50 @@ impl Tree {
         self.root.sums().len_utf16
     }
 
+    /// Byte offset of a position whose column is a char index within its line
     pub fn doc_pos_to_byte(&self, pos: DocPos) -> usize {
-        self.doc_pos_to_byte_with_tab_width(pos, 4) // Default tab width
-    }
-
-    pub fn doc_pos_to_byte_with_tab_width(&self, pos: DocPos, _tab_width: u32) -> usize {
-        if let Some(line_start) = self.line_to_byte(pos.line) {
-            let line_end = self.line_to_byte(pos.line + 1).unwrap_or(self.byte_count());
-            let line_text = self.get_text_slice(line_start..line_end);
-
-            let mut byte_offset = 0;
-            let mut char_count = 0;
+        let Some(line_start) = self.line_to_byte(pos.line) else {
+            return pos.byte_offset;
+        };
+        let line_end = self.line_to_byte(pos.line + 1).unwrap_or(self.byte_count());
+        let line_text = self.get_text_slice(line_start..line_end);
+        let byte_offset: usize = line_text
+            .chars()
+            .take(pos.column as usize)
+            .map(char::len_utf8)
+            .sum();
+        line_start + byte_offset
+    }
+
+    /// Byte offset of a position whose column is a visual column, with tabs advancing
+    /// to the next multiple of `tab_width`. Columns inside a tab land before it, and
+    /// columns past the end of the line at its end.
+    pub fn doc_pos_to_byte_with_tab_width(&self, pos: DocPos, tab_width: u32) -> usize {
+        let Some(line_start) = self.line_to_byte(pos.line) else {
+            return pos.byte_offset;
+        };
+        let line_end = self.line_to_byte(pos.line + 1).unwrap_or(self.byte_count());
+        let line_text = self.get_text_slice(line_start..line_end);
 
-            // pos.column is now a character index, not a visual column
-            for ch in line_text.chars() {
-                if char_count >= pos.column {
-                    break;
-                }
-                byte_offset += ch.len_utf8();
-                char_count += 1; // Each character (including tab) counts as 1
+        let mut byte_offset = 0;
+        let mut column = 0;
+        for ch in line_text.chars() {
+            let next = advance_column(column, ch, tab_width);
+            if ch == '\n' || next > pos.column {
+                break;
             }
-
-            line_start + byte_offset
-        } else {
-            pos.byte_offset
+            column = next;
+            byte_offset += ch.len_utf8();
         }
+        line_start + byte_offset
+    }
+
+    /// Visual column of a position (column as a char index), tabs expanded to `tab_width`
+    pub fn visual_column(&self, pos: DocPos, tab_width: u32) -> u32 {
+        self.line_text(pos.line)
+            .chars()
+            .take(pos.column as usize)
+            .fold(0, |column, ch| advance_column(column, ch, tab_width))
     }
 
     /// Get the text of a line (including newline if present)
@@ -1932,3 +1962,32 @@ fn validate_tree_structure(node: &Node) -> bool {
 fn validate_tree_structure(_node: &Node) -> bool {
     true // No-op in release builds
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pos(line: u32, column: u32) -> DocPos {
+        DocPos {
+            line,
+            column,
+            byte_offset: 0,
+        }
+    }
+
+    #[test]
+    fn visual_columns_follow_tab_width() {
+        let tree = Tree::from_str("a\tb\n\t\tc");
+        assert_eq!(tree.visual_column(pos(0, 2), 4), 4);
+        assert_eq!(tree.visual_column(pos(0, 2), 8), 8);
+        assert_eq!(tree.visual_column(pos(1, 2), 2), 4);
+
+        // Columns inside a tab land before it
+        assert_eq!(tree.doc_pos_to_byte_with_tab_width(pos(0, 3), 4), 1);
+        assert_eq!(tree.doc_pos_to_byte_with_tab_width(pos(0, 4), 4), 2);
+        assert_eq!(tree.doc_pos_to_byte_with_tab_width(pos(1, 4), 2), 6);
+        // Past the end of a line
+        assert_eq!(tree.doc_pos_to_byte_with_tab_width(pos(0, 20), 4), 3);
+        assert_eq!(tree.doc_pos_to_byte(pos(0, 2)), 2);
+    }
+}
@@ -0,0 +1,16 @@
+; Nodes that can be folded when they span several lines
+[
+  (block)
+  (declaration_list)
+  (field_declaration_list)
+  (ordered_field_declaration_list)
+  (enum_variant_list)
+  (field_initializer_list)
+  (match_block)
+  (use_list)
+  (arguments)
+  (parameters)
+  (token_tree)
+  (array_expression)
+  (block_comment)
+] @fold
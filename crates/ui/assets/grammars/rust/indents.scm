@@ -0,0 +1,23 @@
+; Nodes whose contents are indented one level
+[
+  (block)
+  (declaration_list)
+  (field_declaration_list)
+  (ordered_field_declaration_list)
+  (enum_variant_list)
+  (field_initializer_list)
+  (match_block)
+  (use_list)
+  (arguments)
+  (parameters)
+  (token_tree)
+  (array_expression)
+  (tuple_expression)
+] @indent
+
+; Closing delimiters at the start of a line sit one level further out
+[
+  "}"
+  "]"
+  ")"
+] @outdent
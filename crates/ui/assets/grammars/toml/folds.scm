@@ -0,0 +1,7 @@
+; Nodes that can be folded when they span several lines
+[
+  (table)
+  (table_array_element)
+  (array)
+  (inline_table)
+] @fold
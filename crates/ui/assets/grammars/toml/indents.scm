@@ -0,0 +1,11 @@
+; Nodes whose contents are indented one level
+[
+  (array)
+  (inline_table)
+] @indent
+
+; Closing delimiters at the start of a line sit one level further out
+[
+  "]"
+  "}"
+] @outdent
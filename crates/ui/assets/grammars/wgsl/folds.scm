@@ -0,0 +1,6 @@
+; Nodes that can be folded when they span several lines
+[
+  (compound_statement)
+  (struct_declaration)
+  (block_comment)
+] @fold
@@ -11,6 +11,7 @@
 
 use std::sync::Arc;
 
+use tiny_core::tree::advance_column;
 use tiny_core::DocTree;
 use tiny_sdk::{
     DocPos, LayoutPos, LayoutRect, LogicalSize, PhysicalPos, PhysicalSize, ViewPos, ViewRect,
@@ -118,12 +119,7 @@ impl TextMetrics {
             if byte_pos >= byte_in_line {
                 break;
             }
-            if ch == '\t' {
-                // Tab advances to next tab stop
-                column = ((column / self.tab_stops) + 1) * self.tab_stops;
-            } else {
-                column += 1;
-            }
+            column = advance_column(column, ch, self.tab_stops);
             byte_pos += ch.len_utf8();
         }
         column
@@ -131,6 +127,293 @@ impl TextMetrics {
 
 }
 
+// === Soft Wrap ===
+
+/// Where a document line wraps onto further display rows
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineWraps {
+    /// Char columns where each continuation row starts (ascending)
+    pub breaks: Vec<u32>,
+    /// Visual columns continuation rows are indented by
+    pub indent: u32,
+}
+
+impl LineWraps {
+    /// Row within the line that a char column is displayed on
+    pub fn row_of(&self, column: u32) -> u32 {
+        self.breaks.partition_point(|&start| start <= column) as u32
+    }
+
+    /// Char columns displayed on a row (the last row ends at `line_chars`)
+    pub fn row_columns(&self, row: u32, line_chars: u32) -> std::ops::Range<u32> {
+        let row = row as usize;
+        let start = if row == 0 { 0 } else { self.breaks[row - 1] };
+        let end = self.breaks.get(row).copied().unwrap_or(line_chars);
+        start..end
+    }
+}
+
+/// Wrap a line (without its line break) to `columns` visual columns
+///
+/// Rows break after whitespace where possible and mid-word otherwise. Whitespace may
+/// hang past the wrap column. Continuation rows can repeat the line's indentation,
+/// as long as that leaves them at least half the width.
+pub fn wrap_line(
+    line_text: &str,
+    columns: u32,
+    tab_stops: u32,
+    indent_continuations: bool,
+) -> LineWraps {
+    let columns = columns.max(1);
+    let advance = |column: u32, ch: char| advance_column(column, ch, tab_stops);
+
+    let indent = if indent_continuations {
+        let indent = line_text
+            .chars()
+            .take_while(|ch| *ch == ' ' || *ch == '\t')
+            .fold(0, advance);
+        if indent * 2 <= columns {
+            indent
+        } else {
+            0
+        }
+    } else {
+        0
+    };
+
+    let mut breaks = Vec::new();
+    // Row start as (char column, visual column), and the width available to the row
+    let mut row_start = (0, 0);
+    let mut row_width = columns;
+    // Latest char column following whitespace in this row, with its visual column
+    // (the line's indentation isn't a place to break)
+    let mut break_after_space: Option<(u32, u32)> = None;
+    let mut in_indent = true;
+    let mut visual = 0;
+    for (column, ch) in line_text.chars().enumerate() {
+        let column = column as u32;
+        let next = advance(visual, ch);
+        while !ch.is_whitespace() && next - row_start.1 > row_width && column > row_start.0 {
+            let start = break_after_space
+                .filter(|&(start, _)| start > row_start.0)
+                .unwrap_or((column, visual));
+            breaks.push(start.0);
+            row_start = start;
+            row_width = columns - indent;
+            break_after_space = None;
+        }
+        if !ch.is_whitespace() {
+            in_indent = false;
+        } else if !in_indent {
+            break_after_space = Some((column + 1, next));
+        }
+        visual = next;
+    }
+
+    LineWraps { breaks, indent }
+}
+
+// === Display Lines (folding and soft wrap) ===
+
+/// Maps document lines to display lines when folds hide part of the document
+/// or soft wrap spreads lines over several display rows
+///
+/// Without hidden lines or wraps every document line is a display line. A hidden range
+/// collapses into the line before it (the fold's header line).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DisplayMap {
+    /// Hidden document lines, sorted, non-overlapping and never starting at line 0
+    hidden: Vec<std::ops::Range<u32>>,
+    /// Soft wrapped rows, None when lines don't wrap
+    wraps: Option<Arc<WrapRows>>,
+}
+
+/// Soft wrap rows of a whole document
+#[derive(Debug, PartialEq, Eq)]
+struct WrapRows {
+    /// Lines that wrap, by line number
+    lines: Vec<(u32, LineWraps)>,
+    /// First display row of every document line (hidden lines take none), then the row count
+    row_starts: Vec<u32>,
+}
+
+impl DisplayMap {
+    /// Build from hidden line ranges in any order (overlapping ranges are merged)
+    pub fn new(mut hidden: Vec<std::ops::Range<u32>>) -> Self {
+        hidden.retain(|range| range.start > 0 && range.end > range.start);
+        hidden.sort_by_key(|range| range.start);
+
+        let mut merged: Vec<std::ops::Range<u32>> = Vec::with_capacity(hidden.len());
+        for range in hidden {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+        Self {
+            hidden: merged,
+            wraps: None,
+        }
+    }
+
+    /// Add soft wraps (`lines` by line number) for a document with `total_lines` lines
+    pub fn with_wraps(mut self, lines: Vec<(u32, LineWraps)>, total_lines: u32) -> Self {
+        let mut row_starts = Vec::with_capacity(total_lines as usize + 1);
+        let mut wrapped = lines.iter().peekable();
+        let mut row = 0;
+        for line in 0..total_lines {
+            row_starts.push(row);
+            while wrapped.next_if(|(wrapped, _)| *wrapped < line).is_some() {}
+            if self.is_hidden(line) {
+                continue;
+            }
+            row += 1;
+            if let Some((_, wraps)) = wrapped.peek().filter(|(wrapped, _)| *wrapped == line) {
+                row += wraps.breaks.len() as u32;
+            }
+        }
+        row_starts.push(row);
+
+        self.wraps = Some(Arc::new(WrapRows { lines, row_starts }));
+        self
+    }
+
+    /// Whether no lines are hidden or wrapped
+    pub fn is_empty(&self) -> bool {
+        self.hidden.is_empty() && self.wraps.is_none()
+    }
+
+    /// Whether lines are soft wrapped
+    pub fn is_wrapped(&self) -> bool {
+        self.wraps.is_some()
+    }
+
+    /// Lines that wrap with where they wrap, by line number (empty without soft wrap)
+    pub fn wrapped_lines(&self) -> &[(u32, LineWraps)] {
+        self.wraps
+            .as_ref()
+            .map_or(&[], |wraps| wraps.lines.as_slice())
+    }
+
+    /// Where a document line wraps, None if it fits on one row
+    pub fn line_wraps(&self, line: u32) -> Option<&LineWraps> {
+        let wraps = self.wraps.as_ref()?;
+        let idx = wraps
+            .lines
+            .binary_search_by_key(&line, |(wrapped, _)| *wrapped)
+            .ok()?;
+        Some(&wraps.lines[idx].1)
+    }
+
+    /// Number of display rows a document line takes (hidden lines still count one)
+    pub fn rows_in_line(&self, line: u32) -> u32 {
+        1 + self
+            .line_wraps(line)
+            .map_or(0, |wraps| wraps.breaks.len() as u32)
+    }
+
+    /// Display row of a position (its line's first row plus the row it wraps onto)
+    pub fn display_row(&self, line: u32, column: u32) -> u32 {
+        let row = match self.line_wraps(line) {
+            Some(wraps) if !self.is_hidden(line) => wraps.row_of(column),
+            _ => 0,
+        };
+        self.doc_to_display(line) + row
+    }
+
+    /// Hidden document line ranges in order
+    pub fn hidden_ranges(&self) -> &[std::ops::Range<u32>] {
+        &self.hidden
+    }
+
+    /// Whether a document line is folded away
+    pub fn is_hidden(&self, line: u32) -> bool {
+        self.hidden_range_at(line).is_some()
+    }
+
+    /// The hidden range containing a document line
+    pub fn hidden_range_at(&self, line: u32) -> Option<std::ops::Range<u32>> {
+        let idx = self.hidden.partition_point(|range| range.end <= line);
+        self.hidden
+            .get(idx)
+            .filter(|range| range.start <= line)
+            .cloned()
+    }
+
+    /// Display line of a document line (hidden lines map to their fold's header)
+    /// With soft wrap this is the line's first display row
+    pub fn doc_to_display(&self, line: u32) -> u32 {
+        if let Some(wraps) = &self.wraps {
+            let line = self.visible_line(line, false);
+            let last = wraps.row_starts.len() as u32 - 1;
+            return match wraps.row_starts.get(line as usize) {
+                Some(&row) if line < last => row,
+                _ => wraps.row_starts[last as usize] + (line - last),
+            };
+        }
+
+        let mut hidden_before = 0;
+        for range in &self.hidden {
+            if range.end <= line {
+                hidden_before += range.end - range.start;
+            } else if range.start <= line {
+                return range.start - 1 - hidden_before;
+            } else {
+                break;
+            }
+        }
+        line - hidden_before
+    }
+
+    /// Document line shown at a display line
+    pub fn display_to_doc(&self, display_line: u32) -> u32 {
+        if let Some(wraps) = &self.wraps {
+            let total_rows = *wraps.row_starts.last().unwrap_or(&0);
+            let lines = wraps.row_starts.len() as u32 - 1;
+            if display_line >= total_rows {
+                return lines + (display_line - total_rows);
+            }
+            // Hidden lines take no rows, so the last line starting at or before the row
+            // is the visible one
+            return wraps.row_starts.partition_point(|&row| row <= display_line) as u32 - 1;
+        }
+
+        let mut line = display_line;
+        for range in &self.hidden {
+            if range.start <= line {
+                line += range.end - range.start;
+            } else {
+                break;
+            }
+        }
+        line
+    }
+
+    /// Number of display lines for a document with `total_lines` lines
+    pub fn display_line_count(&self, total_lines: u32) -> u32 {
+        if let Some(wraps) = &self.wraps {
+            let lines = wraps.row_starts.len() as u32 - 1;
+            return wraps.row_starts[lines as usize] + total_lines.saturating_sub(lines);
+        }
+
+        let hidden: u32 = self
+            .hidden
+            .iter()
+            .map(|range| range.end.min(total_lines).saturating_sub(range.start))
+            .sum();
+        total_lines - hidden.min(total_lines)
+    }
+
+    /// Nearest visible document line at or after `line` (forward) or at or before it
+    pub fn visible_line(&self, line: u32, forward: bool) -> u32 {
+        match self.hidden_range_at(line) {
+            Some(range) if forward => range.end,
+            Some(range) => range.start - 1,
+            None => line,
+        }
+    }
+}
+
 // === THE Viewport - Central transformation hub ===
 
 /// Manages all coordinate transformations
@@ -164,6 +447,8 @@ pub struct Viewport {
     // === Line rendering mode ===
     /// How to render lines (with horizontal scroll or soft wrap)
     pub line_mode: LineMode,
+    /// Document lines hidden by folds
+    pub display_map: DisplayMap,
 
     // === Cached document bounds ===
     /// Cached document bounds (width, height) to avoid recalculation
@@ -199,6 +484,7 @@ impl Viewport {
             scale_factor: info.scale_factor,
             metrics: TextMetrics::with_line_height(info.line_height),
             line_mode: LineMode::default(),
+            display_map: DisplayMap::default(),
             cached_doc_bounds: None,
             cached_bounds_version: 0,
             cached_longest_line_chars: 0,
@@ -224,6 +510,7 @@ impl Viewport {
             scale_factor,
             metrics: TextMetrics::new(13.0), // Default 14pt font
             line_mode: LineMode::default(),  // Default to no wrap
+            display_map: DisplayMap::default(),
             cached_doc_bounds: None,
             cached_bounds_version: 0,
             cached_longest_line_chars: 0,
@@ -232,7 +519,9 @@ impl Viewport {
     }
 
     pub fn set_font_size(&mut self, font_size: f32) {
+        let tab_stops = self.metrics.tab_stops;
         self.metrics = TextMetrics::new(font_size);
+        self.metrics.tab_stops = tab_stops;
     }
 
     /// Update metrics from a source of truth (single-direction data flow)
@@ -251,6 +540,7 @@ impl Viewport {
             scale_factor: self.scale_factor,
             metrics: self.metrics.clone(),
             line_mode: self.line_mode.clone(),
+            display_map: DisplayMap::default(),
             cached_doc_bounds: None,
             cached_bounds_version: 0,
             cached_longest_line_chars: 0,
@@ -296,8 +586,32 @@ impl Viewport {
     ///
     /// PANICS if font_system is not set - call set_font_system() first!
     pub fn doc_to_layout_with_text(&self, pos: DocPos, line_text: &str) -> LayoutPos {
+        let mut x = self.column_x(line_text, pos.column);
+
+        // Continuation rows of a wrapped line start at the wrap indent
+        if let Some(wraps) = self.display_map.line_wraps(pos.line) {
+            let row = wraps.row_of(pos.column) as usize;
+            if row > 0 {
+                x += self.wrap_indent_x(wraps) - self.column_x(line_text, wraps.breaks[row - 1]);
+            }
+        }
+
+        // Just convert to logical pixels - NO positioning (folded lines take no space)
+        let display_line = self.display_map.display_row(pos.line, pos.column);
+        LayoutPos::new(x, display_line as f32 * self.metrics.line_height)
+    }
+
+    /// X offset of a char column within an unwrapped line
+    ///
+    /// PANICS if font_system is not set - call set_font_system() first!
+    fn column_x(&self, line_text: &str, column: u32) -> f32 {
         let font_system = self.font_system.as_ref()
             .expect("font_system must be set before calling doc_to_layout_with_text - call set_font_system() first!");
+        let pos = DocPos {
+            byte_offset: 0,
+            line: 0,
+            column,
+        };
 
         // Build the text up to the cursor position (pos.column is character index)
         let mut expanded = String::new();
@@ -311,8 +625,7 @@ impl Viewport {
 
             if ch == '\t' {
                 // Add spaces to reach next tab stop
-                let next_tab_stop =
-                    ((visual_column / self.metrics.tab_stops) + 1) * self.metrics.tab_stops;
+                let next_tab_stop = advance_column(visual_column, ch, self.metrics.tab_stops);
                 let spaces_to_add = next_tab_stop - visual_column;
                 for _ in 0..spaces_to_add {
                     expanded.push(' ');
@@ -326,7 +639,7 @@ impl Viewport {
         }
 
         // Now measure the expanded text
-        let x = if !expanded.is_empty() {
+        if !expanded.is_empty() {
             let layout = font_system.layout_text_scaled(
                 &expanded,
                 self.metrics.font_size,
@@ -335,10 +648,12 @@ impl Viewport {
             layout.width / self.scale_factor
         } else {
             0.0
-        };
+        }
+    }
 
-        // Just convert to logical pixels - NO positioning
-        LayoutPos::new(x, pos.line as f32 * self.metrics.line_height)
+    /// Indentation of a wrapped line's continuation rows in logical pixels
+    pub fn wrap_indent_x(&self, wraps: &LineWraps) -> f32 {
+        wraps.indent as f32 * self.metrics.space_width
     }
 
     /// Layout position to view position (apply scroll)
@@ -400,7 +715,9 @@ impl Viewport {
         let doc_x = pos.x.0.max(0.0);
         let doc_y = pos.y.0.max(0.0);
 
-        let line = (doc_y / self.metrics.line_height) as u32;
+        let line = self
+            .display_map
+            .display_to_doc((doc_y / self.metrics.line_height) as u32);
         let column = (doc_x / self.metrics.space_width) as u32;
 
         DocPos {
@@ -418,7 +735,8 @@ impl Viewport {
         let doc_y = pos.y.0.max(0.0);
 
         // Clamp line to valid document bounds
-        let unclamped_line = (doc_y / self.metrics.line_height) as u32;
+        let display_row = (doc_y / self.metrics.line_height) as u32;
+        let unclamped_line = self.display_map.display_to_doc(display_row);
         let total_lines = tree.line_count();
         let line = if total_lines > 0 {
             unclamped_line.min(total_lines - 1)
@@ -426,6 +744,20 @@ impl Viewport {
             0
         };
 
+        if let Some(wraps) = self.display_map.line_wraps(line) {
+            if self.font_system.is_some() {
+                let row = display_row
+                    .saturating_sub(self.display_map.doc_to_display(line))
+                    .min(wraps.breaks.len() as u32);
+                let column = self.wrapped_row_hit_test(tree, line, wraps, row, doc_x);
+                return DocPos {
+                    byte_offset: 0,
+                    line,
+                    column,
+                };
+            }
+        }
+
         let column = if let Some(font_system) = &self.font_system {
             // Get the line text and use font system's accurate hit testing
             if let Some(line_start) = tree.line_to_byte(line) {
@@ -471,6 +803,44 @@ impl Viewport {
         }
     }
 
+    /// Char column at an x position on one row of a soft wrapped line
+    /// Clicks past the end of a continuation row land before its last character
+    fn wrapped_row_hit_test(
+        &self,
+        tree: &DocTree,
+        line: u32,
+        wraps: &LineWraps,
+        row: u32,
+        x: f32,
+    ) -> u32 {
+        let Some(font_system) = &self.font_system else {
+            return 0;
+        };
+        let line_text = tree.line_text(line);
+        let line_text = line_text.trim_end_matches('\n').trim_end_matches('\r');
+        let line_chars = line_text.chars().count() as u32;
+        let columns = wraps.row_columns(row, line_chars);
+
+        // Hit test the whole line, shifted to where the row starts in it
+        let mut line_x = x;
+        if row > 0 {
+            line_x += self.column_x(line_text, columns.start) - self.wrap_indent_x(wraps);
+        }
+        let column = font_system.hit_test_line_shaped(
+            line_text,
+            self.metrics.font_size,
+            self.scale_factor,
+            line_x.max(0.0),
+        );
+
+        let last = if (row as usize) < wraps.breaks.len() {
+            columns.end.saturating_sub(1).max(columns.start)
+        } else {
+            columns.end
+        };
+        column.clamp(columns.start, last)
+    }
+
     /// Physical to layout position
     pub fn physical_to_layout(&self, pos: PhysicalPos) -> LayoutPos {
         self.view_to_layout(self.physical_to_view(pos))
@@ -559,14 +929,14 @@ impl Viewport {
         self.scroll.x.0 = target_x.max(0.0);
     }
 
-    /// Get visible line range
+    /// Get visible line range (document lines, including any folded away in between)
     pub fn visible_lines(&self) -> std::ops::Range<u32> {
         let first_line = (self.scroll.y / self.metrics.line_height) as u32;
         // Use bounds.height for the viewport height (works for both root and child viewports)
         let viewport_height = self.bounds.height.0;
         let last_line = ((self.scroll.y + viewport_height) / self.metrics.line_height) as u32 + 1;
 
-        first_line..last_line
+        self.display_map.display_to_doc(first_line)..self.display_map.display_to_doc(last_line)
     }
 
     /// Get visible line range with margins for smooth scrolling
@@ -609,10 +979,19 @@ impl Viewport {
 
     /// Get document bounds with caching
     pub fn get_document_bounds(&mut self, tree: &DocTree) -> (f32, f32) {
+        // Folding and wrapping change the height without a new document version
+        let total_lines = self.display_map.display_line_count(tree.line_count());
+        let doc_height = (total_lines as f32 + 5.0) * self.metrics.line_height;
+
+        // Wrapped lines never extend past the wrap width
+        if let LineMode::SoftWrap { wrap_width } = self.line_mode {
+            return (wrap_width, doc_height);
+        }
+
         // Check cache first - if version hasn't changed, use cached bounds
-        if let Some(bounds) = self.cached_doc_bounds {
+        if let Some((doc_width, _)) = self.cached_doc_bounds {
             if self.cached_bounds_version == tree.version {
-                return bounds;
+                return (doc_width, doc_height);
             }
         }
 
@@ -628,9 +1007,6 @@ impl Viewport {
             }
         }
 
-        let total_lines = tree.line_count();
-        let doc_height = (total_lines as f32 + 5.0) * self.metrics.line_height;
-
         // Estimate line width without measuring (measuring is expensive)
         let max_line_width = longest_line_chars as f32 * self.metrics.space_width;
 
@@ -747,12 +1123,22 @@ impl Viewport {
                     x_offset,
                 }
             }
-            LineMode::SoftWrap { wrap_width: _ } => {
-                // For now, return as single visual line - will implement wrapping later
-                // TODO: Implement proper line wrapping
-                VisibleLineContent::Wrapped {
-                    visual_lines: vec![line_text.to_string()],
-                }
+            LineMode::SoftWrap { wrap_width } => {
+                let line_text = line_text.trim_end_matches('\n').trim_end_matches('\r');
+                let columns = (wrap_width / self.metrics.space_width) as u32;
+                let wraps = wrap_line(line_text, columns, self.metrics.tab_stops, false);
+
+                let chars: Vec<char> = line_text.chars().collect();
+                let line_chars = chars.len() as u32;
+                let visual_lines = (0..=wraps.breaks.len() as u32)
+                    .map(|row| {
+                        let columns = wraps.row_columns(row, line_chars);
+                        chars[columns.start as usize..columns.end as usize]
+                            .iter()
+                            .collect()
+                    })
+                    .collect();
+                VisibleLineContent::Wrapped { visual_lines }
             }
         }
     }
@@ -25,6 +25,7 @@ pub enum WidgetId {
     Editor,
     FilePicker,
     Grep,
+    CommandPalette,
     TabBar,
     Diagnostics,
     // Add more as needed
@@ -5,7 +5,7 @@
 use crate::text_effects::{priority, EffectType, TextEffect, TextStyleProvider};
 use arc_swap::ArcSwap;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::{mpsc, Arc};
+use std::sync::{mpsc, Arc, Mutex, OnceLock};
 use std::thread;
 use tree_sitter::{
     InputEdit, Language, Parser, Point, Query, QueryCursor, StreamingIterator, Tree as TSTree,
@@ -24,6 +24,174 @@ enum ParserSetup {
     },
 }
 
+/// Comment syntax of a language (for toggle comment)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CommentTokens {
+    /// Line comment prefix, e.g. `//`
+    pub line: Option<&'static str>,
+    /// Block comment delimiters, e.g. `/*` and `*/`
+    pub block: Option<(&'static str, &'static str)>,
+}
+
+/// Characters typed as a pair (auto-closing brackets and quotes)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AutoPair {
+    pub open: char,
+    pub close: char,
+    /// Never auto-close where the opener starts a Rust lifetime (`&'a`, `T: 'static`, `<'a, 'b>`)
+    pub lifetimes: bool,
+}
+
+impl AutoPair {
+    const fn new(open: char, close: char) -> Self {
+        Self {
+            open,
+            close,
+            lifetimes: false,
+        }
+    }
+
+    /// Whether typing the opener after `before` (the line up to the cursor) must not
+    /// insert the closer
+    pub fn suppressed_after(&self, before: &str) -> bool {
+        self.lifetimes && starts_lifetime(before)
+    }
+}
+
+/// Whether a `'` typed after `before` starts a lifetime rather than a char literal:
+/// after `&` or `+`, after a bound like `T:` or `'a:`, or inside generics like `<'a, 'b>`
+fn starts_lifetime(before: &str) -> bool {
+    let before = before.trim_end();
+    match before.chars().next_back() {
+        Some('&' | '+') => return true,
+        Some(':') => {
+            let bounded = before[..before.len() - 1].trim_end();
+            let word_start = bounded.trim_end_matches(|c: char| c.is_alphanumeric() || c == '_');
+            let word = &bounded[word_start.len()..];
+            // Field names are lowercase (`Foo { sep: ',' }`), type parameters aren't
+            if word.starts_with(char::is_uppercase)
+                || (!word.is_empty() && word_start.ends_with('\''))
+            {
+                return true;
+            }
+        }
+        _ => {}
+    }
+
+    // Walk back to an unclosed `<`, giving up at anything that ends a type
+    let mut depth = 0usize;
+    let mut chars = before.chars().rev().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '>' if matches!(chars.peek(), Some('-' | '=')) => return false,
+            '>' => depth += 1,
+            '<' if depth == 0 => return true,
+            '<' => depth -= 1,
+            '(' | ')' | '{' | '}' | ';' | '=' | '"' => return false,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Pairs for plain text and languages without their own list
+pub const DEFAULT_AUTO_PAIRS: &[AutoPair] = &[
+    AutoPair::new('(', ')'),
+    AutoPair::new('[', ']'),
+    AutoPair::new('{', '}'),
+    AutoPair::new('"', '"'),
+    AutoPair::new('\'', '\''),
+    AutoPair::new('`', '`'),
+];
+
+const RUST_AUTO_PAIRS: &[AutoPair] = &[
+    AutoPair::new('(', ')'),
+    AutoPair::new('[', ']'),
+    AutoPair::new('{', '}'),
+    AutoPair::new('"', '"'),
+    // `&'a` and `<'a>` are lifetimes, not char literals
+    AutoPair {
+        open: '\'',
+        close: '\'',
+        lifetimes: true,
+    },
+];
+
+const WGSL_AUTO_PAIRS: &[AutoPair] = &[
+    AutoPair::new('(', ')'),
+    AutoPair::new('[', ']'),
+    AutoPair::new('{', '}'),
+];
+
+const TOML_AUTO_PAIRS: &[AutoPair] = &[
+    AutoPair::new('[', ']'),
+    AutoPair::new('{', '}'),
+    AutoPair::new('"', '"'),
+    AutoPair::new('\'', '\''),
+];
+
+/// Indentation of a line according to a language's indents query
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IndentLevel {
+    /// Number of `@indent` nodes opened on earlier lines that are still open
+    pub level: u32,
+    /// The line starts with an `@outdent` node (e.g. `}`) and sits one level further out
+    pub outdent: bool,
+}
+
+impl IndentLevel {
+    /// Indent level of the line's own content
+    pub fn content_level(&self) -> u32 {
+        self.level.saturating_sub(self.outdent as u32)
+    }
+}
+
+/// Indentation queries over one snapshot of the text (see `SyntaxHighlighter::indenter`)
+pub struct Indenter {
+    tree: TSTree,
+    /// The highlighter's indents query, compiled before the indenter is handed out
+    query: LazyQuery,
+}
+
+impl Indenter {
+    /// Indent level of a line starting at `line_start` whose content starts at `content`
+    /// For a line break about to be inserted, `line_start` is the break position and
+    /// `content` the first non-blank byte after it
+    pub fn indent_level(&self, text: &str, line_start: usize, content: usize) -> IndentLevel {
+        let Some(query) = self.query.get().and_then(Option::as_ref) else {
+            return IndentLevel::default();
+        };
+        let mut cursor = QueryCursor::new();
+        cursor.set_byte_range(content.saturating_sub(1)..content + 1);
+        let capture_names = query.capture_names();
+        let mut matches = cursor.matches(query, self.tree.root_node(), text.as_bytes());
+
+        // Nodes opened on the same row only indent once (e.g. `foo(vec![`)
+        let mut rows: Vec<usize> = Vec::new();
+        let mut outdent = false;
+        while let Some(match_) = matches.next() {
+            for capture in match_.captures {
+                let node = capture.node;
+                match capture_names[capture.index as usize] {
+                    "indent" if node.start_byte() < line_start && node.end_byte() > content => {
+                        let row = node.start_position().row;
+                        if !rows.contains(&row) {
+                            rows.push(row);
+                        }
+                    }
+                    "outdent" if node.start_byte() == content => outdent = true,
+                    _ => {}
+                }
+            }
+        }
+
+        IndentLevel {
+            level: rows.len() as u32,
+            outdent,
+        }
+    }
+}
+
 /// Complete language specification
 #[derive(Clone)]
 struct LanguageSpec {
@@ -31,6 +199,12 @@ struct LanguageSpec {
     extensions: &'static [&'static str],
     parser_setup: ParserSetup,
     highlights_query: &'static str,
+    comment_tokens: CommentTokens,
+    auto_pairs: &'static [AutoPair],
+    /// Optional indents query (`@indent` nodes indent their contents, `@outdent` closes them)
+    indents_query: Option<&'static str>,
+    /// Optional folds query (`@fold` nodes spanning several lines can be folded)
+    folds_query: Option<&'static str>,
     /// Optional injections query (for detecting embedded languages)
     injections_query: Option<&'static str>,
     /// Optional inline/secondary grammar (for markdown, etc.)
@@ -104,6 +278,13 @@ impl LanguageRegistry {
             extensions: &["rs"],
             parser_setup: ParserSetup::Native(tree_sitter_rust::LANGUAGE.into()),
             highlights_query: tree_sitter_rust::HIGHLIGHTS_QUERY,
+            comment_tokens: CommentTokens {
+                line: Some("//"),
+                block: Some(("/*", "*/")),
+            },
+            auto_pairs: RUST_AUTO_PAIRS,
+            indents_query: Some(include_str!("../assets/grammars/rust/indents.scm")),
+            folds_query: Some(include_str!("../assets/grammars/rust/folds.scm")),
             injections_query: None,
             inline_spec: None,
         });
@@ -118,6 +299,13 @@ impl LanguageRegistry {
                 wasm_bytes: include_bytes!("../assets/grammars/toml/tree-sitter-toml.wasm"),
             },
             highlights_query: include_str!("../assets/grammars/toml/highlights.scm"),
+            comment_tokens: CommentTokens {
+                line: Some("#"),
+                block: None,
+            },
+            auto_pairs: TOML_AUTO_PAIRS,
+            indents_query: Some(include_str!("../assets/grammars/toml/indents.scm")),
+            folds_query: Some(include_str!("../assets/grammars/toml/folds.scm")),
             injections_query: None,
             inline_spec: None,
         });
@@ -132,6 +320,13 @@ impl LanguageRegistry {
                 wasm_bytes: include_bytes!("../assets/grammars/wgsl/tree-sitter-wgsl.wasm"),
             },
             highlights_query: include_str!("../assets/grammars/wgsl/highlights.scm"),
+            comment_tokens: CommentTokens {
+                line: Some("//"),
+                block: Some(("/*", "*/")),
+            },
+            auto_pairs: WGSL_AUTO_PAIRS,
+            indents_query: Some(include_str!("../assets/grammars/wgsl/indents.scm")),
+            folds_query: Some(include_str!("../assets/grammars/wgsl/folds.scm")),
             injections_query: None,
             inline_spec: None,
         });
@@ -144,6 +339,10 @@ impl LanguageRegistry {
             extensions: &[],
             parser_setup: ParserSetup::Native(tree_sitter_md::INLINE_LANGUAGE.into()),
             highlights_query: tree_sitter_md::HIGHLIGHT_QUERY_INLINE,
+            comment_tokens: CommentTokens::default(),
+            auto_pairs: DEFAULT_AUTO_PAIRS,
+            indents_query: None,
+            folds_query: None,
             injections_query: Some(tree_sitter_md::INJECTION_QUERY_INLINE),
             inline_spec: None,
         };
@@ -153,6 +352,13 @@ impl LanguageRegistry {
             extensions: &["md"],
             parser_setup: ParserSetup::Native(tree_sitter_md::LANGUAGE.into()),
             highlights_query: tree_sitter_md::HIGHLIGHT_QUERY_BLOCK,
+            comment_tokens: CommentTokens {
+                line: None,
+                block: Some(("<!--", "-->")),
+            },
+            auto_pairs: DEFAULT_AUTO_PAIRS,
+            indents_query: None,
+            folds_query: None,
             injections_query: Some(tree_sitter_md::INJECTION_QUERY_BLOCK),
             inline_spec: Some(Box::new(inline_spec)),
         });
@@ -167,6 +373,12 @@ impl LanguageRegistry {
             .iter()
             .find(|spec| spec.extensions.contains(&ext))
     }
+
+    /// Look up a language by name or extension (e.g. a code fence's `rust` or `rs`)
+    fn resolve(&self, name: &str) -> Option<&LanguageSpec> {
+        let name = name.to_lowercase();
+        self.by_name(&name).or_else(|| self.by_extension(&name))
+    }
 }
 
 lazy_static::lazy_static! {
@@ -219,6 +431,21 @@ impl Languages {
     pub fn toml() -> LanguageConfig {
         Self::spec_to_config(LANGUAGE_REGISTRY.by_name("toml").unwrap())
     }
+
+    /// Comment syntax for a language name (none for unknown languages)
+    pub fn comment_tokens(name: &str) -> CommentTokens {
+        LANGUAGE_REGISTRY
+            .by_name(name)
+            .map(|spec| spec.comment_tokens)
+            .unwrap_or_default()
+    }
+
+    /// Auto-closing pairs for a language name (the defaults for unknown languages)
+    pub fn auto_pairs(name: &str) -> &'static [AutoPair] {
+        LANGUAGE_REGISTRY
+            .by_name(name)
+            .map_or(DEFAULT_AUTO_PAIRS, |spec| spec.auto_pairs)
+    }
 }
 
 /// Syntax highlighting mode for debugging and validation
@@ -329,6 +556,28 @@ pub struct SyntaxHighlighter {
     highlights_query: &'static str,
     /// Syntax highlighting mode (for debugging/validation)
     mode: Arc<ArcSwap<SyntaxMode>>,
+    /// Injections query, for the language at a byte offset
+    injections_query: LazyQuery,
+    /// Indents query, for auto-indentation
+    indents_query: LazyQuery,
+    /// Folds query, for syntax-aware folding
+    folds_query: LazyQuery,
+    /// Parser for syntax lookups on text the background thread hasn't parsed yet
+    /// (created on first use)
+    lookup_parser: Arc<Mutex<Option<Parser>>>,
+}
+
+/// A query compiled on first use and shared by clones of a highlighter
+/// None once compiling failed, or for a language without that query
+type LazyQuery = Arc<OnceLock<Option<Query>>>;
+
+fn lazy_query<'a>(
+    cell: &'a OnceLock<Option<Query>>,
+    language: &Language,
+    source: Option<&str>,
+) -> Option<&'a Query> {
+    cell.get_or_init(|| Query::new(language, source?).ok())
+        .as_ref()
 }
 
 /// Text edit information for tree-sitter incremental parsing
@@ -347,8 +596,8 @@ struct ParseRequest {
     text: String,
     #[allow(dead_code)]
     version: u64,
-    /// Edit information for incremental parsing
-    edit: Option<TextEdit>,
+    /// Edits since the last request, applied to the old tree in order for incremental parsing
+    edits: Vec<TextEdit>,
     /// Force fresh parse (discard old tree) - needed when multiple edits accumulate
     reset_tree: bool,
 }
@@ -369,17 +618,6 @@ impl SyntaxHighlighter {
         **self.mode.load()
     }
 
-    /// Apply an incremental edit for efficient reparsing
-    pub fn apply_edit(&self, edit: TextEdit) {
-        // Send edit to background thread
-        let _ = self.tx.send(ParseRequest {
-            text: String::new(), // Will be set by request_update_with_edit
-            version: 0,          // Will be set by request_update_with_edit
-            edit: Some(edit),
-            reset_tree: false,
-        });
-    }
-
     /// Request update with edit information
     /// Note: For explicit reset (undo/redo), use request_update_with_reset directly
     pub fn request_update_with_edit(&self, text: &str, version: u64, edit: Option<TextEdit>) {
@@ -387,6 +625,11 @@ impl SyntaxHighlighter {
         self.request_update_with_reset(text, version, edit, false);
     }
 
+    /// Request update after several edits, applied to the old tree in order before re-parsing
+    pub fn request_update_with_edits(&self, text: &str, version: u64, edits: Vec<TextEdit>) {
+        self.send_parse_request(text, version, edits, false);
+    }
+
     /// Request update with optional tree reset
     pub fn request_update_with_reset(
         &self,
@@ -395,6 +638,10 @@ impl SyntaxHighlighter {
         edit: Option<TextEdit>,
         reset_tree: bool,
     ) {
+        self.send_parse_request(text, version, edit.into_iter().collect(), reset_tree);
+    }
+
+    fn send_parse_request(&self, text: &str, version: u64, edits: Vec<TextEdit>, reset_tree: bool) {
         eprintln!(
             "🔄 SYNTAX: Requesting parse for {} ({} bytes, version={})",
             self.name,
@@ -404,7 +651,7 @@ impl SyntaxHighlighter {
         let _ = self.tx.send(ParseRequest {
             text: text.to_string(),
             version,
-            edit,
+            edits,
             reset_tree,
         });
     }
@@ -486,8 +733,8 @@ impl SyntaxHighlighter {
                 // Apply edit to tree IMMEDIATELY (before debounce) - cheap operation!
                 if request.reset_tree {
                     tree = None;
-                } else if let Some(edit) = &request.edit {
-                    if let Some(ref mut existing_tree) = tree {
+                } else if let Some(ref mut existing_tree) = tree {
+                    for edit in &request.edits {
                         let ts_edit = InputEdit {
                             start_byte: edit.start_byte,
                             old_end_byte: edit.old_end_byte,
@@ -510,8 +757,8 @@ impl SyntaxHighlighter {
                     request = additional_request;
                     if request.reset_tree {
                         tree = None;
-                    } else if let Some(edit) = &request.edit {
-                        if let Some(ref mut existing_tree) = tree {
+                    } else if let Some(ref mut existing_tree) = tree {
+                        for edit in &request.edits {
                             let ts_edit = InputEdit {
                                 start_byte: edit.start_byte,
                                 old_end_byte: edit.old_end_byte,
@@ -529,7 +776,7 @@ impl SyntaxHighlighter {
 
                 // Skip if text hasn't changed (avoid redundant parsing)
                 if final_request.text == last_text
-                    && final_request.edit.is_none()
+                    && final_request.edits.is_empty()
                     && !final_request.reset_tree
                 {
                     continue;
@@ -921,6 +1168,10 @@ impl SyntaxHighlighter {
             language: config.language,
             highlights_query: config.highlights_query,
             mode: Arc::new(ArcSwap::from_pointee(SyntaxMode::Incremental)),
+            injections_query: LazyQuery::default(),
+            indents_query: LazyQuery::default(),
+            folds_query: LazyQuery::default(),
+            lookup_parser: Arc::default(),
         })
     }
 
@@ -984,6 +1235,10 @@ impl SyntaxHighlighter {
             language: tree_sitter_rust::LANGUAGE.into(),
             highlights_query: "",
             mode: Arc::new(ArcSwap::from_pointee(SyntaxMode::Incremental)),
+            injections_query: LazyQuery::default(),
+            indents_query: LazyQuery::default(),
+            folds_query: LazyQuery::default(),
+            lookup_parser: Arc::default(),
         }
     }
 
@@ -1230,6 +1485,245 @@ impl SyntaxHighlighter {
         self.name
     }
 
+    /// Language at a byte offset, following injections (e.g. a rust code fence in markdown)
+    /// Based on the last parsed tree, which may lag slightly behind unparsed edits
+    pub fn language_at(&self, byte: usize) -> &'static str {
+        let source = LANGUAGE_REGISTRY
+            .by_name(self.name)
+            .and_then(|spec| spec.injections_query);
+        let Some(query) = lazy_query(&self.injections_query, &self.language, source) else {
+            return self.name;
+        };
+
+        let tree_guard = self.cached_tree.load();
+        let text_guard = self.cached_text.load();
+        let (Some(tree), Some(text)) = (tree_guard.as_ref(), text_guard.as_ref()) else {
+            return self.name;
+        };
+
+        let mut cursor = QueryCursor::new();
+        cursor.set_byte_range(byte..byte + 1);
+        let capture_names = query.capture_names();
+        let mut matches = cursor.matches(query, tree.root_node(), text.as_bytes());
+
+        while let Some(match_) = matches.next() {
+            let mut injection_lang = None;
+            let mut injection_range = None;
+            for capture in match_.captures {
+                match capture_names[capture.index as usize] {
+                    "injection.language" => injection_lang = text.get(capture.node.byte_range()),
+                    "injection.content" => injection_range = Some(capture.node.byte_range()),
+                    _ => {}
+                }
+            }
+
+            if let (Some(lang), Some(range)) = (injection_lang, injection_range) {
+                if range.contains(&byte) {
+                    if let Some(spec) = LANGUAGE_REGISTRY.resolve(lang.trim()) {
+                        return spec.name;
+                    }
+                }
+            }
+        }
+
+        self.name
+    }
+
+    /// Indentation queries for `text` (the current document text)
+    /// None without an indents query or a tree for `text` - callers fall back.
+    pub fn indenter(&self, text: &str) -> Option<Indenter> {
+        let source = LANGUAGE_REGISTRY
+            .by_name(self.name)
+            .and_then(|spec| spec.indents_query);
+        lazy_query(&self.indents_query, &self.language, source)?;
+        let tree = self.tree_for_text(text)?;
+
+        Some(Indenter {
+            tree,
+            query: self.indents_query.clone(),
+        })
+    }
+
+    /// Syntax tree for `text`: the cached tree when it matches, otherwise parsed right
+    /// away, reusing the cached tree for the text that didn't change since
+    /// (None for dual-grammar languages, which only the background thread parses)
+    fn tree_for_text(&self, text: &str) -> Option<TSTree> {
+        let tree_guard = self.cached_tree.load();
+        let text_guard = self.cached_text.load();
+        let old_tree = match (tree_guard.as_ref(), text_guard.as_ref()) {
+            (Some(tree), Some(cached)) if cached == text => return Some(tree.clone()),
+            (Some(tree), Some(cached)) => {
+                let mut tree = tree.clone();
+                tree.edit(&diff_input_edit(cached, text));
+                Some(tree)
+            }
+            _ => None,
+        };
+
+        let spec = LANGUAGE_REGISTRY.by_name(self.name)?;
+        if spec.inline_spec.is_some() {
+            return None;
+        }
+        let mut parser = self.lookup_parser.lock().ok()?;
+        if parser.is_none() {
+            *parser = Some(spec.setup_parser().ok()?);
+        }
+        parser.as_mut()?.parse(text, old_tree.as_ref())
+    }
+
+    /// Byte ranges of the named nodes enclosing each of `ranges` in `text`, innermost first
+    /// Ranges equal to their child's are skipped, so each one is strictly larger.
+    /// Empty for every range when there's no usable tree.
+    pub fn enclosing_node_ranges(
+        &self,
+        text: &str,
+        ranges: &[std::ops::Range<usize>],
+    ) -> Vec<Vec<std::ops::Range<usize>>> {
+        let Some(tree) = self.tree_for_text(text) else {
+            return vec![Vec::new(); ranges.len()];
+        };
+
+        ranges
+            .iter()
+            .map(|range| {
+                let mut enclosing: Vec<std::ops::Range<usize>> = Vec::new();
+                let mut node = tree
+                    .root_node()
+                    .named_descendant_for_byte_range(range.start, range.end);
+                while let Some(current) = node {
+                    let node_range = current.byte_range();
+                    if node_range.start <= range.start
+                        && node_range.end >= range.end
+                        && enclosing.last() != Some(&node_range)
+                    {
+                        enclosing.push(node_range);
+                    }
+                    node = current.parent();
+                }
+                enclosing
+            })
+            .collect()
+    }
+
+    /// The innermost block or call around each of `ranges` in `text`, for unwrapping: the
+    /// construct's byte range and the range between its delimiters, e.g. `Some(x)` and
+    /// `x`, or `if a { b }` and ` b `. None where nothing encloses the range, or where the
+    /// innermost construct has another branch (`if a { b } else { c }`) it would delete.
+    pub fn unwrap_ranges(
+        &self,
+        text: &str,
+        ranges: &[std::ops::Range<usize>],
+    ) -> Vec<Option<(std::ops::Range<usize>, std::ops::Range<usize>)>> {
+        let Some(tree) = self.tree_for_text(text) else {
+            return vec![None; ranges.len()];
+        };
+
+        ranges
+            .iter()
+            .map(|range| {
+                let mut node = tree
+                    .root_node()
+                    .descendant_for_byte_range(range.start, range.end);
+                while let Some(current) = node {
+                    // The node itself (a block) or one of its children (a call's arguments)
+                    let candidates = std::iter::once(current)
+                        .chain((0..current.child_count()).filter_map(|i| current.child(i)));
+                    for candidate in candidates {
+                        let (Some(inner), Some(target)) =
+                            (delimited_contents(candidate), unwrap_target(candidate))
+                        else {
+                            continue;
+                        };
+                        let (outer, branched) = match target {
+                            UnwrapTarget::Remove(outer) => (outer, false),
+                            UnwrapTarget::Branched(outer) => (outer, true),
+                        };
+                        if outer.start <= range.start && range.end <= outer.end {
+                            return (!branched).then_some((outer, inner));
+                        }
+                    }
+                    node = current.parent();
+                }
+                None
+            })
+            .collect()
+    }
+
+    /// Foldable line ranges `(header, last)` of the folds query on the last parsed tree,
+    /// with the document version that tree was parsed from. A closing delimiter starting
+    /// a node's last line stays visible, so `last` is the last line a fold hides.
+    /// None without a folds query or a parsed tree - callers fall back to indentation.
+    pub fn fold_ranges(&self) -> Option<(u64, Vec<(u32, u32)>)> {
+        let source = LANGUAGE_REGISTRY
+            .by_name(self.name)
+            .and_then(|spec| spec.folds_query);
+        let query = lazy_query(&self.folds_query, &self.language, source)?;
+
+        let tree_guard = self.cached_tree.load();
+        let text_guard = self.cached_text.load();
+        let (Some(tree), Some(text)) = (tree_guard.as_ref(), text_guard.as_ref()) else {
+            return None;
+        };
+
+        let mut ranges: Vec<(u32, u32)> = Vec::new();
+        let mut cursor = QueryCursor::new();
+        let mut matches = cursor.matches(query, tree.root_node(), text.as_bytes());
+        while let Some(match_) = matches.next() {
+            for capture in match_.captures {
+                let node = capture.node;
+                let header = node.start_position().row as u32;
+                let end = node.end_position();
+                let last_line = text
+                    .get(node.end_byte() - end.column..node.end_byte())
+                    .unwrap_or("");
+                let last = if last_line.trim_start().starts_with(['}', ']', ')']) {
+                    (end.row as u32).saturating_sub(1)
+                } else {
+                    end.row as u32
+                };
+                if last > header {
+                    ranges.push((header, last));
+                }
+            }
+        }
+
+        // One range per header line, the widest
+        ranges.sort_unstable_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+        ranges.dedup_by_key(|range| range.0);
+        Some((self.cached_version(), ranges))
+    }
+
+    /// Comment syntax at a byte offset (injection-aware, see `language_at`)
+    pub fn comment_tokens_at(&self, byte: usize) -> CommentTokens {
+        Languages::comment_tokens(self.language_at(byte))
+    }
+
+    /// Auto-closing pairs at a byte offset (injection-aware, see `language_at`)
+    pub fn auto_pairs_at(&self, byte: usize) -> &'static [AutoPair] {
+        Languages::auto_pairs(self.language_at(byte))
+    }
+
+    /// Whether a byte offset of `text` is inside a string or comment (not at its start)
+    /// Parses edits the background thread hasn't caught up with, like the indenter
+    pub fn in_string_or_comment(&self, text: &str, byte: usize) -> bool {
+        let Some(tree) = self.tree_for_text(text) else {
+            return false;
+        };
+
+        let mut node = tree.root_node().descendant_for_byte_range(byte, byte);
+        while let Some(current) = node {
+            let kind = current.kind();
+            if (kind.contains("string") || kind.contains("comment") || kind == "char_literal")
+                && current.start_byte() < byte
+                && byte < current.end_byte()
+            {
+                return true;
+            }
+            node = current.parent();
+        }
+        false
+    }
+
     /// Get syntax effects for only the visible byte range - O(visible nodes)
     pub fn get_visible_effects(
         &self,
@@ -1375,6 +1869,77 @@ impl TextStyleProvider for SyntaxHighlighter {
     }
 }
 
+/// Bytes between a node's opening and closing bracket, if it starts and ends with one
+fn delimited_contents(node: tree_sitter::Node) -> Option<std::ops::Range<usize>> {
+    let count = node.child_count();
+    if count < 2 {
+        return None;
+    }
+    let open = node.child(0)?;
+    let close = node.child(count - 1)?;
+    let paired = matches!(
+        (open.kind(), close.kind()),
+        ("(", ")") | ("{", "}") | ("[", "]")
+    );
+    (paired && !open.is_named() && !close.is_named()).then(|| open.end_byte()..close.start_byte())
+}
+
+/// What unwrapping a delimited node would remove
+enum UnwrapTarget {
+    /// Byte range replaced by the node's contents
+    Remove(std::ops::Range<usize>),
+    /// Control flow with more than one branch (an `else`, `catch`, ..), which can't be
+    /// unwrapped without deleting the others
+    Branched(std::ops::Range<usize>),
+}
+
+/// What unwrapping a delimited node removes: the call or control flow it belongs to
+/// (`foo(..)`, `if .. {..}`, `unsafe {..}`), or just the brackets of a bare block or
+/// parenthesized expression. None for other delimited nodes, like function bodies.
+fn unwrap_target(delimited: tree_sitter::Node) -> Option<UnwrapTarget> {
+    const CONSTRUCTS: &[&str] = &[
+        "call",
+        "invocation",
+        "if",
+        "while",
+        "for",
+        "loop",
+        "unsafe",
+        "async",
+        "try",
+        "with",
+    ];
+    let has_word = |kind: &str, words: &[&str]| kind.split('_').any(|part| words.contains(&part));
+
+    let parent = delimited.parent()?;
+    // Else and elif blocks belong to the whole if
+    let construct = if has_word(parent.kind(), &["clause"]) {
+        parent.parent()?
+    } else {
+        parent
+    };
+    if has_word(construct.kind(), CONSTRUCTS) {
+        const BRANCHES: &[&str] = &["else", "elif", "alternative", "catch", "except", "finally"];
+        // Branches beside it, or it's itself one (`else if`)
+        let branched = (0..construct.child_count())
+            .filter_map(|i| construct.child(i))
+            .chain(construct.parent())
+            .any(|node| has_word(node.kind(), BRANCHES));
+        let range = construct.byte_range();
+        return Some(if branched {
+            UnwrapTarget::Branched(range)
+        } else {
+            UnwrapTarget::Remove(range)
+        });
+    }
+
+    let kind = delimited.kind();
+    let bare_block = has_word(kind, &["block"])
+        && has_word(parent.kind(), &["block", "statement", "statements"]);
+    (bare_block || has_word(kind, &["parenthesized"]))
+        .then(|| UnwrapTarget::Remove(delimited.byte_range()))
+}
+
 /// Convert byte position to tree-sitter Point using efficient tree navigation
 /// IMPORTANT: Tree-sitter expects actual character columns, NOT visual columns
 /// A tab character should count as 1 column, not expanded to tab width
@@ -1426,6 +1991,45 @@ fn calc_new_point(start: Point, text: &str) -> Point {
     }
 }
 
+/// One edit turning `old` into `new` (the span between their common prefix and suffix),
+/// so a tree parsed from `old` can be reused for parsing `new`
+fn diff_input_edit(old: &str, new: &str) -> InputEdit {
+    let prefix = old
+        .bytes()
+        .zip(new.bytes())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old
+        .bytes()
+        .rev()
+        .zip(new.bytes().rev())
+        .take(old.len().min(new.len()) - prefix)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    InputEdit {
+        start_byte: prefix,
+        old_end_byte: old.len() - suffix,
+        new_end_byte: new.len() - suffix,
+        start_position: point_at(old, prefix),
+        old_end_position: point_at(old, old.len() - suffix),
+        new_end_position: point_at(new, new.len() - suffix),
+    }
+}
+
+/// Row and byte column of a byte offset
+fn point_at(text: &str, byte: usize) -> Point {
+    let before = &text.as_bytes()[..byte];
+    let line_start = before
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .map_or(0, |newline| newline + 1);
+    Point {
+        row: before.iter().filter(|&&b| b == b'\n').count(),
+        column: byte - line_start,
+    }
+}
+
 /// Create TextEdit from document edit information using tree navigation
 pub fn create_text_edit(tree: &tiny_core::tree::Tree, edit: &tiny_core::tree::Edit) -> TextEdit {
     use tiny_core::tree::{Content, Edit};
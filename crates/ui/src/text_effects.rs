@@ -24,7 +24,7 @@ pub trait TextStyleProvider: Send + Sync {
 }
 
 /// A single text styling effect
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct TextEffect {
     /// Byte range this effect applies to
     pub range: Range<usize>,
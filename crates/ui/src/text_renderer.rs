@@ -267,6 +267,8 @@ pub struct TextRenderer {
     last_font_size: f32,
     last_line_height: f32,
     last_scale_factor: f32,
+    /// Folded lines we built layout with (hidden lines take no vertical space)
+    last_display_map: crate::coordinates::DisplayMap,
     /// Cluster map for ligature-aware cursor positioning
     pub cluster_maps: Vec<tiny_font::ClusterMap>,
 
@@ -307,6 +309,7 @@ impl TextRenderer {
             last_font_size: 0.0,
             last_line_height: 0.0,
             last_scale_factor: 0.0,
+            last_display_map: crate::coordinates::DisplayMap::default(),
             cluster_maps: Vec::new(),
             syntax_state: SyntaxState {
                 stable_tokens: Vec::new(),
@@ -541,9 +544,10 @@ impl TextRenderer {
         let metrics_changed = (viewport.metrics.font_size - self.last_font_size).abs() > 0.01
             || (viewport.metrics.line_height - self.last_line_height).abs() > 0.01
             || (viewport.scale_factor - self.last_scale_factor).abs() > 0.01;
+        let folds_changed = viewport.display_map != self.last_display_map;
 
-        // Rebuild if text changed, metrics changed, or forced
-        if !force && tree.version == self.last_tree_version && !metrics_changed {
+        // Rebuild if text changed, metrics changed, folds changed, or forced
+        if !force && tree.version == self.last_tree_version && !metrics_changed && !folds_changed {
             return;
         }
 
@@ -640,6 +644,8 @@ impl TextRenderer {
         for (line_idx, line_text) in lines.iter().enumerate() {
             let line_start_char = char_index;
             let line_start_byte = byte_offset;
+            // Folded lines keep their glyphs but collapse to zero height below the fold
+            let hidden = viewport.display_map.is_hidden(line_idx as u32);
 
             // Compute cache key for this line
             let line_hash = {
@@ -765,13 +771,32 @@ impl TextRenderer {
             // Update byte_offset to end of line
             byte_offset = line_byte;
 
+            // Soft wrapped lines continue on the following display rows
+            let rows = match viewport.display_map.line_wraps(line_idx as u32) {
+                Some(wraps) if !hidden => {
+                    self.wrap_glyphs(
+                        line_start_char..char_index,
+                        line_start_byte,
+                        line_text,
+                        wraps,
+                        viewport,
+                    );
+                    wraps.breaks.len() + 1
+                }
+                _ => 1,
+            };
+
             // Add line info
             self.line_cache.push(LineInfo {
                 line_number: line_idx as u32,
                 byte_range: line_start_byte..byte_offset,
                 char_range: line_start_char..char_index,
                 y_position: y_pos,
-                height: viewport.metrics.line_height,
+                height: if hidden {
+                    0.0
+                } else {
+                    rows as f32 * viewport.metrics.line_height
+                },
             });
 
             // Add newline as a glyph (invisible but maintains byte position)
@@ -825,11 +850,14 @@ impl TextRenderer {
                 char_index += 1;
             }
 
-            y_pos += viewport.metrics.line_height;
+            if !hidden {
+                y_pos += rows as f32 * viewport.metrics.line_height;
+            }
         }
 
         // Track which tree version and metrics we built layout from
         self.last_tree_version = tree.version;
+        self.last_display_map = viewport.display_map.clone();
         self.last_font_size = viewport.metrics.font_size;
         self.last_line_height = viewport.metrics.line_height;
         self.last_scale_factor = viewport.scale_factor;
@@ -845,6 +873,46 @@ impl TextRenderer {
         }
     }
 
+    /// Move the glyphs of a soft wrapped line, laid out on a single row, onto its display rows
+    /// Continuation rows start at the wrap indent, like `Viewport::doc_to_layout_with_text`
+    fn wrap_glyphs(
+        &mut self,
+        glyphs: Range<usize>,
+        line_start_byte: usize,
+        line_text: &str,
+        wraps: &crate::coordinates::LineWraps,
+        viewport: &crate::coordinates::Viewport,
+    ) {
+        // Byte offsets where continuation rows start
+        let mut break_bytes = Vec::with_capacity(wraps.breaks.len());
+        let mut breaks = wraps.breaks.iter().peekable();
+        for (column, (byte, _)) in line_text.char_indices().enumerate() {
+            if breaks.next_if(|&&start| start as usize == column).is_some() {
+                break_bytes.push(line_start_byte + byte);
+            }
+        }
+
+        let indent_x = viewport.wrap_indent_x(wraps);
+        let mut row = 0;
+        let mut dx = 0.0;
+        for glyph in &mut self.layout_cache[glyphs] {
+            let glyph_row = break_bytes.partition_point(|&start| start <= glyph.char_byte_offset);
+            if glyph_row != row {
+                // First glyph of a row: shift the row back to the left edge
+                row = glyph_row;
+                dx = indent_x - glyph.layout_pos.x.0;
+            }
+            if row == 0 {
+                continue;
+            }
+            let dy = row as f32 * viewport.metrics.line_height;
+            glyph.layout_pos.x.0 += dx;
+            glyph.layout_pos.y.0 += dy;
+            glyph.physical_pos.x.0 += dx * viewport.scale_factor;
+            glyph.physical_pos.y.0 += dy * viewport.scale_factor;
+        }
+    }
+
     /// Reshape lines that have mixed weight/italic based on glyph attributes
     /// Called after update_syntax_with_theme assigns token_ids and style attributes
     ///
@@ -1018,6 +1086,16 @@ impl TextRenderer {
 
                 x_offset += shaped.width / viewport.scale_factor;
             }
+
+            // Reshaping laid the line out on one row again
+            let line = doc_line_num as u32;
+            if let Some(wraps) = viewport.display_map.line_wraps(line) {
+                if viewport.display_map.is_hidden(line) {
+                    continue;
+                }
+                let line_start_byte = self.line_cache[line_idx].byte_range.start;
+                self.wrap_glyphs(glyph_range, line_start_byte, line_text, wraps, viewport);
+            }
         }
     }
 
@@ -1333,6 +1411,10 @@ impl TextRenderer {
         self.visible_chars.clear();
         for line_idx in self.visible_lines.clone() {
             if let Some(line) = self.line_cache.get(line_idx as usize) {
+                // Folded away
+                if line.height == 0.0 {
+                    continue;
+                }
                 for char_idx in line.char_range.clone() {
                     // Check if this character is within the horizontal viewport
                     if let Some(glyph) = self.layout_cache.get(char_idx) {